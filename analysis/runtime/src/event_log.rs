@@ -0,0 +1,305 @@
+//! The on-disk format of event logs,
+//! as written by the [`Log`](crate::runtime::backend::BackendKind::Log) backend.
+//!
+//! An event log starts with [`MAGIC`] and the little-endian `u32` [`VERSION`],
+//! followed by any number of chunks.
+//! Each chunk is a little-endian `u32` length of its compressed data,
+//! a little-endian `u32` number of [`Event`]s in it,
+//! and then the `bincode`-encoded [`Event`]s, compressed together with deflate.
+//!
+//! Chunks are self-contained, so a log can be read one chunk at a time in bounded memory,
+//! and logs from several runs can be appended to one another, as long as only the first has a header.
+
+use std::io::{self, Cursor, Read, Write};
+
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+
+use crate::events::Event;
+
+/// The first bytes of every event log.
+pub const MAGIC: &[u8; 8] = b"C2RUSTEV";
+
+/// The version of the event log format, which is incremented on incompatible changes,
+/// including changes to [`Event`].
+pub const VERSION: u32 = 3;
+
+/// The number of bytes of encoded [`Event`]s to collect before compressing them into a chunk.
+const CHUNK_SIZE: usize = 1 << 20;
+
+fn invalid_data(e: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+/// Write the event log header.
+pub fn write_header(mut w: impl Write) -> io::Result<()> {
+    w.write_all(MAGIC)?;
+    w.write_all(&VERSION.to_le_bytes())?;
+    Ok(())
+}
+
+/// Read and check the event log header.
+pub fn read_header(mut r: impl Read) -> io::Result<()> {
+    let mut magic = [0; MAGIC.len()];
+    r.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        return Err(invalid_data("not an event log"));
+    }
+    let mut version = [0; 4];
+    r.read_exact(&mut version)?;
+    let version = u32::from_le_bytes(version);
+    if version != VERSION {
+        return Err(invalid_data(format!(
+            "event log version {version} is not supported (expected version {VERSION})"
+        )));
+    }
+    Ok(())
+}
+
+/// Writes [`Event`]s in chunks.  The header must already have been written.
+///
+/// Call [`EventLogWriter::flush`] to write out a partial chunk and see any error in doing so.
+/// Dropping the [`EventLogWriter`] also flushes it, ignoring errors.
+pub struct EventLogWriter<W: Write> {
+    writer: W,
+    buf: Vec<u8>,
+    num_events: u32,
+}
+
+impl<W: Write> EventLogWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            buf: Vec::new(),
+            num_events: 0,
+        }
+    }
+
+    pub fn write(&mut self, event: &Event) -> io::Result<()> {
+        bincode::serialize_into(&mut self.buf, event).map_err(invalid_data)?;
+        self.num_events += 1;
+        if self.buf.len() >= CHUNK_SIZE || self.num_events == u32::MAX {
+            self.write_chunk()?;
+        }
+        Ok(())
+    }
+
+    fn write_chunk(&mut self) -> io::Result<()> {
+        if self.num_events == 0 {
+            return Ok(());
+        }
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::fast());
+        encoder.write_all(&self.buf)?;
+        let compressed = encoder.finish()?;
+        write_raw_chunk(&mut self.writer, self.num_events, &compressed)?;
+        self.buf.clear();
+        self.num_events = 0;
+        Ok(())
+    }
+
+    /// Write out all buffered [`Event`]s as a chunk and flush the underlying writer.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.write_chunk()?;
+        self.writer.flush()
+    }
+}
+
+impl<W: Write> Drop for EventLogWriter<W> {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+fn write_raw_chunk(mut w: impl Write, num_events: u32, compressed: &[u8]) -> io::Result<()> {
+    let len: u32 = compressed
+        .len()
+        .try_into()
+        .map_err(|_| invalid_data("chunk is too large"))?;
+    w.write_all(&len.to_le_bytes())?;
+    w.write_all(&num_events.to_le_bytes())?;
+    w.write_all(compressed)?;
+    Ok(())
+}
+
+/// A chunk as stored in the log, before decompression.
+pub struct RawChunk {
+    pub num_events: u32,
+    pub compressed: Vec<u8>,
+}
+
+impl RawChunk {
+    /// Decompress the chunk's [`Event`]s.
+    pub fn events(&self) -> io::Result<Vec<Event>> {
+        let mut buf = Vec::new();
+        DeflateDecoder::new(&self.compressed[..]).read_to_end(&mut buf)?;
+        let mut cursor = Cursor::new(&buf[..]);
+        let events = (0..self.num_events)
+            .map(|_| bincode::deserialize_from(&mut cursor).map_err(invalid_data))
+            .collect::<io::Result<Vec<Event>>>()?;
+        if cursor.position() != buf.len() as u64 {
+            return Err(invalid_data("trailing data after the events of a chunk"));
+        }
+        Ok(events)
+    }
+
+    pub fn write(&self, w: impl Write) -> io::Result<()> {
+        write_raw_chunk(w, self.num_events, &self.compressed)
+    }
+
+    /// Read the next chunk, or return `None` at the end of the log.
+    pub fn read(mut r: impl Read) -> io::Result<Option<Self>> {
+        let mut header = [0; 8];
+        // Distinguish a clean end of the log from one in the middle of a chunk header.
+        let mut filled = 0;
+        while filled < header.len() {
+            match r.read(&mut header[filled..])? {
+                0 if filled == 0 => return Ok(None),
+                0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+                n => filled += n,
+            }
+        }
+        let len = u32::from_le_bytes(header[..4].try_into().unwrap());
+        let num_events = u32::from_le_bytes(header[4..].try_into().unwrap());
+        let mut compressed = vec![0; len as usize];
+        r.read_exact(&mut compressed)?;
+        Ok(Some(Self {
+            num_events,
+            compressed,
+        }))
+    }
+}
+
+/// Reads the [`Event`]s of a log one chunk at a time.
+pub struct EventLogReader<R: Read> {
+    reader: R,
+    chunk: std::vec::IntoIter<Event>,
+}
+
+impl<R: Read> EventLogReader<R> {
+    /// Start reading an event log, checking its header.
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        read_header(&mut reader)?;
+        Ok(Self {
+            reader,
+            chunk: Vec::new().into_iter(),
+        })
+    }
+}
+
+impl<R: Read> Iterator for EventLogReader<R> {
+    type Item = io::Result<Event>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(event) = self.chunk.next() {
+                return Some(Ok(event));
+            }
+            let chunk = match RawChunk::read(&mut self.reader) {
+                Ok(Some(chunk)) => chunk,
+                Ok(None) => return None,
+                Err(e) => return Some(Err(e)),
+            };
+            match chunk.events() {
+                Ok(events) => self.chunk = events.into_iter(),
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::events::EventKind;
+
+    fn event(i: usize) -> Event {
+        Event {
+            mir_loc: i as u32,
+            kind: EventKind::CopyPtr(i),
+            thread: (i % 3) as u32,
+            seq: i as u64,
+        }
+    }
+
+    fn assert_events(events: impl Iterator<Item = io::Result<Event>>, n: usize) {
+        let mut count = 0;
+        for (i, e) in events.enumerate() {
+            let e = e.unwrap();
+            let expected = event(i);
+            assert_eq!(e.mir_loc, expected.mir_loc);
+            assert_eq!(e.thread, expected.thread);
+            assert_eq!(e.seq, expected.seq);
+            assert_eq!(format!("{:?}", e.kind), format!("{:?}", expected.kind));
+            count += 1;
+        }
+        assert_eq!(count, n);
+    }
+
+    fn write_log(log: &mut Vec<u8>, events: std::ops::Range<usize>) {
+        let mut writer = EventLogWriter::new(log);
+        for i in events {
+            writer.write(&event(i)).unwrap();
+        }
+        writer.flush().unwrap();
+    }
+
+    #[test]
+    fn round_trip() {
+        // Enough events to fill several chunks, plus a partial one.
+        let n = 3 * CHUNK_SIZE / 16 + 7;
+        let mut log = Vec::new();
+        write_header(&mut log).unwrap();
+        write_log(&mut log, 0..n);
+
+        let mut num_chunks = 0;
+        let mut chunks = &log[MAGIC.len() + 4..];
+        while let Some(chunk) = RawChunk::read(&mut chunks).unwrap() {
+            assert_eq!(chunk.events().unwrap().len(), chunk.num_events as usize);
+            num_chunks += 1;
+        }
+        assert!(num_chunks > 1);
+
+        assert_events(EventLogReader::new(&log[..]).unwrap(), n);
+    }
+
+    #[test]
+    fn appended() {
+        let mut log = Vec::new();
+        write_header(&mut log).unwrap();
+        write_log(&mut log, 0..10);
+        write_log(&mut log, 10..25);
+        assert_events(EventLogReader::new(&log[..]).unwrap(), 25);
+    }
+
+    #[test]
+    fn flush_on_drop() {
+        let mut log = Vec::new();
+        write_header(&mut log).unwrap();
+        {
+            let mut writer = EventLogWriter::new(&mut log);
+            for i in 0..10 {
+                writer.write(&event(i)).unwrap();
+            }
+        }
+        assert_events(EventLogReader::new(&log[..]).unwrap(), 10);
+    }
+
+    #[test]
+    fn truncated() {
+        let mut log = Vec::new();
+        write_header(&mut log).unwrap();
+        write_log(&mut log, 0..10);
+        log.pop();
+        let result = EventLogReader::new(&log[..])
+            .unwrap()
+            .collect::<io::Result<Vec<_>>>();
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn bad_header() {
+        assert!(EventLogReader::new(&b"C2RUSTEX\x03\0\0\0"[..]).is_err());
+        let mut log = MAGIC.to_vec();
+        log.extend((VERSION + 1).to_le_bytes());
+        assert!(EventLogReader::new(&log[..]).is_err());
+    }
+}
@@ -1,24 +1,71 @@
-use crate::mir_loc::{Local, MirLocId};
+use crate::mir_loc::{DefPathHash, Local, MirLocId};
 use serde::{Deserialize, Serialize};
+use std::cell::Cell;
 use std::fmt;
 use std::fmt::Debug;
 use std::fmt::Formatter;
+use std::sync::atomic::{AtomicU32, Ordering};
 
 pub type Pointer = usize;
 
+/// Identifies the thread that emitted an [`Event`].
+///
+/// Threads are numbered in the order in which they emit their first [`Event`], starting from 0.
+/// Unlike [`std::thread::ThreadId`], this is also usable before `main` and while the thread is
+/// exiting.
+pub type ThreadId = u32;
+
 #[derive(Serialize, Deserialize)]
 pub struct Event {
     pub mir_loc: MirLocId,
     pub kind: EventKind,
+    /// The thread that emitted this [`Event`].
+    pub thread: ThreadId,
+    /// The index of this [`Event`] among all [`Event`]s emitted by `thread`.
+    ///
+    /// [`Event`]s from different threads are interleaved arbitrarily in the event log,
+    /// but the [`Event`]s of each thread appear in increasing `seq` order.
+    pub seq: u64,
+}
+
+static NEXT_THREAD_ID: AtomicU32 = AtomicU32::new(0);
+
+thread_local! {
+    /// This thread's [`ThreadId`], assigned when it emits its first [`Event`].
+    static THREAD_ID: Cell<Option<ThreadId>> = const { Cell::new(None) };
+
+    /// The `seq` of the next [`Event`] emitted by this thread.
+    static NEXT_SEQ: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Get the [`ThreadId`] of the current thread and the next sequence number for it.
+fn next_thread_seq() -> (ThreadId, u64) {
+    let thread = THREAD_ID.with(|id| {
+        id.get().unwrap_or_else(|| {
+            let new_id = NEXT_THREAD_ID.fetch_add(1, Ordering::Relaxed);
+            id.set(Some(new_id));
+            new_id
+        })
+    });
+    let seq = NEXT_SEQ.with(|seq| seq.replace(seq.get() + 1));
+    (thread, seq)
 }
 
 impl Event {
-    pub fn done() -> Self {
+    /// Create an [`Event`] emitted by the current thread.
+    pub fn new(mir_loc: MirLocId, kind: EventKind) -> Self {
+        let (thread, seq) = next_thread_seq();
         Self {
-            mir_loc: 0,
-            kind: EventKind::Done,
+            mir_loc,
+            kind,
+            thread,
+            seq,
         }
     }
+
+    pub fn done() -> Self {
+        Self::new(0, EventKind::Done)
+    }
 }
 
 #[derive(Serialize, Deserialize, Copy, Clone)]
@@ -55,9 +102,18 @@ pub enum EventKind {
     /// The pointer appears as the address of a store operation.
     StoreAddr(Pointer),
 
+    /// A libc function read the given number of bytes starting at the pointer.
+    LoadBytes(Pointer, usize),
+
+    /// A libc function wrote the given number of bytes starting at the pointer.
+    StoreBytes(Pointer, usize),
+
     /// The pointer that appears as the address result of addr_of(Local)
     AddrOfLocal(Pointer, Local),
 
+    /// The address of the `static` identified by the [`DefPathHash`].
+    AddrOfStatic(Pointer, DefPathHash),
+
     /// Casting the pointer to an int
     ToInt(Pointer),
 
@@ -92,8 +148,11 @@ impl Debug for EventKind {
             Done => write!(f, "done"),
             LoadAddr(ptr) => write!(f, "load(0x{:x})", ptr),
             StoreAddr(ptr) => write!(f, "store(0x{:x})", ptr),
+            LoadBytes(ptr, size) => write!(f, "load(0x{:x}, {} bytes)", ptr, size),
+            StoreBytes(ptr, size) => write!(f, "store(0x{:x}, {} bytes)", ptr, size),
             CopyRef => write!(f, "copy_ref"),
             AddrOfLocal(ptr, _) => write!(f, "addr_of_local = 0x{:x}", ptr),
+            AddrOfStatic(ptr, _) => write!(f, "addr_of_static = 0x{:x}", ptr),
             ToInt(ptr) => write!(f, "to_int(0x{:x})", ptr),
             FromInt(ptr) => write!(f, "from_int(0x{:x})", ptr),
             LoadValue(ptr) => write!(f, "load_value(0x{:x})", ptr),
@@ -6,53 +6,47 @@ use crate::runtime::global_runtime::RUNTIME;
 ///
 /// Instruments 64-bit `c2rust transpile`d `malloc`, which is similar to `libc::malloc`.
 pub fn malloc(mir_loc: MirLocId, size: u64, ptr: usize) {
-    RUNTIME.send_event(Event {
+    RUNTIME.send_event(Event::new(
         mir_loc,
-        kind: EventKind::Alloc {
+        EventKind::Alloc {
             size: size as usize,
             ptr,
         },
-    });
+    ));
 }
 
 /// A hook function (see [`HOOK_FUNCTIONS`]).
 ///
 /// Instruments 64-bit `c2rust transpile`d `free`, which is similar to `libc::free`.
 pub fn free(mir_loc: MirLocId, ptr: usize, _free_ret_val: ()) {
-    RUNTIME.send_event(Event {
-        mir_loc,
-        kind: EventKind::Free { ptr },
-    });
+    RUNTIME.send_event(Event::new(mir_loc, EventKind::Free { ptr }));
 }
 
 /// A hook function (see [`HOOK_FUNCTIONS`]).
 ///
 /// Instruments 64-bit `c2rust transpile`d `calloc`, which is similar to `libc::calloc`.
 pub fn calloc(mir_loc: MirLocId, nmemb: u64, size: u64, ptr: usize) {
-    RUNTIME.send_event(Event {
+    RUNTIME.send_event(Event::new(
         mir_loc,
-        kind: EventKind::Alloc {
+        EventKind::Alloc {
             size: (nmemb * size) as usize,
             ptr,
         },
-    });
+    ));
 }
 
 /// A hook function (see [`HOOK_FUNCTIONS`]).
 ///
 /// Instruments 64-bit `c2rust transpile`d `realloc`, which is similar to `libc::realloc`.
 pub fn realloc(mir_loc: MirLocId, old_ptr: usize, size: u64, new_ptr: usize) {
-    RUNTIME.send_event(Event {
-        mir_loc,
-        kind: EventKind::Free { ptr: old_ptr },
-    });
-    RUNTIME.send_event(Event {
+    RUNTIME.send_event(Event::new(mir_loc, EventKind::Free { ptr: old_ptr }));
+    RUNTIME.send_event(Event::new(
         mir_loc,
-        kind: EventKind::Alloc {
+        EventKind::Alloc {
             size: size as usize,
             ptr: new_ptr,
         },
-    });
+    ));
 }
 
 /// A hook function (see [`HOOK_FUNCTIONS`]).
@@ -64,6 +58,238 @@ pub fn reallocarray(mir_loc: MirLocId, old_ptr: usize, nmemb: u64, size: u64, ne
     realloc(mir_loc, old_ptr, size * nmemb, new_ptr)
 }
 
+fn load_bytes(mir_loc: MirLocId, ptr: usize, size: usize) {
+    RUNTIME.send_event(Event::new(mir_loc, EventKind::LoadBytes(ptr, size)));
+}
+
+fn store_bytes(mir_loc: MirLocId, ptr: usize, size: usize) {
+    RUNTIME.send_event(Event::new(mir_loc, EventKind::StoreBytes(ptr, size)));
+}
+
+/// Records that a libc function returned `ret`, which is derived from its argument `ptr`.
+fn ret_offset_of(mir_loc: MirLocId, ptr: usize, ret: usize) {
+    if ret != 0 {
+        let offset = ret.wrapping_sub(ptr) as isize;
+        RUNTIME.send_event(Event::new(mir_loc, EventKind::Offset(ptr, offset, ret)));
+    }
+}
+
+/// The length of the NUL-terminated string at `ptr`, including the NUL,
+/// or `max` if there is no NUL in its first `max` bytes.
+fn c_str_len(ptr: usize, max: usize) -> usize {
+    if ptr == 0 {
+        return 0;
+    }
+    let ptr = ptr as *const u8;
+    // SAFETY: The instrumented program just passed `ptr` to a libc string function,
+    // which read the same bytes.
+    (0..max)
+        .find(|&i| unsafe { *ptr.add(i) } == 0)
+        .map_or(max, |i| i + 1)
+}
+
+/// A hook function (see [`HOOK_FUNCTIONS`]).
+///
+/// Instruments 64-bit `c2rust transpile`d `memcpy`, which is similar to `libc::memcpy`.
+pub fn memcpy(mir_loc: MirLocId, dest: usize, src: usize, n: u64, ret: usize) {
+    load_bytes(mir_loc, src, n as usize);
+    store_bytes(mir_loc, dest, n as usize);
+    RUNTIME.send_event(Event::new(mir_loc, EventKind::CopyPtr(ret)));
+}
+
+/// A hook function (see [`HOOK_FUNCTIONS`]).
+///
+/// Instruments 64-bit `c2rust transpile`d `memmove`, which is similar to `libc::memmove`.
+pub fn memmove(mir_loc: MirLocId, dest: usize, src: usize, n: u64, ret: usize) {
+    memcpy(mir_loc, dest, src, n, ret)
+}
+
+/// A hook function (see [`HOOK_FUNCTIONS`]).
+///
+/// Instruments 64-bit `c2rust transpile`d `memset`, which is similar to `libc::memset`.
+pub fn memset(mir_loc: MirLocId, dest: usize, _c: i32, n: u64, ret: usize) {
+    store_bytes(mir_loc, dest, n as usize);
+    RUNTIME.send_event(Event::new(mir_loc, EventKind::CopyPtr(ret)));
+}
+
+/// A hook function (see [`HOOK_FUNCTIONS`]).
+///
+/// Instruments 64-bit `c2rust transpile`d `memcmp`, which is similar to `libc::memcmp`.
+///
+/// `memcmp` may stop at the first difference, so the recorded reads are an upper bound.
+pub fn memcmp(mir_loc: MirLocId, s1: usize, s2: usize, n: u64, _ret: i32) {
+    load_bytes(mir_loc, s1, n as usize);
+    load_bytes(mir_loc, s2, n as usize);
+}
+
+/// A hook function (see [`HOOK_FUNCTIONS`]).
+///
+/// Instruments 64-bit `c2rust transpile`d `strlen`, which is similar to `libc::strlen`.
+pub fn strlen(mir_loc: MirLocId, s: usize, len: u64) {
+    load_bytes(mir_loc, s, len as usize + 1);
+}
+
+/// A hook function (see [`HOOK_FUNCTIONS`]).
+///
+/// Instruments 64-bit `c2rust transpile`d `strcmp`, which is similar to `libc::strcmp`.
+///
+/// `strcmp` may stop at the first difference, so the recorded reads are an upper bound.
+pub fn strcmp(mir_loc: MirLocId, s1: usize, s2: usize, _ret: i32) {
+    load_bytes(mir_loc, s1, c_str_len(s1, usize::MAX));
+    load_bytes(mir_loc, s2, c_str_len(s2, usize::MAX));
+}
+
+/// A hook function (see [`HOOK_FUNCTIONS`]).
+///
+/// Instruments 64-bit `c2rust transpile`d `strncmp`, which is similar to `libc::strncmp`.
+///
+/// `strncmp` may stop at the first difference, so the recorded reads are an upper bound.
+pub fn strncmp(mir_loc: MirLocId, s1: usize, s2: usize, n: u64, _ret: i32) {
+    load_bytes(mir_loc, s1, c_str_len(s1, n as usize));
+    load_bytes(mir_loc, s2, c_str_len(s2, n as usize));
+}
+
+/// A hook function (see [`HOOK_FUNCTIONS`]).
+///
+/// Instruments 64-bit `c2rust transpile`d `strcpy`, which is similar to `libc::strcpy`.
+pub fn strcpy(mir_loc: MirLocId, dest: usize, src: usize, ret: usize) {
+    let len = c_str_len(src, usize::MAX);
+    load_bytes(mir_loc, src, len);
+    store_bytes(mir_loc, dest, len);
+    RUNTIME.send_event(Event::new(mir_loc, EventKind::CopyPtr(ret)));
+}
+
+/// A hook function (see [`HOOK_FUNCTIONS`]).
+///
+/// Instruments 64-bit `c2rust transpile`d `strncpy`, which is similar to `libc::strncpy`.
+pub fn strncpy(mir_loc: MirLocId, dest: usize, src: usize, n: u64, ret: usize) {
+    load_bytes(mir_loc, src, c_str_len(src, n as usize));
+    // `strncpy` pads `dest` with NULs, so it always writes all `n` bytes.
+    store_bytes(mir_loc, dest, n as usize);
+    RUNTIME.send_event(Event::new(mir_loc, EventKind::CopyPtr(ret)));
+}
+
+/// A hook function (see [`HOOK_FUNCTIONS`]).
+///
+/// Instruments 64-bit `c2rust transpile`d `strcat`, which is similar to `libc::strcat`.
+///
+/// Only whole-object accesses can be attributed to `dest`, so the recorded write covers
+/// the original string as well as the appended one.
+pub fn strcat(mir_loc: MirLocId, dest: usize, src: usize, ret: usize) {
+    let src_len = c_str_len(src, usize::MAX);
+    let dest_len = c_str_len(dest, usize::MAX);
+    load_bytes(mir_loc, src, src_len);
+    load_bytes(mir_loc, dest, dest_len - src_len + 1);
+    store_bytes(mir_loc, dest, dest_len);
+    RUNTIME.send_event(Event::new(mir_loc, EventKind::CopyPtr(ret)));
+}
+
+/// A hook function (see [`HOOK_FUNCTIONS`]).
+///
+/// Instruments 64-bit `c2rust transpile`d `strchr`, which is similar to `libc::strchr`.
+///
+/// `strchr` stops at the first match, so the recorded read is an upper bound.
+pub fn strchr(mir_loc: MirLocId, s: usize, _c: i32, ret: usize) {
+    load_bytes(mir_loc, s, c_str_len(s, usize::MAX));
+    ret_offset_of(mir_loc, s, ret);
+}
+
+/// A hook function (see [`HOOK_FUNCTIONS`]).
+///
+/// Instruments 64-bit `c2rust transpile`d `strrchr`, which is similar to `libc::strrchr`.
+pub fn strrchr(mir_loc: MirLocId, s: usize, c: i32, ret: usize) {
+    strchr(mir_loc, s, c, ret)
+}
+
+/// A hook function (see [`HOOK_FUNCTIONS`]).
+///
+/// Instruments 64-bit `c2rust transpile`d `strstr`, which is similar to `libc::strstr`.
+///
+/// `strstr` stops at the first match, so the recorded reads are an upper bound.
+pub fn strstr(mir_loc: MirLocId, haystack: usize, needle: usize, ret: usize) {
+    load_bytes(mir_loc, haystack, c_str_len(haystack, usize::MAX));
+    load_bytes(mir_loc, needle, c_str_len(needle, usize::MAX));
+    ret_offset_of(mir_loc, haystack, ret);
+}
+
+/// A hook function (see [`HOOK_FUNCTIONS`]).
+///
+/// Instruments 64-bit `c2rust transpile`d `strdup`, which is similar to `libc::strdup`.
+///
+/// The new string is recorded as an allocation that is written with the bytes read from `s`.
+pub fn strdup(mir_loc: MirLocId, s: usize, ret: usize) {
+    let len = c_str_len(s, usize::MAX);
+    load_bytes(mir_loc, s, len);
+    if ret != 0 {
+        RUNTIME.send_event(Event::new(
+            mir_loc,
+            EventKind::Alloc {
+                size: len,
+                ptr: ret,
+            },
+        ));
+        store_bytes(mir_loc, ret, len);
+    }
+}
+
+/// A hook function (see [`HOOK_FUNCTIONS`]).
+///
+/// Instruments 64-bit `c2rust transpile`d `strndup`, which is similar to `libc::strndup`.
+pub fn strndup(mir_loc: MirLocId, s: usize, n: u64, ret: usize) {
+    let read = c_str_len(s, n as usize);
+    load_bytes(mir_loc, s, read);
+    if ret != 0 {
+        // The copy is always NUL-terminated, even if `s` wasn't within `n` bytes.
+        let len = c_str_len(ret, usize::MAX);
+        RUNTIME.send_event(Event::new(
+            mir_loc,
+            EventKind::Alloc {
+                size: len,
+                ptr: ret,
+            },
+        ));
+        store_bytes(mir_loc, ret, len);
+    }
+}
+
+/// A hook function (see [`HOOK_FUNCTIONS`]).
+///
+/// Instruments 64-bit `c2rust transpile`d `fgets`, which is similar to `libc::fgets`.
+pub fn fgets(mir_loc: MirLocId, s: usize, _size: i32, _stream: usize, ret: usize) {
+    if ret != 0 {
+        store_bytes(mir_loc, s, c_str_len(s, usize::MAX));
+        RUNTIME.send_event(Event::new(mir_loc, EventKind::CopyPtr(ret)));
+    }
+}
+
+/// A hook function (see [`HOOK_FUNCTIONS`]).
+///
+/// Instruments 64-bit `c2rust transpile`d `fputs`, which is similar to `libc::fputs`.
+pub fn fputs(mir_loc: MirLocId, s: usize, _stream: usize, _ret: i32) {
+    load_bytes(mir_loc, s, c_str_len(s, usize::MAX));
+}
+
+/// A hook function (see [`HOOK_FUNCTIONS`]).
+///
+/// Instruments 64-bit `c2rust transpile`d `puts`, which is similar to `libc::puts`.
+pub fn puts(mir_loc: MirLocId, s: usize, _ret: i32) {
+    load_bytes(mir_loc, s, c_str_len(s, usize::MAX));
+}
+
+/// A hook function (see [`HOOK_FUNCTIONS`]).
+///
+/// Instruments 64-bit `c2rust transpile`d `fread`, which is similar to `libc::fread`.
+pub fn fread(mir_loc: MirLocId, ptr: usize, size: u64, _nmemb: u64, _stream: usize, ret: u64) {
+    store_bytes(mir_loc, ptr, (size * ret) as usize);
+}
+
+/// A hook function (see [`HOOK_FUNCTIONS`]).
+///
+/// Instruments 64-bit `c2rust transpile`d `fwrite`, which is similar to `libc::fwrite`.
+pub fn fwrite(mir_loc: MirLocId, ptr: usize, size: u64, _nmemb: u64, _stream: usize, ret: u64) {
+    load_bytes(mir_loc, ptr, (size * ret) as usize);
+}
+
 /// A hook function (see [`HOOK_FUNCTIONS`]).
 ///
 /// Instruments [`pointer::offset`](https://doc.rust-lang.org/std/primitive.pointer.html#method.offset).
@@ -77,10 +303,7 @@ pub fn reallocarray(mir_loc: MirLocId, old_ptr: usize, nmemb: u64, size: u64, ne
 ///   = note: rustdoc does not allow disambiguating between `*const` and `*mut`, and pointers are unstable until it does
 /// ```
 pub fn offset(mir_loc: MirLocId, ptr: usize, offset: isize, new_ptr: usize) {
-    RUNTIME.send_event(Event {
-        mir_loc,
-        kind: EventKind::Offset(ptr, offset, new_ptr),
-    });
+    RUNTIME.send_event(Event::new(mir_loc, EventKind::Offset(ptr, offset, new_ptr)));
 }
 
 macro_rules! hook_fn {
@@ -95,82 +318,88 @@ macro_rules! hook_fn {
 ///
 /// For functions in [`HOOK_FUNCTIONS`], the tracing passes
 /// the return value of the traced function as the last argument to the trace hook for it.
-/// See the `if after_call` block in `apply_instrumentation` in `dynamic_instrumentation/src/instrument_memory.rs`.
+/// Pointers are passed as `usize`s, but integers are passed as is,
+/// so the hook's last parameter must have the traced function's return type.
+/// See the `if after_call` block in `InstrumentationApplier::apply_point` in `dynamic_instrumentation/src/point/apply.rs`.
 pub const HOOK_FUNCTIONS: &[&str] = &[
     hook_fn!(malloc),
     hook_fn!(free),
     hook_fn!(calloc),
     hook_fn!(realloc),
     hook_fn!(reallocarray),
+    hook_fn!(memcpy),
+    hook_fn!(memmove),
+    hook_fn!(memset),
+    hook_fn!(memcmp),
+    hook_fn!(strlen),
+    hook_fn!(strcmp),
+    hook_fn!(strncmp),
+    hook_fn!(strcpy),
+    hook_fn!(strncpy),
+    hook_fn!(strcat),
+    hook_fn!(strchr),
+    hook_fn!(strrchr),
+    hook_fn!(strstr),
+    hook_fn!(strdup),
+    hook_fn!(strndup),
+    hook_fn!(fgets),
+    hook_fn!(fputs),
+    hook_fn!(puts),
+    hook_fn!(fread),
+    hook_fn!(fwrite),
     hook_fn!(offset),
 ];
 
 pub fn ptr_field(mir_loc: MirLocId, ptr: usize, field_id: u32) {
-    RUNTIME.send_event(Event {
-        mir_loc,
-        kind: EventKind::Field(ptr, field_id),
-    });
+    RUNTIME.send_event(Event::new(mir_loc, EventKind::Field(ptr, field_id)));
 }
 
 pub fn ptr_copy(mir_loc: MirLocId, ptr: usize) {
-    RUNTIME.send_event(Event {
-        mir_loc,
-        kind: EventKind::CopyPtr(ptr as usize),
-    });
+    RUNTIME.send_event(Event::new(mir_loc, EventKind::CopyPtr(ptr as usize)));
 }
 
 pub fn ptr_contrive(mir_loc: MirLocId, ptr: usize) {
-    RUNTIME.send_event(Event {
-        mir_loc,
-        kind: EventKind::FromInt(ptr as usize),
-    });
+    RUNTIME.send_event(Event::new(mir_loc, EventKind::FromInt(ptr as usize)));
 }
 
 pub fn ptr_to_int(mir_loc: MirLocId, ptr: usize) {
-    RUNTIME.send_event(Event {
-        mir_loc,
-        kind: EventKind::ToInt(ptr as usize),
-    });
+    RUNTIME.send_event(Event::new(mir_loc, EventKind::ToInt(ptr as usize)));
 }
 
 pub fn addr_of_local(mir_loc: MirLocId, ptr: usize, local: u32) {
-    RUNTIME.send_event(Event {
+    RUNTIME.send_event(Event::new(
         mir_loc,
-        kind: EventKind::AddrOfLocal(ptr, local.into()),
-    });
+        EventKind::AddrOfLocal(ptr, local.into()),
+    ));
 }
 
-pub fn load_value(mir_loc: MirLocId, ptr: usize) {
-    RUNTIME.send_event(Event {
+/// Instruments taking the address of a `static`, whose [`DefPathHash`] is split into two halves,
+/// as MIR constants of the [`DefPathHash`] type itself can't be passed to hooks.
+///
+/// [`DefPathHash`]: crate::mir_loc::DefPathHash
+pub fn addr_of_static(mir_loc: MirLocId, ptr: usize, def_path_hash_0: u64, def_path_hash_1: u64) {
+    RUNTIME.send_event(Event::new(
         mir_loc,
-        kind: EventKind::LoadValue(ptr),
-    });
+        EventKind::AddrOfStatic(ptr, (def_path_hash_0, def_path_hash_1).into()),
+    ));
+}
+
+pub fn load_value(mir_loc: MirLocId, ptr: usize) {
+    RUNTIME.send_event(Event::new(mir_loc, EventKind::LoadValue(ptr)));
 }
 
 pub fn store_value(mir_loc: MirLocId, ptr: usize) {
-    RUNTIME.send_event(Event {
-        mir_loc,
-        kind: EventKind::StoreValue(ptr),
-    });
+    RUNTIME.send_event(Event::new(mir_loc, EventKind::StoreValue(ptr)));
 }
 
 pub fn ptr_ret(mir_loc: MirLocId, ptr: usize) {
-    RUNTIME.send_event(Event {
-        mir_loc,
-        kind: EventKind::Ret(ptr),
-    });
+    RUNTIME.send_event(Event::new(mir_loc, EventKind::Ret(ptr)));
 }
 
 pub fn ptr_load(mir_loc: MirLocId, ptr: usize) {
-    RUNTIME.send_event(Event {
-        mir_loc,
-        kind: EventKind::LoadAddr(ptr),
-    });
+    RUNTIME.send_event(Event::new(mir_loc, EventKind::LoadAddr(ptr)));
 }
 
 pub fn ptr_store(mir_loc: MirLocId, ptr: usize) {
-    RUNTIME.send_event(Event {
-        mir_loc,
-        kind: EventKind::StoreAddr(ptr),
-    });
+    RUNTIME.send_event(Event::new(mir_loc, EventKind::StoreAddr(ptr)));
 }
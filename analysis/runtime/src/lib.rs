@@ -1,3 +1,4 @@
+pub mod event_log;
 pub mod events;
 mod handlers;
 pub mod metadata;
@@ -8,9 +9,23 @@ pub mod runtime;
 pub use handlers::*;
 use runtime::{global_runtime::RUNTIME, skip::notify_if_events_were_skipped_before_main};
 
+extern "C" {
+    fn atexit(f: extern "C" fn()) -> std::os::raw::c_int;
+}
+
+/// Finalizes the [`RUNTIME`] when the program calls `exit` instead of returning from `main`,
+/// so that the [`Event`](events::Event)s that are still buffered are written out.
+extern "C" fn finalize_at_exit() {
+    RUNTIME.finalize();
+}
+
 pub fn initialize() {
     notify_if_events_were_skipped_before_main();
     RUNTIME.init();
+    // SAFETY: `finalize_at_exit` is a plain function that doesn't unwind.
+    if unsafe { atexit(finalize_at_exit) } != 0 {
+        eprintln!("could not register the event log finalizer with atexit");
+    }
 }
 
 pub fn finalize() {
@@ -4,9 +4,8 @@ use std::fmt::Debug;
 use std::io::BufWriter;
 use std::sync::mpsc::Receiver;
 
-use bincode;
-
 use super::{AnyError, FINISHED};
+use crate::event_log::{self, EventLogWriter};
 use crate::events::{Event, EventKind};
 use crate::metadata::Metadata;
 use crate::parse::{self, AsStr, GetChoices};
@@ -14,6 +13,9 @@ use crate::parse::{self, AsStr, GetChoices};
 #[enum_dispatch]
 pub(super) trait WriteEvent {
     fn write(&mut self, event: Event);
+
+    /// Make sure all [`Event`]s written so far have reached their destination.
+    fn flush(&mut self) {}
 }
 
 pub(super) trait DetectBackend
@@ -57,17 +59,24 @@ pub struct DebugBackend {
 impl WriteEvent for DebugBackend {
     fn write(&mut self, event: Event) {
         let mir_loc = self.metadata.get(event.mir_loc);
-        eprintln!("{:?}: {:?}", mir_loc, event.kind);
+        eprintln!(
+            "[thread {}, #{}] {:?}: {:?}",
+            event.thread, event.seq, mir_loc, event.kind
+        );
     }
 }
 
 pub struct LogBackend {
-    writer: BufWriter<File>,
+    writer: EventLogWriter<BufWriter<File>>,
 }
 
 impl WriteEvent for LogBackend {
     fn write(&mut self, event: Event) {
-        bincode::serialize_into(&mut self.writer, &event).unwrap();
+        self.writer.write(&event).unwrap();
+    }
+
+    fn flush(&mut self) {
+        self.writer.flush().unwrap();
     }
 }
 
@@ -83,9 +92,10 @@ impl Backend {
             let done = matches!(event.kind, EventKind::Done);
             self.write(event);
             if done {
-                return;
+                break;
             }
         }
+        self.flush();
     }
 
     pub fn run(&mut self, rx: Receiver<Event>) {
@@ -118,7 +128,13 @@ impl DetectBackend for LogBackend {
             .append(append)
             .truncate(!append)
             .open(&path)?;
-        let writer = BufWriter::new(file);
+        // When appending to an existing log, it already has a header.
+        let is_empty = file.metadata()?.len() == 0;
+        let mut writer = BufWriter::new(file);
+        if is_empty {
+            event_log::write_header(&mut writer)?;
+        }
+        let writer = EventLogWriter::new(writer);
         Ok(Self { writer })
     }
 }
@@ -0,0 +1,119 @@
+//! A program that calls each of the hooked libc string and memory functions, declared the way
+//! `c2rust transpile` declares them, for testing that their hooks are instrumented and record the
+//! bytes they access.
+
+extern "C" {
+    fn malloc(_: libc::c_ulong) -> *mut libc::c_void;
+    fn free(__ptr: *mut libc::c_void);
+    fn memcpy(_: *mut libc::c_void, _: *const libc::c_void, _: libc::c_ulong) -> *mut libc::c_void;
+    fn memmove(_: *mut libc::c_void, _: *const libc::c_void, _: libc::c_ulong)
+        -> *mut libc::c_void;
+    fn memset(_: *mut libc::c_void, _: libc::c_int, _: libc::c_ulong) -> *mut libc::c_void;
+    fn memcmp(_: *const libc::c_void, _: *const libc::c_void, _: libc::c_ulong) -> libc::c_int;
+    fn strlen(_: *const libc::c_char) -> libc::c_ulong;
+    fn strcmp(_: *const libc::c_char, _: *const libc::c_char) -> libc::c_int;
+    fn strncmp(_: *const libc::c_char, _: *const libc::c_char, _: libc::c_ulong) -> libc::c_int;
+    fn strcpy(_: *mut libc::c_char, _: *const libc::c_char) -> *mut libc::c_char;
+    fn strncpy(_: *mut libc::c_char, _: *const libc::c_char, _: libc::c_ulong)
+        -> *mut libc::c_char;
+    fn strcat(_: *mut libc::c_char, _: *const libc::c_char) -> *mut libc::c_char;
+    fn strchr(_: *const libc::c_char, _: libc::c_int) -> *mut libc::c_char;
+    fn strrchr(_: *const libc::c_char, _: libc::c_int) -> *mut libc::c_char;
+    fn strstr(_: *const libc::c_char, _: *const libc::c_char) -> *mut libc::c_char;
+    fn strdup(_: *const libc::c_char) -> *mut libc::c_char;
+    fn strndup(_: *const libc::c_char, _: libc::c_ulong) -> *mut libc::c_char;
+    fn fopen(_: *const libc::c_char, _: *const libc::c_char) -> *mut FILE;
+    fn fclose(__stream: *mut FILE) -> libc::c_int;
+    fn fgets(__s: *mut libc::c_char, __n: libc::c_int, __stream: *mut FILE) -> *mut libc::c_char;
+    fn fputs(__s: *const libc::c_char, __stream: *mut FILE) -> libc::c_int;
+    fn puts(__s: *const libc::c_char) -> libc::c_int;
+    fn fread(
+        _: *mut libc::c_void,
+        _: libc::c_ulong,
+        _: libc::c_ulong,
+        _: *mut FILE,
+    ) -> libc::c_ulong;
+    fn fwrite(
+        _: *const libc::c_void,
+        _: libc::c_ulong,
+        _: libc::c_ulong,
+        _: *mut FILE,
+    ) -> libc::c_ulong;
+}
+use libc::FILE;
+
+unsafe fn mem() {
+    let mut a: [libc::c_char; 16] = [0; 16];
+    let mut b: [libc::c_char; 16] = [0; 16];
+    memset(a.as_mut_ptr() as *mut libc::c_void, 'a' as i32, 15);
+    memcpy(
+        b.as_mut_ptr() as *mut libc::c_void,
+        a.as_ptr() as *const libc::c_void,
+        16,
+    );
+    memmove(
+        b.as_mut_ptr().offset(1) as *mut libc::c_void,
+        b.as_ptr() as *const libc::c_void,
+        8,
+    );
+    if memcmp(
+        a.as_ptr() as *const libc::c_void,
+        b.as_ptr() as *const libc::c_void,
+        16,
+    ) != 0
+    {
+        puts(b"memcmp\0" as *const u8 as *const libc::c_char);
+    }
+}
+
+unsafe fn str() {
+    let hello = b"hello\0" as *const u8 as *const libc::c_char;
+    let world = b"world\0" as *const u8 as *const libc::c_char;
+    let buf = malloc(32) as *mut libc::c_char;
+    strcpy(buf, hello);
+    strcat(buf, world);
+    strncpy(buf.offset(strlen(buf) as isize), hello, 6);
+    if strcmp(buf, hello) > 0 && strncmp(buf, hello, 5) == 0 {
+        let o = strchr(buf, 'o' as i32);
+        let last_o = strrchr(buf, 'o' as i32);
+        let w = strstr(buf, world);
+        if !o.is_null() && !last_o.is_null() && !w.is_null() {
+            let copy = strdup(w);
+            let prefix = strndup(o, 3);
+            puts(copy);
+            puts(prefix);
+            free(copy as *mut libc::c_void);
+            free(prefix as *mut libc::c_void);
+        }
+    }
+    free(buf as *mut libc::c_void);
+}
+
+unsafe fn file() {
+    let mut buf: [libc::c_char; 8] = [0; 8];
+    let zero = fopen(
+        b"/dev/zero\0" as *const u8 as *const libc::c_char,
+        b"r\0" as *const u8 as *const libc::c_char,
+    );
+    let null = fopen(
+        b"/dev/null\0" as *const u8 as *const libc::c_char,
+        b"w\0" as *const u8 as *const libc::c_char,
+    );
+    if zero.is_null() || null.is_null() {
+        return;
+    }
+    fread(buf.as_mut_ptr() as *mut libc::c_void, 1, 8, zero);
+    fgets(buf.as_mut_ptr(), 8, zero);
+    fwrite(buf.as_ptr() as *const libc::c_void, 1, 8, null);
+    fputs(b"file\n\0" as *const u8 as *const libc::c_char, null);
+    fclose(zero);
+    fclose(null);
+}
+
+fn main() {
+    unsafe {
+        mem();
+        str();
+        file();
+    }
+}
@@ -0,0 +1,28 @@
+//! A multi-threaded program for testing that the runtime tags each event with the thread that
+//! emitted it.  Each thread works on its own allocation, so the objects of different threads are
+//! disjoint.
+
+use std::thread;
+
+fn fill(n: i32) -> i32 {
+    let mut values = Box::new([0; 4]);
+    let p = values.as_mut_ptr();
+    for i in 0..4 {
+        unsafe {
+            *p.add(i) = n * i as i32;
+        }
+    }
+    values.iter().sum()
+}
+
+fn main() {
+    let handles = (1..=2)
+        .map(|n| thread::spawn(move || fill(n)))
+        .collect::<Vec<_>>();
+    let total = handles
+        .into_iter()
+        .map(|handle| handle.join().unwrap())
+        .sum::<i32>()
+        + fill(3);
+    println!("{total}");
+}
@@ -190,6 +190,19 @@ fn run_polonius<'tcx>(
         local_ltys.push(lty);
     }
 
+    // Field types are shared by all accesses to a field within the function, so each pointer in a
+    // field type gets a single origin.  Fields are visited in a fixed order so that the numbering
+    // of origins is deterministic.
+    let mut field_tys = acx.gacx.field_tys.iter().collect::<Vec<_>>();
+    field_tys.sort_by_key(|&(&key, _)| key);
+    let field_ltys = field_tys
+        .into_iter()
+        .map(|(&key, &lty)| {
+            let lty = assign_origins(ltcx, hypothesis, &mut facts, &mut maps, lty);
+            (key, lty)
+        })
+        .collect::<HashMap<_, _>>();
+
     let mut loans = HashMap::<Local, Vec<(Path, Loan, BorrowKind)>>::new();
     // Populate `loan_issued_at` and `loans`.
     type_check::visit(
@@ -199,6 +212,7 @@ fn run_polonius<'tcx>(
         &mut maps,
         &mut loans,
         &local_ltys,
+        &field_ltys,
         mir,
     );
 
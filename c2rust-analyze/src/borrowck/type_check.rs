@@ -1,13 +1,14 @@
 use crate::borrowck::atoms::{AllFacts, AtomMaps, Loan, Origin, Path, Point, SubPoint};
 use crate::borrowck::{LTy, LTyCtxt, Label};
-use crate::context::PermissionSet;
+use crate::context::{self, PermissionSet};
 use crate::util::{self, Callee};
 use rustc_index::vec::IndexVec;
 use rustc_middle::mir::{
     BinOp, Body, BorrowKind, Local, LocalDecl, Location, Operand, Place, ProjectionElem, Rvalue,
     Statement, StatementKind, Terminator, TerminatorKind,
 };
-use rustc_middle::ty::{TyCtxt, TyKind};
+use rustc_middle::ty::{Ty, TyCtxt, TyKind};
+use rustc_span::def_id::DefId;
 use std::collections::HashMap;
 
 struct TypeChecker<'tcx, 'a> {
@@ -17,6 +18,7 @@ struct TypeChecker<'tcx, 'a> {
     maps: &'a mut AtomMaps<'tcx>,
     loans: &'a mut HashMap<Local, Vec<(Path, Loan, BorrowKind)>>,
     local_ltys: &'a [LTy<'tcx>],
+    field_ltys: &'a HashMap<(DefId, Ty<'tcx>), LTy<'tcx>>,
     local_decls: &'a IndexVec<Local, LocalDecl<'tcx>>,
 
     current_location: Location,
@@ -33,6 +35,7 @@ impl<'tcx> TypeChecker<'tcx, '_> {
 
     pub fn visit_place(&mut self, pl: Place<'tcx>) -> LTy<'tcx> {
         let mut lty = self.local_ltys[pl.local.index()];
+        let mut variant = None;
         for proj in pl.projection {
             match proj {
                 ProjectionElem::Deref => {
@@ -40,13 +43,21 @@ impl<'tcx> TypeChecker<'tcx, '_> {
                     lty = lty.args[0];
                 }
 
-                ProjectionElem::Field(f, _field_ty) => match lty.ty.kind() {
+                ProjectionElem::Field(f, field_ty) => match *lty.ty.kind() {
                     TyKind::Tuple(..) => {
                         lty = lty.args[f.as_usize()];
                     }
+                    TyKind::Adt(adt_def, _) => {
+                        let did = context::field_did(adt_def, variant.take(), f);
+                        lty = self.field_ltys[&(did, self.tcx.erase_regions(field_ty))];
+                    }
                     _ => todo!("field of {:?}", lty),
                 },
 
+                ProjectionElem::Downcast(_, idx) => {
+                    variant = Some(idx);
+                }
+
                 ref proj => panic!("unsupported projection {:?} in {:?}", proj, pl),
             }
         }
@@ -163,13 +174,38 @@ impl<'tcx> TypeChecker<'tcx, '_> {
                 })
             }
 
-            Rvalue::Cast(_, _, ty) => self.ltcx.label(ty, &mut |ty| {
-                assert!(
-                    !matches!(ty.kind(), TyKind::RawPtr(..) | TyKind::Ref(..)),
-                    "pointer Cast NYI"
-                );
-                Label::default()
-            }),
+            Rvalue::Discriminant(..) => {
+                let ty = rv.ty(self.local_decls, *self.ltcx);
+                self.ltcx.label(ty, &mut |_| Label::default())
+            }
+
+            Rvalue::Cast(_, ref op, ty) => {
+                let pointee_ty = match *ty.kind() {
+                    TyKind::RawPtr(tm) => Some(tm.ty),
+                    TyKind::Ref(_, ty, _) => Some(ty),
+                    _ => None,
+                };
+                let ltcx = self.ltcx;
+                let label_default = |ty: Ty<'tcx>| {
+                    ltcx.label(ty, &mut |ty| {
+                        assert!(
+                            !matches!(ty.kind(), TyKind::RawPtr(..) | TyKind::Ref(..)),
+                            "pointer Cast NYI"
+                        );
+                        Label::default()
+                    })
+                };
+                match pointee_ty {
+                    // A pointer-to-pointer cast produces the same pointer, so the result keeps
+                    // the operand's origin.
+                    Some(pointee_ty) => {
+                        let op_lty = self.visit_operand(op);
+                        let pointee_lty = label_default(pointee_ty);
+                        ltcx.mk(ty, ltcx.mk_slice(&[pointee_lty]), op_lty.label)
+                    }
+                    None => label_default(ty),
+                }
+            }
 
             ref rv => panic!("unsupported rvalue {:?}", rv),
         }
@@ -223,7 +259,11 @@ impl<'tcx> TypeChecker<'tcx, '_> {
                         let rv_lty = self.visit_operand(&args[0]);
                         self.do_assign(pl_lty, rv_lty);
                     }
-                    None => {}
+                    Some(Callee::Malloc)
+                    | Some(Callee::Realloc)
+                    | Some(Callee::Free)
+                    | Some(Callee::Other { .. })
+                    | None => {}
                 }
             }
             // TODO(spernsteiner): handle other `TerminatorKind`s
@@ -232,6 +272,7 @@ impl<'tcx> TypeChecker<'tcx, '_> {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn visit<'tcx>(
     tcx: TyCtxt<'tcx>,
     ltcx: LTyCtxt<'tcx>,
@@ -239,6 +280,7 @@ pub fn visit<'tcx>(
     maps: &mut AtomMaps<'tcx>,
     loans: &mut HashMap<Local, Vec<(Path, Loan, BorrowKind)>>,
     local_ltys: &[LTy<'tcx>],
+    field_ltys: &HashMap<(DefId, Ty<'tcx>), LTy<'tcx>>,
     mir: &Body<'tcx>,
 ) {
     let mut tc = TypeChecker {
@@ -248,6 +290,7 @@ pub fn visit<'tcx>(
         maps,
         loans,
         local_ltys,
+        field_ltys,
         local_decls: &mir.local_decls,
         current_location: Location::START,
     };
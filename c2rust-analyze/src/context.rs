@@ -3,14 +3,19 @@ use crate::pointer_id::{
     GlobalPointerTable, LocalPointerTable, NextGlobalPointerId, NextLocalPointerId, PointerTable,
     PointerTableMut,
 };
-use crate::util::{describe_rvalue, RvalueDesc};
+use crate::util::{self, describe_rvalue, RvalueDesc};
 use bitflags::bitflags;
 use rustc_index::vec::IndexVec;
+use rustc_middle::mir::tcx::PlaceTy;
+use rustc_middle::mir::visit::{PlaceContext, Visitor};
 use rustc_middle::mir::{
-    Body, HasLocalDecls, Local, LocalDecls, Operand, Place, PlaceElem, PlaceRef, ProjectionElem,
-    Rvalue,
+    Body, Field, HasLocalDecls, Local, LocalDecls, Location, Operand, Place, PlaceElem, PlaceRef,
+    ProjectionElem, Rvalue,
 };
-use rustc_middle::ty::{Ty, TyCtxt, TyKind};
+use rustc_middle::ty::{AdtDef, Ty, TyCtxt, TyKind};
+use rustc_span::def_id::DefId;
+use rustc_target::abi::VariantIdx;
+use std::collections::HashMap;
 
 bitflags! {
     #[derive(Default)]
@@ -34,6 +39,10 @@ bitflags! {
         const OFFSET_ADD = 0x0010;
         /// This pointer can be offset in the negative direction.
         const OFFSET_SUB = 0x0020;
+        /// This pointer can be freed, so it owns its pointee.  Pointers derived from the result
+        /// of an allocation function like `malloc` get this permission if they are later passed
+        /// to `free` or `realloc`.
+        const FREE = 0x0040;
     }
 }
 
@@ -55,10 +64,29 @@ pub use crate::pointer_id::PointerId;
 pub type LTy<'tcx> = LabeledTy<'tcx, PointerId>;
 pub type LTyCtxt<'tcx> = LabeledTyCtxt<'tcx, PointerId>;
 
+/// The signature of a function defined in the current crate, labeled with global `PointerId`s.
+/// The corresponding locals of the function's body use the same labels.
+#[derive(Clone, Copy, Debug)]
+pub struct LFnSig<'tcx> {
+    pub inputs: &'tcx [LTy<'tcx>],
+    pub output: LTy<'tcx>,
+}
+
 pub struct GlobalAnalysisCtxt<'tcx> {
     pub tcx: TyCtxt<'tcx>,
     pub lcx: LTyCtxt<'tcx>,
 
+    /// Signatures of the functions defined in the current crate.
+    pub fn_sigs: HashMap<DefId, LFnSig<'tcx>>,
+    /// Types of the fields of ADTs, keyed by the field's `DefId` and its type with regions
+    /// erased.  This covers the fields of ADTs defined in the current crate, and the fields of
+    /// ADTs from other crates and of instances of generic ADTs that are used in a function body.
+    pub field_tys: HashMap<(DefId, Ty<'tcx>), LTy<'tcx>>,
+    /// Types of the statics defined in the current crate.
+    pub static_tys: HashMap<DefId, LTy<'tcx>>,
+    /// The pointer to each static, for use in `&STATIC` expressions.
+    pub addr_of_static: HashMap<DefId, PointerId>,
+
     next_ptr_id: NextGlobalPointerId,
 }
 
@@ -83,6 +111,10 @@ impl<'tcx> GlobalAnalysisCtxt<'tcx> {
         GlobalAnalysisCtxt {
             tcx,
             lcx: LabeledTyCtxt::new(tcx),
+            fn_sigs: HashMap::new(),
+            field_tys: HashMap::new(),
+            static_tys: HashMap::new(),
+            addr_of_static: HashMap::new(),
             next_ptr_id: NextGlobalPointerId::new(),
         }
     }
@@ -99,27 +131,120 @@ impl<'tcx> GlobalAnalysisCtxt<'tcx> {
         AnalysisCtxt::from_data(self, mir, data)
     }
 
-    #[allow(dead_code)]
-    pub fn _new_pointer(&mut self) -> PointerId {
+    pub fn new_pointer(&mut self) -> PointerId {
         self.next_ptr_id.next()
     }
 
+    /// Label `ty` with a fresh global `PointerId` in each pointer position.
+    pub fn assign_pointer_ids(&mut self, ty: Ty<'tcx>) -> LTy<'tcx> {
+        self.lcx.label(ty, &mut |ty| match ty.kind() {
+            TyKind::Ref(_, _, _) | TyKind::RawPtr(_) => self.next_ptr_id.next(),
+            _ => PointerId::NONE,
+        })
+    }
+
+    /// Label the type `ty` of the field `did` with fresh global `PointerId`s, unless it already
+    /// has a labeled type.
+    pub fn assign_field_pointer_ids(&mut self, did: DefId, ty: Ty<'tcx>) {
+        let ty = self.tcx.erase_regions(ty);
+        if self.field_tys.contains_key(&(did, ty)) {
+            return;
+        }
+        let lty = self.assign_pointer_ids(ty);
+        self.field_tys.insert((did, ty), lty);
+    }
+
+    /// Assign global `PointerId`s to the types of all fields that are projected out of places in
+    /// `mir` and don't have a type yet, such as fields of ADTs from other crates.  Each distinct
+    /// field type gets its own `PointerId`s, so the fields of `S<*mut i32>` and `S<*mut u8>`
+    /// don't share them.
+    pub fn assign_body_field_pointer_ids(&mut self, mir: &Body<'tcx>) {
+        struct FieldVisitor<'a, 'tcx> {
+            gacx: &'a mut GlobalAnalysisCtxt<'tcx>,
+            mir: &'a Body<'tcx>,
+        }
+
+        impl<'tcx> Visitor<'tcx> for FieldVisitor<'_, 'tcx> {
+            fn visit_place(&mut self, pl: &Place<'tcx>, _ctx: PlaceContext, _loc: Location) {
+                let tcx = self.gacx.tcx;
+                let mut place_ty = PlaceTy::from_ty(self.mir.local_decls[pl.local].ty);
+                for proj in pl.projection {
+                    if let ProjectionElem::Field(f, field_ty) = proj {
+                        if let TyKind::Adt(adt_def, _) = *place_ty.ty.kind() {
+                            let did = field_did(adt_def, place_ty.variant_index, f);
+                            self.gacx.assign_field_pointer_ids(did, field_ty);
+                        }
+                    }
+                    place_ty = place_ty.projection_ty(tcx, proj);
+                }
+            }
+        }
+
+        FieldVisitor { gacx: self, mir }.visit_body(mir);
+    }
+
+    /// Get the type of field `field` of the struct or union `adt_def`, or of variant `variant`
+    /// of the enum `adt_def`.  `ty` is the unlabeled type of the field, as given in its
+    /// `ProjectionElem::Field`.
+    pub fn field_ty(
+        &self,
+        adt_def: AdtDef<'tcx>,
+        variant: Option<VariantIdx>,
+        field: Field,
+        ty: Ty<'tcx>,
+    ) -> LTy<'tcx> {
+        let did = field_did(adt_def, variant, field);
+        let ty = self.tcx.erase_regions(ty);
+        self.field_tys.get(&(did, ty)).copied().unwrap_or_else(|| {
+            panic!(
+                "no type for field {:?} of {:?} at type {:?}",
+                field, adt_def, ty
+            )
+        })
+    }
+
     pub fn num_pointers(&self) -> usize {
         self.next_ptr_id.num_pointers()
     }
 
     pub fn remap_pointers(
         &mut self,
-        _map: &GlobalPointerTable<PointerId>,
+        map: &GlobalPointerTable<PointerId>,
         counter: NextGlobalPointerId,
     ) {
         let GlobalAnalysisCtxt {
             tcx: _,
-            lcx: _,
+            lcx,
+            ref mut fn_sigs,
+            ref mut field_tys,
+            ref mut static_tys,
+            ref mut addr_of_static,
             ref mut next_ptr_id,
         } = *self;
 
-        // `GlobalAnalysisCtxt` doesn't yet have any fields that need remapping.
+        let remap = |lty: LTy<'tcx>| {
+            lcx.relabel(lty, &mut |inner_lty| {
+                if inner_lty.label.is_none() {
+                    PointerId::NONE
+                } else {
+                    map[inner_lty.label]
+                }
+            })
+        };
+
+        for sig in fn_sigs.values_mut() {
+            let inputs = sig.inputs.iter().map(|&lty| remap(lty)).collect::<Vec<_>>();
+            sig.inputs = lcx.mk_slice(&inputs);
+            sig.output = remap(sig.output);
+        }
+
+        for lty in field_tys.values_mut().chain(static_tys.values_mut()) {
+            *lty = remap(*lty);
+        }
+
+        for ptr in addr_of_static.values_mut() {
+            *ptr = map[*ptr];
+        }
 
         *next_ptr_id = counter;
     }
@@ -195,24 +320,43 @@ impl<'a, 'tcx> AnalysisCtxt<'a, 'tcx> {
         }
     }
 
-    fn project(&self, lty: LTy<'tcx>, proj: &PlaceElem<'tcx>) -> LTy<'tcx> {
-        match *proj {
-            ProjectionElem::Deref => {
-                assert!(matches!(lty.kind(), TyKind::Ref(..) | TyKind::RawPtr(..)));
-                assert_eq!(lty.args.len(), 1);
-                lty.args[0]
-            }
-            ProjectionElem::Field(f, _) => match lty.kind() {
-                TyKind::Tuple(_) => lty.args[f.index()],
-                TyKind::Adt(..) => todo!("type_of Field(Adt)"),
-                _ => panic!("Field projection is unsupported on type {:?}", lty),
-            },
-            ProjectionElem::Index(..) | ProjectionElem::ConstantIndex { .. } => {
-                todo!("type_of Index")
-            }
-            ProjectionElem::Subslice { .. } => todo!("type_of Subslice"),
-            ProjectionElem::Downcast(..) => todo!("type_of Downcast"),
+    /// The type of a reference to the static `did`, where `ty` is the type of the reference.
+    pub fn static_ptr_lty(&self, did: DefId, ty: Ty<'tcx>) -> LTy<'tcx> {
+        let pointee_lty = self.gacx.static_tys[&did];
+        let args = self.lcx().mk_slice(&[pointee_lty]);
+        self.lcx().mk(ty, args, self.gacx.addr_of_static[&did])
+    }
+
+    /// Apply the projections `projs` to a place of type `lty`.
+    fn project(&self, lty: LTy<'tcx>, projs: &[PlaceElem<'tcx>]) -> LTy<'tcx> {
+        let mut lty = lty;
+        // The enum variant selected by the preceding `Downcast`, if any.
+        let mut variant = None;
+        for proj in projs {
+            lty = match *proj {
+                ProjectionElem::Deref => {
+                    assert!(matches!(lty.kind(), TyKind::Ref(..) | TyKind::RawPtr(..)));
+                    assert_eq!(lty.args.len(), 1);
+                    lty.args[0]
+                }
+                ProjectionElem::Field(f, field_ty) => match *lty.kind() {
+                    TyKind::Tuple(_) => lty.args[f.index()],
+                    TyKind::Adt(adt_def, _) => {
+                        self.gacx.field_ty(adt_def, variant.take(), f, field_ty)
+                    }
+                    _ => panic!("Field projection is unsupported on type {:?}", lty),
+                },
+                ProjectionElem::Index(..) | ProjectionElem::ConstantIndex { .. } => {
+                    todo!("type_of Index")
+                }
+                ProjectionElem::Subslice { .. } => todo!("type_of Subslice"),
+                ProjectionElem::Downcast(_, idx) => {
+                    variant = Some(idx);
+                    lty
+                }
+            };
         }
+        lty
     }
 }
 
@@ -279,11 +423,7 @@ impl<'tcx> TypeOf<'tcx> for Place<'tcx> {
 
 impl<'tcx> TypeOf<'tcx> for PlaceRef<'tcx> {
     fn type_of(&self, acx: &AnalysisCtxt<'_, 'tcx>) -> LTy<'tcx> {
-        let mut ty = acx.type_of(self.local);
-        for proj in self.projection {
-            ty = acx.project(ty, proj);
-        }
-        ty
+        acx.project(acx.type_of(self.local), self.projection)
     }
 }
 
@@ -291,7 +431,10 @@ impl<'tcx> TypeOf<'tcx> for Operand<'tcx> {
     fn type_of(&self, acx: &AnalysisCtxt<'_, 'tcx>) -> LTy<'tcx> {
         match *self {
             Operand::Move(pl) | Operand::Copy(pl) => acx.type_of(pl),
-            Operand::Constant(ref c) => label_no_pointers(acx, c.ty()),
+            Operand::Constant(ref c) => match c.check_static_ptr(acx.tcx()) {
+                Some(did) => acx.static_ptr_lty(did, c.ty()),
+                None => label_no_pointers(acx, c.ty()),
+            },
         }
     }
 }
@@ -309,7 +452,7 @@ impl<'tcx> TypeOf<'tcx> for Rvalue<'tcx> {
                             self, desc, base_lty
                         );
                         (
-                            acx.project(base_lty, &PlaceElem::Deref),
+                            acx.project(base_lty, &[PlaceElem::Deref]),
                             proj,
                             base_lty.label,
                         )
@@ -319,10 +462,7 @@ impl<'tcx> TypeOf<'tcx> for Rvalue<'tcx> {
                     }
                 };
 
-                let mut pointee_lty = pointee_lty;
-                for p in proj {
-                    pointee_lty = acx.project(pointee_lty, p);
-                }
+                let pointee_lty = acx.project(pointee_lty, proj);
 
                 let ty = self.ty(acx, acx.tcx());
                 let pointee_ty = match *ty.kind() {
@@ -372,6 +512,18 @@ impl<'tcx> TypeOf<'tcx> for Rvalue<'tcx> {
                     // same structure.
                     return acx.lcx().mk(ty, op_lty.args, op_lty.label);
                 }
+                if let (Some(op_pointee), Some(ty_pointee)) = (op_pointee, ty_pointee) {
+                    if util::is_c_void(acx.tcx(), op_pointee.ty)
+                        || util::is_c_void(acx.tcx(), ty_pointee)
+                    {
+                        // A cast to or from `void*`, such as on the result of `malloc` or the
+                        // argument of `free`.  The result is the same pointer, but its pointee
+                        // has a different type.
+                        let pointee_lty = label_no_pointers(acx, ty_pointee);
+                        let args = acx.lcx().mk_slice(&[pointee_lty]);
+                        return acx.lcx().mk(ty, args, op_lty.label);
+                    }
+                }
 
                 label_no_pointers(acx, ty)
             }
@@ -390,6 +542,16 @@ impl<'tcx> TypeOf<'tcx> for Rvalue<'tcx> {
     }
 }
 
+/// The `DefId` of field `field` of the struct or union `adt_def`, or of variant `variant` of the
+/// enum `adt_def`.
+pub fn field_did(adt_def: AdtDef, variant: Option<VariantIdx>, field: Field) -> DefId {
+    let variant_def = match variant {
+        Some(idx) => adt_def.variant(idx),
+        None => adt_def.non_enum_variant(),
+    };
+    variant_def.fields[field.index()].did
+}
+
 /// Label a type that contains no pointer types by applying `PointerId::NONE` everywhere.  Panics
 /// if the type does contain pointers.
 fn label_no_pointers<'tcx>(acx: &AnalysisCtxt<'_, 'tcx>, ty: Ty<'tcx>) -> LTy<'tcx> {
@@ -403,6 +565,7 @@ fn label_no_pointers<'tcx>(acx: &AnalysisCtxt<'_, 'tcx>, ty: Ty<'tcx>) -> LTy<'t
     })
 }
 
+#[derive(Clone, PartialEq, Eq)]
 pub struct GlobalAssignment {
     pub perms: GlobalPointerTable<PermissionSet>,
     pub flags: GlobalPointerTable<FlagSet>,
@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::mem;
 
 use crate::context::{AnalysisCtxt, Assignment, FlagSet, PermissionSet, PointerId};
@@ -12,7 +13,9 @@ enum Constraint {
     Subset(PointerId, PointerId),
     /// Pointer `.0` must have all the permissions in `.1`.
     AllPerms(PointerId, PermissionSet),
-    /// Pointer `.0` must not have any of the permissions in `.1`.
+    /// Pointer `.0` must not get any of the permissions in `.1` from the pointers derived from
+    /// it.  This is a hint: permissions that an `AllPerms` constraint requires on `.0` itself are
+    /// still added.
     NoPerms(PointerId, PermissionSet),
 }
 
@@ -30,11 +33,26 @@ impl DataflowConstraints {
         self.constraints.push(Constraint::AllPerms(ptr, perms));
     }
 
-    #[allow(dead_code)]
-    fn _add_no_perms(&mut self, ptr: PointerId, perms: PermissionSet) {
+    pub fn add_no_perms(&mut self, ptr: PointerId, perms: PermissionSet) {
         self.constraints.push(Constraint::NoPerms(ptr, perms));
     }
 
+    /// Iterate over the `AllPerms(ptr, perms)` constraints.
+    pub fn all_perms(&self) -> impl Iterator<Item = (PointerId, PermissionSet)> + '_ {
+        self.constraints.iter().filter_map(|c| match *c {
+            Constraint::AllPerms(ptr, perms) => Some((ptr, perms)),
+            _ => None,
+        })
+    }
+
+    /// Iterate over the `Subset(a, b)` constraints, where `a` is derived from `b`.
+    pub fn subsets(&self) -> impl Iterator<Item = (PointerId, PointerId)> + '_ {
+        self.constraints.iter().filter_map(|c| match *c {
+            Constraint::Subset(a, b) => Some((a, b)),
+            _ => None,
+        })
+    }
+
     /// Update the pointer permissions in `hypothesis` to satisfy these constraints.
     pub fn propagate(&self, hypothesis: &mut PointerTableMut<PermissionSet>) -> bool {
         eprintln!("=== propagating ===");
@@ -47,13 +65,32 @@ impl DataflowConstraints {
             eprintln!("  {}: {:?}", id, p);
         }
 
-        struct PropagatePerms;
+        // Permissions forbidden by `NoPerms` constraints are never propagated to the pointer from
+        // the pointers derived from it, but `AllPerms` constraints on the pointer still add them.
+        let mut forbidden = HashMap::new();
+        for c in &self.constraints {
+            if let Constraint::NoPerms(ptr, perms) = *c {
+                *forbidden.entry(ptr).or_insert_with(PermissionSet::empty) |= perms;
+            }
+        }
+
+        struct PropagatePerms {
+            forbidden: HashMap<PointerId, PermissionSet>,
+        }
+        impl PropagatePerms {
+            fn forbidden(&self, ptr: PointerId) -> PermissionSet {
+                self.forbidden
+                    .get(&ptr)
+                    .copied()
+                    .unwrap_or_else(PermissionSet::empty)
+            }
+        }
         impl PropagateRules<PermissionSet> for PropagatePerms {
             fn subset(
                 &mut self,
                 _a_ptr: PointerId,
                 a_val: &PermissionSet,
-                _b_ptr: PointerId,
+                b_ptr: PointerId,
                 b_val: &PermissionSet,
             ) -> (PermissionSet, PermissionSet) {
                 let old_a = *a_val;
@@ -72,11 +109,21 @@ impl DataflowConstraints {
                 let PROPAGATE_UP = PermissionSet::READ
                     | PermissionSet::WRITE
                     | PermissionSet::OFFSET_ADD
-                    | PermissionSet::OFFSET_SUB;
+                    | PermissionSet::OFFSET_SUB
+                    | PermissionSet::FREE;
+
+                // An owning pointer can't be narrowed to part of its allocation, so if `a` and
+                // `b` are both owning, `a` points to as many elements as `b` does.
+                #[allow(bad_style)]
+                let OFFSET = PermissionSet::OFFSET_ADD | PermissionSet::OFFSET_SUB;
+                let mut new_a = old_a & !(!old_b & PROPAGATE_DOWN);
+                if (old_a & old_b).contains(PermissionSet::FREE) {
+                    new_a |= old_b & OFFSET;
+                }
 
                 (
-                    old_a & !(!old_b & PROPAGATE_DOWN),
-                    old_b | (old_a & PROPAGATE_UP),
+                    new_a,
+                    old_b | (old_a & PROPAGATE_UP & !self.forbidden(b_ptr)),
                 )
             }
 
@@ -92,14 +139,15 @@ impl DataflowConstraints {
             fn no_perms(
                 &mut self,
                 _ptr: PointerId,
-                perms: PermissionSet,
+                _perms: PermissionSet,
                 val: &PermissionSet,
             ) -> PermissionSet {
-                *val & !perms
+                // Handled in `subset`, so that permissions from `AllPerms` are kept.
+                *val
             }
         }
 
-        match self.propagate_inner(hypothesis, &mut PropagatePerms) {
+        match self.propagate_inner(hypothesis, &mut PropagatePerms { forbidden }) {
             Ok(changed) => changed,
             Err(msg) => {
                 panic!("{}", msg);
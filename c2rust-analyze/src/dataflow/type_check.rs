@@ -6,7 +6,7 @@ use rustc_middle::mir::{
     BinOp, Body, Mutability, Operand, Place, PlaceRef, ProjectionElem, Rvalue, Statement,
     StatementKind, Terminator, TerminatorKind,
 };
-use rustc_middle::ty::TyKind;
+use rustc_middle::ty::{ParamEnv, TyKind};
 
 /// Visitor that walks over the MIR, computing types of rvalues/operands/places and generating
 /// constraints as a side effect.
@@ -27,6 +27,19 @@ impl<'tcx> TypeChecker<'tcx, '_> {
         self.equiv_constraints.push((a, b));
     }
 
+    /// Get the value of `op` if it's an `isize` constant.
+    fn const_isize(&self, op: &Operand<'tcx>) -> Option<i128> {
+        let tcx = self.acx.tcx();
+        let c = op.constant()?;
+        if c.ty() != tcx.types.isize {
+            return None;
+        }
+        let bits = c
+            .literal
+            .try_eval_bits(tcx, ParamEnv::reveal_all(), tcx.types.isize)?;
+        Some(tcx.data_layout.pointer_size.sign_extend(bits) as i128)
+    }
+
     fn record_access(&mut self, ptr: PointerId, mutbl: Mutability) {
         eprintln!("record_access({:?}, {:?})", ptr, mutbl);
         if ptr == PointerId::NONE {
@@ -50,6 +63,7 @@ impl<'tcx> TypeChecker<'tcx, '_> {
     pub fn visit_place_ref(&mut self, pl: PlaceRef<'tcx>, ctx: PlaceContext) -> LTy<'tcx> {
         let mut lty = self.acx.local_tys[pl.local];
         let mut prev_deref_ptr = None;
+        let mut variant = None;
 
         for proj in pl.projection {
             match proj {
@@ -64,13 +78,23 @@ impl<'tcx> TypeChecker<'tcx, '_> {
                     lty = lty.args[0];
                 }
 
-                ProjectionElem::Field(f, _field_ty) => match lty.ty.kind() {
+                ProjectionElem::Field(f, field_ty) => match *lty.ty.kind() {
                     TyKind::Tuple(..) => {
                         lty = lty.args[f.as_usize()];
                     }
+                    TyKind::Adt(adt_def, _) => {
+                        lty = self
+                            .acx
+                            .gacx
+                            .field_ty(adt_def, variant.take(), *f, *field_ty);
+                    }
                     _ => todo!("field of {:?}", lty),
                 },
 
+                ProjectionElem::Downcast(_, idx) => {
+                    variant = Some(*idx);
+                }
+
                 ref proj => panic!("unsupported projection {:?} in {:?}", proj, pl),
             }
         }
@@ -106,10 +130,20 @@ impl<'tcx> TypeChecker<'tcx, '_> {
                 Rvalue::BinaryOp(..) => PointerId::NONE,
                 Rvalue::CheckedBinaryOp(BinOp::Offset, _) => todo!("visit_rvalue BinOp::Offset"),
                 Rvalue::CheckedBinaryOp(..) => PointerId::NONE,
-                Rvalue::Cast(_, _, ty) => {
-                    assert!(!matches!(ty.kind(), TyKind::RawPtr(..) | TyKind::Ref(..)));
+                Rvalue::Discriminant(pl) => {
+                    let ctx = PlaceContext::NonMutatingUse(NonMutatingUseContext::Inspect);
+                    self.visit_place(pl, ctx);
                     PointerId::NONE
                 }
+                Rvalue::Cast(_, ref op, ty) => {
+                    if matches!(ty.kind(), TyKind::RawPtr(..) | TyKind::Ref(..)) {
+                        // Pointer-to-pointer casts produce the same pointer.  `type_of` rejects
+                        // the kinds of casts we don't support.
+                        self.visit_operand(op).label
+                    } else {
+                        PointerId::NONE
+                    }
+                }
                 _ => panic!("TODO: handle assignment of {:?}", rv),
             },
         }
@@ -125,11 +159,7 @@ impl<'tcx> TypeChecker<'tcx, '_> {
                 let ctx = PlaceContext::NonMutatingUse(NonMutatingUseContext::Move);
                 self.visit_place(pl, ctx)
             }
-            Operand::Constant(ref c) => {
-                let ty = c.ty();
-                // TODO
-                self.acx.lcx().label(ty, &mut |_| PointerId::NONE)
-            }
+            Operand::Constant(..) => self.acx.type_of(op),
         }
     }
 
@@ -152,7 +182,12 @@ impl<'tcx> TypeChecker<'tcx, '_> {
 
         let pl_pointee = pl_lty.args[0];
         let rv_pointee = rv_lty.args[0];
-        assert_eq!(pl_pointee.ty, rv_pointee.ty);
+        // Types on the two sides of a call can differ in their regions.
+        let tcx = self.acx.tcx();
+        assert_eq!(
+            tcx.erase_regions(pl_pointee.ty),
+            tcx.erase_regions(rv_pointee.ty)
+        );
         for (pl_sub_lty, rv_sub_lty) in pl_pointee.iter().zip(rv_pointee.iter()) {
             eprintln!("equate {:?} = {:?}", pl_sub_lty, rv_sub_lty);
             if pl_sub_lty.label != PointerId::NONE || rv_sub_lty.label != PointerId::NONE {
@@ -209,9 +244,60 @@ impl<'tcx> TypeChecker<'tcx, '_> {
                         assert!(args.len() == 2);
                         let rv_lty = self.visit_operand(&args[0]);
                         self.do_assign(pl_lty.label, rv_lty.label);
-                        let perms = PermissionSet::OFFSET_ADD | PermissionSet::OFFSET_SUB;
+                        let perms = match self.const_isize(&args[1]) {
+                            Some(off) if off > 0 => PermissionSet::OFFSET_ADD,
+                            Some(off) if off < 0 => PermissionSet::OFFSET_SUB,
+                            Some(_) => PermissionSet::empty(),
+                            None => PermissionSet::OFFSET_ADD | PermissionSet::OFFSET_SUB,
+                        };
                         self.constraints.add_all_perms(rv_lty.label, perms);
                     }
+                    Some(Callee::Malloc) => {
+                        // The result is a new pointer, unrelated to any other.
+                        let ctx = PlaceContext::MutatingUse(MutatingUseContext::Store);
+                        self.visit_place(destination, ctx);
+                    }
+                    Some(Callee::Realloc) => {
+                        // `realloc` resizes an array, so both pointers point to one.
+                        let ctx = PlaceContext::MutatingUse(MutatingUseContext::Store);
+                        let pl_lty = self.visit_place(destination, ctx);
+                        self.constraints
+                            .add_all_perms(pl_lty.label, PermissionSet::OFFSET_ADD);
+                        let arg_lty = self.visit_operand(&args[0]);
+                        self.constraints.add_all_perms(
+                            arg_lty.label,
+                            PermissionSet::FREE | PermissionSet::OFFSET_ADD,
+                        );
+                    }
+                    Some(Callee::Free) => {
+                        let arg_lty = self.visit_operand(&args[0]);
+                        self.constraints
+                            .add_all_perms(arg_lty.label, PermissionSet::FREE);
+                    }
+                    Some(Callee::Other { def_id, substs }) => {
+                        // Calls to functions in the current crate pass each argument as if by
+                        // assignment to the callee's parameter, and likewise for the return
+                        // value.  The callee's signature uses global `PointerId`s, so this links
+                        // the permissions of the pointers in the caller with those in the callee.
+                        //
+                        // The signature of a generic callee mentions its type parameters, where
+                        // the caller's types mention their substitutions, and the labels of the
+                        // two don't line up.  We treat such calls like calls to unknown functions.
+                        let is_generic = substs.non_erasable_generics().next().is_some();
+                        let sig = self.acx.gacx.fn_sigs.get(&def_id).filter(|_| !is_generic);
+                        if let Some(&sig) = sig {
+                            for (arg, &input_lty) in args.iter().zip(sig.inputs) {
+                                let arg_lty = self.visit_operand(arg);
+                                self.do_assign(input_lty.label, arg_lty.label);
+                                self.do_unify_pointees(input_lty, arg_lty);
+                            }
+
+                            let ctx = PlaceContext::MutatingUse(MutatingUseContext::Store);
+                            let pl_lty = self.visit_place(destination, ctx);
+                            self.do_assign(pl_lty.label, sig.output.label);
+                            self.do_unify_pointees(pl_lty, sig.output);
+                        }
+                    }
                     None => {}
                 }
             }
@@ -7,6 +7,7 @@ use rustc_middle::mir::{
     TerminatorKind,
 };
 use rustc_span::{Span, DUMMY_SP};
+use serde::Serialize;
 
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct ExprLoc {
@@ -15,7 +16,7 @@ pub struct ExprLoc {
     pub sub: Vec<SubLoc>,
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Debug, Serialize)]
 pub enum SubLoc {
     /// The LHS of an assignment or call.  `StatementKind::Assign/TerminatorKind::Call -> Place`
     Dest,
@@ -31,7 +32,7 @@ pub enum SubLoc {
     PlacePointer(usize),
 }
 
-#[derive(Clone, PartialEq, Eq, Debug)]
+#[derive(Clone, PartialEq, Eq, Debug, Serialize)]
 pub enum RewriteKind {
     /// Replace `ptr.offset(i)` with something like `&ptr[i..]`.
     OffsetSlice { mutbl: bool },
@@ -39,6 +40,34 @@ pub enum RewriteKind {
     SliceFirst { mutbl: bool },
     /// Replace `ptr` with `&*ptr`, converting `&mut T` to `&T`.
     MutToImm,
+    /// Replace `ptr.offset(i)` with `(&*ptr.0, (ptr.1 as isize + i) as usize)`, moving the
+    /// `OffsetPtr` cursor `ptr` to get another one.
+    OffsetPtrOffset { mutbl: bool },
+    /// Replace `ptr.offset(i)` with `&ptr.0[(ptr.1 as isize + i) as usize]`.
+    OffsetPtrOffsetFirst { mutbl: bool },
+    /// Replace `ptr.offset(i)` with `&ptr.0[(ptr.1 as isize + i) as usize..]`.
+    OffsetPtrOffsetSlice { mutbl: bool },
+    /// Replace `ptr` with `&ptr.0[ptr.1]`, converting an `OffsetPtr` to its current element.
+    OffsetPtrFirst { mutbl: bool },
+    /// Replace `ptr` with `&ptr.0[ptr.1..]`, converting an `OffsetPtr` to the rest of its slice.
+    OffsetPtrSlice { mutbl: bool },
+    /// Replace `ptr` with `(&*ptr.0, ptr.1)`, converting `(&mut [T], usize)` to `(&[T], usize)`.
+    OffsetPtrMutToImm,
+    /// Replace `slice` with `(&*slice, 0)`, converting a slice (or `Box<[T]>`) to an `OffsetPtr`
+    /// at its start.
+    SliceOffsetPtr { mutbl: bool },
+    /// Replace `ptr` with `&*ptr` or `&mut *ptr`, borrowing from an owning pointer such as
+    /// `Box<T>`, or from a raw pointer.
+    Borrow { mutbl: bool },
+    /// Replace `malloc(size) as *mut T` with `Box::new(<T>::default())`.
+    MallocBox { pointee: String },
+    /// Replace `malloc(size) as *mut T` or `calloc(n, size) as *mut T` with a `Box<[T]>` built by
+    /// `vec!`, with one element per `size_of::<T>()` bytes.
+    MallocSlice { pointee: String },
+    /// Replace `realloc(ptr, size) as *mut T` with code that resizes the `Box<[T]>` `ptr`.
+    ReallocSlice { pointee: String },
+    /// Replace `free(ptr)` with `drop(ptr)`.
+    FreeToDrop,
 }
 
 #[derive(Clone, PartialEq, Eq, Debug)]
@@ -177,14 +206,30 @@ impl<'a, 'tcx> ExprRewriteVisitor<'a, 'tcx> {
                             self.visit_ptr_offset(&args[0], pl_ty);
                             return;
                         }
+                        Callee::Malloc | Callee::Realloc => {
+                            // These are rewritten at the cast of their result, which gives the
+                            // type being allocated.  See `visit_alloc_cast`.
+                            return;
+                        }
+                        Callee::Free => {
+                            self.visit_free(&args[0]);
+                            return;
+                        }
+                        Callee::Other { .. } => {}
                     }
                 }
 
                 // General case: cast `args` to match the signature of `func`.
                 let poly_sig = func_ty.fn_sig(tcx);
                 let sig = tcx.erase_late_bound_regions(poly_sig);
+                let lsig = match util::ty_callee(tcx, func_ty) {
+                    Some(Callee::Other { def_id, .. }) => {
+                        self.acx.gacx.fn_sigs.get(&def_id).copied()
+                    }
+                    _ => None,
+                };
 
-                for (i, _op) in args.iter().enumerate() {
+                for (i, op) in args.iter().enumerate() {
                     if i >= sig.inputs().len() {
                         // This is a call to a variadic function, and we've gone past the end of
                         // the declared arguments.
@@ -193,9 +238,12 @@ impl<'a, 'tcx> ExprRewriteVisitor<'a, 'tcx> {
                         continue;
                     }
 
-                    // TODO: get the `LTy` to use for the callee's argument
-                    // let expect_ty = ...;
-                    // self.enter_call_arg(i, |v| v.visit_operand(op, expect_ty));
+                    // TODO: get the `LTy` to use for arguments of functions outside the current
+                    // crate
+                    if let Some(lsig) = lsig {
+                        let expect_ty = lsig.inputs[i];
+                        self.enter_call_arg(i, |v| v.visit_operand(op, expect_ty));
+                    }
                 }
             }
             TerminatorKind::Assert { .. } => {}
@@ -228,8 +276,11 @@ impl<'a, 'tcx> ExprRewriteVisitor<'a, 'tcx> {
             Rvalue::Len(_pl) => {
                 // TODO
             }
-            Rvalue::Cast(_kind, ref _op, _ty) => {
-                // TODO
+            Rvalue::Cast(_kind, ref op, _ty) => {
+                if let Some(callee) = self.alloc_call(op) {
+                    self.visit_alloc_cast(callee, expect_ty);
+                }
+                // TODO: other casts
             }
             Rvalue::BinaryOp(_bop, ref _ops) => {
                 // TODO
@@ -296,22 +347,40 @@ impl<'a, 'tcx> ExprRewriteVisitor<'a, 'tcx> {
         let result_ptr = result_ty.label;
         let (result_own, result_qty) =
             type_desc::perms_to_desc(self.perms[result_ptr], self.flags[result_ptr]);
+        let mutbl = matches!(result_own, Ownership::Mut);
 
-        let arg_expect_own = result_own;
-        // TODO: infer `arg_expect_qty` based on the type of offset this is (positive / unknown)
-        let arg_expect_qty = match result_qty {
-            Quantity::Single => Quantity::Slice,
-            Quantity::Slice => Quantity::Slice,
-            Quantity::OffsetPtr => todo!("OffsetPtr"),
-        };
+        // The argument has at least the permissions of the result.  If it stays a raw pointer
+        // (because it's an owner that can be offset backward), so does the offset, and only the
+        // result may need to be converted.
+        let arg_desc = self
+            .acx
+            .ptr_of(op)
+            .map(|ptr| type_desc::perms_to_desc(self.perms[ptr], self.flags[ptr]));
+        if matches!(arg_desc, Some((own, _)) if is_raw(own)) {
+            match (result_own, result_qty) {
+                (Ownership::Raw | Ownership::RawMut, _) => {}
+                (Ownership::Imm | Ownership::Mut, Quantity::Single) => {
+                    self.emit(RewriteKind::Borrow { mutbl });
+                }
+                desc => eprintln!("unsupported raw pointer offset producing {:?}", desc),
+            }
+            return;
+        }
 
-        self.enter_call_arg(0, |v| {
-            v.visit_operand_desc(op, arg_expect_own, arg_expect_qty)
-        });
+        // An `OffsetPtr` moves its index, and borrowing its slice converts it to the ownership of
+        // the result.
+        if matches!(arg_desc, Some((_, Quantity::OffsetPtr))) {
+            self.emit(match result_qty {
+                Quantity::OffsetPtr => RewriteKind::OffsetPtrOffset { mutbl },
+                Quantity::Single => RewriteKind::OffsetPtrOffsetFirst { mutbl },
+                Quantity::Slice => RewriteKind::OffsetPtrOffsetSlice { mutbl },
+            });
+            return;
+        }
 
-        // Emit `OffsetSlice` for the offset itself.
-        let mutbl = matches!(result_own, Ownership::Mut);
+        self.enter_call_arg(0, |v| v.visit_operand_desc(op, result_own, Quantity::Slice));
 
+        // Emit `OffsetSlice` for the offset itself.
         self.emit(RewriteKind::OffsetSlice { mutbl });
 
         // If the result is `Single`, also insert an upcast.
@@ -320,6 +389,75 @@ impl<'a, 'tcx> ExprRewriteVisitor<'a, 'tcx> {
         }
     }
 
+    /// If `op` is the result of a call to `malloc`, `calloc` or `realloc`, return the callee.
+    fn alloc_call(&self, op: &Operand<'tcx>) -> Option<Callee<'tcx>> {
+        let local = op.place()?.as_local()?;
+        let tcx = self.acx.tcx();
+        self.mir
+            .basic_blocks()
+            .iter()
+            .find_map(|bb| match bb.terminator().kind {
+                TerminatorKind::Call {
+                    ref func,
+                    destination,
+                    ..
+                } if destination.as_local() == Some(local) => {
+                    match util::ty_callee(tcx, func.ty(self.mir, tcx))? {
+                        callee @ (Callee::Malloc | Callee::Realloc) => Some(callee),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            })
+    }
+
+    /// Rewrite the allocation `malloc(size) as *mut T` to produce the owning pointer inferred
+    /// for its result.
+    fn visit_alloc_cast(&mut self, callee: Callee<'tcx>, result_ty: LTy<'tcx>) {
+        let ptr = result_ty.label;
+        if ptr == PointerId::NONE {
+            return;
+        }
+        let (own, qty) = type_desc::perms_to_desc(self.perms[ptr], self.flags[ptr]);
+        if is_raw(own) {
+            return;
+        }
+
+        let pointee = result_ty.args[0].ty.to_string();
+        let kind = match (callee, own, qty) {
+            (Callee::Malloc, Ownership::Box, Quantity::Single) => {
+                RewriteKind::MallocBox { pointee }
+            }
+            (Callee::Malloc, Ownership::Box, Quantity::Slice) => {
+                RewriteKind::MallocSlice { pointee }
+            }
+            (Callee::Realloc, Ownership::Box, Quantity::Slice) => {
+                RewriteKind::ReallocSlice { pointee }
+            }
+            (callee, own, qty) => {
+                eprintln!(
+                    "unsupported allocation: {:?} producing {:?}",
+                    callee,
+                    (own, qty)
+                );
+                return;
+            }
+        };
+        self.emit(kind);
+    }
+
+    /// Rewrite `free(ptr)` to `drop(ptr)` if `ptr` is an owning pointer.
+    fn visit_free(&mut self, op: &Operand<'tcx>) {
+        let ptr = match self.acx.ptr_of(op) {
+            Some(ptr) => ptr,
+            None => return,
+        };
+        let (own, _) = type_desc::perms_to_desc(self.perms[ptr], self.flags[ptr]);
+        if own == Ownership::Box {
+            self.emit(RewriteKind::FreeToDrop);
+        }
+    }
+
     fn emit(&mut self, rw: RewriteKind) {
         if let Some(er) = self.rewrites.last_mut() {
             if er.loc == self.loc {
@@ -352,11 +490,51 @@ impl<'a, 'tcx> ExprRewriteVisitor<'a, 'tcx> {
             return;
         }
 
-        if qty1 == qty2 && (own1, own2) == (Ownership::Mut, Ownership::Imm) {
+        let mutbl = own2 == Ownership::Mut;
+        let mut_to_imm = (own1, own2) == (Ownership::Mut, Ownership::Imm);
+
+        if qty1 == Quantity::OffsetPtr && (own1 == own2 || mut_to_imm) {
+            self.emit(match qty2 {
+                Quantity::Single => RewriteKind::OffsetPtrFirst { mutbl },
+                Quantity::Slice => RewriteKind::OffsetPtrSlice { mutbl },
+                Quantity::OffsetPtr => RewriteKind::OffsetPtrMutToImm,
+            });
+            return;
+        }
+
+        if qty1 == qty2 && mut_to_imm {
             self.emit(RewriteKind::MutToImm);
             return;
         }
 
+        let borrows_box = own1 == Ownership::Box && matches!(own2, Ownership::Imm | Ownership::Mut);
+
+        if (qty1, qty2) == (Quantity::Slice, Quantity::OffsetPtr)
+            && (own1 == own2 || mut_to_imm || borrows_box)
+        {
+            self.emit(RewriteKind::SliceOffsetPtr { mutbl });
+            return;
+        }
+
+        if qty1 == qty2 && borrows_box {
+            self.emit(RewriteKind::Borrow { mutbl });
+            return;
+        }
+
+        if qty1 == qty2
+            && qty1 == Quantity::Single
+            && (own1 == Ownership::RawMut || (own1, own2) == (Ownership::Raw, Ownership::Imm))
+            && matches!(own2, Ownership::Imm | Ownership::Mut)
+        {
+            self.emit(RewriteKind::Borrow { mutbl });
+            return;
+        }
+
+        if own1 == own2 && (qty1, qty2) == (Quantity::Slice, Quantity::Single) {
+            self.emit(RewriteKind::SliceFirst { mutbl });
+            return;
+        }
+
         eprintln!(
             "unsupported cast kind: {:?} {:?} -> {:?}",
             self.perms[ptr],
@@ -366,6 +544,10 @@ impl<'a, 'tcx> ExprRewriteVisitor<'a, 'tcx> {
     }
 }
 
+fn is_raw(own: Ownership) -> bool {
+    matches!(own, Ownership::Raw | Ownership::RawMut)
+}
+
 pub fn gen_expr_rewrites<'tcx>(
     acx: &AnalysisCtxt<'_, 'tcx>,
     asn: &Assignment,
@@ -0,0 +1,224 @@
+//! Machine-readable output of the analysis results.
+//!
+//! When `C2RUST_ANALYZE_JSON_OUTPUT` is set to a path, the inferred permissions, flags and
+//! pointer types of every local and the expression rewrites of every function are written there
+//! as a single JSON object.  The schema is described by the types in this module; `version` is
+//! bumped whenever a change to them would break existing readers.  Enums use serde's default
+//! external tagging: a unit variant is written as its name, and any other variant as an object
+//! with the name as its only key, such as `{"CallArg": 0}` or `{"Borrow": {"mutbl": false}}`.
+use crate::context::{AnalysisCtxt, Assignment, FlagSet, LTy, PermissionSet, PointerId};
+use crate::expr_rewrite::{ExprRewrite, RewriteKind, SubLoc};
+use crate::type_desc::{self, Ownership, Quantity};
+use rustc_middle::mir::{BindingForm, Body, LocalDecl, LocalInfo, Location};
+use rustc_span::def_id::LocalDefId;
+use rustc_span::Span;
+use serde::Serialize;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// The version of the output schema.
+pub const SCHEMA_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+pub struct Output {
+    pub version: u32,
+    pub functions: Vec<FunctionOutput>,
+}
+
+#[derive(Serialize)]
+pub struct FunctionOutput {
+    /// The path of the function, as printed by `TyCtxt::def_path_str`.
+    pub name: String,
+    pub span: Option<SpanOutput>,
+    pub locals: Vec<LocalOutput>,
+    pub rewrites: Vec<RewriteOutput>,
+}
+
+#[derive(Serialize)]
+pub struct LocalOutput {
+    /// The MIR local, such as `_1`.
+    pub local: String,
+    /// The name of the variable, for locals that correspond to a user variable.
+    pub name: Option<String>,
+    pub span: Option<SpanOutput>,
+    /// The type of the local, as written in MIR.
+    pub ty: String,
+    /// The pointer to the local itself, for use in `&local` expressions.
+    pub addr_of: PointerOutput,
+    /// The pointers that appear in the type of the local, in preorder.
+    pub pointers: Vec<PointerOutput>,
+}
+
+#[derive(Serialize)]
+pub struct PointerOutput {
+    pub id: String,
+    pub permissions: Vec<&'static str>,
+    pub flags: Vec<&'static str>,
+    pub ownership: Ownership,
+    pub quantity: Quantity,
+}
+
+#[derive(Serialize)]
+pub struct RewriteOutput {
+    pub span: Option<SpanOutput>,
+    pub location: LocationOutput,
+    /// The path from the MIR statement to the rewritten expression.
+    pub sub: Vec<SubLoc>,
+    pub kinds: Vec<RewriteKind>,
+}
+
+#[derive(Serialize)]
+pub struct LocationOutput {
+    pub block: usize,
+    pub statement: usize,
+}
+
+/// A source span.  Lines and columns are 1-based, and columns count characters.
+#[derive(Serialize)]
+pub struct SpanOutput {
+    pub file: String,
+    pub lo_line: usize,
+    pub lo_col: usize,
+    pub hi_line: usize,
+    pub hi_col: usize,
+}
+
+const PERMISSION_NAMES: &[(PermissionSet, &str)] = &[
+    (PermissionSet::READ, "READ"),
+    (PermissionSet::WRITE, "WRITE"),
+    (PermissionSet::UNIQUE, "UNIQUE"),
+    (PermissionSet::LINEAR, "LINEAR"),
+    (PermissionSet::OFFSET_ADD, "OFFSET_ADD"),
+    (PermissionSet::OFFSET_SUB, "OFFSET_SUB"),
+    (PermissionSet::FREE, "FREE"),
+];
+
+const FLAG_NAMES: &[(FlagSet, &str)] = &[(FlagSet::CELL, "CELL")];
+
+fn permission_names(perms: PermissionSet) -> Vec<&'static str> {
+    PERMISSION_NAMES
+        .iter()
+        .filter(|&&(p, _)| perms.contains(p))
+        .map(|&(_, name)| name)
+        .collect()
+}
+
+fn flag_names(flags: FlagSet) -> Vec<&'static str> {
+    FLAG_NAMES
+        .iter()
+        .filter(|&&(f, _)| flags.contains(f))
+        .map(|&(_, name)| name)
+        .collect()
+}
+
+fn describe_pointer(asn: &Assignment, ptr: PointerId) -> PointerOutput {
+    let perms = asn.perms()[ptr];
+    let flags = asn.flags()[ptr];
+    let (ownership, quantity) = type_desc::perms_to_desc(perms, flags);
+    PointerOutput {
+        id: ptr.to_string(),
+        permissions: permission_names(perms),
+        flags: flag_names(flags),
+        ownership,
+        quantity,
+    }
+}
+
+fn describe_pointers(asn: &Assignment, lty: LTy) -> Vec<PointerOutput> {
+    lty.iter()
+        .filter(|lty| lty.label != PointerId::NONE)
+        .map(|lty| describe_pointer(asn, lty.label))
+        .collect()
+}
+
+fn span_output(acx: &AnalysisCtxt, span: Span) -> Option<SpanOutput> {
+    if span.is_dummy() {
+        return None;
+    }
+    let source_map = acx.tcx().sess.source_map();
+    let lo = source_map.lookup_char_pos(span.lo());
+    let hi = source_map.lookup_char_pos(span.hi());
+    Some(SpanOutput {
+        file: lo.file.name.prefer_local().to_string(),
+        lo_line: lo.line,
+        lo_col: lo.col.0 + 1,
+        hi_line: hi.line,
+        hi_col: hi.col.0 + 1,
+    })
+}
+
+fn location_output(loc: Location) -> LocationOutput {
+    LocationOutput {
+        block: loc.block.as_usize(),
+        statement: loc.statement_index,
+    }
+}
+
+/// The span of the binding pattern of a user variable.
+fn user_var_span(decl: &LocalDecl) -> Option<Span> {
+    if let LocalInfo::User(ref binding_form) = **decl.local_info.as_ref()? {
+        if let BindingForm::Var(ref var) = *binding_form.as_ref().assert_crate_local() {
+            return Some(var.pat_span);
+        }
+    }
+    None
+}
+
+/// Collect the analysis results for the function `ldid`.
+pub fn describe_function<'tcx>(
+    acx: &AnalysisCtxt<'_, 'tcx>,
+    asn: &Assignment,
+    mir: &Body<'tcx>,
+    ldid: LocalDefId,
+    rewrites: &[ExprRewrite],
+) -> FunctionOutput {
+    let tcx = acx.tcx();
+
+    let locals = mir
+        .local_decls
+        .iter_enumerated()
+        .map(|(local, decl)| {
+            let var_span = user_var_span(decl);
+            let span = var_span.unwrap_or(decl.source_info.span);
+            let name = var_span.and_then(|span| tcx.sess.source_map().span_to_snippet(span).ok());
+            LocalOutput {
+                local: format!("{:?}", local),
+                name,
+                span: span_output(acx, span),
+                ty: decl.ty.to_string(),
+                addr_of: describe_pointer(asn, acx.addr_of_local[local]),
+                pointers: describe_pointers(asn, acx.local_tys[local]),
+            }
+        })
+        .collect();
+
+    let rewrites = rewrites
+        .iter()
+        .map(|rw| RewriteOutput {
+            span: span_output(acx, rw.loc.span),
+            location: location_output(rw.loc.stmt),
+            sub: rw.loc.sub.clone(),
+            kinds: rw.kinds.clone(),
+        })
+        .collect();
+
+    FunctionOutput {
+        name: tcx.def_path_str(ldid.to_def_id()),
+        span: span_output(acx, tcx.def_span(ldid)),
+        locals,
+        rewrites,
+    }
+}
+
+/// Write the results for `functions` to `path`.
+pub fn write_output(path: &Path, functions: Vec<FunctionOutput>) -> io::Result<()> {
+    let output = Output {
+        version: SCHEMA_VERSION,
+        functions,
+    };
+    let mut w = BufWriter::new(File::create(path)?);
+    serde_json::to_writer_pretty(&mut w, &output)?;
+    writeln!(w)?;
+    w.flush()
+}
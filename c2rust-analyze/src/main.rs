@@ -15,49 +15,112 @@ extern crate rustc_target;
 extern crate rustc_type_ir;
 
 use crate::context::{
-    AnalysisCtxt, FlagSet, GlobalAnalysisCtxt, GlobalAssignment, LTy, LocalAssignment,
+    AnalysisCtxt, FlagSet, GlobalAnalysisCtxt, GlobalAssignment, LFnSig, LTy, LocalAssignment,
     PermissionSet, PointerId,
 };
 use crate::equiv::{GlobalEquivSet, LocalEquivSet};
+use rustc_hir::def::DefKind;
 use rustc_index::vec::IndexVec;
-use rustc_middle::mir::{BindingForm, LocalDecl, LocalInfo};
+use rustc_middle::mir::{BindingForm, LocalDecl, LocalInfo, RETURN_PLACE};
 use rustc_middle::ty::{Ty, TyCtxt, TyKind, WithOptConstParam};
 use rustc_span::Span;
 use std::env;
+use std::path::PathBuf;
 
 mod borrowck;
 mod context;
 mod dataflow;
 mod equiv;
 mod expr_rewrite;
+mod json_output;
 mod labeled_ty;
+mod pdg;
 mod pointer_id;
+mod rewrite;
 mod type_desc;
 mod util;
 
 fn run(tcx: TyCtxt) {
+    let rewrite_mode = rewrite::RewriteMode::from_env();
+    let json_output_path = env::var_os("C2RUST_ANALYZE_JSON_OUTPUT").map(PathBuf::from);
+    let pdg_hints = match pdg::PdgHints::from_env(tcx) {
+        Ok(x) => x,
+        Err(e) => tcx.sess.fatal(e),
+    };
     let mut gacx = GlobalAnalysisCtxt::new(tcx);
     let mut func_info = Vec::new();
 
+    // Assign global `PointerId`s to the types of struct, union and enum fields and statics, and to
+    // the signatures of functions.  These are shared by all functions, so constraints on them that
+    // arise in one function also affect its callers and callees.  Fields of ADTs from other
+    // crates, and of instances of generic ADTs, get `PointerId`s as they are used in each body.
+
+    for item_id in tcx.hir().items() {
+        let did = item_id.def_id.to_def_id();
+        match tcx.def_kind(did) {
+            DefKind::Struct | DefKind::Union | DefKind::Enum => {
+                for field in tcx.adt_def(did).all_fields() {
+                    gacx.assign_field_pointer_ids(field.did, tcx.type_of(field.did));
+                }
+            }
+            DefKind::Static(_) => {
+                let lty = gacx.assign_pointer_ids(tcx.type_of(did));
+                gacx.static_tys.insert(did, lty);
+                let ptr = gacx.new_pointer();
+                gacx.addr_of_static.insert(did, ptr);
+            }
+            _ => {}
+        }
+    }
+
+    for ldid in tcx.hir().body_owners() {
+        let mir = tcx.mir_built(WithOptConstParam::unknown(ldid));
+        let mir = mir.borrow();
+        gacx.assign_body_field_pointer_ids(&mir);
+
+        if !matches!(tcx.def_kind(ldid), DefKind::Fn | DefKind::AssocFn) {
+            continue;
+        }
+        let inputs = mir
+            .args_iter()
+            .map(|local| gacx.assign_pointer_ids(mir.local_decls[local].ty))
+            .collect::<Vec<_>>();
+        let inputs = gacx.lcx.mk_slice(&inputs);
+        let output = gacx.assign_pointer_ids(mir.local_decls[RETURN_PLACE].ty);
+        gacx.fn_sigs
+            .insert(ldid.to_def_id(), LFnSig { inputs, output });
+    }
+
     // Initial pass to gather equivalence constraints, which state that two pointer types must be
     // converted to the same reference type.  Some additional data computed during this the process
     // is kept around for use in later passes.
 
-    // TODO: assign global PointerIds
-
     let mut g_equiv = GlobalEquivSet::new(gacx.num_pointers());
     for ldid in tcx.hir().body_owners() {
         let ldid_const = WithOptConstParam::unknown(ldid);
         let mir = tcx.mir_built(ldid_const);
         let mir = mir.borrow();
 
+        let sig = gacx.fn_sigs.get(&ldid.to_def_id()).copied();
+        let static_lty = gacx.static_tys.get(&ldid.to_def_id()).copied();
+
         let mut acx = gacx.function_context(&mir);
 
-        // Assign PointerIds to local types
+        // Assign PointerIds to local types.  The arguments and return value of a function use the
+        // global `PointerId`s of its signature, and the return value of a static's initializer
+        // uses those of the static.
         assert!(acx.local_tys.is_empty());
         acx.local_tys = IndexVec::with_capacity(mir.local_decls.len());
         for (local, decl) in mir.local_decls.iter_enumerated() {
-            let lty = assign_pointer_ids(&mut acx, decl.ty);
+            let global_lty = match sig {
+                Some(sig) if local == RETURN_PLACE => Some(sig.output),
+                Some(sig) if local.as_usize() <= sig.inputs.len() => {
+                    Some(sig.inputs[local.as_usize() - 1])
+                }
+                None if local == RETURN_PLACE => static_lty,
+                _ => None,
+            };
+            let lty = global_lty.unwrap_or_else(|| assign_pointer_ids(&mut acx, decl.ty));
             let l = acx.local_tys.push(lty);
             assert_eq!(local, l);
 
@@ -76,36 +139,115 @@ fn run(tcx: TyCtxt) {
         func_info.push((acx.into_data(), dataflow, l_equiv));
     }
 
-    // Compute permission and flag assignments.
+    // Remap pointers based on equivalence classes, so all members of an equivalence class now use
+    // the same `PointerId`.
 
     let (g_counter, g_equiv_map) = g_equiv.renumber();
     eprintln!("g_equiv_map = {:?}", g_equiv_map);
     gacx.remap_pointers(&g_equiv_map, g_counter);
-    let mut gasn = GlobalAssignment::new(0, PermissionSet::UNIQUE, FlagSet::empty());
+
+    let mut func_info = func_info
+        .into_iter()
+        .map(|(mut data, mut dataflow, l_equiv)| {
+            let (l_counter, l_equiv_map) = l_equiv.renumber(&g_equiv_map);
+            eprintln!("l_equiv_map = {:?}", l_equiv_map);
+            let lasn = LocalAssignment::new(
+                l_counter.num_pointers(),
+                PermissionSet::UNIQUE,
+                FlagSet::empty(),
+            );
+            data.remap_pointers(gacx.lcx, g_equiv_map.and(&l_equiv_map), l_counter);
+            dataflow.remap_pointers(g_equiv_map.and(&l_equiv_map));
+            (Some(data), dataflow, lasn)
+        })
+        .collect::<Vec<_>>();
+
+    // Turn the facts observed by the dynamic analysis into constraints, if requested.
+
+    if let Some(ref pdg_hints) = pdg_hints {
+        let mut observed = Vec::new();
+        for (ldid, info) in tcx.hir().body_owners().zip(func_info.iter_mut()) {
+            let (ref mut data, _, _) = *info;
+            let mir = tcx.mir_built(WithOptConstParam::unknown(ldid));
+            let mir = mir.borrow();
+
+            let acx = gacx.function_context_with_data(&mir, data.take().unwrap());
+            observed.push(pdg_hints.observed_ptrs(&acx, &mir, ldid));
+            *data = Some(acx.into_data());
+        }
+        let mut dataflows = func_info
+            .iter_mut()
+            .map(|&mut (_, ref mut dataflow, _)| dataflow)
+            .collect::<Vec<_>>();
+        pdg_hints.add_constraints(&observed, &mut dataflows);
+    }
+
+    // Compute permission and flag assignments.  Each function is analyzed separately, but
+    // updating the permissions of global pointers in one function can invalidate the results for
+    // another, so we repeat until the global assignment reaches a fixpoint.  This terminates
+    // because permissions only ever change in one direction.
+
+    let mut gasn =
+        GlobalAssignment::new(gacx.num_pointers(), PermissionSet::UNIQUE, FlagSet::empty());
+    let mut loop_count = 0;
+    loop {
+        loop_count += 1;
+        let old_gasn = gasn.clone();
+
+        for (ldid, info) in tcx.hir().body_owners().zip(func_info.iter_mut()) {
+            let (ref mut data, ref dataflow, ref mut lasn) = *info;
+            let name = tcx.item_name(ldid.to_def_id());
+            let mir = tcx.mir_built(WithOptConstParam::unknown(ldid));
+            let mir = mir.borrow();
+
+            let acx = gacx.function_context_with_data(&mir, data.take().unwrap());
+            let mut asn = gasn.and(lasn);
+
+            dataflow.propagate(&mut asn.perms_mut());
+
+            borrowck::borrowck_mir(&acx, dataflow, &mut asn.perms_mut(), name.as_str(), &mir);
+
+            *data = Some(acx.into_data());
+        }
+
+        if gasn == old_gasn {
+            break;
+        }
+    }
+    eprintln!(
+        "global assignment converged after {} iterations",
+        loop_count
+    );
+
+    // Check the results against the permissions observed by the dynamic analysis, if available.
+
+    if let Some(ref pdg_hints) = pdg_hints {
+        for (ldid, info) in tcx.hir().body_owners().zip(func_info.iter_mut()) {
+            let (ref mut data, _, ref mut lasn) = *info;
+            let mir = tcx.mir_built(WithOptConstParam::unknown(ldid));
+            let mir = mir.borrow();
+
+            let acx = gacx.function_context_with_data(&mir, data.take().unwrap());
+            let asn = gasn.and(lasn);
+
+            pdg_hints.check(&acx, &asn, &mir, ldid);
+
+            *data = Some(acx.into_data());
+        }
+    }
+
+    let mut all_rewrites = Vec::new();
+    let mut json_functions = Vec::new();
     for (ldid, info) in tcx.hir().body_owners().zip(func_info.into_iter()) {
         let ldid_const = WithOptConstParam::unknown(ldid);
         let name = tcx.item_name(ldid.to_def_id());
         let mir = tcx.mir_built(ldid_const);
         let mir = mir.borrow();
 
-        let (mut data, mut dataflow, l_equiv) = info;
-        // Remap pointers based on equivalence classes, so all members of an equivalence class now
-        // use the same `PointerId`.
-        let (l_counter, l_equiv_map) = l_equiv.renumber(&g_equiv_map);
-        eprintln!("l_equiv_map = {:?}", l_equiv_map);
-        data.remap_pointers(gacx.lcx, g_equiv_map.and(&l_equiv_map), l_counter);
-        dataflow.remap_pointers(g_equiv_map.and(&l_equiv_map));
-
-        let acx = gacx.function_context_with_data(&mir, data);
-
-        let mut lasn =
-            LocalAssignment::new(acx.num_pointers(), PermissionSet::UNIQUE, FlagSet::empty());
+        let (data, dataflow, mut lasn) = info;
+        let acx = gacx.function_context_with_data(&mir, data.unwrap());
         let mut asn = gasn.and(&mut lasn);
 
-        dataflow.propagate(&mut asn.perms_mut());
-
-        borrowck::borrowck_mir(&acx, &dataflow, &mut asn.perms_mut(), name.as_str(), &mir);
-
         dataflow.propagate_cell(&mut asn);
 
         // Print labeling and rewrites for the current function.
@@ -177,7 +319,27 @@ fn run(tcx: TyCtxt) {
                 eprintln!("  {:?}", kind);
             }
         }
+
+        eprintln!();
+        let mut fn_rewrites = rewrite::gen_ty_rewrites(&acx, &asn, &mir, ldid);
+        fn_rewrites.extend(rewrite::convert_expr_rewrites(tcx, ldid, &rewrites));
+        rewrite::print_rewrites(tcx, &fn_rewrites);
+        all_rewrites.extend(fn_rewrites);
+
+        if json_output_path.is_some() {
+            json_functions.push(json_output::describe_function(
+                &acx, &asn, &mir, ldid, &rewrites,
+            ));
+        }
+    }
+
+    if let Some(ref path) = json_output_path {
+        if let Err(e) = json_output::write_output(path, json_functions) {
+            eprintln!("error: failed to write {}: {}", path.display(), e);
+        }
     }
+
+    rewrite::apply_rewrites(tcx, &all_rewrites, rewrite_mode);
 }
 
 fn assign_pointer_ids<'tcx>(acx: &mut AnalysisCtxt<'_, 'tcx>, ty: Ty<'tcx>) -> LTy<'tcx> {
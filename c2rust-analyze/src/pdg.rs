@@ -0,0 +1,344 @@
+//! Hints from the dynamic analysis.
+//!
+//! When `C2RUST_ANALYZE_PDG_METADATA` and `C2RUST_ANALYZE_PDG_EVENT_LOG` are set, we load the
+//! pointer derivation graph (PDG) built by `c2rust-pdg` from the metadata of an instrumented
+//! build of the crate and the event logs of one or more runs of it.  Each PDG node that stores
+//! its result in a MIR local is mapped back to the `PointerId` of that local, and the facts
+//! observed for it at runtime are compared with the statically inferred permissions.
+//!
+//! In `refine` mode, the observed facts also become hints for the static analysis: a pointer that
+//! was never written through gets a `NoPerms(WRITE)` constraint, as does every pointer derived
+//! from it, before the permissions are computed.  These constraints only keep `WRITE` from
+//! propagating, and are left out for pointers that the code writes through, or that have a
+//! derived pointer that it writes through, so hints never remove a permission that the code
+//! requires.
+use crate::context::{AnalysisCtxt, Assignment, PermissionSet, PointerId};
+use crate::dataflow::DataflowConstraints;
+use c2rust_analysis_rt::mir_loc;
+use c2rust_pdg::graph::{Graphs, NodeKind};
+use c2rust_pdg::Pdg;
+use rustc_middle::mir::{Body, Local};
+use rustc_middle::ty::TyCtxt;
+use rustc_span::def_id::LocalDefId;
+use std::collections::{HashMap, HashSet};
+use std::env::{self, VarError};
+use std::path::PathBuf;
+
+/// What to do with the PDG, selected with the `C2RUST_ANALYZE_PDG_MODE` environment variable.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PdgMode {
+    /// Only report where the static and dynamic results disagree (`check`, the default).
+    Check,
+    /// Also keep `WRITE` from propagating to pointers that were never written through in any
+    /// run, unless the code writes through them or a pointer derived from them (`refine`).
+    Refine,
+}
+
+/// The MIR pointer that a PDG node produces.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+enum PdgPtr {
+    /// The outermost pointer in the type of a local.
+    Local(Local),
+    /// The address of a local.
+    AddrOfLocal(Local),
+}
+
+/// Facts observed at runtime about a single pointer, combined over all runs.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+struct Observed {
+    /// Some value of this pointer was later written through.
+    write: bool,
+}
+
+pub struct PdgHints {
+    mode: PdgMode,
+    funcs: HashMap<LocalDefId, HashMap<PdgPtr, Observed>>,
+}
+
+/// A pointer of a function that was observed at runtime.
+pub struct ObservedPtr {
+    ptr: PointerId,
+    /// A description of the pointer for messages.
+    desc: String,
+    observed: Observed,
+}
+
+impl PdgHints {
+    /// Load the PDG named by the `C2RUST_ANALYZE_PDG_*` environment variables, if they are
+    /// set.  `C2RUST_ANALYZE_PDG_EVENT_LOG` may list several event logs, separated as in
+    /// `PATH`.
+    pub fn from_env(tcx: TyCtxt) -> Result<Option<PdgHints>, String> {
+        let metadata_path = match env::var_os("C2RUST_ANALYZE_PDG_METADATA") {
+            Some(path) => PathBuf::from(path),
+            None => return Ok(None),
+        };
+        let event_logs = env::var_os("C2RUST_ANALYZE_PDG_EVENT_LOG")
+            .ok_or("C2RUST_ANALYZE_PDG_METADATA is set, but C2RUST_ANALYZE_PDG_EVENT_LOG is not")?;
+
+        let mode = match env::var("C2RUST_ANALYZE_PDG_MODE") {
+            Err(VarError::NotPresent) => PdgMode::Check,
+            Err(VarError::NotUnicode(mode)) => {
+                return Err(format!("invalid C2RUST_ANALYZE_PDG_MODE {:?}", mode))
+            }
+            Ok(mode) => match mode.as_str() {
+                "" | "check" => PdgMode::Check,
+                "refine" => PdgMode::Refine,
+                _ => {
+                    return Err(format!(
+                        "unknown C2RUST_ANALYZE_PDG_MODE {:?}; expected check or refine",
+                        mode
+                    ))
+                }
+            },
+        };
+
+        let fn_ids = tcx
+            .hir()
+            .body_owners()
+            .map(|ldid| {
+                let hash = tcx.def_path_hash(ldid.to_def_id());
+                (mir_loc::DefPathHash::from(hash.0.as_value()), ldid)
+            })
+            .collect::<HashMap<_, _>>();
+
+        let mut hints = PdgHints {
+            mode,
+            funcs: HashMap::new(),
+        };
+        for event_log_path in env::split_paths(&event_logs) {
+            eprintln!(
+                "loading PDG from {} and {}",
+                metadata_path.display(),
+                event_log_path.display()
+            );
+            let pdg = Pdg::new(&metadata_path, &event_log_path).map_err(|e| {
+                format!(
+                    "failed to load PDG from {} and {}: {:#}",
+                    metadata_path.display(),
+                    event_log_path.display(),
+                    e
+                )
+            })?;
+            hints.add_graphs(&pdg.graphs, &fn_ids);
+        }
+        Ok(Some(hints))
+    }
+
+    fn add_graphs(&mut self, graphs: &Graphs, fn_ids: &HashMap<mir_loc::DefPathHash, LocalDefId>) {
+        for graph in &graphs.graphs {
+            let needs_write = graph.needs_write_permission().collect::<HashSet<_>>();
+            for (node_id, node) in graph.nodes.iter_enumerated() {
+                let ldid = match fn_ids.get(&node.function.def_path_hash) {
+                    Some(&ldid) => ldid,
+                    // The function belongs to some other crate.
+                    None => continue,
+                };
+                let ptr = match (&node.kind, &node.dest) {
+                    (&NodeKind::AddrOfLocal(local), _) => PdgPtr::AddrOfLocal(local),
+                    (_, &Some(ref dest)) if dest.projection.is_empty() => {
+                        PdgPtr::Local(Local::from_u32(dest.local.as_u32()))
+                    }
+                    _ => continue,
+                };
+                let observed = self.funcs.entry(ldid).or_default().entry(ptr).or_default();
+                observed.write |= needs_write.contains(&node_id);
+            }
+        }
+    }
+
+    /// Map the pointers of `ldid` that were observed at runtime to their `PointerId`s.
+    pub fn observed_ptrs<'tcx>(
+        &self,
+        acx: &AnalysisCtxt<'_, 'tcx>,
+        mir: &Body<'tcx>,
+        ldid: LocalDefId,
+    ) -> Vec<ObservedPtr> {
+        let tcx = acx.tcx();
+        let observed_ptrs = match self.funcs.get(&ldid) {
+            Some(x) => x,
+            None => return Vec::new(),
+        };
+
+        let mut ptrs = observed_ptrs.iter().collect::<Vec<_>>();
+        ptrs.sort_by_key(|&(&ptr, _)| match ptr {
+            PdgPtr::Local(local) => (local, false),
+            PdgPtr::AddrOfLocal(local) => (local, true),
+        });
+        let mut out = Vec::new();
+        for (&pdg_ptr, &observed) in ptrs {
+            let (local, ptr) = match pdg_ptr {
+                PdgPtr::Local(local) if local.index() < acx.local_tys.len() => {
+                    (local, acx.local_tys[local].label)
+                }
+                PdgPtr::AddrOfLocal(local) if local.index() < acx.addr_of_local.len() => {
+                    (local, acx.addr_of_local[local])
+                }
+                _ => {
+                    eprintln!(
+                        "pdg: {:?} in {:?} doesn't exist in the MIR",
+                        pdg_ptr,
+                        tcx.def_path_str(ldid.to_def_id())
+                    );
+                    continue;
+                }
+            };
+            if ptr == PointerId::NONE {
+                continue;
+            }
+
+            let what = match pdg_ptr {
+                PdgPtr::Local(_) => "",
+                PdgPtr::AddrOfLocal(_) => "address of ",
+            };
+            let desc = format!(
+                "{}{:?} ({}) in {:?}",
+                what,
+                local,
+                crate::describe_local(tcx, &mir.local_decls[local]),
+                tcx.def_path_str(ldid.to_def_id()),
+            );
+            out.push(ObservedPtr {
+                ptr,
+                desc,
+                observed,
+            });
+        }
+        out
+    }
+
+    /// In `Refine` mode, constrain pointers that were never written through at runtime to not
+    /// get `WRITE`.  `observed[i]` and `dataflows[i]` are the observed pointers and the
+    /// constraints of the `i`th function.
+    ///
+    /// `Subset(a, b)` constraints propagate `WRITE` from `a`, which is derived from `b`, to `b`,
+    /// so `WRITE` can only be withheld from a pointer along with every pointer derived from it.
+    /// If one of those was written through at runtime, or has an `AllPerms` constraint requiring
+    /// `WRITE` because the code writes through it, the pointer keeps `WRITE`.
+    pub fn add_constraints(
+        &self,
+        observed: &[Vec<ObservedPtr>],
+        dataflows: &mut [&mut DataflowConstraints],
+    ) {
+        if self.mode != PdgMode::Refine {
+            return;
+        }
+
+        // Global pointers are shared by all functions, while local ones are only unique within
+        // their function.
+        type Node = (Option<usize>, PointerId);
+        let node = |func: usize, ptr: PointerId| -> Node {
+            if ptr.is_global() {
+                (None, ptr)
+            } else {
+                (Some(func), ptr)
+            }
+        };
+
+        let mut derived: HashMap<Node, Vec<Node>> = HashMap::new();
+        for (func, dataflow) in dataflows.iter().enumerate() {
+            for (a, b) in dataflow.subsets() {
+                derived
+                    .entry(node(func, b))
+                    .or_default()
+                    .push(node(func, a));
+            }
+        }
+
+        let written = observed
+            .iter()
+            .enumerate()
+            .flat_map(|(func, ptrs)| {
+                ptrs.iter()
+                    .filter(|p| p.observed.write)
+                    .map(move |p| node(func, p.ptr))
+            })
+            .collect::<HashSet<_>>();
+        let static_written = dataflows
+            .iter()
+            .enumerate()
+            .flat_map(|(func, dataflow)| {
+                dataflow
+                    .all_perms()
+                    .filter(|&(_, perms)| perms.contains(PermissionSet::WRITE))
+                    .map(move |(ptr, _)| node(func, ptr))
+            })
+            .collect::<HashSet<_>>();
+
+        let mut no_write: HashSet<Node> = HashSet::new();
+        for (func, ptrs) in observed.iter().enumerate() {
+            for p in ptrs {
+                let start = node(func, p.ptr);
+                if written.contains(&start) || no_write.contains(&start) {
+                    continue;
+                }
+
+                let mut closure = HashSet::new();
+                let mut stack = vec![start];
+                let mut conflict = None;
+                while let Some(cur) = stack.pop() {
+                    if written.contains(&cur) {
+                        conflict = Some("at runtime");
+                        break;
+                    }
+                    if static_written.contains(&cur) {
+                        conflict = Some("in the code");
+                        break;
+                    }
+                    if closure.insert(cur) {
+                        stack.extend(derived.get(&cur).into_iter().flatten().copied());
+                    }
+                }
+
+                if let Some(place) = conflict {
+                    eprintln!(
+                        "pdg: {}: never written through at runtime, but it or a derived pointer is written through {}; keeping WRITE",
+                        p.desc, place
+                    );
+                } else {
+                    eprintln!(
+                        "pdg: {}: never written through at runtime; removing WRITE",
+                        p.desc
+                    );
+                    no_write.extend(closure);
+                }
+            }
+        }
+
+        for (func, dataflow) in dataflows.iter_mut().enumerate() {
+            for &(ptr_func, ptr) in &no_write {
+                if ptr_func.is_none() || ptr_func == Some(func) {
+                    dataflow.add_no_perms(ptr, PermissionSet::WRITE);
+                }
+            }
+        }
+    }
+
+    /// Compare the permissions inferred for the pointers of `ldid` with the facts observed at
+    /// runtime, reporting any disagreement.
+    pub fn check<'tcx>(
+        &self,
+        acx: &AnalysisCtxt<'_, 'tcx>,
+        asn: &Assignment,
+        mir: &Body<'tcx>,
+        ldid: LocalDefId,
+    ) {
+        for p in self.observed_ptrs(acx, mir, ldid) {
+            let static_write = asn.perms()[p.ptr].contains(PermissionSet::WRITE);
+            match (static_write, p.observed.write) {
+                (true, false) => {
+                    eprintln!(
+                        "pdg: {}: inferred WRITE, but it was never written through at runtime",
+                        p.desc
+                    );
+                }
+                (false, true) => {
+                    eprintln!(
+                        "pdg: {}: written through at runtime, but WRITE was not inferred",
+                        p.desc
+                    );
+                }
+                _ => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,418 @@
+//! Application of rewrites to the source files of the crate.
+use super::{Piece, Replacement, Rewrite};
+use rustc_middle::ty::TyCtxt;
+use rustc_span::{BytePos, FileName, Pos, Span};
+use std::collections::{BTreeMap, VecDeque};
+use std::env;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+/// What to do with the rewritten source, selected with the `C2RUST_ANALYZE_REWRITE_MODE`
+/// environment variable.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RewriteMode {
+    /// Don't modify any files (`none`, the default).
+    None,
+    /// Overwrite the input files (`inplace`).
+    InPlace,
+    /// Write the rewritten files next to the originals, with a `.new` extension (`alongside`).
+    Alongside,
+    /// Print a unified diff of the rewritten files to stdout (`diff`).
+    Diff,
+}
+
+impl RewriteMode {
+    /// Read the mode from the environment. An invalid mode is reported and treated as `none`, so
+    /// that no file is modified in a way the user didn't ask for.
+    pub fn from_env() -> RewriteMode {
+        let mode = match env::var("C2RUST_ANALYZE_REWRITE_MODE") {
+            Ok(mode) => mode,
+            Err(env::VarError::NotPresent) => return RewriteMode::None,
+            Err(env::VarError::NotUnicode(mode)) => {
+                eprintln!(
+                    "error: invalid C2RUST_ANALYZE_REWRITE_MODE {:?}; not rewriting any files",
+                    mode
+                );
+                return RewriteMode::None;
+            }
+        };
+        match mode.as_str() {
+            "" | "none" => RewriteMode::None,
+            "inplace" => RewriteMode::InPlace,
+            "alongside" => RewriteMode::Alongside,
+            "diff" => RewriteMode::Diff,
+            _ => {
+                eprintln!(
+                    "error: unknown C2RUST_ANALYZE_REWRITE_MODE {:?}; expected none, inplace, \
+                    alongside or diff; not rewriting any files",
+                    mode
+                );
+                RewriteMode::None
+            }
+        }
+    }
+}
+
+/// The rewrites of a single source file, as byte ranges of its text.
+struct FileRewrites<'a> {
+    name: String,
+    path: Option<PathBuf>,
+    src: String,
+    /// The position of the start of the file in the `SourceMap`.
+    start_pos: BytePos,
+    /// Edits sorted by start, with enclosing edits before the edits they contain.
+    edits: Vec<(Range<usize>, &'a Replacement)>,
+}
+
+impl FileRewrites<'_> {
+    /// Compute the new text of each outermost edit, with the nested edits applied.
+    fn render(&self) -> Vec<(Range<usize>, String)> {
+        let mut out = Vec::new();
+        let mut end = 0;
+        for (i, (range, _)) in self.edits.iter().enumerate() {
+            if range.start < end {
+                self.check_nested(range, end);
+                continue;
+            }
+            out.push((range.clone(), self.render_edit(i)));
+            end = range.end;
+        }
+        out
+    }
+
+    /// Compute the new text of `self.edits[i]`.
+    fn render_edit(&self, i: usize) -> String {
+        match *self.edits[i].1 {
+            Replacement::Text(ref s) => s.clone(),
+            Replacement::Template(ref pieces) => self.render_pieces(pieces, i),
+        }
+    }
+
+    fn render_pieces(&self, pieces: &[Piece], i: usize) -> String {
+        let mut text = String::new();
+        for piece in pieces {
+            match *piece {
+                Piece::Text(ref s) => text.push_str(s),
+                Piece::Expr(span) => text.push_str(&self.render_range(self.range_of(span), i + 1)),
+                Piece::Parens(ref pieces) => text.push_str(&parens(&self.render_pieces(pieces, i))),
+            }
+        }
+        text
+    }
+
+    /// Render the text of `range`, applying the edits from `self.edits[first..]` that lie inside
+    /// it.
+    fn render_range(&self, range: Range<usize>, first: usize) -> String {
+        let mut text = String::new();
+        let mut pos = range.start;
+        for (i, (inner, _)) in self.edits.iter().enumerate().skip(first) {
+            if inner.start >= range.end {
+                break;
+            }
+            if inner.start < range.start {
+                continue;
+            }
+            if inner.start < pos {
+                self.check_nested(inner, pos);
+                continue;
+            }
+            if inner.end > range.end {
+                self.report_overlap(inner);
+                continue;
+            }
+            text.push_str(&self.src[pos..inner.start]);
+            text.push_str(&self.render_edit(i));
+            pos = inner.end;
+        }
+        text.push_str(&self.src[pos..range.end]);
+        text
+    }
+
+    /// Check that the edit of `range`, which starts inside an edit ending at `end`, is nested
+    /// inside it.
+    fn check_nested(&self, range: &Range<usize>, end: usize) {
+        if range.end > end {
+            self.report_overlap(range);
+        }
+    }
+
+    fn report_overlap(&self, range: &Range<usize>) {
+        eprintln!(
+            "{}: skipping rewrite of {:?}, which overlaps another rewrite",
+            self.name,
+            &self.src[range.clone()],
+        );
+    }
+
+    fn range_of(&self, span: Span) -> Range<usize> {
+        (span.lo() - self.start_pos).to_usize()..(span.hi() - self.start_pos).to_usize()
+    }
+
+    fn line_of(&self, pos: usize) -> usize {
+        self.src[..pos].matches('\n').count() + 1
+    }
+}
+
+/// Wrap `expr` in parentheses unless it is a path or a chain of field accesses.
+fn parens(expr: &str) -> String {
+    let is_simple = expr
+        .chars()
+        .all(|c| c.is_alphanumeric() || c == '_' || c == '.' || c == ':');
+    if is_simple {
+        expr.to_owned()
+    } else {
+        format!("({})", expr)
+    }
+}
+
+/// Resolve `rewrites` to byte ranges, grouped by file.
+fn group_by_file<'a>(tcx: TyCtxt, rewrites: &'a [Rewrite]) -> Vec<FileRewrites<'a>> {
+    let source_map = tcx.sess.source_map();
+    let mut files = BTreeMap::new();
+    for rw in rewrites {
+        let lo = source_map.lookup_byte_offset(rw.span.lo());
+        let hi = source_map.lookup_byte_offset(rw.span.hi());
+        if lo.sf.start_pos != hi.sf.start_pos {
+            eprintln!("skipping rewrite {:?} spanning multiple files", rw);
+            continue;
+        }
+        let src = match lo.sf.src {
+            Some(ref src) => src,
+            None => continue,
+        };
+        let name = lo.sf.name.prefer_local().to_string();
+        let file = files.entry(name.clone()).or_insert_with(|| FileRewrites {
+            name,
+            path: match lo.sf.name {
+                FileName::Real(ref real) => real.local_path().map(Path::to_owned),
+                _ => None,
+            },
+            src: String::clone(src),
+            start_pos: lo.sf.start_pos,
+            edits: Vec::new(),
+        });
+        file.edits
+            .push((lo.pos.0 as usize..hi.pos.0 as usize, &rw.repl));
+    }
+
+    let mut files = files.into_values().collect::<Vec<_>>();
+    for file in &mut files {
+        file.edits
+            .sort_by_key(|(range, _)| (range.start, usize::MAX - range.end));
+        file.edits.dedup_by(|(r1, _), (r2, _)| r1 == r2);
+    }
+    files
+}
+
+/// Print each rewrite as the old and new text of the source it changes.
+pub fn print_rewrites(tcx: TyCtxt, rewrites: &[Rewrite]) {
+    for file in group_by_file(tcx, rewrites) {
+        for (range, new) in file.render() {
+            eprintln!(
+                "rewrite {}:{}: `{}` -> `{}`",
+                file.name,
+                file.line_of(range.start),
+                &file.src[range],
+                new,
+            );
+        }
+    }
+}
+
+/// Apply `rewrites` to the source files of the crate according to `mode`.
+pub fn apply_rewrites(tcx: TyCtxt, rewrites: &[Rewrite], mode: RewriteMode) {
+    if mode == RewriteMode::None {
+        return;
+    }
+
+    for file in group_by_file(tcx, rewrites) {
+        let mut new_src = String::new();
+        let mut pos = 0;
+        for (range, new) in file.render() {
+            new_src.push_str(&file.src[pos..range.start]);
+            new_src.push_str(&new);
+            pos = range.end;
+        }
+        new_src.push_str(&file.src[pos..]);
+
+        let dest = match mode {
+            RewriteMode::None => unreachable!(),
+            RewriteMode::Diff => {
+                println!("--- old/{}", file.name);
+                println!("+++ new/{}", file.name);
+                print_diff(&file.src, &new_src);
+                continue;
+            }
+            RewriteMode::InPlace => file.path.clone(),
+            RewriteMode::Alongside => file.path.as_ref().map(|p| p.with_extension("new")),
+        };
+        match dest {
+            Some(dest) => {
+                eprintln!("writing rewritten {} to {}", file.name, dest.display());
+                if let Err(e) = fs::write(&dest, new_src) {
+                    eprintln!(
+                        "error: failed to write {}: {}; skipping {}",
+                        dest.display(),
+                        e,
+                        file.name
+                    );
+                }
+            }
+            None => eprintln!("{} is not a local file; not writing it", file.name),
+        }
+    }
+}
+
+/// Print a unified diff between lines of `s1` and lines of `s2`.
+fn print_diff(s1: &str, s2: &str) {
+    enum State {
+        /// We're not in a hunk, just keeping `buf` populated with `CONTEXT` lines of history.
+        History,
+        /// We're inside a hunk containing at least one changed line.
+        Hunk {
+            /// Number of unchanged lines we need to see to end this hunk.
+            unchanged_limit: usize,
+            l_start: usize,
+            r_start: usize,
+        },
+    }
+
+    const CONTEXT: usize = 3;
+
+    let mut buf = VecDeque::new();
+    let mut state = State::History;
+
+    let mut l_line = 1;
+    let mut r_line = 1;
+
+    for r in diff::lines(s1, s2) {
+        let changed = match r {
+            diff::Result::Both(l, r) => l != r,
+            _ => true,
+        };
+
+        let (l_line_old, r_line_old) = (l_line, r_line);
+
+        match r {
+            diff::Result::Left(..) => {
+                l_line += 1;
+            }
+            diff::Result::Right(..) => {
+                r_line += 1;
+            }
+            diff::Result::Both(..) => {
+                l_line += 1;
+                r_line += 1;
+            }
+        }
+
+        buf.push_back(r);
+
+        if !changed {
+            match state {
+                State::History => {
+                    while buf.len() > CONTEXT {
+                        buf.pop_front();
+                    }
+                }
+                State::Hunk {
+                    unchanged_limit,
+                    l_start,
+                    r_start,
+                } => {
+                    if unchanged_limit == 1 {
+                        // End of the hunk
+                        let end = buf.len() - CONTEXT;
+                        let suffix = buf.split_off(end);
+                        print_hunk(&buf, l_start, r_start);
+                        buf = suffix;
+                        state = State::History;
+                    } else {
+                        state = State::Hunk {
+                            unchanged_limit: unchanged_limit - 1,
+                            l_start,
+                            r_start,
+                        };
+                    }
+                }
+            }
+        } else {
+            match state {
+                State::History => {
+                    state = State::Hunk {
+                        unchanged_limit: 2 * CONTEXT,
+                        // Adjust start lines for context already stored in `buf`.
+                        l_start: l_line_old - (buf.len() - 1),
+                        r_start: r_line_old - (buf.len() - 1),
+                    };
+                }
+                State::Hunk {
+                    l_start, r_start, ..
+                } => {
+                    state = State::Hunk {
+                        unchanged_limit: 2 * CONTEXT,
+                        l_start,
+                        r_start,
+                    };
+                }
+            }
+        }
+    }
+
+    if let State::Hunk {
+        unchanged_limit,
+        l_start,
+        r_start,
+    } = state
+    {
+        if unchanged_limit < CONTEXT {
+            let end = buf.len() - (CONTEXT - unchanged_limit);
+            buf.truncate(end);
+        }
+        print_hunk(&buf, l_start, r_start);
+    }
+}
+
+/// Print a single diff hunk, starting at line `l_start` in the left file and `r_start` in the
+/// right file.
+fn print_hunk(buf: &VecDeque<diff::Result<&str>>, l_start: usize, r_start: usize) {
+    let l_size = buf
+        .iter()
+        .filter(|r| !matches!(r, diff::Result::Right(_)))
+        .count();
+    let r_size = buf
+        .iter()
+        .filter(|r| !matches!(r, diff::Result::Left(_)))
+        .count();
+
+    println!("@@ -{},{} +{},{} @@", l_start, l_size, r_start, r_size);
+
+    // Print all "left" lines immediately.  Keep all "right" lines and print them just before the
+    // next unchanged line, so that old and new lines form separate blocks.
+    let mut right_buf = Vec::new();
+    for r in buf {
+        match r {
+            diff::Result::Left(s) => {
+                println!("-{}", s);
+            }
+            diff::Result::Right(s) => {
+                right_buf.push(s);
+            }
+            diff::Result::Both(s1, s2) => {
+                if s1 != s2 {
+                    println!("-{}", s1);
+                    right_buf.push(s2);
+                } else {
+                    for s in right_buf.drain(..) {
+                        println!("+{}", s);
+                    }
+                    println!(" {}", s1);
+                }
+            }
+        }
+    }
+    for s in right_buf {
+        println!("+{}", s);
+    }
+}
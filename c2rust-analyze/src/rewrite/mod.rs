@@ -0,0 +1,429 @@
+//! Conversion of the analysis results into edits of the input source.
+//!
+//! Each `Rewrite` replaces the source text of a span.  Type rewrites replace a type annotation
+//! with the type inferred for it.  Expression rewrites are mapped from their MIR location to the
+//! HIR expression they apply to, and build the new text of that expression from the spans of its
+//! subexpressions.  Rewrites nested inside those subexpressions are applied first, so that they
+//! compose: rewriting `p.offset(i)` uses the rewritten text of the receiver `p`.
+use crate::expr_rewrite::{ExprRewrite, RewriteKind, SubLoc};
+use rustc_hir as hir;
+use rustc_hir::intravisit::{self, Visitor};
+use rustc_middle::ty::TyCtxt;
+use rustc_span::def_id::LocalDefId;
+use rustc_span::Span;
+use std::collections::{HashMap, HashSet};
+
+mod apply;
+mod ty;
+
+pub use self::apply::{apply_rewrites, print_rewrites, RewriteMode};
+pub use self::ty::gen_ty_rewrites;
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Rewrite {
+    pub span: Span,
+    pub repl: Replacement,
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Replacement {
+    /// Replace the span with fixed text.
+    Text(String),
+    /// Replace the span with the concatenation of `pieces`.
+    Template(Vec<Piece>),
+}
+
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Piece {
+    Text(String),
+    /// The text of the expression at this span, with the rewrites nested inside it applied.
+    /// This is either a subexpression of the rewritten expression, or the expression itself.
+    Expr(Span),
+    /// The text of the pieces, in parentheses unless it is a path or a chain of field accesses,
+    /// which bind tighter than any operator we apply to them.
+    Parens(Vec<Piece>),
+}
+
+impl Piece {
+    fn text(s: &str) -> Piece {
+        Piece::Text(s.to_owned())
+    }
+
+    fn parens(expr: &hir::Expr) -> Piece {
+        Piece::Parens(vec![Piece::Expr(expr.span)])
+    }
+
+    /// Check that the subexpressions used by this piece are inside `span`.  They may not be if
+    /// they come from a macro expansion.
+    fn is_within(&self, span: Span) -> bool {
+        match *self {
+            Piece::Text(_) => true,
+            Piece::Expr(sub) => span.contains(sub),
+            Piece::Parens(ref pieces) => pieces.iter().all(|p| p.is_within(span)),
+        }
+    }
+}
+
+/// Build the template for the expression `expr` after applying `kind` to `cur`, which is the
+/// template for `expr` with the preceding rewrites applied.  Rewrites that replace a call use the
+/// subexpressions of `expr`, so they must come before any other rewrite of the same expression.
+fn apply_kind(kind: &RewriteKind, expr: &hir::Expr, cur: Vec<Piece>) -> Option<Vec<Piece>> {
+    let mut_prefix = |mutbl: bool| Piece::text(if mutbl { "&mut " } else { "&" });
+    let is_orig = cur == [Piece::Expr(expr.span)];
+    Some(match *kind {
+        RewriteKind::OffsetSlice { mutbl } => {
+            let (ptr, offset) = match (is_orig, call_args(expr)?) {
+                (true, &[ref ptr, ref offset]) => (ptr, offset),
+                _ => return None,
+            };
+            vec![
+                mut_prefix(mutbl),
+                Piece::parens(ptr),
+                Piece::text("["),
+                Piece::parens(offset),
+                Piece::text(" as usize..]"),
+            ]
+        }
+        RewriteKind::SliceFirst { mutbl } => {
+            vec![mut_prefix(mutbl), Piece::Parens(cur), Piece::text("[0]")]
+        }
+        RewriteKind::MutToImm => vec![Piece::text("&*"), Piece::Parens(cur)],
+        RewriteKind::OffsetPtrOffset { mutbl }
+        | RewriteKind::OffsetPtrOffsetFirst { mutbl }
+        | RewriteKind::OffsetPtrOffsetSlice { mutbl } => {
+            let (ptr, offset) = match (is_orig, call_args(expr)?) {
+                (true, &[ref ptr, ref offset]) => (ptr, offset),
+                _ => return None,
+            };
+            let pair = vec![Piece::Expr(ptr.span)];
+            let offset = vec![Piece::parens(offset)];
+            let borrow = if mutbl { "&mut " } else { "&" };
+            let (open, sep, close) = match *kind {
+                RewriteKind::OffsetPtrOffset { .. } => {
+                    (if mutbl { "(&mut *" } else { "(&*" }, ", ", ")")
+                }
+                RewriteKind::OffsetPtrOffsetFirst { .. } => (borrow, "[", "]"),
+                _ => (borrow, "[", "..]"),
+            };
+            with_offset_ptr(pair, is_place(ptr), Some(offset), |slice, index, offset| {
+                let mut pieces = vec![Piece::text(open)];
+                pieces.extend(slice);
+                pieces.push(Piece::text(sep));
+                pieces.push(Piece::text("("));
+                pieces.extend(index);
+                pieces.push(Piece::text(" as isize + "));
+                pieces.extend(offset.unwrap());
+                pieces.push(Piece::text(") as usize"));
+                pieces.push(Piece::text(close));
+                pieces
+            })
+        }
+        RewriteKind::OffsetPtrFirst { mutbl } | RewriteKind::OffsetPtrSlice { mutbl } => {
+            let end = match *kind {
+                RewriteKind::OffsetPtrFirst { .. } => "]",
+                _ => "..]",
+            };
+            with_offset_ptr(cur, is_orig && is_place(expr), None, |slice, index, _| {
+                let mut pieces = vec![mut_prefix(mutbl)];
+                pieces.extend(slice);
+                pieces.push(Piece::text("["));
+                pieces.extend(index);
+                pieces.push(Piece::text(end));
+                pieces
+            })
+        }
+        RewriteKind::OffsetPtrMutToImm => {
+            with_offset_ptr(cur, is_orig && is_place(expr), None, |slice, index, _| {
+                let mut pieces = vec![Piece::text("(&*")];
+                pieces.extend(slice);
+                pieces.push(Piece::text(", "));
+                pieces.extend(index);
+                pieces.push(Piece::text(")"));
+                pieces
+            })
+        }
+        RewriteKind::SliceOffsetPtr { mutbl } => vec![
+            Piece::text(if mutbl { "(&mut *" } else { "(&*" }),
+            Piece::Parens(cur),
+            Piece::text(", 0)"),
+        ],
+        RewriteKind::Borrow { mutbl } => {
+            vec![mut_prefix(mutbl), Piece::text("*"), Piece::Parens(cur)]
+        }
+        RewriteKind::MallocBox { ref pointee } => {
+            if !is_orig {
+                return None;
+            }
+            alloc_call_args(expr)?;
+            vec![Piece::Text(format!("Box::new(<{}>::default())", pointee))]
+        }
+        RewriteKind::MallocSlice { ref pointee } => {
+            let size = match (is_orig, alloc_call_args(expr)?) {
+                // `malloc(size)`
+                (true, &[ref size]) => Piece::parens(size),
+                // `calloc(n, size)`
+                (true, &[ref n, ref size]) => Piece::Parens(vec![
+                    Piece::parens(n),
+                    Piece::text(" * "),
+                    Piece::parens(size),
+                ]),
+                _ => return None,
+            };
+            vec![
+                Piece::Text(format!("vec![<{}>::default(); ", pointee)),
+                size,
+                Piece::Text(format!(
+                    " / core::mem::size_of::<{}>()].into_boxed_slice()",
+                    pointee
+                )),
+            ]
+        }
+        RewriteKind::ReallocSlice { ref pointee } => {
+            let (ptr, size) = match (is_orig, alloc_call_args(expr)?) {
+                (true, &[ref ptr, ref size]) => (strip_cast(ptr), size),
+                _ => return None,
+            };
+            vec![
+                Piece::text("{ let mut v = "),
+                Piece::parens(ptr),
+                Piece::text(".into_vec(); v.resize("),
+                Piece::parens(size),
+                Piece::Text(format!(
+                    " / core::mem::size_of::<{}>(), <{}>::default()); v.into_boxed_slice() }}",
+                    pointee, pointee
+                )),
+            ]
+        }
+        RewriteKind::FreeToDrop => {
+            let ptr = match (is_orig, call_args(expr)?) {
+                (true, &[ref ptr]) => strip_cast(ptr),
+                _ => return None,
+            };
+            vec![
+                Piece::text("drop("),
+                Piece::Expr(ptr.span),
+                Piece::text(")"),
+            ]
+        }
+    })
+}
+
+/// Get the arguments of the call `expr`, including the receiver of a method call.
+fn call_args<'hir>(expr: &'hir hir::Expr<'hir>) -> Option<&'hir [hir::Expr<'hir>]> {
+    match expr.peel_drop_temps().kind {
+        hir::ExprKind::Call(_, args) | hir::ExprKind::MethodCall(_, args, _) => Some(args),
+        _ => None,
+    }
+}
+
+/// Check whether `expr` is a place, such as a local or a field, rather than a value.
+fn is_place(expr: &hir::Expr) -> bool {
+    expr.peel_drop_temps().is_place_expr(|_| true)
+}
+
+/// Build the template `f(slice, index, arg)` that uses the slice and index of the `(&[T], usize)`
+/// pair `pair`.  If `pair` is a place, `slice` and `index` are its fields, so that it isn't moved
+/// out of.  Otherwise it is evaluated once and destructured in a block, together with `arg` so
+/// that the block's bindings can't shadow names that `arg` uses.
+fn with_offset_ptr(
+    pair: Vec<Piece>,
+    pair_is_place: bool,
+    arg: Option<Vec<Piece>>,
+    f: impl FnOnce(Vec<Piece>, Vec<Piece>, Option<Vec<Piece>>) -> Vec<Piece>,
+) -> Vec<Piece> {
+    if pair_is_place {
+        let slice = vec![Piece::Parens(pair.clone()), Piece::text(".0")];
+        let index = vec![Piece::Parens(pair), Piece::text(".1")];
+        return f(slice, index, arg);
+    }
+
+    let mut pieces = Vec::new();
+    let arg = match arg {
+        Some(arg) => {
+            pieces.push(Piece::text("{ let ((s, i), x) = ("));
+            pieces.extend(pair);
+            pieces.push(Piece::text(", "));
+            pieces.extend(arg);
+            pieces.push(Piece::text("); "));
+            Some(vec![Piece::text("x")])
+        }
+        None => {
+            pieces.push(Piece::text("{ let (s, i) = "));
+            pieces.extend(pair);
+            pieces.push(Piece::text("; "));
+            None
+        }
+    };
+    pieces.extend(f(vec![Piece::text("s")], vec![Piece::text("i")], arg));
+    pieces.push(Piece::text(" }"));
+    pieces
+}
+
+/// Get the arguments of the allocation `expr`, which should be a call to `malloc`, `calloc` or
+/// `realloc` followed by a cast to the allocated pointer type.
+fn alloc_call_args<'hir>(expr: &'hir hir::Expr<'hir>) -> Option<&'hir [hir::Expr<'hir>]> {
+    match expr.peel_drop_temps().kind {
+        hir::ExprKind::Cast(call, _) => call_args(call),
+        _ => None,
+    }
+}
+
+/// Remove a cast from `expr`, turning `e as T` into `e`.
+fn strip_cast<'hir>(expr: &'hir hir::Expr<'hir>) -> &'hir hir::Expr<'hir> {
+    match expr.peel_drop_temps().kind {
+        hir::ExprKind::Cast(inner, _) => inner,
+        _ => expr,
+    }
+}
+
+/// Find the subexpression of `expr` that corresponds to `sub`, where `expr` is the expression
+/// that the MIR statement or terminator containing `sub` was built from.  MIR is more
+/// fine-grained than HIR, so some steps (such as the operand of a `Use`) stay on the same
+/// expression.
+fn sub_expr<'hir>(expr: &'hir hir::Expr<'hir>, sub: &SubLoc) -> Option<&'hir hir::Expr<'hir>> {
+    let expr = expr.peel_drop_temps();
+    match (sub, &expr.kind) {
+        (&SubLoc::Dest, &hir::ExprKind::Assign(lhs, _, _))
+        | (&SubLoc::Dest, &hir::ExprKind::AssignOp(_, lhs, _)) => Some(lhs),
+        (&SubLoc::Dest, _) => None,
+        (&SubLoc::AssignRvalue, &hir::ExprKind::Assign(_, rhs, _)) => Some(rhs),
+        // The assignment of `let x = e;` is built from `e` itself.
+        (&SubLoc::AssignRvalue, _) => Some(expr),
+        (&SubLoc::CallArg(i), _) => call_args(expr)?.get(i),
+        (&SubLoc::RvalueOperand(0), &hir::ExprKind::Cast(e, _))
+        | (&SubLoc::RvalueOperand(0), &hir::ExprKind::Binary(_, e, _))
+        | (&SubLoc::RvalueOperand(1), &hir::ExprKind::Binary(_, _, e))
+        | (&SubLoc::RvalueOperand(0), &hir::ExprKind::AssignOp(_, e, _))
+        | (&SubLoc::RvalueOperand(1), &hir::ExprKind::AssignOp(_, _, e)) => Some(e),
+        (&SubLoc::RvalueOperand(0), &hir::ExprKind::Unary(op, e)) if op != hir::UnOp::Deref => {
+            Some(e)
+        }
+        // Any other operand is that of a `Use`, which is the whole expression.
+        (&SubLoc::RvalueOperand(0), _) => Some(expr),
+        (&SubLoc::RvalueOperand(_), _) => None,
+        (&SubLoc::OperandPlace, _) => Some(expr),
+        (&SubLoc::PlacePointer(i), _) => {
+            // Collect the operands of the derefs in the place expression, outermost first.
+            let mut ptrs = Vec::new();
+            let mut cur = expr;
+            loop {
+                match cur.peel_drop_temps().kind {
+                    hir::ExprKind::Unary(hir::UnOp::Deref, e) => {
+                        ptrs.push(e);
+                        cur = e;
+                    }
+                    hir::ExprKind::Field(e, _) | hir::ExprKind::Index(e, _) => cur = e,
+                    _ => break,
+                }
+            }
+            ptrs.into_iter().rev().nth(i)
+        }
+    }
+}
+
+/// Collects the expressions of a body by span.  Where several expressions share a span, such as
+/// an expression and the `DropTemps` around it, the innermost one is kept.
+struct ExprVisitor<'hir> {
+    exprs: HashMap<Span, &'hir hir::Expr<'hir>>,
+}
+
+impl<'hir> Visitor<'hir> for ExprVisitor<'hir> {
+    fn visit_expr(&mut self, e: &'hir hir::Expr<'hir>) {
+        self.exprs.insert(e.span, e);
+        intravisit::walk_expr(self, e);
+    }
+}
+
+/// Convert the expression rewrites inferred for the function `ldid` into source rewrites.
+/// Rewrites whose location we can't map to an expression in the source are reported and
+/// skipped.
+pub fn convert_expr_rewrites(
+    tcx: TyCtxt,
+    ldid: LocalDefId,
+    rewrites: &[ExprRewrite],
+) -> Vec<Rewrite> {
+    let hir_map = tcx.hir();
+    let mut v = ExprVisitor {
+        exprs: HashMap::new(),
+    };
+    v.visit_body(hir_map.body(hir_map.body_owned_by(ldid)));
+
+    // The rewrites of each expression, in order.
+    let mut expr_kinds: Vec<(&hir::Expr, Vec<RewriteKind>)> = Vec::new();
+    for rw in rewrites {
+        let span = rw.loc.span;
+        let snippet = || tcx.sess.source_map().span_to_snippet(span);
+        if span.is_dummy() || span.from_expansion() {
+            eprintln!(
+                "skipping rewrite {:?} in macro expansion at {:?}",
+                rw.kinds, rw.loc
+            );
+            continue;
+        }
+
+        let expr = v
+            .exprs
+            .get(&span)
+            .copied()
+            .and_then(|expr| rw.loc.sub.iter().try_fold(expr, sub_expr))
+            .filter(|expr| !expr.span.from_expansion());
+        let expr = match expr {
+            Some(expr) => expr,
+            None => {
+                eprintln!(
+                    "skipping rewrite {:?}: no expression for {:?} in {:?}",
+                    rw.kinds,
+                    rw.loc.sub,
+                    snippet(),
+                );
+                continue;
+            }
+        };
+
+        match expr_kinds.iter_mut().find(|(e, _)| e.hir_id == expr.hir_id) {
+            Some((_, kinds)) => kinds.extend(rw.kinds.iter().cloned()),
+            None => expr_kinds.push((expr, rw.kinds.clone())),
+        }
+    }
+
+    // An immutable offset of an `OffsetPtr` only borrows the slice immutably, so its receiver
+    // doesn't need to be converted from a mutable one first.
+    let imm_offset_recvs = expr_kinds
+        .iter()
+        .filter(|(_, kinds)| {
+            matches!(
+                kinds.first(),
+                Some(
+                    RewriteKind::OffsetPtrOffset { mutbl: false }
+                        | RewriteKind::OffsetPtrOffsetFirst { mutbl: false }
+                        | RewriteKind::OffsetPtrOffsetSlice { mutbl: false }
+                )
+            )
+        })
+        .filter_map(|(expr, _)| Some(call_args(expr)?.first()?.hir_id))
+        .collect::<HashSet<_>>();
+    expr_kinds.retain(|(expr, kinds)| {
+        !(imm_offset_recvs.contains(&expr.hir_id) && kinds == &[RewriteKind::OffsetPtrMutToImm])
+    });
+
+    let mut out = Vec::new();
+    for (expr, kinds) in expr_kinds {
+        let pieces = kinds
+            .iter()
+            .try_fold(vec![Piece::Expr(expr.span)], |cur, kind| {
+                apply_kind(kind, expr, cur)
+            });
+        match pieces {
+            Some(pieces) if pieces.iter().all(|p| p.is_within(expr.span)) => out.push(Rewrite {
+                span: expr.span,
+                repl: Replacement::Template(pieces),
+            }),
+            _ => eprintln!(
+                "unable to apply {:?} to {:?}",
+                kinds,
+                tcx.sess.source_map().span_to_snippet(expr.span),
+            ),
+        }
+    }
+    out
+}
@@ -0,0 +1,172 @@
+//! Rewrites of the type annotations of a function's signature and locals.
+use super::{Replacement, Rewrite};
+use crate::context::{AnalysisCtxt, Assignment, LTy, PermissionSet, PointerId};
+use crate::type_desc::{self, Ownership, Quantity};
+use rustc_hir as hir;
+use rustc_hir::intravisit::{self, Visitor};
+use rustc_middle::mir::{BindingForm, Body, Local, LocalInfo, Mutability, RETURN_PLACE};
+use rustc_middle::ty::BindingMode;
+use rustc_span::def_id::LocalDefId;
+use rustc_span::Span;
+use std::collections::HashMap;
+
+/// Collects the type annotations of `let` statements in a body, by span.
+struct LocalTyVisitor<'hir> {
+    tys: HashMap<Span, &'hir hir::Ty<'hir>>,
+}
+
+impl<'hir> Visitor<'hir> for LocalTyVisitor<'hir> {
+    fn visit_local(&mut self, l: &'hir hir::Local<'hir>) {
+        if let Some(ty) = l.ty {
+            self.tys.insert(ty.span, ty);
+        }
+        intravisit::walk_local(self, l);
+    }
+}
+
+/// Generate rewrites of the type annotations in the signature and body of `ldid` that contain
+/// pointers, replacing each pointer with the type inferred for it.
+pub fn gen_ty_rewrites<'tcx>(
+    acx: &AnalysisCtxt<'_, 'tcx>,
+    asn: &Assignment,
+    mir: &Body<'tcx>,
+    ldid: LocalDefId,
+) -> Vec<Rewrite> {
+    let tcx = acx.tcx();
+    let hir_map = tcx.hir();
+    let hir_id = hir_map.local_def_id_to_hir_id(ldid);
+
+    let mut hir_tys: Vec<(LTy<'tcx>, &hir::Ty)> = Vec::new();
+
+    // The first argument of a closure is its environment, which has no type annotation
+    let is_closure = tcx.is_closure(ldid.to_def_id());
+    if let Some(decl) = hir_map.fn_decl_by_hir_id(hir_id).filter(|_| !is_closure) {
+        if let hir::FnRetTy::Return(ty) = decl.output {
+            hir_tys.push((acx.local_tys[RETURN_PLACE], ty));
+        }
+        for (local, ty) in mir.args_iter().zip(decl.inputs) {
+            hir_tys.push((acx.local_tys[local], ty));
+        }
+    }
+
+    let mut v = LocalTyVisitor {
+        tys: HashMap::new(),
+    };
+    v.visit_body(hir_map.body(hir_map.body_owned_by(ldid)));
+    let mut rewrites: Vec<Rewrite> = Vec::new();
+    for local in mir.vars_and_temps_iter() {
+        let decl = &mir.local_decls[local];
+        let mut ty_span = None;
+        if let Some(ref info) = decl.local_info {
+            if let LocalInfo::User(ref binding_form) = **info {
+                let binding_form = binding_form.as_ref().assert_crate_local();
+                if let BindingForm::Var(ref var) = *binding_form {
+                    ty_span = var.opt_ty_info;
+                    if var.binding_mode == BindingMode::BindByValue(Mutability::Not) {
+                        rewrites.extend(mut_binding_rewrite(acx, asn, local, var.pat_span));
+                    }
+                }
+            }
+        }
+        if let Some(&ty) = ty_span.and_then(|span| v.tys.get(&span)) {
+            hir_tys.push((acx.local_tys[local], ty));
+        }
+    }
+
+    for (lty, hir_ty) in hir_tys {
+        if hir_ty.span.from_expansion() || rewrites.iter().any(|rw| rw.span == hir_ty.span) {
+            continue;
+        }
+        let old = match tcx.sess.source_map().span_to_snippet(hir_ty.span) {
+            Ok(old) => old,
+            Err(_) => continue,
+        };
+        match ty_text(acx, asn, lty, hir_ty) {
+            Some(new) if new != old => rewrites.push(Rewrite {
+                span: hir_ty.span,
+                repl: Replacement::Text(new),
+            }),
+            Some(_) => {}
+            None => eprintln!("unable to rewrite type {}", old),
+        }
+    }
+    rewrites
+}
+
+/// Writing through a `Box` requires a mutable binding, so an immutable binding `x` of a local
+/// whose outermost pointer becomes a `Box` that is written through is rewritten to `mut x`.
+fn mut_binding_rewrite(
+    acx: &AnalysisCtxt,
+    asn: &Assignment,
+    local: Local,
+    pat_span: Span,
+) -> Option<Rewrite> {
+    let ptr = acx.local_tys[local].label;
+    if ptr == PointerId::NONE || pat_span.from_expansion() {
+        return None;
+    }
+    let perms = asn.perms()[ptr];
+    let (own, _) = type_desc::perms_to_desc(perms, asn.flags()[ptr]);
+    if own != Ownership::Box || !perms.contains(PermissionSet::WRITE) {
+        return None;
+    }
+    // Only simple `x` patterns are handled.
+    let name = acx.tcx().sess.source_map().span_to_snippet(pat_span).ok()?;
+    if !name.chars().all(|c| c == '_' || c.is_alphanumeric()) {
+        return None;
+    }
+    Some(Rewrite {
+        span: pat_span,
+        repl: Replacement::Text(format!("mut {}", name)),
+    })
+}
+
+/// Render the type `hir_ty`, whose labeled type is `lty`, with each pointer replaced by its
+/// inferred type.  Parts of the type that contain no pointers keep their original spelling.
+fn ty_text<'tcx>(
+    acx: &AnalysisCtxt<'_, 'tcx>,
+    asn: &Assignment,
+    lty: LTy<'tcx>,
+    hir_ty: &hir::Ty,
+) -> Option<String> {
+    match hir_ty.kind {
+        hir::TyKind::Ptr(ref mt) | hir::TyKind::Rptr(_, ref mt) if lty.label != PointerId::NONE => {
+            assert_eq!(lty.args.len(), 1);
+            let pointee = ty_text(acx, asn, lty.args[0], mt.ty)?;
+            let ptr = lty.label;
+            let (own, qty) = type_desc::perms_to_desc(asn.perms()[ptr], asn.flags()[ptr]);
+            Some(desc_text(own, qty, pointee))
+        }
+        _ => acx
+            .tcx()
+            .sess
+            .source_map()
+            .span_to_snippet(hir_ty.span)
+            .ok(),
+    }
+}
+
+/// Render a pointer to `pointee` with the given ownership and quantity, following
+/// `type_desc::convert_type`.
+fn desc_text(own: Ownership, qty: Quantity, pointee: String) -> String {
+    let mut ty = pointee;
+    if own == Ownership::Cell {
+        ty = format!("core::cell::Cell<{}>", ty);
+    }
+    ty = match qty {
+        Quantity::Single => ty,
+        Quantity::Slice | Quantity::OffsetPtr => format!("[{}]", ty),
+    };
+    ty = match own {
+        Ownership::Raw => format!("*const {}", ty),
+        Ownership::RawMut => format!("*mut {}", ty),
+        Ownership::Imm | Ownership::Cell => format!("&{}", ty),
+        Ownership::Mut => format!("&mut {}", ty),
+        Ownership::Rc => format!("std::rc::Rc<{}>", ty),
+        Ownership::Box => format!("Box<{}>", ty),
+    };
+    if qty == Quantity::OffsetPtr {
+        ty = format!("({}, usize)", ty);
+    }
+    ty
+}
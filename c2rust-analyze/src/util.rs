@@ -1,6 +1,8 @@
 use rustc_hir::def::DefKind;
 use rustc_middle::mir::{Local, Mutability, Operand, PlaceElem, PlaceRef, Rvalue};
+use rustc_middle::ty::subst::SubstsRef;
 use rustc_middle::ty::{DefIdTree, Ty, TyCtxt, TyKind};
+use rustc_span::def_id::DefId;
 
 #[derive(Debug)]
 pub enum RvalueDesc<'tcx> {
@@ -70,32 +72,60 @@ pub enum Callee<'tcx> {
         pointee_ty: Ty<'tcx>,
         mutbl: Mutability,
     },
+    /// `malloc` or `calloc`, which return a new owning pointer.
+    Malloc,
+    /// `realloc`, which takes ownership of its first argument and returns a new owning pointer.
+    Realloc,
+    /// `free`, which takes ownership of its argument.
+    Free,
+    /// Any other function.  If the callee is defined in the current crate, its signature is
+    /// available in `GlobalAnalysisCtxt::fn_sigs`.
+    Other {
+        def_id: DefId,
+        substs: SubstsRef<'tcx>,
+    },
 }
 
 pub fn ty_callee<'tcx>(tcx: TyCtxt<'tcx>, ty: Ty<'tcx>) -> Option<Callee<'tcx>> {
-    let (did, _substs) = match *ty.kind() {
+    let (did, substs) = match *ty.kind() {
         TyKind::FnDef(did, substs) => (did, substs),
         _ => return None,
     };
     let name = tcx.item_name(did);
 
-    match name.as_str() {
-        "offset" => {
-            // The `offset` inherent method of `*const T` and `*mut T`.
-            let parent_did = tcx.parent(did);
-            if tcx.def_kind(parent_did) != DefKind::Impl {
-                return None;
-            }
-            if tcx.impl_trait_ref(parent_did).is_some() {
-                return None;
-            }
+    if tcx.is_foreign_item(did) {
+        match name.as_str() {
+            "malloc" | "calloc" => return Some(Callee::Malloc),
+            "realloc" => return Some(Callee::Realloc),
+            "free" => return Some(Callee::Free),
+            _ => {}
+        }
+    }
+
+    if name.as_str() == "offset" {
+        // The `offset` inherent method of `*const T` and `*mut T`.
+        let parent_did = tcx.parent(did);
+        if tcx.def_kind(parent_did) == DefKind::Impl && tcx.impl_trait_ref(parent_did).is_none() {
             let parent_impl_ty = tcx.type_of(parent_did);
-            let (pointee_ty, mutbl) = match parent_impl_ty.kind() {
-                TyKind::RawPtr(tm) => (tm.ty, tm.mutbl),
-                _ => return None,
-            };
-            Some(Callee::PtrOffset { pointee_ty, mutbl })
+            if let TyKind::RawPtr(tm) = *parent_impl_ty.kind() {
+                return Some(Callee::PtrOffset {
+                    pointee_ty: tm.ty,
+                    mutbl: tm.mutbl,
+                });
+            }
         }
-        _ => None,
+    }
+
+    Some(Callee::Other {
+        def_id: did,
+        substs,
+    })
+}
+
+/// Check whether `ty` is `c_void`, as used in `*mut c_void`.
+pub fn is_c_void(tcx: TyCtxt, ty: Ty) -> bool {
+    match *ty.kind() {
+        TyKind::Adt(adt_def, _) => tcx.item_name(adt_def.did()).as_str() == "c_void",
+        _ => false,
     }
 }
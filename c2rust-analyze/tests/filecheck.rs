@@ -1,6 +1,9 @@
+use serde_json::{json, Value};
 use std::env;
 use std::fs;
+use std::io::Write;
 use std::os::unix::io::{AsRawFd, FromRawFd};
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 
 fn detect_filecheck() -> Option<&'static str> {
@@ -101,3 +104,366 @@ fn filecheck() {
         );
     }
 }
+
+/// Find the newest `lib{name}-*.rlib` in `dir`.
+fn find_rlib(dir: &Path, name: &str) -> PathBuf {
+    let prefix = format!("lib{}-", name);
+    fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| {
+            let file_name = path.file_name().unwrap().to_str().unwrap();
+            file_name.starts_with(&prefix) && file_name.ends_with(".rlib")
+        })
+        .max_by_key(|path| fs::metadata(path).unwrap().modified().unwrap())
+        .unwrap_or_else(|| panic!("no {}*.rlib in {}", prefix, dir.display()))
+}
+
+fn run(cmd: &mut Command) {
+    let status = cmd.status().unwrap();
+    assert!(
+        status.success(),
+        "{:?} failed with status {:?}",
+        cmd,
+        status
+    );
+}
+
+/// Instrument and run `tests/pdg_hints/write_unexercised.rs`, then analyze it using the resulting
+/// PDG in refine mode.  The program is compiled by `c2rust-instrument` acting as `rustc` directly
+/// rather than through `cargo`, so that the crate has the same `DefPathHash`es in both compilations.
+#[test]
+fn pdg_hints() {
+    let lib_dir = env::var("C2RUST_TARGET_LIB_DIR").unwrap();
+    let filecheck_bin = env::var("FILECHECK")
+        .ok()
+        .or_else(|| detect_filecheck().map(|s| s.to_owned()))
+        .unwrap_or_else(|| panic!("FileCheck not found - set FILECHECK=/path/to/FileCheck"));
+
+    let src = Path::new("tests/pdg_hints/write_unexercised.rs");
+    let crate_args = [
+        "--edition",
+        "2021",
+        "--crate-type",
+        "bin",
+        "--crate-name",
+        "pdg_hints",
+    ];
+    let tmp_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("pdg_hints");
+    fs::create_dir_all(&tmp_dir).unwrap();
+    let metadata_path = tmp_dir.join("metadata.bc");
+    let event_log_path = tmp_dir.join("event.log.bc");
+    let exe_path = tmp_dir.join("instrumented");
+
+    // This test runs from `target/<profile>/deps`, next to the `c2rust-analysis-rt` that
+    // `c2rust-analyze` depends on, and `c2rust-instrument` is built into `target/<profile>`.
+    let deps_dir = env::current_exe().unwrap().parent().unwrap().to_owned();
+    let instrument = deps_dir.parent().unwrap().join("c2rust-instrument");
+    run(Command::new("cargo")
+        .arg("build")
+        .arg("--manifest-path")
+        .arg(format!("{}/../Cargo.toml", env!("CARGO_MANIFEST_DIR")))
+        .args(&["--bin", "c2rust-instrument"]));
+    let instrument = fs::canonicalize(instrument).unwrap();
+
+    let sysroot = Command::new("rustc")
+        .args(&["--print", "sysroot"])
+        .output()
+        .unwrap()
+        .stdout;
+    let sysroot = String::from_utf8(sysroot).unwrap();
+
+    // The instrumentation appends to the metadata file, which the `cargo` wrapper would create.
+    fs::File::create(&metadata_path).unwrap();
+
+    let mut extern_arg = std::ffi::OsString::from("c2rust_analysis_rt=");
+    extern_arg.push(find_rlib(&deps_dir, "c2rust_analysis_rt"));
+    run(Command::new(&instrument)
+        .arg("rustc")
+        .arg(src)
+        .args(&crate_args)
+        .arg("-o")
+        .arg(&exe_path)
+        .arg("-L")
+        .arg(format!("dependency={}", deps_dir.display()))
+        .arg("--extern")
+        .arg(extern_arg)
+        .env("RUSTC_WRAPPER", &instrument)
+        .env("RUST_SYSROOT", sysroot.trim())
+        .env("C2RUST_INSTRUMENT_METADATA_PATH", &metadata_path)
+        .env("CARGO_PRIMARY_PACKAGE", "1")
+        .env("CARGO_BIN_NAME", "pdg_hints"));
+
+    run(Command::new(&exe_path)
+        .env("METADATA_FILE", &metadata_path)
+        .env("INSTRUMENT_BACKEND", "log")
+        .env("INSTRUMENT_OUTPUT", &event_log_path)
+        .env("INSTRUMENT_OUTPUT_APPEND", "false"));
+
+    let analyze = Command::new("cargo")
+        .arg("run")
+        .arg("--manifest-path")
+        .arg(format!("{}/Cargo.toml", env!("CARGO_MANIFEST_DIR")))
+        .arg("--")
+        .arg(src)
+        .args(&crate_args)
+        .arg("-o")
+        .arg(tmp_dir.join("analyzed"))
+        .arg("-L")
+        .arg(&lib_dir)
+        .env("C2RUST_ANALYZE_PDG_METADATA", &metadata_path)
+        .env("C2RUST_ANALYZE_PDG_EVENT_LOG", &event_log_path)
+        .env("C2RUST_ANALYZE_PDG_MODE", "refine")
+        .output()
+        .unwrap();
+    assert!(
+        analyze.status.success(),
+        "c2rust-analyze failed with status {:?}:\n{}",
+        analyze.status,
+        String::from_utf8_lossy(&analyze.stderr),
+    );
+
+    let mut filecheck = Command::new(&filecheck_bin)
+        .arg(src)
+        .stdin(Stdio::piped())
+        .spawn()
+        .unwrap();
+    filecheck
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(&analyze.stderr)
+        .unwrap();
+    let filecheck_status = filecheck.wait().unwrap();
+    assert!(
+        filecheck_status.success(),
+        "{:?}: FileCheck failed with status {:?}",
+        src,
+        filecheck_status,
+    );
+}
+
+/// Get the field `key` of the JSON object `obj`.
+fn field<'a>(obj: &'a Value, key: &str) -> &'a Value {
+    obj.get(key)
+        .unwrap_or_else(|| panic!("missing field {:?} in {}", key, obj))
+}
+
+fn check_span(span: &Value) {
+    if span.is_null() {
+        return;
+    }
+    assert!(field(span, "file").is_string(), "bad span {}", span);
+    for key in ["lo_line", "lo_col", "hi_line", "hi_col"] {
+        assert!(field(span, key).is_u64(), "bad span {}", span);
+    }
+}
+
+fn check_pointer(ptr: &Value) {
+    assert!(field(ptr, "id").is_string(), "bad pointer {}", ptr);
+    for key in ["permissions", "flags"] {
+        let names = field(ptr, key).as_array().unwrap();
+        assert!(names.iter().all(Value::is_string), "bad pointer {}", ptr);
+    }
+    assert!(field(ptr, "ownership").is_string(), "bad pointer {}", ptr);
+    assert!(field(ptr, "quantity").is_string(), "bad pointer {}", ptr);
+}
+
+/// Find the element of the array `items` whose `key` is `value`.
+fn find<'a>(items: &'a Value, key: &str, value: &str) -> &'a Value {
+    items
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|item| field(item, key) == value)
+        .unwrap_or_else(|| panic!("no element with {} = {:?} in {}", key, value, items))
+}
+
+/// Check that an externally tagged enum value is either a variant name or an object with the
+/// variant name as its only key.
+fn check_enum(value: &Value) {
+    match *value {
+        Value::String(_) => {}
+        Value::Object(ref obj) => assert_eq!(obj.len(), 1, "bad enum value {}", value),
+        _ => panic!("bad enum value {}", value),
+    }
+}
+
+/// Analyze `tests/filecheck/alias2.rs` with `C2RUST_ANALYZE_JSON_OUTPUT` set, and check that the
+/// output follows the schema in `src/json_output.rs`.
+#[test]
+fn json_output() {
+    let lib_dir = env::var("C2RUST_TARGET_LIB_DIR").unwrap();
+    let src = Path::new("tests/filecheck/alias2.rs");
+    let tmp_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("json_output");
+    fs::create_dir_all(&tmp_dir).unwrap();
+    let json_path = tmp_dir.join("alias2.json");
+    let _ = fs::remove_file(&json_path);
+
+    run(Command::new("cargo")
+        .arg("run")
+        .arg("--manifest-path")
+        .arg(format!("{}/Cargo.toml", env!("CARGO_MANIFEST_DIR")))
+        .arg("--")
+        .arg(src)
+        .arg("-L")
+        .arg(&lib_dir)
+        .arg("--crate-type")
+        .arg("rlib")
+        .env("C2RUST_ANALYZE_JSON_OUTPUT", &json_path)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null()));
+
+    let output: Value = serde_json::from_str(&fs::read_to_string(&json_path).unwrap()).unwrap();
+    assert_eq!(field(&output, "version"), 1);
+
+    for func in field(&output, "functions").as_array().unwrap() {
+        assert!(field(func, "name").is_string(), "bad function {}", func);
+        check_span(field(func, "span"));
+        for local in field(func, "locals").as_array().unwrap() {
+            assert!(field(local, "local").is_string(), "bad local {}", local);
+            let name = field(local, "name");
+            assert!(name.is_null() || name.is_string(), "bad local {}", local);
+            check_span(field(local, "span"));
+            assert!(field(local, "ty").is_string(), "bad local {}", local);
+            check_pointer(field(local, "addr_of"));
+            field(local, "pointers")
+                .as_array()
+                .unwrap()
+                .iter()
+                .for_each(check_pointer);
+        }
+        for rw in field(func, "rewrites").as_array().unwrap() {
+            check_span(field(rw, "span"));
+            let location = field(rw, "location");
+            assert!(field(location, "block").is_u64(), "bad rewrite {}", rw);
+            assert!(field(location, "statement").is_u64(), "bad rewrite {}", rw);
+            field(rw, "sub")
+                .as_array()
+                .unwrap()
+                .iter()
+                .for_each(check_enum);
+            field(rw, "kinds")
+                .as_array()
+                .unwrap()
+                .iter()
+                .for_each(check_enum);
+        }
+    }
+
+    let copy_good = find(field(&output, "functions"), "name", "alias2_copy_good");
+    let p = find(field(copy_good, "locals"), "name", "p");
+    assert_eq!(field(p, "local"), "_2");
+    assert_eq!(field(p, "ty"), "*mut i32");
+    let p_ptr = &field(p, "pointers")[0];
+    assert_eq!(field(p_ptr, "permissions"), &json!(["UNIQUE"]));
+    assert_eq!(field(p_ptr, "flags"), &json!([]));
+    assert_eq!(field(p_ptr, "ownership"), "Imm");
+    assert_eq!(field(p_ptr, "quantity"), "Single");
+    assert_eq!(
+        field(copy_good, "rewrites"),
+        &json!([{
+            "span": {
+                "file": "tests/filecheck/alias2.rs",
+                "lo_line": 7,
+                "lo_col": 13,
+                "hi_line": 7,
+                "hi_col": 14,
+            },
+            "location": { "block": 0, "statement": 1 },
+            "sub": ["AssignRvalue", { "RvalueOperand": 0 }],
+            "kinds": ["MutToImm"],
+        }])
+    );
+
+    let copy_bad = find(field(&output, "functions"), "name", "alias2_copy_bad");
+    let x = find(field(copy_bad, "locals"), "name", "x");
+    let x_ptr = &field(x, "pointers")[0];
+    assert_eq!(field(x_ptr, "permissions"), &json!(["READ", "WRITE"]));
+    assert_eq!(field(x_ptr, "flags"), &json!(["CELL"]));
+    assert_eq!(field(x_ptr, "ownership"), "Cell");
+}
+
+/// Apply the rewrites of `tests/rewrite_modes/refs.rs` in each `C2RUST_ANALYZE_REWRITE_MODE`.
+/// The `diff` output is checked with FileCheck, and the files written by `alongside` and `inplace`
+/// must be the same and must compile.  Those two modes are run on copies of the input.
+#[test]
+fn rewrite_modes() {
+    let lib_dir = env::var("C2RUST_TARGET_LIB_DIR").unwrap();
+    let filecheck_bin = env::var("FILECHECK")
+        .ok()
+        .or_else(|| detect_filecheck().map(|s| s.to_owned()))
+        .unwrap_or_else(|| panic!("FileCheck not found - set FILECHECK=/path/to/FileCheck"));
+
+    let src = Path::new("tests/rewrite_modes/refs.rs");
+    let orig = fs::read_to_string(src).unwrap();
+    let tmp_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("rewrite_modes");
+    fs::create_dir_all(&tmp_dir).unwrap();
+
+    let analyze = |path: &Path, mode: &str| {
+        let output = Command::new("cargo")
+            .arg("run")
+            .arg("--manifest-path")
+            .arg(format!("{}/Cargo.toml", env!("CARGO_MANIFEST_DIR")))
+            .arg("--")
+            .arg(path)
+            .arg("-L")
+            .arg(&lib_dir)
+            .arg("--crate-type")
+            .arg("rlib")
+            .arg("--out-dir")
+            .arg(&tmp_dir)
+            .env("C2RUST_ANALYZE_REWRITE_MODE", mode)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "c2rust-analyze failed with status {:?} in {} mode:\n{}",
+            output.status,
+            mode,
+            String::from_utf8_lossy(&output.stderr),
+        );
+        output
+    };
+
+    let diff = analyze(src, "diff");
+    assert_eq!(fs::read_to_string(src).unwrap(), orig);
+    let mut filecheck = Command::new(&filecheck_bin)
+        .arg(src)
+        .arg("--check-prefix=DIFF")
+        .stdin(Stdio::piped())
+        .spawn()
+        .unwrap();
+    filecheck
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(&diff.stdout)
+        .unwrap();
+    let filecheck_status = filecheck.wait().unwrap();
+    assert!(
+        filecheck_status.success(),
+        "{:?}: FileCheck failed with status {:?}",
+        src,
+        filecheck_status,
+    );
+
+    let alongside_src = tmp_dir.join("alongside.rs");
+    fs::copy(src, &alongside_src).unwrap();
+    analyze(&alongside_src, "alongside");
+    assert_eq!(fs::read_to_string(&alongside_src).unwrap(), orig);
+    let alongside = fs::read_to_string(tmp_dir.join("alongside.new")).unwrap();
+
+    let inplace_src = tmp_dir.join("inplace.rs");
+    fs::copy(src, &inplace_src).unwrap();
+    analyze(&inplace_src, "inplace");
+    let inplace = fs::read_to_string(&inplace_src).unwrap();
+    assert_ne!(inplace, orig);
+    assert_eq!(inplace, alongside);
+
+    run(Command::new("rustc")
+        .arg(&inplace_src)
+        .args(&["--edition", "2021", "--crate-type", "rlib"])
+        .arg("--out-dir")
+        .arg(&tmp_dir));
+}
@@ -0,0 +1,14 @@
+// The caller is analyzed before the callee, so the permissions of `p` in `call_set` are only
+// known after the callee's signature has been updated.
+
+// CHECK-LABEL: final labeling for "call_set"
+// CHECK-DAG: ([[#@LINE+1]]: p): {{.*}}type = READ | WRITE | UNIQUE#
+pub unsafe fn call_set(p: *mut i32) {
+    set(p, 1);
+}
+
+// CHECK-LABEL: final labeling for "set"
+// CHECK-DAG: ([[#@LINE+1]]: p): {{.*}}type = READ | WRITE | UNIQUE#
+pub unsafe fn set(p: *mut i32, x: i32) {
+    *p = x;
+}
@@ -0,0 +1,15 @@
+// Calls to generic functions are treated like calls to unknown functions, since the callee's
+// signature mentions `T` where the caller has `i32`.
+
+// CHECK-LABEL: final labeling for "call_generic"
+pub unsafe fn call_generic(p: *mut i32) -> i32 {
+    // CHECK-DAG: ([[#@LINE+1]]: q): {{.*}}type = READ | UNIQUE#
+    let q = first(p);
+    *q
+}
+
+// CHECK-LABEL: final labeling for "first"
+// CHECK-DAG: ([[#@LINE+1]]: p): {{.*}}type = UNIQUE#
+pub unsafe fn first<T>(p: *mut T) -> *mut T {
+    p
+}
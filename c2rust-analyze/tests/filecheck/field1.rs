@@ -0,0 +1,23 @@
+// Fields of enum variants, and of ADTs from other crates.
+
+pub enum Slot {
+    Empty,
+    Full(*mut i32),
+}
+
+// CHECK-LABEL: final labeling for "fill_slot"
+pub unsafe fn fill_slot(s: Slot) {
+    // CHECK-DAG: ([[#@LINE+1]]: Slot::Full(p)): {{.*}}type = READ | WRITE | UNIQUE#
+    if let Slot::Full(p) = s {
+        *p = 1;
+    }
+}
+
+// CHECK-LABEL: final labeling for "read_option"
+pub unsafe fn read_option(o: Option<*const i32>) -> i32 {
+    match o {
+        // CHECK-DAG: ([[#@LINE+1]]: Some(p)): {{.*}}type = READ | UNIQUE#
+        Some(p) => *p,
+        None => 0,
+    }
+}
@@ -0,0 +1,39 @@
+use std::ffi::c_void;
+
+extern "C" {
+    fn malloc(size: usize) -> *mut c_void;
+    fn realloc(ptr: *mut c_void, size: usize) -> *mut c_void;
+    fn free(ptr: *mut c_void);
+}
+
+// CHECK-LABEL: final labeling for "malloc_free"
+pub unsafe fn malloc_free(n: usize) -> i32 {
+    // CHECK-DAG: ([[#@LINE+3]]: p): {{.*}}type = READ | WRITE | UNIQUE | FREE#
+    // CHECK-DAG: rewrite {{.*}}:[[#@LINE+2]]: `p` -> `mut p`
+    // CHECK-DAG: rewrite {{.*}}:[[#@LINE+1]]: `malloc(n) as *mut i32` -> `Box::new(<i32>::default())`
+    let p = malloc(n) as *mut i32;
+    *p = 1;
+    let x = *p;
+    // CHECK-DAG: rewrite {{.*}}:[[#@LINE+1]]: `free(p as *mut c_void)` -> `drop(p)`
+    free(p as *mut c_void);
+    x
+}
+
+// CHECK-LABEL: final labeling for "malloc_array"
+pub unsafe fn malloc_array(n: usize) -> i32 {
+    // CHECK-DAG: ([[#@LINE+2]]: p): {{.*}}type = UNIQUE | OFFSET_ADD | FREE#
+    // CHECK-DAG: rewrite {{.*}}:[[#@LINE+1]]: `malloc(n * 4) as *mut i32` -> `vec![<i32>::default(); (n * 4) / core::mem::size_of::<i32>()].into_boxed_slice()`
+    let p = malloc(n * 4) as *mut i32;
+    // CHECK-DAG: ([[#@LINE+4]]: p): {{.*}}type = READ | WRITE | UNIQUE | OFFSET_ADD | FREE#
+    // CHECK-DAG: ([[#@LINE+3]]: p): std::boxed::Box<[i32]>
+    // CHECK-DAG: rewrite {{.*}}:[[#@LINE+2]]: `p` -> `mut p`
+    // CHECK-DAG: rewrite {{.*}}:[[#@LINE+1]]: `realloc(p as *mut c_void, n * 8) as *mut i32` -> `{ let mut v = p.into_vec(); v.resize((n * 8) / core::mem::size_of::<i32>(), <i32>::default()); v.into_boxed_slice() }`
+    let p = realloc(p as *mut c_void, n * 8) as *mut i32;
+    // CHECK-DAG: rewrite {{.*}}:[[#@LINE+1]]: `p.offset(1)` -> `&mut (&mut (&mut *p)[1 as usize..])[0]`
+    *p.offset(1) = 1;
+    // CHECK-DAG: rewrite {{.*}}:[[#@LINE+1]]: `p.offset(1)` -> `&(&(&*p)[1 as usize..])[0]`
+    let x = *p.offset(1);
+    // CHECK-DAG: rewrite {{.*}}:[[#@LINE+1]]: `free(p as *mut c_void)` -> `drop(p)`
+    free(p as *mut c_void);
+    x
+}
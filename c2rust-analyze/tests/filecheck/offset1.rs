@@ -1,13 +1,25 @@
 use std::ptr;
 
 // CHECK-LABEL: final labeling for "offset1_const"
-// CHECK-DAG: ([[#@LINE+1]]: x): {{.*}}type = READ | UNIQUE | OFFSET_ADD | OFFSET_SUB#
+// CHECK-DAG: ([[#@LINE+2]]: x): {{.*}}type = READ | UNIQUE | OFFSET_ADD#
+// CHECK-DAG: rewrite {{.*}}:[[#@LINE+1]]: `*mut i32` -> `&[i32]`
 pub unsafe fn offset1_const(x: *mut i32) -> i32 {
-    // CHECK-DAG: ([[#@LINE+2]]: x): {{.*}}type = READ | UNIQUE | OFFSET_ADD | OFFSET_SUB#
-    // CHECK-DAG: ([[#@LINE+1]]: x.offset(1)): {{.*}}type = READ | UNIQUE#
+    // CHECK-DAG: ([[#@LINE+3]]: x): {{.*}}type = READ | UNIQUE | OFFSET_ADD#
+    // CHECK-DAG: ([[#@LINE+2]]: x.offset(1)): {{.*}}type = READ | UNIQUE#
+    // CHECK-DAG: rewrite {{.*}}:[[#@LINE+1]]: `x.offset(1)` -> `&(&x[1 as usize..])[0]`
     *x.offset(1)
 }
 
+// CHECK-LABEL: final labeling for "offset1_neg"
+// CHECK-DAG: ([[#@LINE+2]]: x): {{.*}}type = READ | UNIQUE | OFFSET_SUB#
+// CHECK-DAG: rewrite {{.*}}:[[#@LINE+1]]: `*mut i32` -> `(&[i32], usize)`
+pub unsafe fn offset1_neg(x: *mut i32) -> i32 {
+    // CHECK-DAG: ([[#@LINE+3]]: x): {{.*}}type = READ | UNIQUE | OFFSET_SUB#
+    // CHECK-DAG: ([[#@LINE+2]]: x.offset(-1)): {{.*}}type = READ | UNIQUE#
+    // CHECK-DAG: rewrite {{.*}}:[[#@LINE+1]]: `x.offset(-1)` -> `&x.0[(x.1 as isize + (-1)) as usize]`
+    *x.offset(-1)
+}
+
 // CHECK-LABEL: final labeling for "offset1_unknown"
 // CHECK-DAG: ([[#@LINE+1]]: x): {{.*}}type = READ | UNIQUE | OFFSET_ADD | OFFSET_SUB#
 pub unsafe fn offset1_unknown(x: *mut i32, off: isize) -> i32 {
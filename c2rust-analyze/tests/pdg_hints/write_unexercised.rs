@@ -0,0 +1,14 @@
+// Run with a PDG of a run that doesn't take the branch writing through `p`, in refine mode.  The
+// hint must not remove `WRITE`, since the code writes through `p`.
+
+// CHECK: pdg: {{.*}} ([[#@LINE+5]]: p) in "main": never written through at runtime, but it or a derived pointer is written through in the code; keeping WRITE
+// CHECK-LABEL: final labeling for "main"
+fn main() {
+    let mut x = 1;
+    // CHECK-DAG: ([[#@LINE+1]]: p): {{.*}}type = READ | WRITE{{.*}}#*mut i32
+    let p = &mut x as *mut i32;
+    if std::env::args().count() > 1 {
+        unsafe { *p = 2 };
+    }
+    std::process::exit(unsafe { *p } - 1);
+}
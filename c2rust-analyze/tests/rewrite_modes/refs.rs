@@ -0,0 +1,48 @@
+// The `rewrite_modes` test in `tests/filecheck.rs` checks the output of the `diff` mode against
+// the `DIFF` lines, and that the files written by the `alongside` and `inplace` modes compile.
+// DIFF: {{^}}--- old/tests/rewrite_modes/refs.rs
+// DIFF: {{^}}+++ new/tests/rewrite_modes/refs.rs
+use std::ffi::c_void;
+
+extern "C" {
+    fn malloc(size: usize) -> *mut c_void;
+    fn free(ptr: *mut c_void);
+}
+
+// DIFF: {{^}}-pub unsafe fn copy_ptr(x: *mut i32) -> i32 {
+// DIFF: {{^}}-    let p = x;
+// DIFF: {{^}}+pub unsafe fn copy_ptr(x: &mut i32) -> i32 {
+// DIFF: {{^}}+    let p = &*x;
+pub unsafe fn copy_ptr(x: *mut i32) -> i32 {
+    let p = x;
+    let y = *p;
+    let q = x;
+    *q = y + 1;
+    *q
+}
+
+// DIFF: {{^}}-    *p.offset(1) = 2;
+// DIFF: {{^}}-    *p.offset((b')' - b'(') as isize)
+// DIFF: {{^}}+pub unsafe fn second(p: (&mut [i32], usize)) -> i32 {
+// DIFF: {{^}}+    *&mut (&mut (&mut p.0[p.1..])[1 as usize..])[0] = 2;
+// DIFF: {{^}}+    *&p.0[(p.1 as isize + ((b')' - b'(') as isize)) as usize]
+pub unsafe fn second(p: *mut i32) -> i32 {
+    *p.offset(1) = 2;
+    *p.offset((b')' - b'(') as isize)
+}
+
+// DIFF: {{^}}-    let p = malloc(n * 4) as *mut i32;
+// DIFF: {{^}}-    *p.offset(1) = 2;
+// DIFF: {{^}}-    let x = *p.offset(1);
+// DIFF: {{^}}-    free(p as *mut c_void);
+// DIFF: {{^}}+    let mut p = vec![<i32>::default(); (n * 4) / core::mem::size_of::<i32>()].into_boxed_slice();
+// DIFF: {{^}}+    *&mut (&mut (&mut *p)[1 as usize..])[0] = 2;
+// DIFF: {{^}}+    let x = *&(&(&*p)[1 as usize..])[0];
+// DIFF: {{^}}+    drop(p);
+pub unsafe fn boxed(n: usize) -> i32 {
+    let p = malloc(n * 4) as *mut i32;
+    *p.offset(1) = 2;
+    let x = *p.offset(1);
+    free(p as *mut c_void);
+    x
+}
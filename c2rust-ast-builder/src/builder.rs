@@ -1243,6 +1243,22 @@ impl Builder {
         })
     }
 
+    pub fn tuple_struct_pat<Pa>(self, path: Pa, pats: Vec<Pat>) -> Pat
+    where
+        Pa: Make<Path>,
+    {
+        let path = path.make(&self);
+        Pat::TupleStruct(PatTupleStruct {
+            attrs: self.attrs,
+            path,
+            pat: PatTuple {
+                attrs: vec![],
+                paren_token: token::Paren(self.span),
+                elems: punct(pats),
+            },
+        })
+    }
+
     pub fn qpath_pat<Pa>(self, qself: Option<QSelf>, path: Pa) -> Box<Pat>
     where
         Pa: Make<Path>,
@@ -1625,6 +1641,28 @@ impl Builder {
         }))
     }
 
+    /// `macro_rules! name { rules }` item
+    pub fn macro_rules_item<I, Ts>(self, name: I, rules: Ts) -> Box<Item>
+    where
+        I: Make<Ident>,
+        Ts: Make<TokenStream>,
+    {
+        let name = name.make(&self);
+        let rules = rules.make(&self);
+        let mac = Macro {
+            path: "macro_rules".make(&self),
+            tokens: rules,
+            bang_token: Token![!](self.span),
+            delimiter: MacroDelimiter::Brace(token::Brace(self.span)),
+        };
+        Box::new(Item::Macro(ItemMacro {
+            attrs: self.attrs,
+            semi_token: None,
+            ident: Some(name),
+            mac,
+        }))
+    }
+
     pub fn variant<I>(self, name: I, fields: Fields) -> Variant
     where
         I: Make<Ident>,
@@ -1665,6 +1703,25 @@ impl Builder {
         }))
     }
 
+    /// `impl Trait for ty { items }` item
+    pub fn trait_impl_item<Pa>(self, trait_: Pa, ty: Box<Type>, items: Vec<ImplItem>) -> Box<Item>
+    where
+        Pa: Make<Path>,
+    {
+        let trait_ = trait_.make(&self);
+        Box::new(Item::Impl(ItemImpl {
+            attrs: self.attrs,
+            unsafety: self.unsafety.to_token(),
+            defaultness: Defaultness::Final.to_token(),
+            generics: self.generics,
+            trait_: Some((None, trait_, Token![for](self.span))),
+            self_ty: ty,
+            impl_token: Token![impl](self.span),
+            brace_token: token::Brace(self.span),
+            items,
+        }))
+    }
+
     pub fn extern_crate_item<I>(self, name: I, rename: Option<I>) -> Box<Item>
     where
         I: Make<Ident>,
@@ -1823,6 +1880,38 @@ impl Builder {
 
     // Impl Items
 
+    pub fn fn_impl_item<S>(self, sig: S, block: Block) -> ImplItem
+    where
+        S: Make<Signature>,
+    {
+        let sig = sig.make(&self);
+        ImplItem::Method(ImplItemMethod {
+            attrs: self.attrs,
+            vis: self.vis,
+            defaultness: None,
+            sig,
+            block,
+        })
+    }
+
+    pub fn type_impl_item<I>(self, name: I, ty: Box<Type>) -> ImplItem
+    where
+        I: Make<Ident>,
+    {
+        let name = name.make(&self);
+        ImplItem::Type(ImplItemType {
+            attrs: self.attrs,
+            vis: self.vis,
+            defaultness: None,
+            type_token: Token![type](self.span),
+            ident: name,
+            generics: self.generics,
+            eq_token: Token![=](self.span),
+            ty: *ty,
+            semi_token: Token![;](self.span),
+        })
+    }
+
     pub fn mac_impl_item(self, mac: Macro) -> ImplItem {
         ImplItem::Macro(ImplItemMacro {
             attrs: self.attrs,
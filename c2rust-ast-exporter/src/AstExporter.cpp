@@ -532,8 +532,18 @@ class TypeEncoder final : public TypeVisitor<TypeEncoder> {
 class TranslateASTVisitor final
     : public RecursiveASTVisitor<TranslateASTVisitor> {
 
+    // An expression that is a complete argument of a function-like macro
+    // invocation, along with the index of the parameter it was passed for and
+    // its spelling at the call site.
+    struct MacroArgInfo {
+        Expr *E;
+        unsigned Param;
+        std::string Text;
+    };
+
     struct MacroExpansionInfo {
         StringRef Name;
+        std::vector<MacroArgInfo> Args;
     };
 
     ASTContext *Context;
@@ -763,6 +773,62 @@ class TranslateASTVisitor final
         return true;
     }
 
+    // Record E if it is a complete argument of a function-like macro
+    // invocation, so that the macro can be translated into a function that
+    // takes E as an argument.
+    void VisitMacroArg(Expr *E) {
+        auto &Mgr = Context->getSourceManager();
+        auto Begin = E->getSourceRange().getBegin();
+        auto End = E->getSourceRange().getEnd();
+        if (!Begin.isMacroID() || !End.isMacroID() ||
+            !Mgr.isMacroArgExpansion(Begin) || !Mgr.isMacroArgExpansion(End))
+            return;
+
+        // The immediate expansion of an argument token is the use of the
+        // parameter it was substituted for, inside the expanded macro body.
+        // The immediate expansion of that is the macro invocation.
+#if CLANG_VERSION_MAJOR < 7
+        auto ParamLoc = Mgr.getImmediateExpansionRange(Begin).first;
+        auto EndParamLoc = Mgr.getImmediateExpansionRange(End).first;
+        auto CallLoc = Mgr.getImmediateExpansionRange(ParamLoc).first;
+#else // CLANG_VERSION_MAJOR >= 7
+        auto ParamLoc = Mgr.getImmediateExpansionRange(Begin).getBegin();
+        auto EndParamLoc = Mgr.getImmediateExpansionRange(End).getBegin();
+        auto CallLoc = Mgr.getImmediateExpansionRange(ParamLoc).getBegin();
+#endif
+        if (ParamLoc != EndParamLoc || !ParamLoc.isMacroID())
+            return;
+
+        StringRef name;
+        MacroInfo *mac = getMacroInfo(CallLoc, name);
+        if (!mac || !mac->isFunctionLike())
+            return;
+        // Only record arguments of invocations that VisitMacro accepted
+        auto info = macros.find(mac);
+        if (info == macros.end() || info->second.Name != name)
+            return;
+
+        Token ParamTok;
+        if (Lexer::getRawToken(Mgr.getSpellingLoc(ParamLoc), ParamTok, Mgr,
+                               Context->getLangOpts(), false) ||
+            !ParamTok.is(tok::raw_identifier))
+            return;
+        auto Params = mac->params();
+        auto Param = std::find_if(Params.begin(), Params.end(),
+                                  [&](const IdentifierInfo *II) {
+                                      return II->getName() == ParamTok.getRawIdentifier();
+                                  });
+        if (Param == Params.end())
+            return;
+
+        auto Text = Lexer::getSourceText(
+            CharSourceRange::getTokenRange(Mgr.getSpellingLoc(Begin),
+                                           Mgr.getSpellingLoc(End)),
+            Mgr, Context->getLangOpts());
+        info->second.Args.push_back(
+            {E, unsigned(Param - Params.begin()), Text.str()});
+    }
+
     static bool isScalarAsmType(QualType ty) {
         ty = ty.getCanonicalType();
         switch (ty->getTypeClass()) {
@@ -831,8 +897,31 @@ class TranslateASTVisitor final
             std::vector<void *> childIds;
             auto range = SourceRange(Mac->getDefinitionLoc(), Mac->getDefinitionEndLoc());
             encode_entry_raw(Mac, tag, range, QualType(), false,
-                             false, false, childIds, [Name](CborEncoder *local) {
+                             false, false, childIds, [Name, Mac, &Info](CborEncoder *local) {
                                  cbor_encode_string(local, Name.str());
+                                 if (!Mac->isFunctionLike())
+                                     return;
+
+                                 CborEncoder arrayEncoder;
+                                 cbor_encoder_create_array(local, &arrayEncoder,
+                                                           Mac->getNumParams());
+                                 for (auto *Param : Mac->params())
+                                     cbor_encode_string(&arrayEncoder, Param->getName().str());
+                                 cbor_encoder_close_container(local, &arrayEncoder);
+
+                                 cbor_encode_boolean(local, Mac->isVariadic());
+
+                                 cbor_encoder_create_array(local, &arrayEncoder,
+                                                           Info.Args.size());
+                                 for (auto &Arg : Info.Args) {
+                                     CborEncoder argEncoder;
+                                     cbor_encoder_create_array(&arrayEncoder, &argEncoder, 3);
+                                     cbor_encode_uint(&argEncoder, uintptr_t(Arg.E));
+                                     cbor_encode_uint(&argEncoder, Arg.Param);
+                                     cbor_encode_string(&argEncoder, Arg.Text);
+                                     cbor_encoder_close_container(&arrayEncoder, &argEncoder);
+                                 }
+                                 cbor_encoder_close_container(local, &arrayEncoder);
                              });
 
         }
@@ -1182,6 +1271,8 @@ class TranslateASTVisitor final
         curMacroExpansionStack.clear();
         curMacroExpansionSource = StringRef();
 
+        VisitMacroArg(E);
+
         // We only translate constant macro objects to Rust consts, so this
         // expression must be constant.
         // if (!E->isConstantInitializer(*Context, false))
@@ -2248,7 +2339,8 @@ class TranslateASTVisitor final
 #endif // CLANG_VERSION_MAJOR
 
     bool VisitImaginaryLiteral(ImaginaryLiteral *L) {
-        printWarning("Encountered unsupported imaginary literal", L);
+        std::vector<void *> childIds = {L->getSubExpr()};
+        encode_entry(L, TagImaginaryLiteral, childIds);
         return true;
     }
 
@@ -2492,7 +2584,7 @@ class TranslateConsumer : public clang::ASTConsumer {
             cbor_encoder_init(&encoder, buffer, len, 0);
 
             CborEncoder outer;
-            cbor_encoder_create_array(&encoder, &outer, 6);
+            cbor_encoder_create_array(&encoder, &outer, 7);
 
             CborEncoder array;
 
@@ -2598,6 +2690,19 @@ class TranslateConsumer : public clang::ASTConsumer {
             auto target = Context.getTargetInfo().getTriple().str();
             cbor_encode_string(&outer, target);
 
+            // 7. Enabled target features, e.g. "avx"
+            std::vector<std::string> features;
+            for (auto &feature :
+                 Context.getTargetInfo().getTargetOpts().FeatureMap) {
+                if (feature.getValue())
+                    features.push_back(feature.getKey().str());
+            }
+            std::sort(features.begin(), features.end());
+            cbor_encoder_create_array(&outer, &array, features.size());
+            for (auto &feature : features)
+                cbor_encode_string(&array, feature);
+            cbor_encoder_close_container(&outer, &array);
+
             cbor_encoder_close_container(&encoder, &outer);
         };
 
@@ -89,6 +89,7 @@ enum ASTEntryTag {
     TagStringLiteral,
     TagCharacterLiteral,
     TagFloatingLiteral,
+    TagImaginaryLiteral,
 };
 
 enum TypeTag {
@@ -120,6 +120,7 @@ pub struct AstContext {
     pub files: Vec<SrcFile>,
     pub va_list_kind: BuiltinVaListKind,
     pub target: String,
+    pub target_features: Vec<String>,
 }
 
 pub fn expect_opt_str(val: &Value) -> Option<Option<&str>> {
@@ -161,14 +162,18 @@ pub fn process(items: Value) -> error::Result<AstContext> {
     type RawComment = (u64, u64, u64, ByteBuf);
     type VaListKind = u64;
     type Target = String;
-    let (all_nodes, top_nodes, files, raw_comments, va_list_kind, target): (
+    type TargetFeature = String;
+    type Exported = (
         Vec<AllNode>,
         Vec<TopNode>,
         Vec<File>,
         Vec<RawComment>,
         VaListKind,
         Target,
-    ) = from_value(items)?;
+        Vec<TargetFeature>,
+    );
+    let (all_nodes, top_nodes, files, raw_comments, va_list_kind, target, target_features): Exported =
+        from_value(items)?;
 
     let va_list_kind = import_va_list_kind(va_list_kind);
 
@@ -272,5 +277,6 @@ pub fn process(items: Value) -> error::Result<AstContext> {
         files,
         va_list_kind,
         target,
+        target_features,
     })
 }
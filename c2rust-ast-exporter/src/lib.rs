@@ -1,12 +1,30 @@
 use serde_cbor::{from_slice, Value};
 use std::collections::HashMap;
-use std::ffi::{CStr, CString};
-use std::io::{Error, ErrorKind};
+use std::env;
+use std::ffi::{CStr, CString, OsString};
+use std::io::{self, Error, ErrorKind, Write};
 use std::path::Path;
+use std::process::{self, Command, Stdio};
 use std::slice;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
 
 pub mod clang_ast;
 
+/// The exporter parses its arguments with LLVM's global command-line option
+/// registry and keeps other process-wide state, so only one export may run at
+/// a time in a given process. Exports that should run in parallel have to go
+/// through `get_ast_cbor_in_subprocess` instead.
+static EXPORTER_LOCK: Mutex<()> = Mutex::new(());
+
+/// Environment variable telling a process started by
+/// `get_ast_cbor_in_subprocess` to act as an exporter.
+const SUBPROCESS_ENV: &str = "C2RUST_AST_EXPORTER_SUBPROCESS";
+
+/// Whether the current executable called `run_as_exporter_subprocess`, so that
+/// it can be used to export ASTs in subprocesses.
+static SUBPROCESS_SUPPORTED: AtomicBool = AtomicBool::new(false);
+
 pub fn get_clang_major_version() -> Option<u32> {
     let s = unsafe { CStr::from_ptr(clang_version()) };
     s.to_str()
@@ -24,18 +42,117 @@ pub fn get_untyped_ast(
     extra_args: &[&str],
     debug: bool,
 ) -> Result<clang_ast::AstContext, Error> {
+    let buffer = get_ast_cbor(file_path, cc_db, extra_args, debug)?;
+    untyped_ast_from_cbor(&buffer[..])
+}
+
+/// Run the AST exporter on a single translation unit and return the
+/// serialized CBOR it produced, without decoding it.
+pub fn get_ast_cbor(
+    file_path: &Path,
+    cc_db: &Path,
+    extra_args: &[&str],
+    debug: bool,
+) -> Result<Vec<u8>, Error> {
     let cbors = get_ast_cbors(file_path, cc_db, extra_args, debug);
-    let buffer = cbors
-        .values()
+    cbors
+        .into_values()
         .next()
-        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "Could not parse input file"))?;
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "Could not parse input file"))
+}
+
+/// Whether `get_ast_cbor_in_subprocess` can be used, i.e., whether the current
+/// executable called `run_as_exporter_subprocess` on startup.
+pub fn subprocess_export_supported() -> bool {
+    SUBPROCESS_SUPPORTED.load(Ordering::Relaxed)
+}
 
-    // let cbor_path = file_path.with_extension("cbor");
-    // let mut cbor_file = File::create(&cbor_path)?;
-    // cbor_file.write_all(&buffer[..])?;
-    // eprintln!("Dumped CBOR to {}", cbor_path.to_string_lossy());
+/// Like `get_ast_cbor`, but run the exporter in a child process running the
+/// current executable, so that several exports can run in parallel. Also
+/// returns what the exporter wrote to stderr, e.g., clang's diagnostics.
+///
+/// Requires `subprocess_export_supported()`.
+pub fn get_ast_cbor_in_subprocess(
+    file_path: &Path,
+    cc_db: &Path,
+    extra_args: &[&str],
+    debug: bool,
+) -> Result<(Vec<u8>, Vec<u8>), Error> {
+    assert!(
+        subprocess_export_supported(),
+        "the current executable doesn't call run_as_exporter_subprocess"
+    );
+    let output = Command::new(env::current_exe()?)
+        .env(SUBPROCESS_ENV, "1")
+        .arg(file_path)
+        .arg(cc_db)
+        .arg(if debug { "1" } else { "0" })
+        .args(extra_args)
+        .stdin(Stdio::null())
+        .output()?;
+    if !output.status.success() {
+        return Err(Error::new(
+            ErrorKind::Other,
+            format!(
+                "AST exporter failed ({}): {}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        ));
+    }
+    Ok((output.stdout, output.stderr))
+}
+
+/// Enable `get_ast_cbor_in_subprocess` for the current executable. Must be
+/// called at the start of `main`, before any arguments are parsed: if this
+/// process was started by `get_ast_cbor_in_subprocess`, this exports the
+/// requested AST to stdout and exits instead of returning.
+pub fn run_as_exporter_subprocess() {
+    if env::var_os(SUBPROCESS_ENV).is_none() {
+        SUBPROCESS_SUPPORTED.store(true, Ordering::Relaxed);
+        return;
+    }
+
+    let args: Vec<OsString> = env::args_os().skip(1).collect();
+    let (file_path, cc_db, debug, extra_args) = match &args[..] {
+        [file_path, cc_db, debug, extra_args @ ..] => (
+            Path::new(file_path),
+            Path::new(cc_db),
+            debug == "1",
+            extra_args
+                .iter()
+                .map(|arg| arg.to_str().expect("non-UTF-8 clang argument"))
+                .collect::<Vec<_>>(),
+        ),
+        _ => {
+            eprintln!(
+                "{} requires a file, compile database and debug flag",
+                SUBPROCESS_ENV
+            );
+            process::exit(2);
+        }
+    };
+
+    // `process::exit` doesn't flush stdout, so do that before exiting
+    let res = get_ast_cbor(file_path, cc_db, &extra_args, debug).and_then(|cbor| {
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(&cbor)?;
+        stdout.flush()
+    });
+    match res {
+        Ok(()) => process::exit(0),
+        Err(e) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+    }
+}
 
-    let items: Value = from_slice(&buffer[..]).unwrap();
+/// Decode CBOR previously produced by the AST exporter (e.g., by
+/// `get_ast_cbor`) into an untyped AST.
+pub fn untyped_ast_from_cbor(buffer: &[u8]) -> Result<clang_ast::AstContext, Error> {
+    let items: Value =
+        from_slice(buffer).map_err(|e| Error::new(ErrorKind::InvalidData, format!("{:}", e)))?;
 
     match clang_ast::process(items) {
         Ok(cxt) => Ok(cxt),
@@ -63,6 +180,9 @@ fn get_ast_cbors(
     let args_ptrs: Vec<*const libc::c_char> = args_owned.iter().map(|x| x.as_ptr()).collect();
 
     let hashmap;
+    // A panic while holding the lock can't leave the exporter in a worse state
+    // than it would be in anyway, so ignore poisoning.
+    let _guard = EXPORTER_LOCK.lock().unwrap_or_else(|e| e.into_inner());
     unsafe {
         let ptr = ast_exporter(
             args_ptrs.len() as libc::c_int,
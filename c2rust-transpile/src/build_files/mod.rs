@@ -61,9 +61,35 @@ pub struct CrateConfig<'lcmd> {
     pub modules: Vec<PathBuf>,
     pub pragmas: PragmaSet,
     pub crates: CrateSet,
+    pub link_deps: Vec<LinkDependency>,
     pub link_cmd: &'lcmd LinkCmd,
 }
 
+/// Another crate of the workspace whose library this crate links against.
+pub struct LinkDependency {
+    pub crate_name: String,
+    pub build_dir: PathBuf,
+}
+
+/// Templates of the build files, by the name of the file they render
+fn templates() -> Handlebars<'static> {
+    let mut reg = Handlebars::new();
+
+    reg.register_template_string("Cargo.toml", include_str!("Cargo.toml.hbs"))
+        .unwrap();
+    reg.register_template_string("lib.rs", include_str!("lib.rs.hbs"))
+        .unwrap();
+    reg.register_template_string("build.rs", include_str!("build.rs.hbs"))
+        .unwrap();
+    reg
+}
+
+#[derive(Serialize)]
+struct PathDependency {
+    name: String,
+    path: String,
+}
+
 /// Emit `Cargo.toml` and `lib.rs` for a library or `main.rs` for a binary.
 /// Returns the path to `lib.rs` or `main.rs` (or `None` if the output file
 /// existed already).
@@ -73,14 +99,7 @@ pub fn emit_build_files<'lcmd>(
     crate_cfg: Option<CrateConfig<'lcmd>>,
     workspace_members: Option<Vec<String>>,
 ) -> Option<PathBuf> {
-    let mut reg = Handlebars::new();
-
-    reg.register_template_string("Cargo.toml", include_str!("Cargo.toml.hbs"))
-        .unwrap();
-    reg.register_template_string("lib.rs", include_str!("lib.rs.hbs"))
-        .unwrap();
-    reg.register_template_string("build.rs", include_str!("build.rs.hbs"))
-        .unwrap();
+    let reg = templates();
 
     if !build_dir.exists() {
         fs::create_dir_all(&build_dir)
@@ -88,7 +107,7 @@ pub fn emit_build_files<'lcmd>(
     }
 
     emit_cargo_toml(tcfg, &reg, build_dir, &crate_cfg, workspace_members);
-    if tcfg.translate_valist {
+    if tcfg.translate_valist && !tcfg.stable {
         emit_rust_toolchain(tcfg, build_dir);
     }
     crate_cfg.and_then(|ccfg| {
@@ -220,12 +239,18 @@ fn emit_build_rs(
     build_dir: &Path,
     link_cmd: &LinkCmd,
 ) -> Option<PathBuf> {
+    let output = render_build_rs(reg, link_cmd);
+    let output_path = build_dir.join("build.rs");
+    maybe_write_to_file(&output_path, output, tcfg.overwrite_existing)
+}
+
+/// Render `build.rs`, which links the native libraries of `link_cmd`
+fn render_build_rs(reg: &Handlebars, link_cmd: &LinkCmd) -> String {
     let json = json!({
         "libraries": link_cmd.libs,
+        "library_dirs": link_cmd.lib_dirs,
     });
-    let output = reg.render("build.rs", &json).unwrap();
-    let output_path = build_dir.join("build.rs");
-    maybe_write_to_file(&output_path, output, tcfg.overwrite_existing)
+    reg.render("build.rs", &json).unwrap()
 }
 
 /// Emit lib.rs (main.rs) for a library (binary). Returns `Some(path)`
@@ -286,6 +311,18 @@ fn emit_cargo_toml<'lcmd>(
             ModuleSubset::Binaries,
         );
         let dependencies = convert_dependencies_list(ccfg.crates.clone());
+        let path_dependencies = ccfg
+            .link_deps
+            .iter()
+            .map(|dep| PathDependency {
+                name: dep.crate_name.clone(),
+                path: diff_paths(&dep.build_dir, build_dir)
+                    .unwrap()
+                    .to_str()
+                    .unwrap()
+                    .to_string(),
+            })
+            .collect::<Vec<_>>();
         let crate_json = json!({
             "crate_name": ccfg.crate_name,
             "crate_rust_name": ccfg.crate_name.replace('-', "_"),
@@ -294,6 +331,7 @@ fn emit_cargo_toml<'lcmd>(
             "lib_rs_file": get_lib_rs_file_name(tcfg),
             "binaries": binaries,
             "dependencies": dependencies,
+            "path_dependencies": path_dependencies,
         });
         json.as_object_mut().unwrap().extend(
             crate_json
@@ -327,3 +365,85 @@ fn maybe_write_to_file(output_path: &Path, output: String, overwrite: bool) -> O
 
     Some(PathBuf::from(output_path))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compile_cmds::get_compile_commands;
+    use std::env;
+
+    /// A link command in the form `scripts/convert_build_commands.py` emits
+    fn link_cmd_file(inputs: &[&str], lib_dirs: &[&str], libs: &[&str], ty: &str) -> String {
+        let string = |s: &str| format!("{}:{}", s.len(), s);
+        let list = |l: &[&str]| format!("l{}e", l.iter().map(|&s| string(s)).collect::<String>());
+        format!(
+            "/c2rust/link/d{}{}{}{}{}{}{}{}e",
+            string("inputs"),
+            list(inputs),
+            string("lib_dirs"),
+            list(lib_dirs),
+            string("libs"),
+            list(libs),
+            string("type"),
+            string(ty),
+        )
+    }
+
+    #[test]
+    fn build_rs_links_native_libraries() {
+        let dir = env::temp_dir().join(format!("c2rust-build-rs-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let compile_cmd = |file: &str, output: &str| {
+            json!({
+                "directory": dir,
+                "file": dir.join(file),
+                "arguments": ["cc", "-c", file, "-o", output],
+                "output": output,
+            })
+        };
+        let link_cmd = |file: String, output: &str| {
+            json!({
+                "directory": dir,
+                "file": file,
+                "arguments": ["cc"],
+                "output": output,
+            })
+        };
+        let cmds = json!([
+            compile_cmd("foo.c", "foo.o"),
+            compile_cmd("main.c", "main.o"),
+            link_cmd(
+                link_cmd_file(&["foo.o"], &["lib"], &["m"], "shared"),
+                "libfoo.so",
+            ),
+            link_cmd(
+                link_cmd_file(&["main.o"], &["/opt/z/lib"], &["foo", "z"], "exe"),
+                "main",
+            ),
+        ]);
+        let cc_db = dir.join("compile_commands.json");
+        fs::write(&cc_db, cmds.to_string()).unwrap();
+        let lcmds = get_compile_commands(&cc_db, &None).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let reg = templates();
+        let build_rs = lcmds
+            .iter()
+            .map(|lcmd| render_build_rs(&reg, lcmd))
+            .collect::<Vec<_>>();
+        let search = |dir: &Path| format!("cargo:rustc-link-search=native={}", dir.display());
+
+        // Library directories are relative to the directory of the link
+        assert_eq!(lcmds[0].crate_name().as_deref(), Some("libfoo"));
+        assert!(build_rs[0].contains(&search(&dir.join("lib"))));
+        assert!(build_rs[0].contains("cargo:rustc-link-lib=m\""));
+
+        // `-lfoo` is provided by the other crate, so it becomes a dependency
+        // rather than a native library
+        assert_eq!(lcmds[1].crate_name().as_deref(), Some("main"));
+        assert_eq!(lcmds[1].deps, ["libfoo"]);
+        assert!(build_rs[1].contains(&search(Path::new("/opt/z/lib"))));
+        assert!(build_rs[1].contains("cargo:rustc-link-lib=z\""));
+        assert!(!build_rs[1].contains("cargo:rustc-link-lib=foo"));
+    }
+}
@@ -156,6 +156,7 @@ fn parse_cast_kind(kind: &str) -> CastKind {
         "FloatingComplexToIntegralComplex" => CastKind::FloatingComplexToIntegralComplex,
         "IntegralRealToComplex" => CastKind::IntegralRealToComplex,
         "IntegralComplexToReal" => CastKind::IntegralComplexToReal,
+        "FloatingComplexToBoolean" => CastKind::FloatingComplexToBoolean,
         "IntegralComplexToBoolean" => CastKind::IntegralComplexToBoolean,
         "IntegralComplexCast" => CastKind::IntegralComplexCast,
         "IntegralComplexToFloatingComplex" => CastKind::IntegralComplexToFloatingComplex,
@@ -477,6 +478,8 @@ impl ConversionContext {
 
         self.typed_context.va_list_kind = untyped_context.va_list_kind;
         self.typed_context.target = untyped_context.target.clone();
+        self.typed_context.target_features =
+            untyped_context.target_features.iter().cloned().collect();
     }
 
     /// Visit one node.
@@ -1226,6 +1229,17 @@ impl ConversionContext {
                     self.expr_possibly_as_stmt(expected_ty, new_id, node, floating_literal);
                 }
 
+                ASTEntryTag::TagImaginaryLiteral if expected_ty & (EXPR | STMT) != 0 => {
+                    let value = node.children[0].expect("Expected imaginary literal value");
+                    let value = self.visit_expr(value);
+                    let ty_old = node.type_id.expect("Expected expression to have type");
+                    let ty = self.visit_qualified_type(ty_old);
+
+                    let imaginary_literal = CExprKind::ImaginaryLiteral(ty, value);
+
+                    self.expr_possibly_as_stmt(expected_ty, new_id, node, imaginary_literal);
+                }
+
                 ASTEntryTag::TagUnaryOperator if expected_ty & (EXPR | STMT) != 0 => {
                     let prefix =
                         from_value(node.extras[1].clone()).expect("Expected prefix information");
@@ -2067,7 +2081,49 @@ impl ConversionContext {
 
                     let mac_object = match node.tag {
                         ASTEntryTag::TagMacroObjectDef => CDeclKind::MacroObject { name },
-                        ASTEntryTag::TagMacroFunctionDef => CDeclKind::MacroFunction { name },
+                        ASTEntryTag::TagMacroFunctionDef => {
+                            // Parameters and arguments are missing from ASTs
+                            // saved by older versions of the exporter
+                            let parameters = node
+                                .extras
+                                .get(1)
+                                .map(|params| {
+                                    from_value::<Vec<String>>(params.clone())
+                                        .expect("Expected macro parameter names")
+                                })
+                                .unwrap_or_default();
+                            let is_variadic = node
+                                .extras
+                                .get(2)
+                                .map_or(false, |v| from_value(v.clone()).expect("variadic flag"));
+                            let args = node
+                                .extras
+                                .get(3)
+                                .map(|args| {
+                                    from_value::<Vec<(ClangId, u64, String)>>(args.clone())
+                                        .expect("Expected macro arguments")
+                                })
+                                .unwrap_or_default();
+                            for (arg_id, param, text) in args {
+                                if !untyped_context.ast_nodes.contains_key(&arg_id) {
+                                    continue;
+                                }
+                                let arg_id = self.visit_expr(arg_id);
+                                self.typed_context.macro_arguments.insert(
+                                    arg_id,
+                                    MacroArgument {
+                                        macro_id: CDeclId(new_id),
+                                        param: param as usize,
+                                        text,
+                                    },
+                                );
+                            }
+                            CDeclKind::MacroFunction {
+                                name,
+                                parameters,
+                                is_variadic,
+                            }
+                        }
                         _ => unreachable!("Unexpected tag for macro"),
                     };
 
@@ -2080,20 +2136,6 @@ impl ConversionContext {
                     self.typed_context.c_decls_top.push(CDeclId(new_id));
                 }
 
-                ASTEntryTag::TagMacroFunctionDef if expected_ty & MACRO_DECL != 0 => {
-                    let name = from_value::<String>(node.extras[0].clone())
-                        .expect("Macros must have a name");
-
-                    let mac_object = CDeclKind::MacroFunction { name };
-                    self.add_decl(new_id, located(node, mac_object));
-                    self.processed_nodes.insert(new_id, MACRO_DECL);
-
-                    // Macros aren't technically top-level decls, so clang
-                    // doesn't put them in top_nodes, but we do need to process
-                    // them early.
-                    self.typed_context.c_decls_top.push(CDeclId(new_id));
-                }
-
                 ASTEntryTag::TagNonCanonicalDecl if expected_ty & DECL != 0 => {
                     let canonical_decl =
                         node.children[0].expect("NonCanonicalDecl must point to a canonical decl");
@@ -44,7 +44,9 @@ fn immediate_expr_children(kind: &CExprKind) -> Vec<SomeId> {
         ShuffleVector(..) | ConvertVector(..) => vec![],
         OffsetOf(..) | Literal(..) | ImplicitValueInit(..) => vec![],
         DeclRef(..) => vec![], // don't follow references back!
-        Unary(_, _, subexpr, _) | ConstantExpr(_, subexpr, _) => intos![subexpr],
+        Unary(_, _, subexpr, _) | ConstantExpr(_, subexpr, _) | ImaginaryLiteral(_, subexpr) => {
+            intos![subexpr]
+        }
         UnaryType(_ty, _op, opt_expr_id, _) => opt_expr_id.iter().map(|&x| x.into()).collect(),
         Binary(_ty, _op, lhs, rhs, _, _) => intos![lhs, rhs],
         Call(_, f, ref args) => {
@@ -95,7 +97,9 @@ fn immediate_expr_children_all_types(kind: &CExprKind) -> Vec<SomeId> {
         OffsetOf(_, OffsetOfKind::Variable(qty, _, _)) => intos![qty.ctype],
         OffsetOf(..) | Literal(..) | ImplicitValueInit(..) => vec![],
         DeclRef(..) => vec![], // don't follow references back!
-        Unary(_, _, subexpr, _) | ConstantExpr(_, subexpr, _) => intos![subexpr],
+        Unary(_, _, subexpr, _) | ConstantExpr(_, subexpr, _) | ImaginaryLiteral(_, subexpr) => {
+            intos![subexpr]
+        }
         UnaryType(_ty, _op, opt_expr_id, qty) => {
             let mut res = intos![qty.ctype];
             if let Some(expr_id) = opt_expr_id {
@@ -323,7 +327,7 @@ fn immediate_type_children(kind: &CTypeKind) -> Vec<SomeId> {
     }
 }
 
-fn immediate_children(context: &TypedAstContext, s_or_e: SomeId) -> Vec<SomeId> {
+pub fn immediate_children(context: &TypedAstContext, s_or_e: SomeId) -> Vec<SomeId> {
     match s_or_e {
         SomeId::Stmt(stmt_id) => immediate_stmt_children(&context[stmt_id].kind),
         SomeId::Expr(expr_id) => immediate_expr_children(&context[expr_id].kind),
@@ -80,6 +80,10 @@ pub struct TypedAstContext {
     // if any
     pub macro_expansion_text: HashMap<CExprId, String>,
 
+    // map expressions to the function-like macro argument they were spelled as,
+    // if any
+    pub macro_arguments: HashMap<CExprId, MacroArgument>,
+
     pub comments: Vec<Located<String>>,
 
     // The key is the typedef decl being squashed away,
@@ -88,6 +92,19 @@ pub struct TypedAstContext {
 
     pub va_list_kind: BuiltinVaListKind,
     pub target: String,
+    /// The features clang enabled for the target, e.g. `avx`
+    pub target_features: HashSet<String>,
+}
+
+/// An expression that was spelled as a complete argument of a function-like
+/// macro invocation
+#[derive(Debug, Clone)]
+pub struct MacroArgument {
+    pub macro_id: CDeclId,
+    /// Index of the macro parameter the argument was passed for
+    pub param: usize,
+    /// Source text of the argument at the invocation
+    pub text: String,
 }
 
 /// Comments associated with a typed AST context
@@ -102,6 +119,16 @@ pub struct DisplaySrcSpan {
     loc: SrcSpan,
 }
 
+impl DisplaySrcSpan {
+    pub fn file(&self) -> Option<&Path> {
+        self.file.as_deref()
+    }
+
+    pub fn span(&self) -> SrcSpan {
+        self.loc
+    }
+}
+
 impl Display for DisplaySrcSpan {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         if let Some(ref file) = self.file {
@@ -181,12 +208,14 @@ impl TypedAstContext {
             macro_invocations: HashMap::new(),
             macro_expansions: HashMap::new(),
             macro_expansion_text: HashMap::new(),
+            macro_arguments: HashMap::new(),
             label_names: Default::default(),
 
             comments: Vec::new(),
             prenamed_decls: IndexMap::new(),
             va_list_kind: BuiltinVaListKind::CharPtrBuiltinVaList,
             target: String::new(),
+            target_features: HashSet::new(),
         }
     }
 
@@ -498,6 +527,7 @@ impl TypedAstContext {
             Atomic{..} => false,
 
             Literal(_, _) |
+            ImaginaryLiteral(_, _) |
             DeclRef(_, _, _) |
             UnaryType(_, _, _, _) |
             OffsetOf(..) |
@@ -970,7 +1000,9 @@ pub enum CDeclKind {
 
     MacroFunction {
         name: String,
-        // replacements: Vec<CExprId>,
+        // Empty if the AST was exported without parameter information
+        parameters: Vec<String>,
+        is_variadic: bool,
     },
 
     NonCanonicalDecl {
@@ -1024,6 +1056,9 @@ pub enum CExprKind {
     // Literals
     Literal(CQualTypeId, CLiteral),
 
+    // [GNU C] Imaginary literal such as `2.0i`, wrapping the literal value
+    ImaginaryLiteral(CQualTypeId, CExprId),
+
     // Unary operator.
     Unary(CQualTypeId, UnOp, CExprId, LRValue),
 
@@ -1093,7 +1128,7 @@ pub enum CExprKind {
     // Variable argument list
     VAArg(CQualTypeId, CExprId),
 
-    // Unsupported vector operations,
+    // Vector operations
     ShuffleVector(CQualTypeId, Vec<CExprId>),
     ConvertVector(CQualTypeId, Vec<CExprId>),
 
@@ -1141,6 +1176,7 @@ impl CExprKind {
         match *self {
             CExprKind::BadExpr => None,
             CExprKind::Literal(ty, _)
+            | CExprKind::ImaginaryLiteral(ty, _)
             | CExprKind::OffsetOf(ty, _)
             | CExprKind::Unary(ty, _, _, _)
             | CExprKind::UnaryType(ty, _, _, _)
@@ -1206,6 +1242,7 @@ pub enum CastKind {
     FloatingComplexToReal,
     FloatingComplexCast,
     FloatingComplexToIntegralComplex,
+    FloatingComplexToBoolean,
     IntegralRealToComplex,
     IntegralComplexToReal,
     IntegralComplexToBoolean,
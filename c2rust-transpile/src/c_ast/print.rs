@@ -130,6 +130,10 @@ impl<W: Write> Printer<W> {
             Literal(_, lit) => {
                 self.print_lit(lit, context)?;
             }
+            &ImaginaryLiteral(_, val) => {
+                self.print_expr(val, context)?;
+                self.writer.write_all(b"i")?;
+            }
             &Unary(_, op, rhs, _) => {
                 if op.is_prefix() {
                     self.print_unop(&op, context)?;
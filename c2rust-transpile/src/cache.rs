@@ -0,0 +1,316 @@
+//! On-disk cache of translated translation units, used by `--incremental`.
+//!
+//! Each cache entry records a key hashing everything that affects the
+//! translation of a TU: the compile command, extra clang arguments, the clang
+//! and c2rust versions, the output-affecting `TranspilerConfig` options, and
+//! the TU itself. The TU is hashed after preprocessing it with the compile
+//! command, so that changes to any header it includes, even one that only
+//! defines macros, invalidate the entry. When translating from a saved AST, the
+//! saved AST is hashed instead. If the key still matches, we can reuse the
+//! cached translation without exporting the AST or running the translator.
+//!
+//! With `--rust-enums`, the translation also depends on the enums that the
+//! other TUs of the crate reject. Each entry records the enums its TU rejects,
+//! so that they can be collected for the crate without exporting the AST, and
+//! the enums the whole crate rejected, and is only reused if the crate still
+//! rejects the same ones.
+
+use failure::{err_msg, format_err, Error};
+use log::warn;
+use serde_derive::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use c2rust_ast_exporter::get_clang_major_version;
+
+use crate::compile_cmds::CompileCmd;
+use crate::report::DeclReport;
+use crate::{CrateSet, ExternCrate, PragmaVec, TranspilerConfig};
+
+#[derive(Serialize, Deserialize)]
+struct CacheEntry {
+    key: String,
+    output: String,
+    pragmas: Vec<(String, Vec<String>)>,
+    crates: Vec<ExternCrate>,
+    declarations: Vec<DeclReport>,
+    rejected_enums: Vec<String>,
+    crate_rejected_enums: Vec<String>,
+}
+
+/// A translation restored from the cache.
+pub struct CachedTranslation {
+    pub output: String,
+    pub pragmas: PragmaVec,
+    pub crates: CrateSet,
+    pub declarations: Vec<DeclReport>,
+    /// The enums that the TU rejects with `--rust-enums`
+    pub rejected_enums: Vec<String>,
+    /// The enums that the crate rejected, which the translation depends on
+    pub crate_rejected_enums: Vec<String>,
+}
+
+pub struct TranslationCache {
+    dir: PathBuf,
+    hits: AtomicUsize,
+    misses: AtomicUsize,
+}
+
+impl TranslationCache {
+    pub fn new(dir: PathBuf) -> Result<Self, Error> {
+        fs::create_dir_all(&dir)?;
+        Ok(Self {
+            dir,
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+        })
+    }
+
+    pub fn hits(&self) -> usize {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    pub fn misses(&self) -> usize {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    fn entry_path(&self, input_path: &Path, output_path: &Path) -> PathBuf {
+        let mut hasher = KeyHasher::default();
+        hasher.add(input_path.as_os_str());
+        hasher.add(output_path.as_os_str());
+        self.dir.join(format!("{}.json", hasher.finish()))
+    }
+
+    /// Count a hit, i.e. a reused translation, or a miss.
+    pub fn count(&self, hit: bool) {
+        let counter = if hit { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Look up the translation of `input_path` into `output_path`. It can only
+    /// be reused if it was translated with the enums the crate now rejects.
+    pub fn lookup(
+        &self,
+        key: &str,
+        input_path: &Path,
+        output_path: &Path,
+    ) -> Option<CachedTranslation> {
+        let entry = fs::read(self.entry_path(input_path, output_path)).ok()?;
+        let entry: CacheEntry = serde_json::from_slice(&entry).ok()?;
+        if entry.key != key {
+            return None;
+        }
+
+        Some(CachedTranslation {
+            output: entry.output,
+            pragmas: entry
+                .pragmas
+                .into_iter()
+                .map(|(key, vals)| (intern(key), vals.into_iter().map(intern).collect()))
+                .collect(),
+            crates: entry.crates.into_iter().collect(),
+            declarations: entry.declarations,
+            rejected_enums: entry.rejected_enums,
+            crate_rejected_enums: entry.crate_rejected_enums,
+        })
+    }
+
+    /// Store a fresh translation. Failures are not fatal; the TU will simply
+    /// be translated again next time.
+    pub fn store(
+        &self,
+        key: &str,
+        input_path: &Path,
+        output_path: &Path,
+        translation: CachedTranslation,
+    ) {
+        let entry = CacheEntry {
+            key: key.to_owned(),
+            output: translation.output,
+            pragmas: translation
+                .pragmas
+                .into_iter()
+                .map(|(key, vals)| (key.to_owned(), vals.into_iter().map(String::from).collect()))
+                .collect(),
+            crates: translation.crates.into_iter().collect(),
+            declarations: translation.declarations,
+            rejected_enums: translation.rejected_enums,
+            crate_rejected_enums: translation.crate_rejected_enums,
+        };
+        let entry_path = self.entry_path(input_path, output_path);
+        let res = serde_json::to_vec(&entry)
+            .map_err(Error::from)
+            .and_then(|entry| fs::write(&entry_path, entry).map_err(Error::from));
+        if let Err(e) = res {
+            warn!(
+                "Could not write cache entry {}: {}",
+                entry_path.display(),
+                e
+            );
+        }
+    }
+}
+
+/// Compute the cache key of a TU whose input, i.e., its preprocessed source or
+/// its saved AST, hashes to `input_hash`.
+pub fn cache_key(
+    tcfg: &TranspilerConfig,
+    cmd: &CompileCmd,
+    extra_clang_args: &[&str],
+    input_hash: &str,
+    module_path: &[String],
+) -> String {
+    let mut hasher = KeyHasher::default();
+    hasher.add(env!("CARGO_PKG_VERSION"));
+    hasher.add(format!("{:?}", get_clang_major_version()));
+    hasher.add(cmd.directory().as_os_str());
+    for arg in cmd.command_line() {
+        hasher.add(arg);
+    }
+    for arg in extra_clang_args {
+        hasher.add(arg);
+    }
+    hasher.add(tcfg.output_options());
+    hasher.add(input_hash);
+    // `macro_rules!` macros refer to items by their path in the crate
+    hasher.add(module_path.join("::"));
+    hasher.finish()
+}
+
+/// Hash of the TU of `cmd` after preprocessing it with clang, using the
+/// arguments of `cmd` and `extra_clang_args`.
+pub fn preprocessed_hash(cmd: &CompileCmd, extra_clang_args: &[&str]) -> Result<String, Error> {
+    let args = preprocess_args(cmd)?;
+    let output = Command::new("clang")
+        .args(&args)
+        .args(extra_clang_args)
+        .arg("-E")
+        .current_dir(cmd.directory())
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .map_err(|e| format_err!("could not run clang: {}", e))?;
+    if !output.status.success() {
+        return Err(format_err!(
+            "could not preprocess {} ({})",
+            cmd.file.display(),
+            output.status
+        ));
+    }
+    Ok(hash_bytes(&output.stdout))
+}
+
+/// The arguments of `cmd` without the compiler and without the options that
+/// select the output or write dependency files, which would conflict with
+/// `-E`.
+fn preprocess_args(cmd: &CompileCmd) -> Result<Vec<String>, Error> {
+    let args = match cmd.command_line()[..] {
+        [command] => {
+            shlex::split(command).ok_or_else(|| err_msg("could not split compile command"))?
+        }
+        ref args => args.iter().map(|&arg| arg.to_owned()).collect(),
+    };
+
+    let mut preprocess_args = vec![];
+    let mut args = args.into_iter().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-c" | "-M" | "-MM" | "-MD" | "-MMD" | "-MP" | "-MG" => {}
+            // These take their value as the next argument
+            "-o" | "--output" | "-MF" | "-MT" | "-MQ" => {
+                args.next();
+            }
+            _ if is_joined_output(&arg) => {}
+            _ => preprocess_args.push(arg),
+        }
+    }
+    Ok(preprocess_args)
+}
+
+/// Whether `arg` is an output option with its value attached, like
+/// `-ofoo.o` or `--output=foo.o`. Other options that start with `-o`, like
+/// `-objcmt-migrate-literals`, are kept.
+fn is_joined_output(arg: &str) -> bool {
+    if arg.starts_with("--output=") {
+        return true;
+    }
+    match arg.strip_prefix("-o") {
+        Some(value) => !value.is_empty() && !value.starts_with("bj"),
+        None => false,
+    }
+}
+
+/// Hash of the contents of `path`, e.g., of a saved AST.
+pub fn hash_file(path: &Path) -> Result<String, Error> {
+    Ok(hash_bytes(&fs::read(path)?))
+}
+
+fn hash_bytes(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+/// SHA-256 of a sequence of strings. Unlike `std`'s hashers, its output is
+/// stable across Rust versions and platforms, so the cache survives upgrading
+/// the toolchain c2rust is built with.
+#[derive(Default)]
+struct KeyHasher(Sha256);
+
+impl KeyHasher {
+    fn add(&mut self, s: impl AsRef<OsStr>) {
+        let s = s.as_ref().to_string_lossy();
+        // Prefix each string with its length so that the sequence is unambiguous
+        self.0.update((s.len() as u64).to_le_bytes());
+        self.0.update(s.as_bytes());
+    }
+
+    fn finish(self) -> String {
+        format!("{:x}", self.0.finalize())
+    }
+}
+
+/// The translator uses `&'static str`s for pragma names, so we leak the
+/// strings we read back from the cache. The set of pragmas is small and each
+/// distinct string is only leaked once.
+fn intern(s: String) -> &'static str {
+    static INTERNED: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+    let mut interned = INTERNED.lock().unwrap();
+    if let Some(&i) = interned.iter().find(|i| **i == s) {
+        return i;
+    }
+    let i = Box::leak(s.into_boxed_str());
+    interned.push(i);
+    i
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn args(command: &str) -> Vec<String> {
+        let cmd: CompileCmd = serde_json::from_value(json!({
+            "directory": "/src",
+            "file": "foo.c",
+            "command": command,
+        }))
+        .unwrap();
+        preprocess_args(&cmd).unwrap()
+    }
+
+    #[test]
+    fn preprocess_args_drop_only_outputs() {
+        assert_eq!(
+            args("cc -c -o foo.o -ofoo2.o --output=foo3.o -O2 -objcmt-migrate-literals foo.c"),
+            ["-O2", "-objcmt-migrate-literals", "foo.c"]
+        );
+        assert_eq!(
+            args("cc -MD -MF foo.d --output foo.o -I inc foo.c"),
+            ["-I", "inc", "foo.c"]
+        );
+    }
+}
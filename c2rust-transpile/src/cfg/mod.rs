@@ -8,6 +8,7 @@
 //! In a nutshell, here are the steps:
 //!
 //!   - given an entry point C statement, translate it into a CFG consisting of `BasicBlock<Label>`
+//!     (`switch`es without fallthrough are translated into a `match` right away, see `switches`)
 //!   - simplify this CFG (by eliminating empty blocks that jump unconditionally to the next block)
 //!   - use the _Relooper algorithm_ to convert this CFG into a sequence of `Structure<StmtOrDecl>`s
 //!   - place the declarations in the right place and produce a sequence of `Structure<Stmt>`s
@@ -52,6 +53,7 @@ pub mod loops;
 pub mod multiples;
 pub mod relooper;
 pub mod structures;
+mod switches;
 
 use crate::cfg::inc_cleanup::IncCleanup;
 use crate::cfg::loops::*;
@@ -1426,6 +1428,21 @@ impl CfgBuilder {
                 Ok(None)
             }
 
+            // `if (setjmp(env)) { .. } else { .. }` doesn't branch in the CFG: its
+            // arms become closures that are run under `catch_unwind`
+            CStmtKind::If {
+                scrutinee,
+                true_variant,
+                false_variant,
+            } if translator.is_setjmp_if(scrutinee, true_variant, false_variant) => {
+                let (stmts, val) = translator
+                    .convert_setjmp_if(ctx, scrutinee, true_variant, false_variant)?
+                    .discard_unsafe();
+                wip.extend(stmts);
+                wip.push_stmt(mk().expr_stmt(val));
+                Ok(Some(wip))
+            }
+
             CStmtKind::If {
                 scrutinee,
                 true_variant,
@@ -1828,26 +1845,7 @@ impl CfgBuilder {
                 self.add_wip_block(wip, Jump(this_label.clone()));
 
                 // Case
-                let resolved = translator.ast_context.resolve_expr(case_expr);
-                let branch = match resolved.1 {
-                    CExprKind::Literal(..) | CExprKind::ConstantExpr(_, _, Some(_)) => {
-                        match translator
-                            .convert_expr(ctx.used(), resolved.0)?
-                            .to_pure_expr()
-                        {
-                            Some(expr) => match *expr {
-                                Expr::Lit(..) | Expr::Path(..) => Some(expr),
-                                _ => None,
-                            },
-                            _ => None,
-                        }
-                    }
-                    _ => None,
-                };
-                let branch = match branch {
-                    Some(expr) => expr,
-                    None => translator.convert_constant(cie)?,
-                };
+                let pat = switches::case_pattern(translator, ctx, case_expr, cie)?;
                 self.switch_expr_cases
                     .last_mut()
                     .ok_or_else(|| {
@@ -1857,7 +1855,7 @@ impl CfgBuilder {
                         )
                     })?
                     .cases
-                    .push((mk().lit_pat(branch), this_label.clone()));
+                    .push((pat, this_label.clone()));
 
                 // Sub stmt
                 let sub_stmt_next =
@@ -1887,58 +1885,79 @@ impl CfgBuilder {
                 scrutinee,
                 body: switch_body,
             } => {
-                let next_label = self.fresh_label();
-                let body_label = self.fresh_label();
-
                 // Convert the condition
                 let (stmts, val) = translator
-                    .convert_expr(ctx.used(), scrutinee)?
+                    .convert_switch_scrutinee(ctx.used(), scrutinee)?
                     .discard_unsafe();
                 wip.extend(stmts);
 
-                let wip_label = wip.label.clone();
-                self.add_wip_block(wip, End); // NOTE: the `End` here is temporary and gets updated
-
-                // Body
-                let saw_unmatched_break = self.last_per_stmt_mut().saw_unmatched_break;
-                let saw_unmatched_case = self.last_per_stmt_mut().saw_unmatched_case;
-                let saw_unmatched_default = self.last_per_stmt_mut().saw_unmatched_default;
-                self.break_labels.push(next_label.clone());
-                self.switch_expr_cases.push(SwitchCases::default());
+                // Switches without fallthrough become a `match` without going through relooper
+                if let Some(arms) =
+                    switches::structured_switch_arms(&translator.ast_context, switch_body)
+                {
+                    let (match_stmt, falls_through) = self.convert_structured_switch(
+                        translator,
+                        ctx,
+                        val,
+                        arms,
+                        in_tail.clone(),
+                    )?;
+                    wip.push_stmt(match_stmt);
 
-                let body_stuff = self.convert_stmt_help(
-                    translator,
-                    ctx,
-                    switch_body,
-                    in_tail.clone(),
-                    body_label,
-                )?;
-                if let Some(body_end) = body_stuff {
-                    let body_wip = self.new_wip_block(body_end);
-                    self.add_wip_block(body_wip, Jump(next_label.clone()));
-                }
+                    if falls_through {
+                        Ok(Some(wip))
+                    } else {
+                        self.add_wip_block(wip, End);
+                        Ok(None)
+                    }
+                } else {
+                    let next_label = self.fresh_label();
+                    let body_label = self.fresh_label();
 
-                self.last_per_stmt_mut().saw_unmatched_break = saw_unmatched_break;
-                self.last_per_stmt_mut().saw_unmatched_case = saw_unmatched_case;
-                self.last_per_stmt_mut().saw_unmatched_default = saw_unmatched_default;
-                self.break_labels.pop();
-                let switch_case = self
-                    .switch_expr_cases
-                    .pop()
-                    .expect("No 'SwitchCases' to pop");
+                    let wip_label = wip.label.clone();
+                    self.add_wip_block(wip, End); // NOTE: the `End` here is temporary and gets updated
 
-                let mut cases: Vec<_> = switch_case.cases.clone();
-                cases.push((
-                    mk().wild_pat(),
-                    switch_case.default.unwrap_or_else(|| next_label.clone()),
-                ));
+                    // Body
+                    let saw_unmatched_break = self.last_per_stmt_mut().saw_unmatched_break;
+                    let saw_unmatched_case = self.last_per_stmt_mut().saw_unmatched_case;
+                    let saw_unmatched_default = self.last_per_stmt_mut().saw_unmatched_default;
+                    self.break_labels.push(next_label.clone());
+                    self.switch_expr_cases.push(SwitchCases::default());
 
-                // Add the condition basic block terminator (we need the information built up during
-                // the conversion of the body to make the right terminator)
-                self.update_terminator(wip_label, Switch { expr: val, cases });
+                    let body_stuff = self.convert_stmt_help(
+                        translator,
+                        ctx,
+                        switch_body,
+                        in_tail.clone(),
+                        body_label,
+                    )?;
+                    if let Some(body_end) = body_stuff {
+                        let body_wip = self.new_wip_block(body_end);
+                        self.add_wip_block(body_wip, Jump(next_label.clone()));
+                    }
 
-                // Return
-                Ok(Some(self.new_wip_block(next_label)))
+                    self.last_per_stmt_mut().saw_unmatched_break = saw_unmatched_break;
+                    self.last_per_stmt_mut().saw_unmatched_case = saw_unmatched_case;
+                    self.last_per_stmt_mut().saw_unmatched_default = saw_unmatched_default;
+                    self.break_labels.pop();
+                    let switch_case = self
+                        .switch_expr_cases
+                        .pop()
+                        .expect("No 'SwitchCases' to pop");
+
+                    let mut cases: Vec<_> = switch_case.cases.clone();
+                    cases.push((
+                        mk().wild_pat(),
+                        switch_case.default.unwrap_or_else(|| next_label.clone()),
+                    ));
+
+                    // Add the condition basic block terminator (we need the information built up during
+                    // the conversion of the body to make the right terminator)
+                    self.update_terminator(wip_label, Switch { expr: val, cases });
+
+                    // Return
+                    Ok(Some(self.new_wip_block(next_label)))
+                }
             }
 
             CStmtKind::Asm {
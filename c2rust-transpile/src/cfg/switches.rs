@@ -0,0 +1,282 @@
+//! This module contains the structured lowering of `switch` statements.
+//!
+//! Relooper can translate any `switch`, including ones whose cases fall through into each other or
+//! that are entered by a `goto`, but it needs a `current_block` state machine to do so more often
+//! than not. Most `switch`es are simpler than that: each group of `case`/`default` labels is
+//! followed by statements ending in a `break` (or a `return`). We translate those directly into a
+//! `match` on the scrutinee, with one arm per group of labels. The labels of a group become a `|`
+//! pattern, the `default` group becomes the `_` arm, and the statements of each group are turned
+//! into a CFG of their own and relooped separately.
+//!
+//! A `switch` is only lowered this way if
+//!
+//!   - its body starts with a label,
+//!   - no group of labels other than the last can fall through into the next one,
+//!   - its body doesn't declare variables outside of a nested block, since those would be visible
+//!     in the following groups,
+//!   - no group contains labels for this `switch` other than the leading ones, a `continue` for a
+//!     loop around the `switch`, or any `goto` or label.
+//!
+//! Every other `switch` goes through relooper.
+
+use super::*;
+
+/// A group of `case`/`default` labels in the body of a `switch`, along with the statements that
+/// follow them up to the next group
+#[derive(Clone, Debug, Default)]
+pub struct SwitchArm {
+    /// Expressions and values of the `case` labels
+    cases: Vec<(CExprId, ConstIntExpr)>,
+    /// Whether the group includes the `default` label
+    is_default: bool,
+    /// Statements following the labels
+    stmts: Vec<CStmtId>,
+}
+
+/// Split the body of a `switch` into its groups of labels, if the `switch` can be translated into
+/// a `match` directly.
+pub fn structured_switch_arms(
+    ast_context: &TypedAstContext,
+    body: CStmtId,
+) -> Option<Vec<SwitchArm>> {
+    let stmt_ids = match ast_context[body].kind {
+        CStmtKind::Compound(ref stmt_ids) => stmt_ids.clone(),
+        _ => vec![body],
+    };
+
+    let mut arms: Vec<SwitchArm> = vec![];
+    for mut stmt_id in stmt_ids {
+        // Consecutive labels are nested: `case 1: case 2: stmt` is `Case(1, Case(2, stmt))`
+        let mut new_arm: Option<SwitchArm> = None;
+        loop {
+            match ast_context[stmt_id].kind {
+                CStmtKind::Case(case_expr, sub_stmt, cie) => {
+                    new_arm
+                        .get_or_insert_with(Default::default)
+                        .cases
+                        .push((case_expr, cie));
+                    stmt_id = sub_stmt;
+                }
+                CStmtKind::Default(sub_stmt) => {
+                    new_arm.get_or_insert_with(Default::default).is_default = true;
+                    stmt_id = sub_stmt;
+                }
+                _ => break,
+            }
+        }
+        arms.extend(new_arm);
+
+        if let CStmtKind::Decls(..) = ast_context[stmt_id].kind {
+            return None;
+        }
+        if !has_local_control_flow(ast_context, stmt_id, false, false) {
+            return None;
+        }
+        arms.last_mut()?.stmts.push(stmt_id);
+    }
+
+    let (_, init) = arms.split_last()?;
+    if init.iter().any(|arm| {
+        arm.stmts
+            .last()
+            .map_or(true, |&s| can_complete(ast_context, s))
+    }) {
+        return None;
+    }
+
+    Some(arms)
+}
+
+/// Returns false if a `continue` or a `case`/`default` label in `stmt_id` belongs to a loop or
+/// `switch` around it, or if `stmt_id` contains a `goto` or a label.
+fn has_local_control_flow(
+    ast_context: &TypedAstContext,
+    stmt_id: CStmtId,
+    in_loop: bool,
+    in_switch: bool,
+) -> bool {
+    let local = |stmt_id: CStmtId| has_local_control_flow(ast_context, stmt_id, in_loop, in_switch);
+    match ast_context[stmt_id].kind {
+        CStmtKind::Continue => in_loop,
+        CStmtKind::Case(_, stmt_id, _) | CStmtKind::Default(stmt_id) => in_switch && local(stmt_id),
+        CStmtKind::Goto(..) | CStmtKind::Label(..) => false,
+        CStmtKind::Attributed {
+            substatement: stmt_id,
+            ..
+        } => local(stmt_id),
+        CStmtKind::Compound(ref stmt_ids) => stmt_ids.iter().all(|&stmt_id| local(stmt_id)),
+        CStmtKind::If {
+            true_variant,
+            false_variant,
+            ..
+        } => local(true_variant) && false_variant.map_or(true, local),
+        CStmtKind::Switch { body, .. } => has_local_control_flow(ast_context, body, in_loop, true),
+        CStmtKind::While { body, .. }
+        | CStmtKind::DoWhile { body, .. }
+        | CStmtKind::ForLoop { body, .. } => {
+            has_local_control_flow(ast_context, body, true, in_switch)
+        }
+        _ => true,
+    }
+}
+
+/// Returns false if control can't reach the end of `stmt_id`. This is conservative: it may return
+/// true for statements that always `break` or `return`.
+fn can_complete(ast_context: &TypedAstContext, stmt_id: CStmtId) -> bool {
+    match ast_context[stmt_id].kind {
+        CStmtKind::Break | CStmtKind::Continue | CStmtKind::Return(..) | CStmtKind::Goto(..) => {
+            false
+        }
+        CStmtKind::Attributed { substatement, .. } => can_complete(ast_context, substatement),
+        CStmtKind::Compound(ref stmt_ids) => stmt_ids
+            .last()
+            .map_or(true, |&stmt_id| can_complete(ast_context, stmt_id)),
+        CStmtKind::If {
+            true_variant,
+            false_variant: Some(false_variant),
+            ..
+        } => can_complete(ast_context, true_variant) || can_complete(ast_context, false_variant),
+        _ => true,
+    }
+}
+
+/// Translate the expression of a `case` label into a pattern.
+pub fn case_pattern(
+    translator: &Translation,
+    ctx: ExprContext,
+    case_expr: CExprId,
+    cie: ConstIntExpr,
+) -> TranslationResult<Pat> {
+    if let Some(variant) = translator.rust_enum_case_pattern(case_expr) {
+        return Ok(mk().lit_pat(variant));
+    }
+
+    let resolved = translator.ast_context.resolve_expr(case_expr);
+    let branch = match resolved.1 {
+        CExprKind::Literal(..) | CExprKind::ConstantExpr(_, _, Some(_)) => {
+            match translator
+                .convert_expr(ctx.used(), resolved.0)?
+                .to_pure_expr()
+            {
+                Some(expr) => match *expr {
+                    Expr::Lit(..) | Expr::Path(..) => Some(expr),
+                    _ => None,
+                },
+                _ => None,
+            }
+        }
+        _ => None,
+    };
+    let branch = match branch {
+        Some(expr) => expr,
+        None => translator.convert_constant(cie)?,
+    };
+    Ok(mk().lit_pat(branch))
+}
+
+impl CfgBuilder {
+    /// Translate a `switch` on `scrutinee` whose body was split into `arms` into a `match`. Also
+    /// returns whether control can reach the end of the `match`.
+    pub(super) fn convert_structured_switch(
+        &mut self,
+        translator: &Translation,
+        ctx: ExprContext,
+        scrutinee: Box<Expr>,
+        arms: Vec<SwitchArm>,
+        in_tail: Option<ImplicitReturnType>,
+    ) -> TranslationResult<(Stmt, bool)> {
+        let mut falls_through = false;
+        let mut match_arms: Vec<Arm> = vec![];
+        let mut default_arm: Option<Arm> = None;
+
+        for arm in arms {
+            let pat = if arm.is_default {
+                mk().wild_pat()
+            } else {
+                let mut pats = arm
+                    .cases
+                    .iter()
+                    .map(|&(case_expr, cie)| case_pattern(translator, ctx, case_expr, cie))
+                    .collect::<TranslationResult<Vec<Pat>>>()?;
+                if pats.len() == 1 {
+                    pats.pop().unwrap()
+                } else {
+                    mk().or_pat(pats)
+                }
+            };
+
+            let (stmts, arm_falls_through) =
+                self.convert_switch_arm(translator, ctx, &arm.stmts, in_tail.clone())?;
+            falls_through |= arm_falls_through;
+
+            let arm_body = mk().arm(pat, None, mk().block_expr(mk().block(stmts)));
+            if arm.is_default {
+                default_arm = Some(arm_body);
+            } else {
+                match_arms.push(arm_body);
+            }
+        }
+
+        // The arms are disjoint, so the `default` group can go last whatever its place in C
+        match default_arm {
+            Some(arm) => match_arms.push(arm),
+            None => {
+                falls_through = true;
+                match_arms.push(mk().arm(
+                    mk().wild_pat(),
+                    None,
+                    mk().block_expr(mk().block(vec![])),
+                ));
+            }
+        }
+
+        let match_stmt = mk().expr_stmt(mk().match_expr(scrutinee, match_arms));
+        Ok((match_stmt, falls_through))
+    }
+
+    /// Translate the statements of one group of labels, with `break`s exiting the group, and
+    /// reloop them on their own. Also returns whether control can reach their end.
+    fn convert_switch_arm(
+        &mut self,
+        translator: &Translation,
+        ctx: ExprContext,
+        stmt_ids: &[CStmtId],
+        in_tail: Option<ImplicitReturnType>,
+    ) -> TranslationResult<(Vec<Stmt>, bool)> {
+        let entry = self.fresh_label();
+        let end = self.fresh_label();
+
+        let live_in: IndexSet<CDeclId> = self.currently_live.last().unwrap().clone();
+        self.per_stmt_stack.push(PerStmt::new(
+            stmt_ids.first().cloned(),
+            entry.clone(),
+            live_in,
+        ));
+
+        self.break_labels.push(end.clone());
+        let body_exit = self.convert_stmts_help(translator, ctx, stmt_ids, in_tail, entry)?;
+        self.break_labels.pop();
+
+        if let Some(body_exit) = body_exit {
+            let body_wip = self.new_wip_block(body_exit);
+            self.add_wip_block(body_wip, Jump(end.clone()));
+        }
+        let end_wip = self.new_wip_block(end.clone());
+        self.add_wip_block(end_wip, End);
+
+        let last_per_stmt = self.per_stmt_stack.pop().unwrap();
+        let stmt_id = last_per_stmt.stmt_id.unwrap_or(CStmtId(0));
+        let (graph, store, live_in) = last_per_stmt.into_cfg();
+        let falls_through = graph.nodes.contains_key(&end);
+
+        let stmts = translator.convert_cfg(
+            &format!("<switch_arm_{:?}>", stmt_id),
+            graph,
+            store,
+            live_in,
+            false,
+        )?;
+
+        Ok((stmts, falls_through))
+    }
+}
@@ -22,17 +22,32 @@ pub struct CompileCmd {
     /// to rerun the exact compilation step for the translation unit in the environment
     /// the build system uses. Parameters use shell quoting and shell escaping of quotes,
     /// with ‘"’ and ‘\’ being the only special characters. Shell expansion is not supported.
-    #[serde(skip_deserializing)]
-    _command: Option<String>,
+    #[serde(default)]
+    command: Option<String>,
     /// The compile command executed as list of strings. Either arguments or command is required.
-    #[serde(default, skip_deserializing)]
-    _arguments: Vec<String>,
+    #[serde(default)]
+    arguments: Vec<String>,
     /// The name of the output created by this compilation step. This field is optional. It can
     /// be used to distinguish different processing modes of the same input file.
     output: Option<String>,
 }
 
 impl CompileCmd {
+    /// The working directory of the compilation.
+    pub fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// The compile command as a list of arguments, as they appear in the
+    /// compilation database. `command` strings are returned as a single,
+    /// unsplit argument.
+    pub fn command_line(&self) -> Vec<&str> {
+        match self.command {
+            Some(ref command) => vec![command.as_str()],
+            None => self.arguments.iter().map(String::as_str).collect(),
+        }
+    }
+
     pub fn abs_file(&self) -> PathBuf {
         match self.file.is_absolute() {
             true => self.file.clone(),
@@ -65,7 +80,9 @@ impl LinkType {
     pub fn as_cargo_types(&self) -> &str {
         match self {
             LinkType::Exe => "\"rlib\"",
-            LinkType::Shared => "\"cdylib\"",
+            // Shared libraries are also `rlib`s so other crates of the
+            // workspace can depend on them
+            LinkType::Shared => "\"cdylib\", \"rlib\"",
             LinkType::Static => "\"staticlib\", \"rlib\"",
         }
     }
@@ -78,9 +95,10 @@ pub struct LinkCmd {
     /// The output file; this is taken from the `CompileCmd`
     #[serde(default)]
     pub output: Option<String>,
-    /// List of libraries to link in (without `-l` prefix)
+    /// List of libraries to link in (without `-l` prefix), not counting
+    /// those built by other link commands, which are listed in `deps`
     pub libs: Vec<String>,
-    /// List of library directories
+    /// List of library directories, relative to the directory of the link
     pub lib_dirs: Vec<PathBuf>,
     /// What type of binary we're building
     pub r#type: LinkType,
@@ -89,6 +107,45 @@ pub struct LinkCmd {
     pub cmd_inputs: Vec<Rc<CompileCmd>>,
     #[serde(default)]
     pub top_level: bool,
+    /// Crate names of the other link commands whose libraries this one links
+    #[serde(default)]
+    pub deps: Vec<String>,
+}
+
+impl LinkCmd {
+    /// Name of the crate built from this command, taken from the output file
+    pub fn crate_name(&self) -> Option<String> {
+        let output = Path::new(self.output.as_ref()?);
+        output.file_stem()?.to_str().map(ToOwned::to_owned)
+    }
+
+    fn output_file_name(&self) -> Option<&str> {
+        Path::new(self.output.as_ref()?).file_name()?.to_str()
+    }
+
+    /// Does linking `input` pull in the library built by this command?
+    fn provides_input(&self, input: &str) -> bool {
+        self.r#type.is_library()
+            && self.output_file_name().is_some()
+            && Path::new(input).file_name().and_then(|f| f.to_str()) == self.output_file_name()
+    }
+
+    /// Does linking with `-l<lib>` pull in the library built by this command?
+    fn provides_lib(&self, lib: &str) -> bool {
+        let file_name = match self.output_file_name() {
+            Some(file_name) if self.r#type.is_library() => file_name,
+            _ => return false,
+        };
+        // `-l:libfoo.a` names the file directly
+        if let Some(file) = lib.strip_prefix(':') {
+            return file == file_name;
+        }
+        // `libfoo.a`, `libfoo.so` and `libfoo.so.1` all provide `-lfoo`
+        file_name
+            .strip_prefix("lib")
+            .and_then(|name| name.split('.').next())
+            == Some(lib)
+    }
 }
 
 /// Convert a linear vector of `CompileCmd`s into a DAG of `LinkCmd`s and `CompileCmd`s
@@ -110,6 +167,11 @@ fn build_link_commands(mut v: Vec<Rc<CompileCmd>>) -> Result<Vec<LinkCmd>, Error
         let mut lcmd: LinkCmd = serde_bencode::from_str(lcmd)?;
 
         lcmd.output = ccmd.output.clone();
+        lcmd.lib_dirs = lcmd
+            .lib_dirs
+            .iter()
+            .map(|dir| ccmd.directory().join(dir))
+            .collect();
         for inp in &lcmd.inputs {
             if let Some(ccmd_idx) = output_map.get(&inp) {
                 let inp_ccmd = Rc::clone(&v[*ccmd_idx]);
@@ -141,6 +203,7 @@ fn build_link_commands(mut v: Vec<Rc<CompileCmd>>) -> Result<Vec<LinkCmd>, Error
             r#type: LinkType::Static,
             cmd_inputs: v,
             top_level: true,
+            deps: vec![],
         };
         res.push(lcmd);
     }
@@ -148,6 +211,60 @@ fn build_link_commands(mut v: Vec<Rc<CompileCmd>>) -> Result<Vec<LinkCmd>, Error
     Ok(res)
 }
 
+/// Find the libraries that each link command takes from the other link
+/// commands, recording them as dependencies instead of external libraries.
+fn resolve_link_dependencies(lcmds: &mut [LinkCmd]) {
+    for idx in 0..lcmds.len() {
+        let (deps, libs) = {
+            let lcmd = &lcmds[idx];
+            let others = || {
+                lcmds
+                    .iter()
+                    .enumerate()
+                    .filter(move |&(other_idx, _)| other_idx != idx)
+                    .map(|(_, other)| other)
+            };
+            let mut deps = vec![];
+            for other in others() {
+                let is_linked = lcmd.inputs.iter().any(|inp| other.provides_input(inp))
+                    || lcmd.libs.iter().any(|lib| other.provides_lib(lib));
+                if let (true, Some(name)) = (is_linked, other.crate_name()) {
+                    deps.push(name);
+                }
+            }
+            let libs = lcmd
+                .libs
+                .iter()
+                .filter(|lib| !others().any(|other| other.provides_lib(lib)))
+                .cloned()
+                .collect();
+            (deps, libs)
+        };
+        lcmds[idx].deps = deps;
+        lcmds[idx].libs = libs;
+    }
+}
+
+/// Order link commands so that every command comes after its dependencies,
+/// keeping the original order otherwise.
+fn sort_link_commands(mut lcmds: Vec<LinkCmd>) -> Vec<LinkCmd> {
+    let mut res = Vec::with_capacity(lcmds.len());
+    let mut done = HashSet::new();
+    while !lcmds.is_empty() {
+        let idx = lcmds
+            .iter()
+            .position(|lcmd| lcmd.deps.iter().all(|dep| done.contains(dep)))
+            .unwrap_or_else(|| {
+                warn!("Link commands have cyclic dependencies; keeping their order");
+                0
+            });
+        let lcmd = lcmds.remove(idx);
+        done.extend(lcmd.crate_name());
+        res.push(lcmd);
+    }
+    res
+}
+
 /// some build scripts repeatedly compile the same input file with different
 /// command line flags thus creating multiple outputs. We remove any duplicates
 /// in the order we see them and warn the user.
@@ -188,6 +305,8 @@ pub fn get_compile_commands(
     };
 
     let mut lcmds = build_link_commands(v)?;
+    resolve_link_dependencies(&mut lcmds);
+    let mut lcmds = sort_link_commands(lcmds);
 
     for lcmd in &mut lcmds {
         let inputs = std::mem::take(&mut lcmd.cmd_inputs);
@@ -397,6 +397,29 @@ impl TypeConverter {
 
             CTypeKind::TypeOf(ty) => self.convert(ctxt, ty),
 
+            CTypeKind::Vector(element, count) => {
+                match simd_vector_type_name(ctxt, element.ctype, count) {
+                    Some(name) => Ok(mk().path_ty(mk().path(vec![name]))),
+                    // An array of lanes would have the wrong alignment and
+                    // calling convention
+                    None => Err(format_err!(
+                        "Unsupported vector type of {} x {:?}",
+                        count,
+                        ctxt.resolve_type(element.ctype).kind
+                    )
+                    .into()),
+                }
+            }
+
+            // `num_complex::Complex` is `#[repr(C)]` with the real part
+            // first, matching the layout of C's `_Complex`
+            CTypeKind::Complex(element) => {
+                let ty = self.convert(ctxt, element)?;
+                let complex =
+                    mk().path_segment_with_args("Complex", mk().angle_bracketed_args(vec![ty]));
+                Ok(mk().path_ty(vec![mk().path_segment("num_complex"), complex]))
+            }
+
             ref t => Err(format_err!("Unsupported type {:?}", t).into()),
         }
     }
@@ -454,3 +477,52 @@ impl TypeConverter {
         }
     }
 }
+
+/// The x86 `core::arch` type used for a vector of `len` lanes of type `elt`,
+/// if there is one and the target has the features it needs.
+pub fn simd_vector_type_name(
+    ctxt: &TypedAstContext,
+    elt: CTypeId,
+    len: usize,
+) -> Option<&'static str> {
+    use CTypeKind::*;
+    let kind = &ctxt.resolve_type(elt).kind;
+    let (name, feature) = match (kind, vector_lane_size(ctxt, kind)? * len) {
+        (Float, 16) => ("__m128", "sse"),
+        (Float, 32) => ("__m256", "avx"),
+        (Double, 16) => ("__m128d", "sse2"),
+        (Double, 32) => ("__m256d", "avx"),
+        (_, 16) if kind.is_integral_type() => ("__m128i", "sse2"),
+        (_, 32) if kind.is_integral_type() => ("__m256i", "avx"),
+        _ => return None,
+    };
+    if ctxt.target_features.contains(feature) {
+        Some(name)
+    } else {
+        None
+    }
+}
+
+/// The size in bytes of a vector lane of type `kind`, if it can be one.
+pub fn vector_lane_size(ctxt: &TypedAstContext, kind: &CTypeKind) -> Option<usize> {
+    use CTypeKind::*;
+    Some(match *kind {
+        Char | SChar | UChar => 1,
+        Short | UShort => 2,
+        Int | UInt | Float => 4,
+        LongLong | ULongLong | Double => 8,
+        // `long` is 32 bits on Windows and 32-bit targets
+        Long | ULong => {
+            let target = &ctxt.target;
+            if target.starts_with("x86_64")
+                && !target.contains("windows")
+                && !target.ends_with("gnux32")
+            {
+                8
+            } else {
+                4
+            }
+        }
+        _ => return None,
+    })
+}
@@ -0,0 +1,79 @@
+//! Selection of the declarations to translate by name.
+//!
+//! Definitions that the filter excludes are not translated. Functions and
+//! globals become `extern "C"` declarations instead, so that the output links
+//! against the original object code, and structs and unions become opaque
+//! extern types. Structs and unions that the translated code uses by value,
+//! through their fields or in `sizeof` need their definition, so they are
+//! translated anyway, with a warning.
+
+use failure::{format_err, Error};
+use serde_derive::Deserialize;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::Path;
+
+/// Names of the functions, globals and types to include in or exclude from
+/// the translation. The file given to `--decl-filter` is the JSON
+/// serialization of this struct, with every field optional.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DeclFilter {
+    /// If set, only these functions are translated
+    pub include_functions: Option<BTreeSet<String>>,
+    /// If set, only these globals are translated
+    pub include_globals: Option<BTreeSet<String>>,
+    pub exclude_functions: BTreeSet<String>,
+    pub exclude_globals: BTreeSet<String>,
+    /// Structs and unions to translate as opaque types
+    pub exclude_types: BTreeSet<String>,
+}
+
+impl DeclFilter {
+    /// Read a filter from a JSON file.
+    pub fn from_file(path: &Path) -> Result<Self, Error> {
+        let contents = fs::read_to_string(path)?;
+        serde_json::from_str(&contents)
+            .map_err(|e| format_err!("invalid declaration filter {}: {}", path.display(), e))
+    }
+
+    /// Only translate the functions and globals in `names`, in addition to
+    /// those already included.
+    pub fn include<'a>(&mut self, names: impl IntoIterator<Item = &'a str>) {
+        let names = names.into_iter().map(String::from).collect::<Vec<_>>();
+        self.include_functions
+            .get_or_insert_with(Default::default)
+            .extend(names.iter().cloned());
+        self.include_globals
+            .get_or_insert_with(Default::default)
+            .extend(names);
+    }
+
+    /// Exclude functions, globals and types named `names`.
+    pub fn exclude<'a>(&mut self, names: impl IntoIterator<Item = &'a str>) {
+        for name in names {
+            self.exclude_functions.insert(name.to_owned());
+            self.exclude_globals.insert(name.to_owned());
+            self.exclude_types.insert(name.to_owned());
+        }
+    }
+
+    pub fn excludes_function(&self, name: &str) -> bool {
+        excludes(&self.include_functions, &self.exclude_functions, name)
+    }
+
+    pub fn excludes_global(&self, name: &str) -> bool {
+        excludes(&self.include_globals, &self.exclude_globals, name)
+    }
+
+    pub fn excludes_type(&self, name: &str) -> bool {
+        self.exclude_types.contains(name)
+    }
+}
+
+fn excludes(include: &Option<BTreeSet<String>>, exclude: &BTreeSet<String>, name: &str) -> bool {
+    exclude.contains(name)
+        || include
+            .as_ref()
+            .map_or(false, |include| !include.contains(name))
+}
@@ -2,9 +2,10 @@ use colored::Colorize;
 use failure::{err_msg, Backtrace, Context, Error, Fail};
 use fern::colors::ColoredLevelConfig;
 use log::{Level, SetLoggerError};
+use std::cell::RefCell;
 use std::collections::HashSet;
 use std::fmt::{self, Display};
-use std::io;
+use std::io::{self, Write};
 use std::str::FromStr;
 use std::sync::Arc;
 use strum_macros::{Display, EnumString};
@@ -12,7 +13,7 @@ use strum_macros::{Display, EnumString};
 use crate::c_ast::{ClangAstParseErrorKind, DisplaySrcSpan};
 use c2rust_ast_exporter::get_clang_major_version;
 
-const DEFAULT_WARNINGS: &[Diagnostic] = &[Diagnostic::ClangAst];
+const DEFAULT_WARNINGS: &[Diagnostic] = &[Diagnostic::ClangAst, Diagnostic::Setjmp];
 
 #[derive(PartialEq, Eq, Hash, Debug, Display, EnumString, Clone)]
 #[strum(serialize_all = "kebab_case")]
@@ -20,6 +21,7 @@ pub enum Diagnostic {
     All,
     Comments,
     ClangAst,
+    Setjmp,
 }
 
 macro_rules! diag {
@@ -62,7 +64,7 @@ pub fn init(mut enabled_warnings: HashSet<Diagnostic>, log_level: log::LevelFilt
                 .map(|d| enabled_warnings.contains(&d))
                 .unwrap_or(true)
         })
-        .chain(io::stderr())
+        .chain(Box::new(DiagnosticSink) as Box<dyn Write + Send>)
         .into_log();
     // Ignore the [`SetLoggerError`] b/c we just want to make sure it's set at least once.
     let _: Result<(), SetLoggerError> = log_reroute::init();
@@ -70,6 +72,95 @@ pub fn init(mut enabled_warnings: HashSet<Diagnostic>, log_level: log::LevelFilt
     log::set_max_level(max_level);
 }
 
+/// Where output written by a translation worker would normally go
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Stream {
+    Stdout,
+    Stderr,
+}
+
+/// Output buffered by `capture`, in the order it was written.
+#[derive(Debug, Default)]
+pub struct Captured(Vec<(Stream, Vec<u8>)>);
+
+thread_local! {
+    /// Output written on this thread while inside `capture`.
+    static CAPTURED: RefCell<Option<Captured>> = RefCell::new(None);
+}
+
+/// Write `buf` to `stream`, unless the current thread is capturing its output
+/// with `capture`.
+fn write_to(stream: Stream, buf: &[u8]) -> io::Result<()> {
+    CAPTURED.with(|captured| match *captured.borrow_mut() {
+        Some(Captured(ref mut chunks)) => {
+            match chunks.last_mut() {
+                Some((last_stream, chunk)) if *last_stream == stream => {
+                    chunk.extend_from_slice(buf)
+                }
+                _ => chunks.push((stream, buf.to_vec())),
+            }
+            Ok(())
+        }
+        None => match stream {
+            Stream::Stdout => io::stdout().write_all(buf),
+            Stream::Stderr => io::stderr().write_all(buf),
+        },
+    })
+}
+
+/// Log output that goes to stderr, unless the logging thread is capturing its
+/// diagnostics with `capture`.
+struct DiagnosticSink;
+
+impl Write for DiagnosticSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        write_to(Stream::Stderr, buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        CAPTURED.with(|captured| match *captured.borrow() {
+            Some(_) => Ok(()),
+            None => io::stderr().flush(),
+        })
+    }
+}
+
+/// Print a progress message such as `Transpiling foo.c` to stdout, or buffer
+/// it along with the diagnostics if the current thread is inside `capture`.
+pub fn status(msg: impl Display) {
+    // There's nowhere to report a failure to write progress messages
+    let _ = write_to(Stream::Stdout, format!("{}\n", msg).as_bytes());
+}
+
+/// Write output of a tool we ran, such as clang's diagnostics from the AST
+/// exporter, to stderr, or buffer it if the current thread is inside `capture`.
+pub fn tool_stderr(buf: &[u8]) {
+    let _ = write_to(Stream::Stderr, buf);
+}
+
+/// Run `f`, buffering the diagnostics and progress messages it writes on the
+/// current thread instead of printing them. Returns the result of `f` and the
+/// buffered output, which can later be written out with `emit_captured`.
+///
+/// This lets us translate several files in parallel while still printing the
+/// output for each file together and in a deterministic order.
+pub fn capture<R>(f: impl FnOnce() -> R) -> (R, Captured) {
+    let outer = CAPTURED.with(|captured| captured.replace(Some(Captured::default())));
+    let res = f();
+    let captured = CAPTURED.with(|captured| captured.replace(outer));
+    (res, captured.unwrap_or_default())
+}
+
+/// Write out output previously buffered by `capture`.
+pub fn emit_captured(captured: &Captured) {
+    // There's nowhere left to report a failure to write diagnostics
+    for (stream, chunk) in &captured.0 {
+        let _ = write_to(*stream, chunk);
+    }
+    let _ = io::stdout().flush().and_then(|()| io::stderr().flush());
+}
+
 #[derive(Debug, Clone)]
 pub struct TranslationError {
     loc: Vec<DisplaySrcSpan>,
@@ -169,6 +260,27 @@ impl TranslationError {
         msg.into()
     }
 
+    /// Source locations this error was raised at, innermost first.
+    pub fn locations(&self) -> &[DisplaySrcSpan] {
+        &self.loc
+    }
+
+    /// The messages of this error and all of its causes, outermost first.
+    pub fn chain(&self) -> Vec<String> {
+        let mut chain = vec![];
+        match self.inner.get_context() {
+            TranslationErrorKind::Generic => {}
+            kind => chain.push(kind.to_string()),
+        }
+        let mut cause = self.cause();
+        while let Some(c) = cause {
+            chain.push(c.to_string());
+            cause = c.cause();
+        }
+        chain.retain(|msg| !msg.is_empty());
+        chain
+    }
+
     pub fn add_loc(mut self, loc: Option<DisplaySrcSpan>) -> Self {
         if let Some(loc) = loc {
             self.loc.push(loc);
@@ -1,4 +1,8 @@
+mod cache;
+mod decl_filter;
 mod diagnostics;
+mod reorganize;
+mod report;
 
 pub mod build_files;
 pub mod c_ast;
@@ -10,28 +14,36 @@ pub mod rust_ast;
 pub mod translator;
 pub mod with_stmts;
 
-use std::collections::HashSet;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::fs::{self, File};
 use std::io;
 use std::io::prelude::*;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
 
 use failure::Error;
 use itertools::Itertools;
 use log::{info, warn};
 use regex::Regex;
-use serde_derive::Serialize;
+use serde_derive::{Deserialize, Serialize};
 
 use crate::c_ast::Printer;
 use crate::c_ast::*;
 pub use crate::diagnostics::Diagnostic;
+pub use crate::report::ReportFormat;
+use crate::report::{Report, UnitReport, UnitStatus};
 use c2rust_ast_exporter as ast_exporter;
 
-use crate::build_files::{emit_build_files, get_build_dir, CrateConfig};
-use crate::compile_cmds::get_compile_commands;
+use crate::build_files::{emit_build_files, get_build_dir, CrateConfig, LinkDependency};
+use crate::cache::{CachedTranslation, TranslationCache};
+use crate::compile_cmds::{get_compile_commands, CompileCmd};
 use crate::convert_type::RESERVED_NAMES;
+pub use crate::decl_filter::DeclFilter;
 pub use crate::translator::ReplaceMode;
+pub use c2rust_ast_exporter::run_as_exporter_subprocess;
 use std::prelude::v1::Vec;
 
 type PragmaVec = Vec<(&'static str, Vec<&'static str>)>;
@@ -52,11 +64,17 @@ pub struct TranspilerConfig {
     pub dump_structures: bool,
     pub verbose: bool,
     pub debug_ast_exporter: bool,
+    /// Save the CBOR exported for each translation unit in this directory
+    pub save_ast: Option<PathBuf>,
+    /// Load previously saved CBOR from this directory instead of running clang
+    pub from_ast: Option<PathBuf>,
 
     // Options that control translation
     pub incremental_relooper: bool,
     pub fail_on_multiple: bool,
     pub filter: Option<Regex>,
+    /// Functions, globals and types to translate or replace by declarations
+    pub decl_filter: DeclFilter,
     pub debug_relooper_labels: bool,
     pub prefix_function_names: Option<String>,
     pub translate_asm: bool,
@@ -71,14 +89,32 @@ pub struct TranspilerConfig {
     pub overwrite_existing: bool,
     pub reduce_type_annotations: bool,
     pub reorganize_definitions: bool,
+    /// Number of translation units to translate in parallel. Their ASTs are
+    /// only exported in parallel if the executable calls
+    /// `run_as_exporter_subprocess`.
+    pub jobs: usize,
+    /// Reuse translations of unchanged translation units from an on-disk cache
+    pub incremental: bool,
+    /// Where to keep the incremental cache; defaults to `.c2rust-cache` in the
+    /// build directory
+    pub cache_dir: Option<PathBuf>,
     pub enabled_warnings: HashSet<Diagnostic>,
     pub emit_no_std: bool,
+    /// Emit code that builds on a stable toolchain, lowering nightly-only
+    /// constructs to stable equivalents where there are any
+    pub stable: bool,
     pub output_dir: Option<PathBuf>,
     pub translate_const_macros: bool,
     pub translate_fn_macros: bool,
+    /// Translate enums that are only used through their constants into Rust
+    /// enums instead of integer type aliases
+    pub rust_enums: bool,
     pub disable_refactoring: bool,
     pub preserve_unused_functions: bool,
     pub log_level: log::LevelFilter,
+    /// Write a machine-readable report of the translation outcome to this file
+    pub report: Option<PathBuf>,
+    pub report_format: ReportFormat,
 
     // Options that control build files
     /// Emit `Cargo.toml` and `lib.rs`
@@ -121,6 +157,49 @@ impl TranspilerConfig {
         ok
     }
 
+    /// Options that affect the translation of a single file, used to decide
+    /// whether a cached translation can be reused. Options that change the
+    /// translator's output must be added here.
+    fn output_options(&self) -> String {
+        format!(
+            "{:?}",
+            (
+                (
+                    self.incremental_relooper,
+                    self.fail_on_multiple,
+                    self.debug_relooper_labels,
+                    &self.prefix_function_names,
+                    self.translate_asm,
+                    self.use_c_loop_info,
+                    self.use_c_multiple_info,
+                    self.simplify_structures,
+                    self.panic_on_translator_failure,
+                    self.emit_modules,
+                    self.fail_on_error,
+                    self.rust_enums,
+                ),
+                (
+                    self.replace_unsupported_decls,
+                    self.translate_valist,
+                    self.reduce_type_annotations,
+                    self.reorganize_definitions,
+                    self.emit_no_std,
+                    self.stable,
+                    &self.output_dir,
+                    self.translate_const_macros,
+                    self.translate_fn_macros,
+                    self.preserve_unused_functions,
+                    &self.binaries,
+                    &self.decl_filter,
+                ),
+                // Declaration reports are only collected if a report is
+                // requested, so cached entries without them can't be reused
+                // for one
+                self.report.is_some(),
+            )
+        )
+    }
+
     fn crate_name(&self) -> String {
         self.output_dir
             .as_ref()
@@ -129,12 +208,13 @@ impl TranspilerConfig {
     }
 }
 
-#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 pub enum ExternCrate {
     C2RustBitfields,
     C2RustAsmCasts,
     F128,
     NumTraits,
+    NumComplex,
     Memoffset,
     Libc,
 }
@@ -165,6 +245,7 @@ impl From<ExternCrate> for ExternCrateDetails {
             ExternCrate::C2RustAsmCasts => Self::new("c2rust-asm-casts", "0.2", true),
             ExternCrate::F128 => Self::new("f128", "0.2", false),
             ExternCrate::NumTraits => Self::new("num-traits", "0.2", true),
+            ExternCrate::NumComplex => Self::new("num-complex", "0.4", false),
             ExternCrate::Memoffset => Self::new("memoffset", "0.5", true),
             ExternCrate::Libc => Self::new("libc", "0.2", false),
         }
@@ -242,29 +323,37 @@ pub fn transpile(tcfg: TranspilerConfig, cc_db: &Path, extra_clang_args: &[&str]
 
     let mut top_level_ccfg = None;
     let mut workspace_members = vec![];
+    let mut crate_exports = HashMap::new();
+    let mut member_dirs: HashMap<String, PathBuf> = HashMap::new();
     let mut num_transpiled_files = 0;
     let mut transpiled_modules = Vec::new();
+    let mut report = Report::default();
     let build_dir = get_build_dir(&tcfg, cc_db);
+    let cache = if tcfg.incremental {
+        let cache_dir = tcfg
+            .cache_dir
+            .clone()
+            .unwrap_or_else(|| build_dir.join(".c2rust-cache"));
+        TranslationCache::new(cache_dir)
+            .map_err(|e| warn!("Could not create translation cache, disabling it: {}", e))
+            .ok()
+    } else {
+        None
+    };
     for lcmd in &lcmds {
         let cmds = &lcmd.cmd_inputs;
-        let lcmd_name = lcmd
-            .output
-            .as_ref()
-            .map(|output| {
-                let output_path = Path::new(output);
-                output_path
-                    .file_stem()
-                    .unwrap()
-                    .to_str()
-                    .unwrap()
-                    .to_owned()
-            })
-            .unwrap_or_else(|| tcfg.crate_name());
+        let lcmd_name = lcmd.crate_name().unwrap_or_else(|| tcfg.crate_name());
         let build_dir = if lcmd.top_level {
             build_dir.to_path_buf()
         } else {
             build_dir.join(&lcmd_name)
         };
+        // Saved ASTs are laid out the same way as the build directories
+        let ast_subdir = if lcmd.top_level {
+            PathBuf::new()
+        } else {
+            PathBuf::from(&lcmd_name)
+        };
 
         // Compute the common ancestor of all input files
         // FIXME: this is quadratic-time in the length of the ancestor path
@@ -287,17 +376,75 @@ pub fn transpile(tcfg: TranspilerConfig, cc_db: &Path, extra_clang_args: &[&str]
             }
         }
 
-        let results = cmds
-            .iter()
-            .map(|cmd| {
+        let cmds = cmds.iter().map(|cmd| &**cmd).collect::<Vec<&CompileCmd>>();
+        let load = |cmd, unit_report: &mut UnitReport| {
+            load_unit(
+                &tcfg,
+                cmd,
+                &ancestor_path,
+                &build_dir,
+                &ast_subdir,
+                cc_db,
+                &clang_args,
+                cache.as_ref(),
+                unit_report,
+            )
+        };
+        let translate =
+            |unit: &LoadedUnit, rejected_enums: &BTreeSet<String>, unit_report: &mut UnitReport| {
                 transpile_single(
                     &tcfg,
-                    cmd.abs_file(),
+                    unit,
                     &ancestor_path,
-                    &build_dir,
+                    &ast_subdir,
                     cc_db,
                     &clang_args,
+                    rejected_enums,
+                    cache.as_ref(),
+                    unit_report,
                 )
+            };
+        let results = if tcfg.rust_enums {
+            // Whether an enum becomes a Rust enum is decided for the whole
+            // crate from the enums each unit rejects, so all units are loaded,
+            // keeping their ASTs, before any is translated. What loading a
+            // unit prints is printed along with its translation.
+            let units = parallel_map(tcfg.jobs, &cmds, |cmd| {
+                let mut unit_report = UnitReport::new(cmd.abs_file());
+                let (unit, diags) = diagnostics::capture(|| load(*cmd, &mut unit_report));
+                (unit, unit_report, diags)
+            });
+            let rejected_enums = units
+                .iter()
+                .filter_map(|(unit, _, _)| unit.as_ref().ok())
+                .flat_map(LoadedUnit::rejected_enums)
+                .cloned()
+                .collect::<BTreeSet<_>>();
+            parallel_map(tcfg.jobs, &units, |(unit, unit_report, load_diags)| {
+                let mut unit_report = unit_report.clone();
+                let (res, diags) = capture_if_parallel(tcfg.jobs, || {
+                    diagnostics::emit_captured(load_diags);
+                    let unit = unit.as_ref().map_err(|_| ())?;
+                    translate(unit, &rejected_enums, &mut unit_report)
+                });
+                (res, unit_report, diags)
+            })
+        } else {
+            parallel_map(tcfg.jobs, &cmds, |cmd| {
+                let mut unit_report = UnitReport::new(cmd.abs_file());
+                let (res, diags) = capture_if_parallel(tcfg.jobs, || {
+                    let unit = load(*cmd, &mut unit_report)?;
+                    translate(&unit, &BTreeSet::new(), &mut unit_report)
+                });
+                (res, unit_report, diags)
+            })
+        };
+        let results = results
+            .into_iter()
+            .map(|(res, unit_report, diags)| {
+                diagnostics::emit_captured(&diags);
+                report.translation_units.push(unit_report);
+                res
             })
             .collect::<Vec<TranspileResult>>();
         let mut modules = vec![];
@@ -331,40 +478,131 @@ pub fn transpile(tcfg: TranspilerConfig, cc_db: &Path, extra_clang_args: &[&str]
             if modules_skipped {
                 // If we skipped a file, we may not have collected all required pragmas
                 warn!("Can't emit build files after incremental transpiler run; skipped.");
+                write_report(&tcfg, &report);
                 return;
             }
 
+            if tcfg.reorganize_definitions && !tcfg.disable_refactoring {
+                match reorganize::reorganize_definitions(&tcfg, &build_dir, &modules) {
+                    Ok(header_modules) => modules.extend(header_modules),
+                    Err(e) => warn!("Reorganizing definitions failed: {}", e),
+                }
+            }
+
+            let deps = lcmd
+                .deps
+                .iter()
+                .filter_map(|dep| crate_exports.get(dep))
+                .collect::<Vec<_>>();
+            if let Err(e) = reorganize::link_crate_dependencies(&build_dir, &modules, &deps) {
+                warn!("Linking against crate dependencies failed: {}", e);
+            }
+            if lcmd.r#type.is_library() {
+                match reorganize::crate_exports(&tcfg, &build_dir, &modules, &lcmd_name) {
+                    Ok(exports) => {
+                        crate_exports.insert(lcmd_name.clone(), exports);
+                    }
+                    Err(e) => warn!("Collecting exports of {} failed: {}", lcmd_name, e),
+                }
+            }
+            let link_deps = lcmd
+                .deps
+                .iter()
+                .filter_map(|dep| {
+                    let build_dir = member_dirs.get(dep)?;
+                    Some(LinkDependency {
+                        crate_name: dep.clone(),
+                        build_dir: build_dir.clone(),
+                    })
+                })
+                .collect();
+            member_dirs.insert(lcmd_name.clone(), build_dir.clone());
+
             let ccfg = CrateConfig {
                 crate_name: lcmd_name.clone(),
                 modules,
                 pragmas,
                 crates,
+                link_deps,
                 link_cmd: lcmd,
             };
             if lcmd.top_level {
                 top_level_ccfg = Some(ccfg);
             } else {
                 let crate_file = emit_build_files(&tcfg, &build_dir, Some(ccfg), None);
-                reorganize_definitions(&tcfg, &build_dir, crate_file)
-                    .unwrap_or_else(|e| warn!("Reorganizing definitions failed: {}", e));
+                format_reorganized_crate(&tcfg, &build_dir, crate_file)
+                    .unwrap_or_else(|e| warn!("Formatting reorganized crate failed: {}", e));
                 workspace_members.push(lcmd_name);
             }
         }
     }
 
+    if let Some(ref cache) = cache {
+        info!(
+            "Translation cache: {} hits, {} misses",
+            cache.hits(),
+            cache.misses()
+        );
+    }
+
     if num_transpiled_files == 0 {
         warn!("No C files found in compile_commands.json; nothing to do.");
+        write_report(&tcfg, &report);
         return;
     }
 
     if tcfg.emit_build_files {
         let crate_file =
             emit_build_files(&tcfg, &build_dir, top_level_ccfg, Some(workspace_members));
-        reorganize_definitions(&tcfg, &build_dir, crate_file)
-            .unwrap_or_else(|e| warn!("Reorganizing definitions failed: {}", e));
+        format_reorganized_crate(&tcfg, &build_dir, crate_file)
+            .unwrap_or_else(|e| warn!("Formatting reorganized crate failed: {}", e));
     }
 
     tcfg.check_if_all_binaries_used(&transpiled_modules);
+    write_report(&tcfg, &report);
+}
+
+/// Write the translation report if one was requested.
+fn write_report(tcfg: &TranspilerConfig, report: &Report) {
+    if let Some(ref path) = tcfg.report {
+        match report.write(path, tcfg.report_format) {
+            Ok(()) => info!("Wrote translation report to {}", path.display()),
+            Err(e) => warn!("Failed to write report to {}: {}", path.display(), e),
+        }
+    }
+}
+
+/// Apply `f` to every element of `items` using up to `jobs` worker threads.
+/// The results are returned in the same order as `items`, regardless of the
+/// order in which the workers finish.
+fn parallel_map<T, R, F>(jobs: usize, items: &[T], f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    if jobs <= 1 || items.len() <= 1 {
+        return items.iter().map(f).collect();
+    }
+
+    let next_item = AtomicUsize::new(0);
+    let results = items.iter().map(|_| Mutex::new(None)).collect::<Vec<_>>();
+    thread::scope(|scope| {
+        for _ in 0..jobs.min(items.len()) {
+            scope.spawn(|| loop {
+                let idx = next_item.fetch_add(1, Ordering::Relaxed);
+                if idx >= items.len() {
+                    break;
+                }
+                let res = f(&items[idx]);
+                *results[idx].lock().unwrap() = Some(res);
+            });
+        }
+    });
+    results
+        .into_iter()
+        .map(|res| res.into_inner().unwrap().unwrap())
+        .collect()
 }
 
 /// Ensure that clang can locate the system headers on macOS 10.14+.
@@ -399,22 +637,18 @@ fn get_extra_args_macos() -> Vec<String> {
     args
 }
 
-fn invoke_refactor(_build_dir: &Path) -> Result<(), Error> {
-    Ok(())
-}
-
-fn reorganize_definitions(
+/// Run `cargo fmt` over a crate whose definitions were reorganized, since the
+/// reorganized modules are printed without the original formatting.
+fn format_reorganized_crate(
     tcfg: &TranspilerConfig,
     build_dir: &Path,
     crate_file: Option<PathBuf>,
 ) -> Result<(), Error> {
-    // We only run the reorganization refactoring if we emitted a fresh crate file
+    // We only reorganize definitions if we emitted a fresh crate file
     if crate_file.is_none() || tcfg.disable_refactoring || !tcfg.reorganize_definitions {
         return Ok(());
     }
 
-    invoke_refactor(build_dir)?;
-    // fix the formatting of the output of `c2rust-refactor`
     let status = process::Command::new("cargo")
         .args(&["fmt"])
         .current_dir(build_dir)
@@ -425,52 +659,254 @@ fn reorganize_definitions(
     Ok(())
 }
 
-fn transpile_single(
+/// Run `f`, buffering its output if translation units are translated in
+/// parallel, so that each unit's progress messages and diagnostics are printed
+/// together and in input order.
+fn capture_if_parallel<R>(jobs: usize, f: impl FnOnce() -> R) -> (R, diagnostics::Captured) {
+    if jobs > 1 {
+        diagnostics::capture(f)
+    } else {
+        (f(), Default::default())
+    }
+}
+
+/// A translation unit loaded by [`load_unit`], ready to be translated
+struct LoadedUnit<'a> {
+    cmd: &'a CompileCmd,
+    output_path: PathBuf,
+    module_path: Vec<String>,
+    cache_key: Option<String>,
+    source: UnitSource,
+}
+
+enum UnitSource {
+    /// A cached translation, which is reused if the crate still rejects the
+    /// same enums as when it was cached
+    Cached(CachedTranslation),
+    /// The untyped AST, and the enums it rejects
+    Ast(ast_exporter::clang_ast::AstContext, Vec<String>),
+}
+
+impl LoadedUnit<'_> {
+    /// The enums that this unit can't translate into Rust enums with
+    /// `--rust-enums`, as given by [`translator::rejected_rust_enums`]
+    fn rejected_enums(&self) -> &[String] {
+        match self.source {
+            UnitSource::Cached(ref cached) => &cached.rejected_enums,
+            UnitSource::Ast(_, ref rejected_enums) => rejected_enums,
+        }
+    }
+}
+
+/// Load the cached translation of a translation unit, or its AST if there is
+/// none.
+#[allow(clippy::too_many_arguments)]
+fn load_unit<'a>(
     tcfg: &TranspilerConfig,
-    input_path: PathBuf,
+    cmd: &'a CompileCmd,
     ancestor_path: &Path,
     build_dir: &Path,
+    ast_subdir: &Path,
     cc_db: &Path,
     extra_clang_args: &[&str],
-) -> TranspileResult {
+    cache: Option<&TranslationCache>,
+    report: &mut UnitReport,
+) -> Result<LoadedUnit<'a>, ()> {
+    let input_path = cmd.abs_file();
     let output_path = get_output_path(tcfg, input_path.clone(), ancestor_path, build_dir);
     if output_path.exists() && !tcfg.overwrite_existing {
-        warn!("Skipping existing file {}", output_path.display());
+        let msg = format!("Skipping existing file {}", output_path.display());
+        warn!("{}", msg);
+        report.set_status(UnitStatus::Skipped, msg);
         return Err(());
     }
 
     let file = input_path.file_name().unwrap().to_str().unwrap();
+    let module_path = get_module_path(tcfg, &output_path, build_dir);
+
+    // The cache key covers the preprocessed TU, or the saved AST we translate
+    let cache_key = cache.and_then(|_| {
+        let input_hash = match tcfg.from_ast {
+            Some(ref from_ast) => cache::hash_file(&get_ast_path(
+                &from_ast.join(ast_subdir),
+                &input_path,
+                ancestor_path,
+            )),
+            None => cache::preprocessed_hash(cmd, extra_clang_args),
+        };
+        match input_hash {
+            Ok(input_hash) => Some(cache::cache_key(
+                tcfg,
+                cmd,
+                extra_clang_args,
+                &input_hash,
+                &module_path,
+            )),
+            Err(e) => {
+                warn!("Not caching the translation of {}: {}", file, e);
+                None
+            }
+        }
+    });
+    // A cache hit skips the AST export, so `--save-ast` always exports
+    let cached = cache
+        .zip(cache_key.as_ref())
+        .filter(|_| tcfg.save_ast.is_none())
+        .and_then(|(cache, key)| cache.lookup(key, &input_path, &output_path));
+
+    let source = match cached {
+        Some(cached) => UnitSource::Cached(cached),
+        None => {
+            let untyped_context = load_ast(
+                tcfg,
+                cmd,
+                ancestor_path,
+                ast_subdir,
+                cc_db,
+                extra_clang_args,
+                report,
+            )?;
+            let rejected_enums = if tcfg.rust_enums {
+                let typed_context = ConversionContext::new(&untyped_context).typed_context;
+                translator::rejected_rust_enums(typed_context, tcfg, input_path)
+            } else {
+                vec![]
+            };
+            UnitSource::Ast(untyped_context, rejected_enums)
+        }
+    };
+    Ok(LoadedUnit {
+        cmd,
+        output_path,
+        module_path,
+        cache_key,
+        source,
+    })
+}
+
+/// Load the untyped AST of a translation unit, exporting it from clang unless
+/// we translate saved ASTs.
+fn load_ast(
+    tcfg: &TranspilerConfig,
+    cmd: &CompileCmd,
+    ancestor_path: &Path,
+    ast_subdir: &Path,
+    cc_db: &Path,
+    extra_clang_args: &[&str],
+    report: &mut UnitReport,
+) -> Result<ast_exporter::clang_ast::AstContext, ()> {
+    let input_path = cmd.abs_file();
+    if let Some(ref from_ast) = tcfg.from_ast {
+        let ast_path = get_ast_path(&from_ast.join(ast_subdir), &input_path, ancestor_path);
+        return load_untyped_ast(&ast_path).map_err(|e| {
+            let msg = format!(
+                "Error: {}. Skipping {}; could not load saved AST {}",
+                e,
+                input_path.display(),
+                ast_path.display()
+            );
+            warn!("{}", msg);
+            report.set_status(UnitStatus::Failed, msg);
+        });
+    }
+
     if !input_path.exists() {
-        warn!(
+        let msg = format!(
             "Input C file {} does not exist, skipping!",
             input_path.display()
         );
+        warn!("{}", msg);
+        report.set_status(UnitStatus::Skipped, msg);
         return Err(());
     }
 
     if tcfg.verbose {
-        println!("Additional Clang arguments: {}", extra_clang_args.join(" "));
+        diagnostics::status(format_args!(
+            "Additional Clang arguments: {}",
+            extra_clang_args.join(" ")
+        ));
     }
 
-    // Extract the untyped AST from the CBOR file
-    let untyped_context = match ast_exporter::get_untyped_ast(
+    let save_ast_path = tcfg
+        .save_ast
+        .as_ref()
+        .map(|dir| get_ast_path(&dir.join(ast_subdir), &input_path, ancestor_path));
+    export_untyped_ast(
+        tcfg,
         input_path.as_path(),
         cc_db,
         extra_clang_args,
-        tcfg.debug_ast_exporter,
-    ) {
-        Err(e) => {
-            warn!(
-                "Error: {}. Skipping {}; is it well-formed C?",
-                e,
-                input_path.display()
-            );
-            return Err(());
+        save_ast_path.as_deref(),
+    )
+    .map_err(|e| {
+        let msg = format!(
+            "Error: {}. Skipping {}; is it well-formed C?",
+            e,
+            input_path.display()
+        );
+        warn!("{}", msg);
+        report.set_status(UnitStatus::Failed, msg);
+    })
+}
+
+/// Translate a loaded translation unit, given the enums its crate rejects, or
+/// reuse its cached translation if it's still valid.
+#[allow(clippy::too_many_arguments)]
+fn transpile_single(
+    tcfg: &TranspilerConfig,
+    unit: &LoadedUnit,
+    ancestor_path: &Path,
+    ast_subdir: &Path,
+    cc_db: &Path,
+    extra_clang_args: &[&str],
+    rejected_enums: &BTreeSet<String>,
+    cache: Option<&TranslationCache>,
+    report: &mut UnitReport,
+) -> TranspileResult {
+    let input_path = unit.cmd.abs_file();
+    let output_path = &unit.output_path;
+    let file = input_path.file_name().unwrap().to_str().unwrap();
+    let crate_rejected_enums = rejected_enums.iter().cloned().collect::<Vec<_>>();
+
+    let loaded;
+    let untyped_context = match unit.source {
+        UnitSource::Cached(ref cached) if cached.crate_rejected_enums == crate_rejected_enums => {
+            if let Some(cache) = cache {
+                cache.count(true);
+            }
+            diagnostics::status(format_args!("Reusing cached translation of {}", file));
+            // Leave unchanged outputs alone so their timestamps don't change
+            if fs::read_to_string(output_path).ok().as_ref() != Some(&cached.output) {
+                write_output(output_path, &cached.output);
+            }
+            report.declarations = cached.declarations.clone();
+            report.output = Some(output_path.clone());
+            return Ok((
+                output_path.clone(),
+                cached.pragmas.clone(),
+                cached.crates.clone(),
+            ));
         }
-        Ok(cxt) => cxt,
+        // The crate rejects different enums than when this unit was cached
+        UnitSource::Cached(_) => {
+            loaded = load_ast(
+                tcfg,
+                unit.cmd,
+                ancestor_path,
+                ast_subdir,
+                cc_db,
+                extra_clang_args,
+                report,
+            )?;
+            &loaded
+        }
+        UnitSource::Ast(ref untyped_context, _) => untyped_context,
     };
+    if let (Some(cache), Some(_)) = (cache, &unit.cache_key) {
+        cache.count(false);
+    }
 
-    println!("Transpiling {}", file);
+    diagnostics::status(format_args!("Transpiling {}", file));
 
     if tcfg.dump_untyped_context {
         println!("CBOR Clang AST");
@@ -479,7 +915,7 @@ fn transpile_single(
 
     // Convert this into a typed AST
     let typed_context = {
-        let conv = ConversionContext::new(&untyped_context);
+        let conv = ConversionContext::new(untyped_context);
         if conv.invalid_clang_ast && tcfg.fail_on_error {
             panic!("Clang AST was invalid");
         }
@@ -497,10 +933,35 @@ fn transpile_single(
     }
 
     // Perform the translation
-    let (translated_string, pragmas, crates) =
-        translator::translate(typed_context, tcfg, input_path);
+    let (translated_string, pragmas, crates, decl_reports) = translator::translate(
+        typed_context,
+        tcfg,
+        input_path.clone(),
+        unit.module_path.clone(),
+        rejected_enums,
+    );
+    report.declarations = decl_reports;
+
+    write_output(output_path, &translated_string);
+
+    if let (Some(cache), Some(key)) = (cache, &unit.cache_key) {
+        let translation = CachedTranslation {
+            output: translated_string,
+            pragmas: pragmas.clone(),
+            crates: crates.clone(),
+            declarations: report.declarations.clone(),
+            rejected_enums: unit.rejected_enums().to_vec(),
+            crate_rejected_enums,
+        };
+        cache.store(key, &input_path, output_path, translation);
+    }
+
+    report.output = Some(output_path.clone());
+    Ok((output_path.clone(), pragmas, crates))
+}
 
-    let mut file = match File::create(&output_path) {
+fn write_output(output_path: &Path, translation: &str) {
+    let mut file = match File::create(output_path) {
         Ok(file) => file,
         Err(e) => panic!(
             "Unable to open file {} for writing: {}",
@@ -509,7 +970,7 @@ fn transpile_single(
         ),
     };
 
-    match file.write_all(translated_string.as_bytes()) {
+    match file.write_all(translation.as_bytes()) {
         Ok(()) => (),
         Err(e) => panic!(
             "Unable to write translation to file {}: {}",
@@ -517,8 +978,82 @@ fn transpile_single(
             e
         ),
     };
+}
 
-    Ok((output_path, pragmas, crates))
+/// Run the clang AST exporter on `input_path`, optionally saving the
+/// exported CBOR to `save_path` before decoding it.
+fn export_untyped_ast(
+    tcfg: &TranspilerConfig,
+    input_path: &Path,
+    cc_db: &Path,
+    extra_clang_args: &[&str],
+    save_path: Option<&Path>,
+) -> io::Result<ast_exporter::clang_ast::AstContext> {
+    // The exporter can only run once at a time per process, so parallel
+    // workers run it in subprocesses when the executable supports that
+    let cbor = if tcfg.jobs > 1 && ast_exporter::subprocess_export_supported() {
+        let (cbor, stderr) = ast_exporter::get_ast_cbor_in_subprocess(
+            input_path,
+            cc_db,
+            extra_clang_args,
+            tcfg.debug_ast_exporter,
+        )?;
+        diagnostics::tool_stderr(&stderr);
+        cbor
+    } else {
+        ast_exporter::get_ast_cbor(input_path, cc_db, extra_clang_args, tcfg.debug_ast_exporter)?
+    };
+
+    if let Some(save_path) = save_path {
+        if let Some(parent) = save_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(save_path, &cbor)?;
+        info!("Saved Clang AST to {}", save_path.display());
+    }
+
+    ast_exporter::untyped_ast_from_cbor(&cbor)
+}
+
+/// Load an untyped AST from CBOR saved by a previous `--save-ast` run.
+fn load_untyped_ast(ast_path: &Path) -> io::Result<ast_exporter::clang_ast::AstContext> {
+    let cbor = fs::read(ast_path)?;
+    ast_exporter::untyped_ast_from_cbor(&cbor)
+}
+
+/// Location of the saved AST for `input_path` inside `ast_dir`. Saved ASTs
+/// mirror the layout of the input files relative to their common ancestor,
+/// e.g. `src/foo.c` is saved as `<ast_dir>/src/foo.c.cbor`.
+fn get_ast_path(ast_dir: &Path, input_path: &Path, ancestor_path: &Path) -> PathBuf {
+    let rel_path = input_path.strip_prefix(ancestor_path).unwrap_or(input_path);
+    let mut ast_path = ast_dir.to_path_buf();
+    // Only keep normal components so that we never escape `ast_dir`
+    ast_path.extend(
+        rel_path
+            .components()
+            .filter(|c| matches!(c, Component::Normal(_))),
+    );
+    let mut file_name = ast_path.file_name().unwrap_or_default().to_owned();
+    file_name.push(".cbor");
+    ast_path.set_file_name(file_name);
+    ast_path
+}
+
+/// Path of the module that `output_path` is translated into, relative to the
+/// root of its crate. Without build files, each file is taken to be a module of
+/// the crate root, as `--emit-modules` files are usually declared.
+fn get_module_path(tcfg: &TranspilerConfig, output_path: &Path, build_dir: &Path) -> Vec<String> {
+    if tcfg.is_binary(output_path) {
+        return vec![];
+    }
+    match output_path.strip_prefix(build_dir) {
+        // Mirrors the modules declared in `lib.rs`
+        Ok(relpath) if tcfg.emit_build_files => relpath
+            .iter()
+            .map(|sm| get_module_name(Path::new(sm), true, false, false).unwrap())
+            .collect(),
+        _ => vec![get_module_name(output_path, true, false, false).unwrap()],
+    }
 }
 
 fn get_output_path(
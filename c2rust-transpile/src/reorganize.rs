@@ -0,0 +1,871 @@
+//! Cross-module deduplication of header declarations.
+//!
+//! With `--reorganize-definitions`, the translator places the declarations a
+//! translation unit takes from each header into a submodule marked with
+//! `#[c2rust::header_src = "<path>:<line>"]`, so every module of the crate
+//! carries its own copy of the headers it includes. Once all translation units
+//! of a crate are translated, we move these copies into a single module per
+//! header so that types from the same header unify across modules, and replace
+//! `extern "C"` declarations of functions that are defined in a sibling module
+//! with imports of the definition.
+//!
+//! The same function linking also applies across the crates of a workspace:
+//! declarations of functions that a crate takes from the library of another
+//! member become imports from that member's crate.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use c2rust_ast_builder::mk;
+use c2rust_ast_printer::pprust;
+use failure::{format_err, Error};
+use indexmap::IndexMap;
+use log::{info, warn};
+use proc_macro2::Span;
+use regex::Regex;
+use syn::__private::ToTokens;
+use syn::{
+    ForeignItem, Ident, Item, ItemMod, ItemUse, UseGlob, UseName, UsePath, UseRename, UseTree,
+    VisPublic, Visibility,
+};
+
+use crate::{get_module_name, TranspilerConfig};
+
+/// Directory, next to the translated modules, that holds the merged header
+/// modules.
+const HEADERS_DIR: &str = "c2rust_headers";
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+enum Namespace {
+    Type,
+    Value,
+}
+
+type ItemKey = (Namespace, String);
+
+/// Move the header declarations of the library `modules` into one module per
+/// header, rewriting the modules in place. Returns the paths of the new header
+/// modules, which must be added to the module tree of the crate.
+pub fn reorganize_definitions(
+    tcfg: &TranspilerConfig,
+    build_dir: &Path,
+    modules: &[PathBuf],
+) -> Result<Vec<PathBuf>, Error> {
+    // Binaries are separate crates, so they can't share the merged modules
+    let mut modules = modules
+        .iter()
+        .filter(|path| !tcfg.is_binary(path))
+        .map(|path| Module::load(build_dir, path))
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let source_dir = if tcfg.output_dir.is_some() {
+        build_dir.join("src")
+    } else {
+        build_dir.to_owned()
+    };
+    let headers_dir = source_dir.join(HEADERS_DIR);
+    let headers_path = crate_path(build_dir, &headers_dir);
+
+    let mut headers = IndexMap::new();
+    for module in &mut modules {
+        module.merge_headers(&mut headers, &headers_path);
+    }
+
+    let definitions = exported_functions(&modules);
+    for module in &mut modules {
+        module.link_functions(&definitions);
+        fs::write(&module.path, pprust::to_string(|| module.file.clone()))?;
+    }
+
+    if !headers.is_empty() {
+        fs::create_dir_all(&headers_dir)?;
+    }
+    let mut header_modules = vec![];
+    for (src, header) in headers {
+        let path = headers_dir.join(format!("{}.rs", header.name));
+        let mut header_path = headers_path.clone();
+        header_path.push(header.name.clone());
+        let items = header.into_items(&definitions, &header_path);
+        fs::write(
+            &path,
+            pprust::to_string(|| syn::File {
+                shebang: None,
+                attrs: vec![],
+                items,
+            }),
+        )?;
+        info!("Merged declarations from {} into {}", src, path.display());
+        header_modules.push(path);
+    }
+    Ok(header_modules)
+}
+
+/// The functions that a library crate of the workspace exports to the crates
+/// linking against it.
+pub struct CrateExports {
+    definitions: HashMap<String, Definition>,
+}
+
+/// Collect the functions exported by the library `modules` of the crate named
+/// `crate_name`.
+pub fn crate_exports(
+    tcfg: &TranspilerConfig,
+    build_dir: &Path,
+    modules: &[PathBuf],
+    crate_name: &str,
+) -> Result<CrateExports, Error> {
+    let modules = modules
+        .iter()
+        .filter(|path| !tcfg.is_binary(path))
+        .map(|path| Module::load(build_dir, path))
+        .collect::<Result<Vec<_>, Error>>()?;
+    let mut definitions = exported_functions(&modules);
+    // Paths are taken from the dependent crate, where the library is an
+    // extern crate rather than `crate`
+    let crate_ident = crate_name.replace('-', "_");
+    for def in definitions.values_mut() {
+        def.module[0] = crate_ident.clone();
+    }
+    Ok(CrateExports { definitions })
+}
+
+/// Replace the `extern` declarations in `modules` of functions that the
+/// crates in `deps` define with imports from those crates. Only the modules
+/// that change are rewritten.
+pub fn link_crate_dependencies(
+    build_dir: &Path,
+    modules: &[PathBuf],
+    deps: &[&CrateExports],
+) -> Result<(), Error> {
+    let mut definitions = HashMap::new();
+    for dep in deps {
+        for (name, def) in &dep.definitions {
+            definitions
+                .entry(name.clone())
+                .or_insert_with(|| def.clone());
+        }
+    }
+    if definitions.is_empty() {
+        return Ok(());
+    }
+
+    for path in modules {
+        let mut module = Module::load(build_dir, path)?;
+        let original = module.file.items.clone();
+        module.link_functions(&definitions);
+        if module.file.items != original {
+            info!("Linked {} against its crate dependencies", path.display());
+            fs::write(&module.path, pprust::to_string(|| module.file.clone()))?;
+        }
+    }
+    Ok(())
+}
+
+/// A translated module of the crate.
+struct Module {
+    path: PathBuf,
+    /// Absolute path of the module in the crate, starting with `crate`
+    crate_path: Vec<String>,
+    file: syn::File,
+}
+
+impl Module {
+    fn load(build_dir: &Path, path: &Path) -> Result<Self, Error> {
+        let source = fs::read_to_string(path)?;
+        let file = syn::parse_file(&source)
+            .map_err(|e| format_err!("could not parse {}: {}", path.display(), e))?;
+        Ok(Module {
+            path: path.to_owned(),
+            crate_path: crate_path(build_dir, path),
+            file,
+        })
+    }
+
+    /// Header submodules of this module by identifier, along with their
+    /// position among the items of the module and the header they came from.
+    fn header_submodules(&self) -> IndexMap<String, (usize, String)> {
+        self.file
+            .items
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, item)| match item {
+                Item::Mod(m) => header_src(m).map(|src| (m.ident.to_string(), (idx, src))),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Move the declarations of our header submodules into `headers`, leaving
+    /// behind re-exports so that paths into the submodules keep working.
+    fn merge_headers(&mut self, headers: &mut IndexMap<String, Header>, headers_path: &[String]) {
+        let submodules = self.header_submodules();
+        let submodule = |idx: usize| match &self.file.items[idx] {
+            Item::Mod(m) => m,
+            _ => unreachable!(),
+        };
+
+        let mut merged = submodules
+            .iter()
+            .filter(|(_, (idx, src))| can_merge(submodule(*idx), headers.get(src)))
+            .map(|(ident, _)| ident.clone())
+            .collect::<HashSet<_>>();
+
+        // Declarations that import from a sibling that stays behind have to
+        // stay behind as well
+        loop {
+            let stays = |ident: &String| {
+                let (idx, _) = submodules[ident];
+                match imported_siblings(submodule(idx)) {
+                    Some(siblings) => !siblings.iter().all(|s| merged.contains(s)),
+                    None => true,
+                }
+            };
+            let staying = merged
+                .iter()
+                .filter(|s| stays(s))
+                .cloned()
+                .collect::<Vec<_>>();
+            if staying.is_empty() {
+                break;
+            }
+            for ident in staying {
+                merged.remove(&ident);
+            }
+        }
+
+        let destinations = submodules
+            .iter()
+            .filter(|(ident, _)| merged.contains(*ident))
+            .map(|(ident, (_, src))| {
+                let header = header_entry(headers, src, ident);
+                let mut path = headers_path.to_owned();
+                path.push(header.name.clone());
+                (ident.clone(), path)
+            })
+            .collect::<HashMap<_, _>>();
+
+        for (ident, (idx, src)) in &submodules {
+            let dest = match destinations.get(ident) {
+                Some(dest) => dest,
+                None => {
+                    info!(
+                        "Keeping declarations from {} in {}",
+                        src,
+                        self.path.display()
+                    );
+                    continue;
+                }
+            };
+            let header = &mut headers[src];
+            let mut m = match self.file.items[*idx].clone() {
+                Item::Mod(m) => m,
+                _ => unreachable!(),
+            };
+            let (_, content) = m.content.take().unwrap();
+
+            let mut uses = vec![];
+            let mut kept = vec![];
+            for item in content {
+                match item {
+                    Item::Use(ref u) => {
+                        for (name, import) in split_use(u, &destinations) {
+                            header.uses.entry(name).or_insert(import);
+                        }
+                        uses.push(item);
+                    }
+                    // Function bodies and non-`extern` statics belong to this
+                    // module, so they stay here
+                    Item::Fn(_) | Item::Static(_) => kept.push(item),
+                    Item::ForeignMod(fm) => {
+                        for fi in fm.items {
+                            let key = foreign_item_key(&fi).unwrap();
+                            header.foreign_items.entry(key).or_insert(fi);
+                        }
+                    }
+                    item => {
+                        let key = item_key(&item).unwrap();
+                        header.items.entry(key).or_insert(item);
+                    }
+                }
+            }
+
+            let dest_path = path_idents(dest);
+            self.file.items[*idx] = if kept.is_empty() {
+                // Re-export the merged module under the name of the submodule
+                let (name, prefix) = dest_path.split_last().unwrap();
+                let leaf = if *name == m.ident {
+                    UseTree::Name(UseName {
+                        ident: name.clone(),
+                    })
+                } else {
+                    UseTree::Rename(UseRename {
+                        ident: name.clone(),
+                        as_token: Default::default(),
+                        rename: m.ident.clone(),
+                    })
+                };
+                use_item(m.vis.clone(), prefix, leaf)
+            } else {
+                let glob = UseTree::Glob(UseGlob {
+                    star_token: Default::default(),
+                });
+                let vis = Visibility::Public(VisPublic {
+                    pub_token: Default::default(),
+                });
+                uses.push(use_item(vis, &dest_path, glob));
+                uses.extend(kept);
+                m.content = Some((Default::default(), uses));
+                Item::Mod(m)
+            };
+        }
+    }
+
+    fn link_functions(&mut self, definitions: &HashMap<String, Definition>) {
+        link_functions(&mut self.file.items, definitions, &self.crate_path);
+        for item in &mut self.file.items {
+            if let Item::Mod(m) = item {
+                if header_src(m).is_none() {
+                    continue;
+                }
+                if let Some((_, items)) = &mut m.content {
+                    link_functions(items, definitions, &self.crate_path);
+                }
+            }
+        }
+    }
+}
+
+/// The declarations from a single header, merged from all modules that
+/// include it.
+struct Header {
+    /// Name of the module holding the declarations
+    name: String,
+    /// Imports needed by the declarations, keyed by the name they bind
+    uses: IndexMap<String, Item>,
+    items: IndexMap<ItemKey, Item>,
+    foreign_items: IndexMap<ItemKey, ForeignItem>,
+}
+
+impl Header {
+    fn into_items(
+        self,
+        definitions: &HashMap<String, Definition>,
+        crate_path: &[String],
+    ) -> Vec<Item> {
+        let defined = self
+            .items
+            .keys()
+            .chain(self.foreign_items.keys())
+            .map(|(_, name)| name.clone())
+            .collect::<HashSet<_>>();
+
+        let mut items = self
+            .uses
+            .into_iter()
+            .filter(|(name, _)| !defined.contains(name))
+            .map(|(_, import)| import)
+            .collect::<Vec<_>>();
+        items.extend(self.items.into_values());
+        if !self.foreign_items.is_empty() {
+            let foreign_items = self.foreign_items.into_values().collect();
+            items.push(*mk().extern_("C").foreign_items(foreign_items));
+        }
+        link_functions(&mut items, definitions, crate_path);
+        items
+    }
+}
+
+/// Get the header module for `src`, creating it with a name based on `ident`
+/// if this is the first module that includes the header.
+fn header_entry<'a>(
+    headers: &'a mut IndexMap<String, Header>,
+    src: &str,
+    ident: &str,
+) -> &'a mut Header {
+    if !headers.contains_key(src) {
+        // Different headers may share a file name
+        let mut name = ident.to_owned();
+        let mut suffix = 0;
+        while headers.values().any(|h| h.name == name) {
+            suffix += 1;
+            name = format!("{}_{}", ident, suffix);
+        }
+        let header = Header {
+            name,
+            uses: IndexMap::new(),
+            items: IndexMap::new(),
+            foreign_items: IndexMap::new(),
+        };
+        headers.insert(src.to_owned(), header);
+    }
+    &mut headers[src]
+}
+
+/// Can the declarations of header submodule `m` be merged into `header`?
+/// That requires that we understand every item, and that declarations
+/// already merged from other modules are identical to ours.
+fn can_merge(m: &ItemMod, header: Option<&Header>) -> bool {
+    let items = match &m.content {
+        Some((_, items)) => items,
+        None => return false,
+    };
+    items.iter().all(|item| match item {
+        Item::Use(_) | Item::Fn(_) | Item::Static(_) => true,
+        Item::ForeignMod(fm) => {
+            let is_c = fm.abi.name.as_ref().map_or(false, |abi| abi.value() == "C");
+            is_c && fm.items.iter().all(|fi| match foreign_item_key(fi) {
+                Some(key) => match header.and_then(|h| h.foreign_items.get(&key)) {
+                    Some(other) if other != fi => {
+                        warn!("Conflicting declarations of `{}` in {}", key.1, m.ident);
+                        false
+                    }
+                    _ => true,
+                },
+                None => false,
+            })
+        }
+        item => match item_key(item) {
+            Some(key) => match header.and_then(|h| h.items.get(&key)) {
+                Some(other) if other != item => {
+                    warn!("Conflicting definitions of `{}` in {}", key.1, m.ident);
+                    false
+                }
+                _ => true,
+            },
+            None => false,
+        },
+    })
+}
+
+/// Sibling modules that header submodule `m` imports from, or `None` if it
+/// imports items of its parent module, which we can't move along with it.
+fn imported_siblings(m: &ItemMod) -> Option<Vec<String>> {
+    let items = m.content.as_ref().map_or(&[][..], |(_, items)| items);
+    let mut siblings = vec![];
+    for item in items {
+        if let Item::Use(u) = item {
+            for (prefix, _) in use_leaves(&u.tree) {
+                if prefix.first().map_or(false, |s| s == "super") {
+                    siblings.push(prefix.get(1)?.to_string());
+                }
+            }
+        }
+    }
+    Some(siblings)
+}
+
+fn item_key(item: &Item) -> Option<ItemKey> {
+    let (ns, ident) = match item {
+        Item::Struct(i) => (Namespace::Type, &i.ident),
+        Item::Union(i) => (Namespace::Type, &i.ident),
+        Item::Enum(i) => (Namespace::Type, &i.ident),
+        Item::Type(i) => (Namespace::Type, &i.ident),
+        Item::Const(i) => (Namespace::Value, &i.ident),
+        _ => return None,
+    };
+    Some((ns, ident.to_string()))
+}
+
+fn foreign_item_key(item: &ForeignItem) -> Option<ItemKey> {
+    let (ns, ident) = match item {
+        ForeignItem::Fn(i) => (Namespace::Value, &i.sig.ident),
+        ForeignItem::Static(i) => (Namespace::Value, &i.ident),
+        ForeignItem::Type(i) => (Namespace::Type, &i.ident),
+        _ => return None,
+    };
+    Some((ns, ident.to_string()))
+}
+
+/// Get the header path from a `#[c2rust::header_src = "<path>:<line>"]`
+/// attribute on `m`.
+fn header_src(m: &ItemMod) -> Option<String> {
+    m.attrs.iter().find_map(|attr| {
+        let segments = attr.path.segments.iter().map(|s| s.ident.to_string());
+        if !segments.eq(["c2rust", "header_src"]) {
+            return None;
+        }
+        match attr.parse_meta().ok()? {
+            syn::Meta::NameValue(syn::MetaNameValue {
+                lit: syn::Lit::Str(s),
+                ..
+            }) => {
+                let value = s.value();
+                let src = value.rsplit_once(':').map_or(&*value, |(src, _line)| src);
+                Some(src.to_owned())
+            }
+            _ => None,
+        }
+    })
+}
+
+/// Flatten a use tree into its leaves, along with the path leading to them.
+fn use_leaves(tree: &UseTree) -> Vec<(Vec<Ident>, UseTree)> {
+    fn go(prefix: &mut Vec<Ident>, tree: &UseTree, out: &mut Vec<(Vec<Ident>, UseTree)>) {
+        match tree {
+            UseTree::Path(p) => {
+                prefix.push(p.ident.clone());
+                go(prefix, &p.tree, out);
+                prefix.pop();
+            }
+            UseTree::Group(g) => {
+                for tree in &g.items {
+                    go(prefix, tree, out);
+                }
+            }
+            leaf => out.push((prefix.clone(), leaf.clone())),
+        }
+    }
+    let mut out = vec![];
+    go(&mut vec![], tree, &mut out);
+    out
+}
+
+/// Split a header submodule's import into single imports that work from the
+/// merged header module, keyed by the name they bind.
+fn split_use(u: &ItemUse, destinations: &HashMap<String, Vec<String>>) -> Vec<(String, Item)> {
+    use_leaves(&u.tree)
+        .into_iter()
+        .map(|(mut prefix, leaf)| {
+            // `super::<sibling>::...` becomes an absolute path to the merged
+            // module of the sibling
+            if prefix.first().map_or(false, |s| s == "super") {
+                let dest = &destinations[&prefix[1].to_string()];
+                prefix.splice(..2, path_idents(dest));
+            }
+            let name = match &leaf {
+                UseTree::Name(n) => n.ident.to_string(),
+                UseTree::Rename(r) => r.rename.to_string(),
+                _ => format!(
+                    "{}::*",
+                    prefix
+                        .iter()
+                        .map(Ident::to_string)
+                        .collect::<Vec<_>>()
+                        .join("::")
+                ),
+            };
+            let mut import = use_item(u.vis.clone(), &prefix, leaf);
+            if let Item::Use(ref mut import) = import {
+                import.attrs = u.attrs.clone();
+                import.leading_colon = u.leading_colon;
+            }
+            (name, import)
+        })
+        .collect()
+}
+
+/// A function definition that other modules can link against.
+#[derive(Clone)]
+struct Definition {
+    module: Vec<String>,
+    signature: SignatureKey,
+}
+
+impl Definition {
+    fn matches(&self, sig: &syn::Signature) -> bool {
+        self.signature == SignatureKey::new(sig)
+    }
+}
+
+/// The parameter and return types of a function signature, with the module
+/// paths of the types they name removed: the same C type is named by different
+/// paths in the module defining a function and in the modules declaring it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct SignatureKey {
+    inputs: Vec<String>,
+    output: String,
+    is_variadic: bool,
+}
+
+impl SignatureKey {
+    fn new(sig: &syn::Signature) -> Self {
+        let inputs = sig
+            .inputs
+            .iter()
+            .map(|arg| match arg {
+                syn::FnArg::Typed(arg) => type_key(&arg.ty),
+                syn::FnArg::Receiver(_) => "self".to_owned(),
+            })
+            .collect();
+        let output = match &sig.output {
+            syn::ReturnType::Default => "()".to_owned(),
+            syn::ReturnType::Type(_, ty) => type_key(ty),
+        };
+        SignatureKey {
+            inputs,
+            output,
+            is_variadic: sig.variadic.is_some(),
+        }
+    }
+}
+
+/// Print `ty` without the module paths of the types it names, e.g.,
+/// `*mut crate::foo_h::S` becomes `*mut S`.
+fn type_key(ty: &syn::Type) -> String {
+    let path_prefix = Regex::new(r"(?:\b(?:r#)?[A-Za-z_][A-Za-z0-9_]*\s*)?::\s*").unwrap();
+    let ty = ty.to_token_stream().to_string();
+    path_prefix.replace_all(&ty, "").into_owned()
+}
+
+/// Collect the `#[no_mangle]` functions defined in `modules`.
+fn exported_functions(modules: &[Module]) -> HashMap<String, Definition> {
+    let mut definitions = HashMap::new();
+    for module in modules {
+        for item in &module.file.items {
+            let f = match item {
+                Item::Fn(f) => f,
+                _ => continue,
+            };
+            let is_exported = matches!(f.vis, Visibility::Public(_))
+                && f.attrs.iter().any(|attr| attr.path.is_ident("no_mangle"));
+            if is_exported {
+                definitions
+                    .entry(f.sig.ident.to_string())
+                    .or_insert_with(|| Definition {
+                        module: module.crate_path.clone(),
+                        signature: SignatureKey::new(&f.sig),
+                    });
+            }
+        }
+    }
+    definitions
+}
+
+/// Replace `extern` declarations among `items` of functions defined in other
+/// modules of the crate with imports of the definitions.
+fn link_functions(
+    items: &mut Vec<Item>,
+    definitions: &HashMap<String, Definition>,
+    module: &[String],
+) {
+    let mut imports = vec![];
+    for item in items.iter_mut() {
+        let fm = match item {
+            Item::ForeignMod(fm) => fm,
+            _ => continue,
+        };
+        fm.items.retain(|fi| {
+            let f = match fi {
+                ForeignItem::Fn(f) => f,
+                _ => return true,
+            };
+            let def = match definitions.get(&f.sig.ident.to_string()) {
+                Some(def) if def.module != module => def,
+                _ => return true,
+            };
+            if !def.matches(&f.sig) {
+                warn!(
+                    "Declaration of `{}` does not match its definition in {}, keeping it",
+                    f.sig.ident,
+                    def.module.join("::"),
+                );
+                return true;
+            }
+            let leaf = UseTree::Name(UseName {
+                ident: f.sig.ident.clone(),
+            });
+            imports.push(use_item(f.vis.clone(), &path_idents(&def.module), leaf));
+            false
+        });
+    }
+    items.retain(|item| !matches!(item, Item::ForeignMod(fm) if fm.items.is_empty()));
+    items.extend(imports);
+}
+
+/// Absolute path in the crate of the module at `path`, following the module
+/// tree that `build_files` emits.
+fn crate_path(build_dir: &Path, path: &Path) -> Vec<String> {
+    let mut crate_path = vec!["crate".to_owned()];
+    match path.strip_prefix(build_dir) {
+        Ok(relpath) => crate_path.extend(
+            relpath
+                .iter()
+                .map(|c| get_module_name(Path::new(c), true, false, false).unwrap()),
+        ),
+        Err(_) => crate_path.push(get_module_name(path, true, false, false).unwrap()),
+    }
+    crate_path
+}
+
+fn path_idents(path: &[String]) -> Vec<Ident> {
+    path.iter()
+        .map(|s| match s.strip_prefix("r#") {
+            Some(raw) => Ident::new_raw(raw, Span::call_site()),
+            None => Ident::new(s, Span::call_site()),
+        })
+        .collect()
+}
+
+/// Build `<vis> use <prefix>::<leaf>;`.
+fn use_item(vis: Visibility, prefix: &[Ident], leaf: UseTree) -> Item {
+    let tree = prefix.iter().rev().fold(leaf, |tree, ident| {
+        UseTree::Path(UsePath {
+            ident: ident.clone(),
+            colon2_token: Default::default(),
+            tree: Box::new(tree),
+        })
+    });
+    Item::Use(ItemUse {
+        attrs: vec![],
+        vis,
+        use_token: Default::default(),
+        leading_colon: None,
+        tree,
+        semi_token: Default::default(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn module(name: &str, source: &str) -> Module {
+        Module {
+            path: PathBuf::from(format!("src/{}.rs", name)),
+            crate_path: vec!["crate".to_owned(), name.to_owned()],
+            file: syn::parse_file(source).unwrap(),
+        }
+    }
+
+    fn item(source: &str) -> Item {
+        syn::parse_str(source).unwrap()
+    }
+
+    const HEADERS_PATH: &[&str] = &["crate", "c2rust_headers"];
+
+    fn merge(modules: &mut [Module]) -> IndexMap<String, Header> {
+        let headers_path = HEADERS_PATH
+            .iter()
+            .map(|s| s.to_string())
+            .collect::<Vec<_>>();
+        let mut headers = IndexMap::new();
+        for module in modules {
+            module.merge_headers(&mut headers, &headers_path);
+        }
+        headers
+    }
+
+    #[test]
+    fn merge_identical_headers() {
+        let source = r#"
+            #[c2rust::header_src = "/usr/include/foo.h:1"]
+            pub mod foo_h {
+                #[repr(C)]
+                pub struct S {
+                    pub x: libc::c_int,
+                }
+                extern "C" {
+                    pub fn f(s: *mut S) -> libc::c_int;
+                }
+            }
+        "#;
+        let mut modules = [module("a", source), module("b", source)];
+        let headers = merge(&mut modules);
+
+        assert_eq!(headers.len(), 1);
+        let header = &headers["/usr/include/foo.h"];
+        assert_eq!(header.name, "foo_h");
+        assert!(header
+            .items
+            .contains_key(&(Namespace::Type, "S".to_owned())));
+        assert!(header
+            .foreign_items
+            .contains_key(&(Namespace::Value, "f".to_owned())));
+        for module in &modules {
+            assert_eq!(
+                module.file.items,
+                [item("pub use crate::c2rust_headers::foo_h;")]
+            );
+        }
+    }
+
+    #[test]
+    fn keep_conflicting_headers() {
+        let a = r#"
+            #[c2rust::header_src = "/usr/include/foo.h:1"]
+            pub mod foo_h {
+                #[repr(C)]
+                pub struct S {
+                    pub x: libc::c_int,
+                }
+            }
+        "#;
+        let b = r#"
+            #[c2rust::header_src = "/usr/include/foo.h:1"]
+            pub mod foo_h {
+                #[repr(C)]
+                pub struct S {
+                    pub x: libc::c_long,
+                }
+            }
+        "#;
+        let mut modules = [module("a", a), module("b", b)];
+        let headers = merge(&mut modules);
+
+        assert_eq!(headers.len(), 1);
+        assert_eq!(
+            modules[0].file.items,
+            [item("pub use crate::c2rust_headers::foo_h;")]
+        );
+        // The second definition of `S` differs, so it stays where it was
+        assert_eq!(modules[1].file, syn::parse_file(b).unwrap());
+    }
+
+    #[test]
+    fn link_matching_definitions() {
+        let a = r#"
+            #[no_mangle]
+            pub unsafe extern "C" fn f(s: *mut crate::a::foo_h::S, n: libc::c_int) -> libc::c_int {
+                n
+            }
+        "#;
+        let b = r#"
+            extern "C" {
+                pub fn f(s: *mut foo_h::S, n: libc::c_int) -> libc::c_int;
+            }
+        "#;
+        let mut modules = [module("a", a), module("b", b)];
+        let definitions = exported_functions(&modules);
+        modules[1].link_functions(&definitions);
+
+        assert_eq!(modules[1].file.items, [item("pub use crate::a::f;")]);
+    }
+
+    #[test]
+    fn keep_mismatched_declarations() {
+        let a = r#"
+            #[no_mangle]
+            pub unsafe extern "C" fn f(x: libc::c_int) -> libc::c_int {
+                x
+            }
+            #[no_mangle]
+            pub unsafe extern "C" fn g(x: libc::c_int) {}
+        "#;
+        // Same arities, but different parameter and return types
+        let b = r#"
+            extern "C" {
+                pub fn f(x: libc::c_int) -> libc::c_long;
+                pub fn g(x: *mut libc::c_char);
+            }
+        "#;
+        let mut modules = [module("a", a), module("b", b)];
+        let definitions = exported_functions(&modules);
+        modules[1].link_functions(&definitions);
+
+        assert_eq!(modules[1].file, syn::parse_file(b).unwrap());
+    }
+
+    #[test]
+    fn signature_key_ignores_paths() {
+        let key = |source: &str| match item(source) {
+            Item::Fn(f) => SignatureKey::new(&f.sig),
+            _ => unreachable!(),
+        };
+        assert_eq!(
+            key("fn f(s: *mut crate::foo_h::S, ...) -> ::libc::c_int {}"),
+            key("fn f(t: *mut S, ...) -> libc::c_int {}"),
+        );
+        assert_ne!(key("fn f(s: *mut S) {}"), key("fn f(s: *const S) {}"));
+    }
+}
@@ -0,0 +1,288 @@
+//! Machine-readable reports of what the transpiler did.
+//!
+//! When `--report` is given, we record one entry per translation unit and one
+//! entry per declaration we attempted to translate, and write them out as JSON
+//! or SARIF once the whole run is done.
+
+use failure::Error;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::json;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+use strum_macros::{Display, EnumString};
+
+use crate::c_ast::{CDeclKind, DisplaySrcSpan};
+use crate::diagnostics::TranslationError;
+
+/// Version of the JSON report schema. Bump this when the layout changes.
+const REPORT_SCHEMA_VERSION: u32 = 2;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Display, EnumString)]
+#[strum(serialize_all = "kebab_case")]
+pub enum ReportFormat {
+    Json,
+    Sarif,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum UnitStatus {
+    Translated,
+    Skipped,
+    Failed,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DeclStatus {
+    Translated,
+    /// Translation failed, so the declaration was replaced according to the
+    /// configured `ReplaceMode`.
+    Replaced,
+    /// The declaration filter excluded the definition, so it was replaced by
+    /// a declaration.
+    Excluded,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReportLocation {
+    pub file: Option<PathBuf>,
+    pub line: u64,
+    pub column: u64,
+}
+
+impl From<&DisplaySrcSpan> for ReportLocation {
+    fn from(loc: &DisplaySrcSpan) -> Self {
+        let span = loc.span();
+        Self {
+            file: loc.file().map(Path::to_path_buf),
+            line: span.begin_line,
+            column: span.begin_column,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeclReport {
+    pub name: Option<String>,
+    pub kind: String,
+    pub status: DeclStatus,
+    pub location: Option<ReportLocation>,
+    /// Error chain, outermost error first
+    pub errors: Vec<String>,
+    /// Nightly features the translation still needs in `--stable` mode
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unstable_features: Vec<String>,
+}
+
+impl DeclReport {
+    pub fn new(kind: &CDeclKind, loc: Option<DisplaySrcSpan>) -> Self {
+        Self {
+            name: kind.get_name().cloned(),
+            kind: decl_kind_name(kind).to_owned(),
+            status: DeclStatus::Translated,
+            location: loc.as_ref().map(ReportLocation::from),
+            errors: vec![],
+            unstable_features: vec![],
+        }
+    }
+
+    pub fn with_error(mut self, status: DeclStatus, err: &TranslationError) -> Self {
+        self.status = status;
+        self.errors = err.chain();
+        // Prefer the innermost location the error was raised at
+        if let Some(loc) = err.locations().first() {
+            self.location = Some(loc.into());
+        }
+        self
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UnitReport {
+    pub input: PathBuf,
+    pub output: Option<PathBuf>,
+    pub status: UnitStatus,
+    pub message: Option<String>,
+    pub declarations: Vec<DeclReport>,
+}
+
+impl UnitReport {
+    pub fn new(input: PathBuf) -> Self {
+        Self {
+            input,
+            output: None,
+            status: UnitStatus::Translated,
+            message: None,
+            declarations: vec![],
+        }
+    }
+
+    pub fn set_status(&mut self, status: UnitStatus, message: impl Into<String>) {
+        self.status = status;
+        self.message = Some(message.into());
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    pub version: u32,
+    pub translation_units: Vec<UnitReport>,
+}
+
+impl Default for Report {
+    fn default() -> Self {
+        Self {
+            version: REPORT_SCHEMA_VERSION,
+            translation_units: vec![],
+        }
+    }
+}
+
+impl Report {
+    pub fn write(&self, path: &Path, format: ReportFormat) -> Result<(), Error> {
+        let file = BufWriter::new(File::create(path)?);
+        match format {
+            ReportFormat::Json => serde_json::to_writer_pretty(file, self)?,
+            ReportFormat::Sarif => serde_json::to_writer_pretty(file, &self.to_sarif())?,
+        }
+        Ok(())
+    }
+
+    /// Convert the report into a SARIF 2.1.0 log. Only problems are reported
+    /// as results; successfully translated declarations are omitted.
+    fn to_sarif(&self) -> serde_json::Value {
+        let mut results = vec![];
+        for unit in &self.translation_units {
+            let unit_rule = match unit.status {
+                UnitStatus::Translated => None,
+                UnitStatus::Skipped => Some(("unit-skipped", "warning")),
+                UnitStatus::Failed => Some(("unit-failed", "error")),
+            };
+            if let Some((rule, level)) = unit_rule {
+                results.push(json!({
+                    "ruleId": rule,
+                    "level": level,
+                    "message": { "text": unit.message.as_deref().unwrap_or("") },
+                    "locations": [{
+                        "physicalLocation": {
+                            "artifactLocation": { "uri": path_uri(&unit.input) },
+                        },
+                    }],
+                }));
+            }
+
+            for decl in &unit.declarations {
+                let name = decl.name.as_deref().unwrap_or("<anonymous>");
+                let mut problems = vec![];
+                match decl.status {
+                    DeclStatus::Translated | DeclStatus::Excluded => {}
+                    DeclStatus::Replaced | DeclStatus::Failed => {
+                        let (rule, level) = if decl.status == DeclStatus::Replaced {
+                            ("decl-replaced", "warning")
+                        } else {
+                            ("decl-failed", "error")
+                        };
+                        let text = format!(
+                            "Could not translate {} {}: {}",
+                            decl.kind,
+                            name,
+                            decl.errors.join(": ")
+                        );
+                        problems.push((rule, level, text));
+                    }
+                }
+                if !decl.unstable_features.is_empty() {
+                    let text = format!(
+                        "{} {} needs nightly features: {}",
+                        decl.kind,
+                        name,
+                        decl.unstable_features.join(", ")
+                    );
+                    problems.push(("decl-unstable", "warning", text));
+                }
+                if problems.is_empty() {
+                    continue;
+                }
+                let locations = match decl.location {
+                    Some(ReportLocation {
+                        file: Some(ref file),
+                        line,
+                        column,
+                    }) => json!([{
+                        "physicalLocation": {
+                            "artifactLocation": { "uri": path_uri(file) },
+                            "region": { "startLine": line, "startColumn": column },
+                        },
+                    }]),
+                    _ => json!([{
+                        "physicalLocation": {
+                            "artifactLocation": { "uri": path_uri(&unit.input) },
+                        },
+                    }]),
+                };
+                for (rule, level, text) in problems {
+                    results.push(json!({
+                        "ruleId": rule,
+                        "level": level,
+                        "message": { "text": text },
+                        "locations": locations,
+                    }));
+                }
+            }
+        }
+
+        json!({
+            "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+            "version": "2.1.0",
+            "runs": [{
+                "tool": {
+                    "driver": {
+                        "name": "c2rust-transpile",
+                        "version": env!("CARGO_PKG_VERSION"),
+                        "informationUri": "https://c2rust.com/",
+                        "rules": [
+                            sarif_rule("unit-skipped", "Translation unit was skipped"),
+                            sarif_rule("unit-failed", "Translation unit could not be translated"),
+                            sarif_rule("decl-replaced", "Declaration was replaced because it could not be translated"),
+                            sarif_rule("decl-failed", "Declaration could not be translated"),
+                            sarif_rule("decl-unstable", "Declaration needs a nightly feature with no stable equivalent"),
+                        ],
+                    },
+                },
+                "results": results,
+            }],
+        })
+    }
+}
+
+fn sarif_rule(id: &str, description: &str) -> serde_json::Value {
+    json!({
+        "id": id,
+        "shortDescription": { "text": description },
+    })
+}
+
+fn path_uri(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+fn decl_kind_name(kind: &CDeclKind) -> &'static str {
+    use CDeclKind::*;
+    match kind {
+        Function { .. } => "function",
+        Variable { .. } => "variable",
+        Enum { .. } => "enum",
+        EnumConstant { .. } => "enum-constant",
+        Typedef { .. } => "typedef",
+        Struct { .. } => "struct",
+        Union { .. } => "union",
+        Field { .. } => "field",
+        MacroObject { .. } => "macro-object",
+        MacroFunction { .. } => "macro-function",
+        NonCanonicalDecl { .. } => "non-canonical-decl",
+        StaticAssert { .. } => "static-assert",
+    }
+}
@@ -6,12 +6,16 @@ pub mod traverse;
 pub use c2rust_ast_printer::pprust::BytePos;
 use proc_macro2::Span;
 
-use std::sync::atomic::{AtomicU32, Ordering};
+use std::cell::Cell;
 
-static SPAN_LIMIT: AtomicU32 = AtomicU32::new(0);
+thread_local! {
+    // proc_macro2's fallback source map is thread-local, so the limit we have
+    // expanded it to must be tracked per thread as well.
+    static SPAN_LIMIT: Cell<u32> = Cell::new(0);
+}
 
 fn raise_span_limit(_new_limit: u32) {
-    let limit = SPAN_LIMIT.load(Ordering::Relaxed);
+    let limit = SPAN_LIMIT.with(Cell::get);
     let new_limit = 0x2000000;
     if new_limit >= limit {
         let delta = new_limit - limit;
@@ -19,7 +23,7 @@ fn raise_span_limit(_new_limit: u32) {
         use std::str::FromStr;
         /* used only for its side-effect of expanding the source map */
         let _ = proc_macro2::TokenStream::from_str(&s);
-        SPAN_LIMIT.store(new_limit, Ordering::Relaxed);
+        SPAN_LIMIT.with(|limit| limit.set(new_limit));
     }
 }
 
@@ -14,7 +14,124 @@ pub struct ConvertAtomicArgs<'a> {
     pub weak_id: Option<CExprId>,
 }
 
+/// The `core::sync::atomic` type that an atomic builtin accesses its object through in `--stable`
+/// mode, where the `core::intrinsics` atomics aren't available.
+pub(crate) struct StableAtomic {
+    /// `AtomicI32`, `AtomicPtr<T>`, etc.
+    atomic_ty: Box<Type>,
+    /// The type of the atomic's values, like `i32` or `*mut T`
+    value_ty: Box<Type>,
+    /// The translated type of the object, which may be an alias like `libc::c_long`
+    object_ty: Box<Type>,
+    /// Whether the object is a pointer, which `AtomicPtr` can't do arithmetic on
+    pub(crate) is_ptr: bool,
+}
+
+impl StableAtomic {
+    /// Emit `(*(ptr as *mut T as *const AtomicT)).method(vals.., orders..)`, casting `vals` to the
+    /// atomic's value type.
+    #[allow(clippy::vec_box)]
+    pub(crate) fn method_call(
+        &self,
+        ptr: Box<Expr>,
+        method: &str,
+        vals: Vec<Box<Expr>>,
+        orders: &[Ordering],
+    ) -> Box<Expr> {
+        // `ptr` may be a reference, which can only be cast to a pointer to its own type
+        let ptr = mk().cast_expr(ptr, mk().mutbl().ptr_ty(self.object_ty.clone()));
+        let atomic_ptr = mk().cast_expr(ptr, mk().ptr_ty(self.atomic_ty.clone()));
+        let atomic = mk().paren_expr(mk().unary_expr(UnOp::Deref(Default::default()), atomic_ptr));
+        let args = vals
+            .into_iter()
+            .map(|val| mk().cast_expr(val, self.value_ty.clone()))
+            .chain(orders.iter().map(|&order| ordering_expr(order)))
+            .collect();
+        mk().method_call_expr(atomic, method, args)
+    }
+
+    /// Cast a value of the atomic back to the object's type.
+    pub(crate) fn to_object(&self, val: Box<Expr>) -> Box<Expr> {
+        mk().cast_expr(val, self.object_ty.clone())
+    }
+}
+
+/// Emit `core::sync::atomic::Ordering::SeqCst`, etc.
+fn ordering_expr(order: Ordering) -> Box<Expr> {
+    use Ordering::*;
+    let name = match order {
+        SeqCst => "SeqCst",
+        AcqRel => "AcqRel",
+        Acquire => "Acquire",
+        Release => "Release",
+        Relaxed => "Relaxed",
+        _ => unreachable!(
+            "new variants added to `{}`",
+            std::any::type_name::<Ordering>()
+        ),
+    };
+    mk().abs_path_expr(vec!["core", "sync", "atomic", "Ordering", name])
+}
+
 impl<'c> Translation<'c> {
+    /// In `--stable` mode, the [`StableAtomic`] to access the object `ptr_id` points to through.
+    /// This is `None` outside of `--stable` mode and for objects that aren't integers or
+    /// pointers, whose atomic builtins still need `core::intrinsics`.
+    pub(crate) fn stable_atomic(&self, ptr_id: CExprId) -> TranslationResult<Option<StableAtomic>> {
+        if !self.tcfg.stable {
+            return Ok(None);
+        }
+        let ptr_ty = match self.ast_context[ptr_id].kind.get_type() {
+            Some(ty) => ty,
+            None => return Ok(None),
+        };
+        let object_ty = match self.ast_context.resolve_type(ptr_ty).kind {
+            CTypeKind::Pointer(pointee) => pointee.ctype,
+            _ => return Ok(None),
+        };
+        // `long` is as wide as a pointer everywhere but on Windows
+        let long_is_isize = !self.ast_context.target.contains("windows");
+        let int = |atomic: &str, prim: &str| {
+            let atomic_ty = mk().path_ty(mk().abs_path(vec!["core", "sync", "atomic", atomic]));
+            (atomic_ty, mk().path_ty(vec![prim]), false)
+        };
+        use CTypeKind::*;
+        let (atomic_ty, value_ty, is_ptr) = match self.ast_context.resolve_type(object_ty).kind {
+            Char | SChar => int("AtomicI8", "i8"),
+            UChar => int("AtomicU8", "u8"),
+            Short => int("AtomicI16", "i16"),
+            UShort => int("AtomicU16", "u16"),
+            Int => int("AtomicI32", "i32"),
+            UInt => int("AtomicU32", "u32"),
+            Long if long_is_isize => int("AtomicIsize", "isize"),
+            ULong if long_is_isize => int("AtomicUsize", "usize"),
+            Long => int("AtomicI32", "i32"),
+            ULong => int("AtomicU32", "u32"),
+            LongLong => int("AtomicI64", "i64"),
+            ULongLong => int("AtomicU64", "u64"),
+            Pointer(pointee) if !self.ast_context.is_function_pointer(object_ty) => {
+                let pointee_ty = self.convert_type(pointee.ctype)?;
+                let atomic_ty = mk().path_ty(mk().abs_path(vec![
+                    mk().path_segment("core"),
+                    mk().path_segment("sync"),
+                    mk().path_segment("atomic"),
+                    mk().path_segment_with_args(
+                        "AtomicPtr",
+                        mk().angle_bracketed_args(vec![pointee_ty.clone()]),
+                    ),
+                ]));
+                (atomic_ty, mk().mutbl().ptr_ty(pointee_ty), true)
+            }
+            _ => return Ok(None),
+        };
+        Ok(Some(StableAtomic {
+            atomic_ty,
+            value_ty,
+            object_ty: self.convert_type(object_ty)?,
+            is_ptr,
+        }))
+    }
+
     fn convert_constant_bool(&self, expr: CExprId) -> Option<bool> {
         let val = self.ast_context.resolve_expr(expr).1;
         match val {
@@ -24,22 +141,116 @@ impl<'c> Translation<'c> {
     }
 
     fn convert_memordering(&self, expr: CExprId) -> Option<Ordering> {
-        let memorder = &self.ast_context[expr];
-        let i = match memorder.kind {
-            CExprKind::Literal(_, CLiteral::Integer(i, _)) => Some(i),
-            _ => None,
-        }?;
-        use Ordering::*;
-        let ordering = match i {
-            0 => Relaxed,
-            1 => Acquire,
-            2 => Acquire,
-            3 => Release,
-            4 => AcqRel,
-            5 => SeqCst,
+        let i = match *self.ast_context.resolve_expr(expr).1 {
+            CExprKind::Literal(_, CLiteral::Integer(i, _)) => i,
+            // `memory_order_*` from `<stdatomic.h>` are enum constants
+            CExprKind::DeclRef(_, decl_id, _) => match self.ast_context[decl_id].kind {
+                CDeclKind::EnumConstant {
+                    value: ConstIntExpr::U(i),
+                    ..
+                } => i,
+                CDeclKind::EnumConstant {
+                    value: ConstIntExpr::I(i),
+                    ..
+                } => i as u64,
+                _ => return None,
+            },
             _ => return None,
         };
-        Some(ordering)
+        Some(memordering_from_c(i))
+    }
+
+    /// Convert a memory ordering argument. Orderings that aren't known at
+    /// translation time are evaluated once into a local so that we can
+    /// select the intrinsic to call at runtime.
+    fn convert_memordering_arg(
+        &self,
+        ctx: ExprContext,
+        expr: CExprId,
+    ) -> TranslationResult<WithStmts<MemOrdering>> {
+        if let Some(order) = self.convert_memordering(expr) {
+            return Ok(WithStmts::new_val(MemOrdering::Static(order)));
+        }
+
+        self.convert_expr(ctx.used(), expr)?.and_then(|order| {
+            let order_name = self.renamer.borrow_mut().fresh();
+            let order_let = mk().local_stmt(Box::new(mk().local(
+                mk().ident_pat(&order_name),
+                None,
+                Some(order),
+            )));
+            Ok(WithStmts::new(
+                vec![order_let],
+                MemOrdering::Dynamic(mk().ident_expr(order_name)),
+            ))
+        })
+    }
+
+    /// Build the intrinsic call for `order`, normalized by `normalize` to an
+    /// ordering the intrinsic supports. For dynamic orderings this emits a
+    /// `match` on the C ordering value with one call per ordering.
+    fn dispatch_memordering(
+        &self,
+        order: &MemOrdering,
+        normalize: fn(Ordering) -> Ordering,
+        mk_call: &dyn Fn(Ordering) -> TranslationResult<Box<Expr>>,
+    ) -> TranslationResult<Box<Expr>> {
+        let order = match order {
+            MemOrdering::Static(order) => return mk_call(normalize(*order)),
+            MemOrdering::Dynamic(order) => order.clone(),
+        };
+
+        use Ordering::*;
+        let lit_pat = |i: u128| mk().lit_pat(mk().lit_expr(mk().int_unsuffixed_lit(i)));
+        let arms = vec![
+            (lit_pat(0), Relaxed),
+            (mk().or_pat(vec![lit_pat(1), lit_pat(2)]), Acquire),
+            (lit_pat(3), Release),
+            (lit_pat(4), AcqRel),
+            // Like GCC, treat invalid orderings as `__ATOMIC_SEQ_CST`
+            (mk().wild_pat(), SeqCst),
+        ]
+        .into_iter()
+        .map(|(pat, ordering)| Ok(mk().arm(pat, None, mk_call(normalize(ordering))?)))
+        .collect::<TranslationResult<Vec<_>>>()?;
+        Ok(mk().match_expr(order, arms))
+    }
+
+    /// Build a call to the `atomic_cxchg` intrinsic for the given orderings, or to
+    /// `compare_exchange` on `stable`. See [`cxchg_result`] for how to use the result.
+    fn atomic_cxchg_call(
+        &self,
+        weak: bool,
+        order: &MemOrdering,
+        order_fail: &MemOrdering,
+        args: &[Box<Expr>],
+        stable: Option<&StableAtomic>,
+    ) -> TranslationResult<Box<Expr>> {
+        self.dispatch_memordering(order, |o| o, &|order| {
+            self.dispatch_memordering(order_fail, cxchg_failure_ordering, &|order_fail| {
+                if let Some(atomic) = stable {
+                    let method = if weak {
+                        "compare_exchange_weak"
+                    } else {
+                        "compare_exchange"
+                    };
+                    return Ok(atomic.method_call(
+                        args[0].clone(),
+                        method,
+                        args[1..].to_vec(),
+                        &[order, order_fail],
+                    ));
+                }
+                let weak = if weak { "weak" } else { "" };
+                let intrinsic_name = format!(
+                    "atomic_cxchg{weak}_{}_{}",
+                    order_name(order),
+                    order_name(order_fail),
+                );
+                let atomic_cxchg = mk().abs_path_expr(vec!["core", "intrinsics", &intrinsic_name]);
+                Ok(mk().call_expr(atomic_cxchg, args.to_vec()))
+            })
+        })
     }
 
     pub fn convert_atomic(
@@ -58,90 +269,99 @@ impl<'c> Translation<'c> {
         } = args;
 
         let ptr = self.convert_expr(ctx.used(), ptr_id)?;
-        let order = self.convert_memordering(order_id);
+        let order = self.convert_memordering_arg(ctx, order_id)?;
         let val1 = val1_id
             .map(|x| self.convert_expr(ctx.used(), x))
             .transpose()?;
-        let order_fail = order_fail_id.and_then(|x| self.convert_memordering(x));
         let val2 = val2_id
             .map(|x| self.convert_expr(ctx.used(), x))
             .transpose()?;
-        let weak = weak_id.and_then(|x| self.convert_constant_bool(x));
-
-        fn static_order<T>(order: Option<T>) -> T {
-            order.unwrap_or_else(|| {
-                // We have to select which intrinsic to use at runtime
-                unimplemented!("Dynamic memory consistency arguments are not yet supported");
-            })
-        }
-
-        fn order_name(order: Ordering) -> &'static str {
-            use Ordering::*;
-            match order {
-                SeqCst => "seqcst",
-                AcqRel => "acqrel",
-                Acquire => "acquire",
-                Release => "release",
-                Relaxed => "relaxed",
-                _ => unreachable!(
-                    "new variants added to `{}`",
-                    std::any::type_name::<Ordering>()
-                ),
-            }
+        // A weak compare-exchange may always be implemented as a strong one,
+        // so fall back to that if we can't tell which one was requested
+        let weak = weak_id
+            .and_then(|x| self.convert_constant_bool(x))
+            .unwrap_or(false);
+        let stable = self.stable_atomic(ptr_id)?;
+
+        fn intrinsic_path(name: &str) -> Box<Expr> {
+            mk().abs_path_expr(vec!["core", "intrinsics", name])
         }
 
         match name {
             "__atomic_load" | "__atomic_load_n" => ptr.and_then(|ptr| {
-                let intrinsic_name = format!("atomic_load_{}", order_name(static_order(order)));
-
-                self.use_feature("core_intrinsics");
-
-                let atomic_load = mk().abs_path_expr(vec!["core", "intrinsics", &intrinsic_name]);
-                let call = mk().call_expr(atomic_load, vec![ptr]);
-                if name == "__atomic_load" {
-                    let ret = val1.expect("__atomic_load should have a ret argument");
-                    ret.and_then(|ret| {
-                        let assignment = mk().assign_expr(
-                            mk().unary_expr(UnOp::Deref(Default::default()), ret),
-                            call,
-                        );
+                order.and_then(|order| {
+                    if stable.is_none() {
+                        self.use_feature("core_intrinsics");
+                    }
+
+                    let call = self.dispatch_memordering(&order, load_ordering, &|order| {
+                        if let Some(atomic) = &stable {
+                            let load = atomic.method_call(ptr.clone(), "load", vec![], &[order]);
+                            return Ok(atomic.to_object(load));
+                        }
+                        let intrinsic_name = format!("atomic_load_{}", order_name(order));
+                        Ok(mk().call_expr(intrinsic_path(&intrinsic_name), vec![ptr.clone()]))
+                    })?;
+                    if name == "__atomic_load" {
+                        let ret = val1.expect("__atomic_load should have a ret argument");
+                        ret.and_then(|ret| {
+                            let assignment = mk().assign_expr(
+                                mk().unary_expr(UnOp::Deref(Default::default()), ret),
+                                call,
+                            );
+                            self.convert_side_effects_expr(
+                                ctx,
+                                WithStmts::new_val(assignment),
+                                "Builtin is not supposed to be used",
+                            )
+                        })
+                    } else {
                         self.convert_side_effects_expr(
                             ctx,
-                            WithStmts::new_val(assignment),
+                            WithStmts::new_val(call),
                             "Builtin is not supposed to be used",
                         )
-                    })
-                } else {
-                    self.convert_side_effects_expr(
-                        ctx,
-                        WithStmts::new_val(call),
-                        "Builtin is not supposed to be used",
-                    )
-                }
+                    }
+                })
             }),
 
             "__atomic_store" | "__atomic_store_n" => {
                 let val = val1.expect("__atomic_store must have a val argument");
                 ptr.and_then(|ptr| {
-                    val.and_then(|val| {
-                        let intrinsic_name =
-                            format!("atomic_store_{}", order_name(static_order(order)));
-
-                        self.use_feature("core_intrinsics");
+                    order.and_then(|order| {
+                        val.and_then(|val| {
+                            if stable.is_none() {
+                                self.use_feature("core_intrinsics");
+                            }
 
-                        let atomic_store =
-                            mk().abs_path_expr(vec!["core", "intrinsics", &intrinsic_name]);
-                        let val = if name == "__atomic_store" {
-                            mk().unary_expr(UnOp::Deref(Default::default()), val)
-                        } else {
-                            val
-                        };
-                        let call = mk().call_expr(atomic_store, vec![ptr, val]);
-                        self.convert_side_effects_expr(
-                            ctx,
-                            WithStmts::new_val(call),
-                            "Builtin is not supposed to be used",
-                        )
+                            let val = if name == "__atomic_store" {
+                                mk().unary_expr(UnOp::Deref(Default::default()), val)
+                            } else {
+                                val
+                            };
+                            let call =
+                                self.dispatch_memordering(&order, store_ordering, &|order| {
+                                    if let Some(atomic) = &stable {
+                                        return Ok(atomic.method_call(
+                                            ptr.clone(),
+                                            "store",
+                                            vec![val.clone()],
+                                            &[order],
+                                        ));
+                                    }
+                                    let intrinsic_name =
+                                        format!("atomic_store_{}", order_name(order));
+                                    Ok(mk().call_expr(
+                                        intrinsic_path(&intrinsic_name),
+                                        vec![ptr.clone(), val.clone()],
+                                    ))
+                                })?;
+                            self.convert_side_effects_expr(
+                                ctx,
+                                WithStmts::new_val(call),
+                                "Builtin is not supposed to be used",
+                            )
+                        })
                     })
                 })
             }
@@ -149,44 +369,58 @@ impl<'c> Translation<'c> {
             "__atomic_exchange" | "__atomic_exchange_n" => {
                 let val = val1.expect("__atomic_store must have a val argument");
                 ptr.and_then(|ptr| {
-                    val.and_then(|val| {
-                        let intrinsic_name =
-                            format!("atomic_xchg_{}", order_name(static_order(order)));
-
-                        self.use_feature("core_intrinsics");
+                    order.and_then(|order| {
+                        val.and_then(|val| {
+                            if stable.is_none() {
+                                self.use_feature("core_intrinsics");
+                            }
 
-                        let fn_path =
-                            mk().abs_path_expr(vec!["core", "intrinsics", &intrinsic_name]);
-                        let val = if name == "__atomic_exchange" {
-                            mk().unary_expr(UnOp::Deref(Default::default()), val)
-                        } else {
-                            val
-                        };
-                        let call = mk().call_expr(fn_path, vec![ptr, val]);
-                        if name == "__atomic_exchange" {
-                            // LLVM stores the ret pointer in the order_fail slot
-                            order_fail_id
-                                .map(|x| self.convert_expr(ctx.used(), x))
-                                .transpose()?
-                                .expect("__atomic_exchange must have a ret pointer argument")
-                                .and_then(|ret| {
-                                    let assignment = mk().assign_expr(
-                                        mk().unary_expr(UnOp::Deref(Default::default()), ret),
-                                        call,
+                            let val = if name == "__atomic_exchange" {
+                                mk().unary_expr(UnOp::Deref(Default::default()), val)
+                            } else {
+                                val
+                            };
+                            let call = self.dispatch_memordering(&order, |o| o, &|order| {
+                                if let Some(atomic) = &stable {
+                                    let swap = atomic.method_call(
+                                        ptr.clone(),
+                                        "swap",
+                                        vec![val.clone()],
+                                        &[order],
                                     );
-                                    self.convert_side_effects_expr(
-                                        ctx,
-                                        WithStmts::new_val(assignment),
-                                        "Builtin is not supposed to be used",
-                                    )
-                                })
-                        } else {
-                            self.convert_side_effects_expr(
-                                ctx,
-                                WithStmts::new_val(call),
-                                "Builtin is not supposed to be used",
-                            )
-                        }
+                                    return Ok(atomic.to_object(swap));
+                                }
+                                let intrinsic_name = format!("atomic_xchg_{}", order_name(order));
+                                Ok(mk().call_expr(
+                                    intrinsic_path(&intrinsic_name),
+                                    vec![ptr.clone(), val.clone()],
+                                ))
+                            })?;
+                            if name == "__atomic_exchange" {
+                                // LLVM stores the ret pointer in the order_fail slot
+                                order_fail_id
+                                    .map(|x| self.convert_expr(ctx.used(), x))
+                                    .transpose()?
+                                    .expect("__atomic_exchange must have a ret pointer argument")
+                                    .and_then(|ret| {
+                                        let assignment = mk().assign_expr(
+                                            mk().unary_expr(UnOp::Deref(Default::default()), ret),
+                                            call,
+                                        );
+                                        self.convert_side_effects_expr(
+                                            ctx,
+                                            WithStmts::new_val(assignment),
+                                            "Builtin is not supposed to be used",
+                                        )
+                                    })
+                            } else {
+                                self.convert_side_effects_expr(
+                                    ctx,
+                                    WithStmts::new_val(call),
+                                    "Builtin is not supposed to be used",
+                                )
+                            }
+                        })
                     })
                 })
             }
@@ -195,68 +429,76 @@ impl<'c> Translation<'c> {
                 let expected =
                     val1.expect("__atomic_compare_exchange must have a expected argument");
                 let desired = val2.expect("__atomic_compare_exchange must have a desired argument");
-                ptr.and_then(|ptr| {
-                    expected.and_then(|expected| {
-                        desired.and_then(|desired| {
-                            let weak = static_order(weak);
-                            let order = static_order(order);
-                            let order_fail = static_order(order_fail);
-                            use Ordering::*;
-                            let intrinsic_name = match (order, order_fail) {
-                                (_, Release | AcqRel) => None,
-                                (SeqCst, SeqCst | Acquire | Relaxed)
-                                | (AcqRel, Acquire | Relaxed)
-                                | (Release, Relaxed)
-                                | (Acquire | Relaxed, Acquire | Relaxed) => {
-                                    Some((order, order_fail))
-                                }
-                                (SeqCst | AcqRel | Release | Acquire | Relaxed, _) => None,
+                let order_fail_id =
+                    order_fail_id.expect("__atomic_compare_exchange must have a failure ordering");
+                let order_fail = self.convert_memordering_arg(ctx, order_fail_id)?;
+
+                // Orderings known at translation time must form a valid pair
+                if let (Some(order), Some(order_fail)) = (
+                    self.convert_memordering(order_id),
+                    self.convert_memordering(order_fail_id),
+                ) {
+                    use Ordering::*;
+                    let valid = match (order, order_fail) {
+                        (_, Release | AcqRel) => false,
+                        (SeqCst, SeqCst | Acquire | Relaxed)
+                        | (AcqRel, Acquire | Relaxed)
+                        | (Release, Relaxed)
+                        | (Acquire | Relaxed, Acquire | Relaxed) => true,
+                        (SeqCst | AcqRel | Release | Acquire | Relaxed, _) => false,
+
+                        (_, _) => unreachable!("Did we not handle a case above??"),
+                    };
+                    if !valid {
+                        return Err(format_translation_err!(
+                            self.ast_context
+                                .display_loc(&self.ast_context[order_fail_id].loc),
+                            "Invalid failure memory ordering",
+                        ));
+                    }
+                }
 
-                                (_, _) => unreachable!("Did we not handle a case above??"),
-                            }
-                            .map(|(order, order_fail)| {
-                                let weak = if weak { "weak" } else { "" };
-                                let order = order_name(order);
-                                let order_fail = order_name(order_fail);
-                                format!("atomic_cxchg{weak}_{order}_{order_fail}")
+                ptr.and_then(|ptr| {
+                    order.and_then(|order| {
+                        order_fail.and_then(|order_fail| {
+                            expected.and_then(|expected| {
+                                desired.and_then(|desired| {
+                                    if stable.is_none() {
+                                        self.use_feature("core_intrinsics");
+                                    }
+                                    let expected =
+                                        mk().unary_expr(UnOp::Deref(Default::default()), expected);
+                                    let desired = if name == "__atomic_compare_exchange_n" {
+                                        desired
+                                    } else {
+                                        mk().unary_expr(UnOp::Deref(Default::default()), desired)
+                                    };
+
+                                    let args = vec![ptr, expected.clone(), desired];
+                                    let call = self.atomic_cxchg_call(
+                                        weak,
+                                        &order,
+                                        &order_fail,
+                                        &args,
+                                        stable.as_ref(),
+                                    )?;
+                                    let res_name = self.renamer.borrow_mut().fresh();
+                                    let res_let = mk().local_stmt(Box::new(mk().local(
+                                        mk().ident_pat(&res_name),
+                                        None,
+                                        Some(call),
+                                    )));
+                                    let (previous, return_value) =
+                                        cxchg_result(stable.as_ref(), &res_name);
+                                    let assignment =
+                                        mk().semi_stmt(mk().assign_expr(expected, previous));
+                                    self.convert_side_effects_expr(
+                                        ctx,
+                                        WithStmts::new(vec![res_let, assignment], return_value),
+                                        "Builtin is not supposed to be used",
+                                    )
+                                })
                             })
-                            .ok_or_else(|| {
-                                format_translation_err!(
-                                    self.ast_context
-                                        .display_loc(&self.ast_context[order_fail_id.unwrap()].loc),
-                                    "Invalid failure memory ordering",
-                                )
-                            })?;
-
-                            self.use_feature("core_intrinsics");
-                            let expected =
-                                mk().unary_expr(UnOp::Deref(Default::default()), expected);
-                            let desired = if name == "__atomic_compare_exchange_n" {
-                                desired
-                            } else {
-                                mk().unary_expr(UnOp::Deref(Default::default()), desired)
-                            };
-
-                            let atomic_cxchg =
-                                mk().abs_path_expr(vec!["core", "intrinsics", &intrinsic_name]);
-                            let call =
-                                mk().call_expr(atomic_cxchg, vec![ptr, expected.clone(), desired]);
-                            let res_name = self.renamer.borrow_mut().fresh();
-                            let res_let = mk().local_stmt(Box::new(mk().local(
-                                mk().ident_pat(&res_name),
-                                None,
-                                Some(call),
-                            )));
-                            let assignment = mk().semi_stmt(mk().assign_expr(
-                                expected,
-                                mk().anon_field_expr(mk().ident_expr(&res_name), 0),
-                            ));
-                            let return_value = mk().anon_field_expr(mk().ident_expr(&res_name), 1);
-                            self.convert_side_effects_expr(
-                                ctx,
-                                WithStmts::new(vec![res_let, assignment], return_value),
-                                "Builtin is not supposed to be used",
-                            )
                         })
                     })
                 })
@@ -288,14 +530,22 @@ impl<'c> Translation<'c> {
                     "atomic_and"
                 };
 
-                let intrinsic_suffix = order_name(static_order(order));
-                let intrinsic_name = format!("{intrinsic_name}_{intrinsic_suffix}");
-
                 let fetch_first = name.starts_with("__atomic_fetch");
                 let val = val1.expect("__atomic arithmetic operations must have a val argument");
+                let stable = stable.filter(|atomic| !atomic.is_ptr);
                 ptr.and_then(|ptr| {
-                    val.and_then(|val| {
-                        self.convert_atomic_op(ctx, &intrinsic_name, ptr, val, fetch_first)
+                    order.and_then(|order| {
+                        val.and_then(|val| {
+                            self.convert_atomic_op(
+                                ctx,
+                                intrinsic_name,
+                                &order,
+                                ptr,
+                                val,
+                                fetch_first,
+                                stable.as_ref(),
+                            )
+                        })
                     })
                 })
             }
@@ -304,6 +554,8 @@ impl<'c> Translation<'c> {
         }
     }
 
+    /// Convert a sequentially consistent compare-exchange, using `stable` if there is one.
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn convert_atomic_cxchg(
         &self,
         ctx: ExprContext,
@@ -312,14 +564,35 @@ impl<'c> Translation<'c> {
         old_val: Box<Expr>,
         src_val: Box<Expr>,
         returns_val: bool,
+        stable: Option<&StableAtomic>,
     ) -> TranslationResult<WithStmts<Box<Expr>>> {
-        self.use_feature("core_intrinsics");
+        let call_expr = if let Some(atomic) = stable {
+            // Emit `(*(a0 as *const AtomicT)).compare_exchange(a1, a2, SeqCst, SeqCst)`, followed
+            // by `.unwrap_or_else(identity)` for the previous value or `.is_ok()`
+            let call = atomic.method_call(
+                dst,
+                "compare_exchange",
+                vec![old_val, src_val],
+                &[Ordering::SeqCst, Ordering::SeqCst],
+            );
+            if returns_val {
+                atomic.to_object(mk().method_call_expr(
+                    call,
+                    "unwrap_or_else",
+                    vec![mk().abs_path_expr(vec!["core", "convert", "identity"])],
+                ))
+            } else {
+                mk().method_call_expr(call, "is_ok", vec![])
+            }
+        } else {
+            self.use_feature("core_intrinsics");
 
-        // Emit `atomic_cxchg(a0, a1, a2).idx`
-        let atomic_cxchg = mk().abs_path_expr(vec!["core", "intrinsics", intrinsic_name]);
-        let call = mk().call_expr(atomic_cxchg, vec![dst, old_val, src_val]);
-        let field_idx = if returns_val { 0 } else { 1 };
-        let call_expr = mk().anon_field_expr(call, field_idx);
+            // Emit `atomic_cxchg(a0, a1, a2).idx`
+            let atomic_cxchg = mk().abs_path_expr(vec!["core", "intrinsics", intrinsic_name]);
+            let call = mk().call_expr(atomic_cxchg, vec![dst, old_val, src_val]);
+            let field_idx = if returns_val { 0 } else { 1 };
+            mk().anon_field_expr(call, field_idx)
+        };
         self.convert_side_effects_expr(
             ctx,
             WithStmts::new_val(call_expr),
@@ -327,21 +600,46 @@ impl<'c> Translation<'c> {
         )
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn convert_atomic_op(
         &self,
         ctx: ExprContext,
         func_name: &str,
+        order: &MemOrdering,
         dst: Box<Expr>,
         src: Box<Expr>,
         fetch_first: bool,
+        stable: Option<&StableAtomic>,
     ) -> TranslationResult<WithStmts<Box<Expr>>> {
-        self.use_feature("core_intrinsics");
+        if stable.is_none() {
+            self.use_feature("core_intrinsics");
+        }
 
-        // Emit `atomic_func(a0, a1) (op a1)?`
-        let atomic_func = mk().abs_path_expr(vec!["core", "intrinsics", func_name]);
+        // Emit `atomic_func_order(a0, a1) (op a1)?`, or
+        // `(*(a0 as *const AtomicT)).fetch_func(a1, order) (op a1)?` with `stable`
+        let mk_call = |dst: Box<Expr>, src: Box<Expr>| {
+            self.dispatch_memordering(order, |o| o, &|order| {
+                if let Some(atomic) = stable {
+                    let method = match func_name {
+                        "atomic_xadd" => "fetch_add",
+                        "atomic_xsub" => "fetch_sub",
+                        "atomic_or" => "fetch_or",
+                        "atomic_xor" => "fetch_xor",
+                        "atomic_nand" => "fetch_nand",
+                        "atomic_and" => "fetch_and",
+                        _ => panic!("Unexpected atomic intrinsic name: {}", func_name),
+                    };
+                    let call = atomic.method_call(dst.clone(), method, vec![src.clone()], &[order]);
+                    return Ok(atomic.to_object(call));
+                }
+                let intrinsic_name = format!("{}_{}", func_name, order_name(order));
+                let atomic_func = mk().abs_path_expr(vec!["core", "intrinsics", &intrinsic_name]);
+                Ok(mk().call_expr(atomic_func, vec![dst.clone(), src.clone()]))
+            })
+        };
 
         if fetch_first {
-            let call_expr = mk().call_expr(atomic_func, vec![dst, src]);
+            let call_expr = mk_call(dst, src)?;
             self.convert_side_effects_expr(
                 ctx,
                 WithStmts::new_val(call_expr),
@@ -381,10 +679,7 @@ impl<'c> Translation<'c> {
                 Some(src),
             )));
 
-            let call = mk().call_expr(
-                atomic_func,
-                vec![mk().ident_expr(&arg0_name), mk().ident_expr(&arg1_name)],
-            );
+            let call = mk_call(mk().ident_expr(&arg0_name), mk().ident_expr(&arg1_name))?;
             let val = mk().binary_expr(binary_op, call, mk().ident_expr(arg1_name));
             let val = if is_nand {
                 // For nand, return `!(atomic_nand(arg0, arg1) & arg1)`
@@ -400,3 +695,83 @@ impl<'c> Translation<'c> {
         }
     }
 }
+
+/// A memory ordering argument of an atomic builtin.
+pub(crate) enum MemOrdering {
+    /// An ordering known at translation time
+    Static(Ordering),
+    /// An expression evaluating to one of the C `__ATOMIC_*` values
+    Dynamic(Box<Expr>),
+}
+
+/// Map a C `__ATOMIC_*` value to the corresponding Rust ordering.
+fn memordering_from_c(i: u64) -> Ordering {
+    use Ordering::*;
+    match i {
+        0 => Relaxed,
+        // There is no consume intrinsic, so use acquire instead
+        1 | 2 => Acquire,
+        3 => Release,
+        4 => AcqRel,
+        // Like GCC, treat invalid orderings as `__ATOMIC_SEQ_CST`
+        _ => SeqCst,
+    }
+}
+
+fn order_name(order: Ordering) -> &'static str {
+    use Ordering::*;
+    match order {
+        SeqCst => "seqcst",
+        AcqRel => "acqrel",
+        Acquire => "acquire",
+        Release => "release",
+        Relaxed => "relaxed",
+        _ => unreachable!(
+            "new variants added to `{}`",
+            std::any::type_name::<Ordering>()
+        ),
+    }
+}
+
+/// Loads can't have release semantics; GCC uses seq_cst instead.
+fn load_ordering(order: Ordering) -> Ordering {
+    use Ordering::*;
+    match order {
+        Release | AcqRel => SeqCst,
+        order => order,
+    }
+}
+
+/// Stores can't have acquire semantics; GCC uses seq_cst instead.
+fn store_ordering(order: Ordering) -> Ordering {
+    use Ordering::*;
+    match order {
+        Acquire | AcqRel => SeqCst,
+        order => order,
+    }
+}
+
+/// The failure ordering of a compare-exchange is a load ordering.
+fn cxchg_failure_ordering(order: Ordering) -> Ordering {
+    load_ordering(order)
+}
+
+/// The previous value and the success flag of the compare-exchange stored in the local `res`, as
+/// built by [`Translation::atomic_cxchg_call`]: the intrinsics return a tuple of both, while
+/// `compare_exchange` returns the previous value as either `Ok` or `Err`.
+fn cxchg_result(stable: Option<&StableAtomic>, res: &str) -> (Box<Expr>, Box<Expr>) {
+    match stable {
+        Some(atomic) => (
+            atomic.to_object(mk().method_call_expr(
+                mk().ident_expr(res),
+                "unwrap_or_else",
+                vec![mk().abs_path_expr(vec!["core", "convert", "identity"])],
+            )),
+            mk().method_call_expr(mk().ident_expr(res), "is_ok", vec![]),
+        ),
+        None => (
+            mk().anon_field_expr(mk().ident_expr(res), 0),
+            mk().anon_field_expr(mk().ident_expr(res), 1),
+        ),
+    }
+}
@@ -3,7 +3,9 @@
 
 use crate::format_translation_err;
 
+use super::atomics::MemOrdering;
 use super::*;
+use std::sync::atomic::Ordering;
 
 impl<'c> Translation<'c> {
     /// Convert a call to a builtin function to a Rust expression
@@ -33,6 +35,10 @@ impl<'c> Translation<'c> {
             }
         };
 
+        if let Some(call) = self.convert_complex_libcall(ctx, builtin_name, args)? {
+            return Ok(call);
+        }
+
         match builtin_name {
             "__builtin_huge_valf" => Ok(WithStmts::new_val(
                 mk().abs_path_expr(vec!["core", "f32", "INFINITY"]),
@@ -416,6 +422,7 @@ impl<'c> Translation<'c> {
                 let arg0 = self.convert_expr(ctx.used(), args[0])?;
                 let arg1 = self.convert_expr(ctx.used(), args[1])?;
                 let arg2 = self.convert_expr(ctx.used(), args[2])?;
+                let stable = self.stable_atomic(args[0])?;
                 arg0.and_then(|arg0| {
                     arg1.and_then(|arg1| {
                         arg2.and_then(|arg2| {
@@ -427,6 +434,7 @@ impl<'c> Translation<'c> {
                                 arg1,
                                 arg2,
                                 returns_val,
+                                stable.as_ref(),
                             )
                         })
                     })
@@ -493,36 +501,53 @@ impl<'c> Translation<'c> {
             | "__sync_nand_and_fetch_8"
             | "__sync_nand_and_fetch_16" => {
                 let func_name = if builtin_name.contains("_add_") {
-                    "atomic_xadd_seqcst"
+                    "atomic_xadd"
                 } else if builtin_name.contains("_sub_") {
-                    "atomic_xsub_seqcst"
+                    "atomic_xsub"
                 } else if builtin_name.contains("_or_") {
-                    "atomic_or_seqcst"
+                    "atomic_or"
                 } else if builtin_name.contains("_xor_") {
-                    "atomic_xor_seqcst"
+                    "atomic_xor"
                 } else if builtin_name.contains("_nand_") {
-                    "atomic_nand_seqcst"
+                    "atomic_nand"
                 } else {
                     // We can't explicitly check for "_and_" since they all contain it
-                    "atomic_and_seqcst"
+                    "atomic_and"
                 };
 
                 let arg0 = self.convert_expr(ctx.used(), args[0])?;
                 let arg1 = self.convert_expr(ctx.used(), args[1])?;
                 let fetch_first = builtin_name.starts_with("__sync_fetch");
+                let stable = self.stable_atomic(args[0])?.filter(|atomic| !atomic.is_ptr);
                 arg0.and_then(|arg0| {
                     arg1.and_then(|arg1| {
-                        self.convert_atomic_op(ctx, func_name, arg0, arg1, fetch_first)
+                        self.convert_atomic_op(
+                            ctx,
+                            func_name,
+                            &MemOrdering::Static(Ordering::SeqCst),
+                            arg0,
+                            arg1,
+                            fetch_first,
+                            stable.as_ref(),
+                        )
                     })
                 })
             }
 
             "__sync_synchronize" => {
-                self.use_feature("core_intrinsics");
+                let call_expr = if self.tcfg.stable {
+                    // Emit `fence(Ordering::SeqCst)`
+                    let fence_func = mk().abs_path_expr(vec!["core", "sync", "atomic", "fence"]);
+                    let seq_cst =
+                        mk().abs_path_expr(vec!["core", "sync", "atomic", "Ordering", "SeqCst"]);
+                    mk().call_expr(fence_func, vec![seq_cst])
+                } else {
+                    self.use_feature("core_intrinsics");
 
-                let atomic_func =
-                    mk().abs_path_expr(vec!["core", "intrinsics", "atomic_fence_seqcst"]);
-                let call_expr = mk().call_expr(atomic_func, vec![]);
+                    let atomic_func =
+                        mk().abs_path_expr(vec!["core", "intrinsics", "atomic_fence_seqcst"]);
+                    mk().call_expr(atomic_func, vec![])
+                };
                 self.convert_side_effects_expr(
                     ctx,
                     WithStmts::new_val(call_expr),
@@ -535,16 +560,27 @@ impl<'c> Translation<'c> {
             | "__sync_lock_test_and_set_4"
             | "__sync_lock_test_and_set_8"
             | "__sync_lock_test_and_set_16" => {
-                self.use_feature("core_intrinsics");
-
-                // Emit `atomic_xchg_acquire(arg0, arg1)`
-                let atomic_func =
-                    mk().abs_path_expr(vec!["core", "intrinsics", "atomic_xchg_acquire"]);
+                let stable = self.stable_atomic(args[0])?;
                 let arg0 = self.convert_expr(ctx.used(), args[0])?;
                 let arg1 = self.convert_expr(ctx.used(), args[1])?;
                 arg0.and_then(|arg0| {
                     arg1.and_then(|arg1| {
-                        let call_expr = mk().call_expr(atomic_func, vec![arg0, arg1]);
+                        let call_expr = if let Some(atomic) = stable {
+                            // Emit `(*(arg0 as *const AtomicT)).swap(arg1, Acquire)`
+                            let swap =
+                                atomic.method_call(arg0, "swap", vec![arg1], &[Ordering::Acquire]);
+                            atomic.to_object(swap)
+                        } else {
+                            self.use_feature("core_intrinsics");
+
+                            // Emit `atomic_xchg_acquire(arg0, arg1)`
+                            let atomic_func = mk().abs_path_expr(vec![
+                                "core",
+                                "intrinsics",
+                                "atomic_xchg_acquire",
+                            ]);
+                            mk().call_expr(atomic_func, vec![arg0, arg1])
+                        };
                         self.convert_side_effects_expr(
                             ctx,
                             WithStmts::new_val(call_expr),
@@ -559,15 +595,21 @@ impl<'c> Translation<'c> {
             | "__sync_lock_release_4"
             | "__sync_lock_release_8"
             | "__sync_lock_release_16" => {
-                self.use_feature("core_intrinsics");
-
-                // Emit `atomic_store_release(arg0, 0)`
-                let atomic_func =
-                    mk().abs_path_expr(vec!["core", "intrinsics", "atomic_store_release"]);
+                let stable = self.stable_atomic(args[0])?;
                 let arg0 = self.convert_expr(ctx.used(), args[0])?;
                 arg0.and_then(|arg0| {
                     let zero = mk().lit_expr(mk().int_lit(0, ""));
-                    let call_expr = mk().call_expr(atomic_func, vec![arg0, zero]);
+                    let call_expr = if let Some(atomic) = stable {
+                        // Emit `(*(arg0 as *const AtomicT)).store(0, Release)`
+                        atomic.method_call(arg0, "store", vec![zero], &[Ordering::Release])
+                    } else {
+                        self.use_feature("core_intrinsics");
+
+                        // Emit `atomic_store_release(arg0, 0)`
+                        let atomic_func =
+                            mk().abs_path_expr(vec!["core", "intrinsics", "atomic_store_release"]);
+                        mk().call_expr(atomic_func, vec![arg0, zero])
+                    };
                     self.convert_side_effects_expr(
                         ctx,
                         WithStmts::new_val(call_expr),
@@ -593,15 +635,28 @@ impl<'c> Translation<'c> {
             | "__builtin_rotateleft16"
             | "__builtin_rotateleft32"
             | "__builtin_rotateleft64" => {
-                self.use_feature("core_intrinsics");
-
-                // Emit `rotate_left(arg0, arg1)`
-                let rotate_func = mk().abs_path_expr(vec!["core", "intrinsics", "rotate_left"]);
                 let arg0 = self.convert_expr(ctx.used(), args[0])?;
                 let arg1 = self.convert_expr(ctx.used(), args[1])?;
                 arg0.and_then(|arg0| {
                     arg1.and_then(|arg1| {
-                        let call_expr = mk().call_expr(rotate_func, vec![arg0, arg1]);
+                        let call_expr = if self.tcfg.stable {
+                            // Emit `(arg0 as uN).rotate_left(arg1 as u32)`
+                            let bits = builtin_name.trim_start_matches("__builtin_rotateleft");
+                            let ty = mk().path_ty(vec![format!("u{}", bits)]);
+                            let arg1 = mk().cast_expr(arg1, mk().path_ty(vec!["u32"]));
+                            mk().method_call_expr(
+                                mk().paren_expr(mk().cast_expr(arg0, ty)),
+                                "rotate_left",
+                                vec![arg1],
+                            )
+                        } else {
+                            self.use_feature("core_intrinsics");
+
+                            // Emit `rotate_left(arg0, arg1)`
+                            let rotate_func =
+                                mk().abs_path_expr(vec!["core", "intrinsics", "rotate_left"]);
+                            mk().call_expr(rotate_func, vec![arg0, arg1])
+                        };
                         self.convert_side_effects_expr(
                             ctx,
                             WithStmts::new_val(call_expr),
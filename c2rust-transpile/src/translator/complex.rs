@@ -0,0 +1,273 @@
+#![deny(missing_docs)]
+//! This module provides translation for C99 complex numbers. A `_Complex T`
+//! is represented by `num_complex::Complex<T>`, which is `#[repr(C)]` and
+//! laid out as the real part followed by the imaginary part, exactly like C.
+
+use super::*;
+
+/// `<complex.h>` functions that map directly onto a `num_complex::Complex`
+/// method of the same arity. The `f`-suffixed variants are handled by
+/// stripping the suffix; `creal`, `cimag` and `conj` are handled separately.
+static COMPLEX_METHODS: [(&str, &str); 18] = [
+    ("cabs", "norm"),
+    ("carg", "arg"),
+    ("cexp", "exp"),
+    ("clog", "ln"),
+    ("csqrt", "sqrt"),
+    ("cpow", "powc"),
+    ("csin", "sin"),
+    ("ccos", "cos"),
+    ("ctan", "tan"),
+    ("casin", "asin"),
+    ("cacos", "acos"),
+    ("catan", "atan"),
+    ("csinh", "sinh"),
+    ("ccosh", "cosh"),
+    ("ctanh", "tanh"),
+    ("casinh", "asinh"),
+    ("cacosh", "acosh"),
+    ("catanh", "atanh"),
+];
+
+impl<'c> Translation<'c> {
+    /// Record the dependency on `num-complex` if `ctype` is spelled using a
+    /// complex type. Typedefs are not followed since their own declaration
+    /// takes care of the import.
+    pub fn import_complex_type(&self, ctype: CTypeId) {
+        if self.mentions_complex_type(ctype) {
+            self.use_crate(ExternCrate::NumComplex);
+        }
+    }
+
+    fn mentions_complex_type(&self, ctype: CTypeId) -> bool {
+        use CTypeKind::*;
+        match self.ast_context[ctype].kind {
+            Complex(_) => true,
+            Pointer(qty) | Attributed(qty, _) => self.mentions_complex_type(qty.ctype),
+            ConstantArray(elt, _)
+            | IncompleteArray(elt)
+            | VariableArray(elt, _)
+            | Elaborated(elt)
+            | Decayed(elt)
+            | Paren(elt)
+            | TypeOf(elt) => self.mentions_complex_type(elt),
+            Function(ret, ref params, ..) => {
+                self.mentions_complex_type(ret.ctype)
+                    || params.iter().any(|p| self.mentions_complex_type(p.ctype))
+            }
+            _ => false,
+        }
+    }
+
+    /// Get the element type of a complex type.
+    fn complex_element(&self, ctype: CTypeId) -> TranslationResult<CTypeId> {
+        match self.ast_context.resolve_type(ctype).kind {
+            CTypeKind::Complex(elt) => Ok(elt),
+            ref t => Err(format_err!("Expected a complex type, found {:?}", t).into()),
+        }
+    }
+
+    /// Is `ctype` a complex type, looking through typedefs?
+    pub fn is_complex_type(&self, ctype: CTypeId) -> bool {
+        matches!(
+            self.ast_context.resolve_type(ctype).kind,
+            CTypeKind::Complex(_)
+        )
+    }
+
+    /// Build `num_complex::Complex::new(re, im)`.
+    fn mk_complex(&self, re: Box<Expr>, im: Box<Expr>) -> Box<Expr> {
+        self.use_crate(ExternCrate::NumComplex);
+        let new = mk().path_expr(vec!["num_complex", "Complex", "new"]);
+        mk().call_expr(new, vec![re, im])
+    }
+
+    /// A zero literal of the given complex element type.
+    fn complex_zero(&self, elt: CTypeId) -> Box<Expr> {
+        if self.ast_context.resolve_type(elt).kind.is_floating_type() {
+            mk().lit_expr(mk().float_unsuffixed_lit("0."))
+        } else {
+            mk().lit_expr(mk().int_unsuffixed_lit(0))
+        }
+    }
+
+    /// The complex number zero with the given element type.
+    pub fn complex_zero_value(&self, elt: CTypeId) -> Box<Expr> {
+        self.mk_complex(self.complex_zero(elt), self.complex_zero(elt))
+    }
+
+    /// Convert an imaginary literal such as `2.0i` into a complex number with
+    /// a zero real part.
+    pub fn convert_imaginary_literal(
+        &self,
+        ctx: ExprContext,
+        ty: CQualTypeId,
+        val: CExprId,
+    ) -> TranslationResult<WithStmts<Box<Expr>>> {
+        let elt = self.complex_element(ty.ctype)?;
+        let val = self.convert_expr(ctx.used(), val)?;
+        Ok(val.map(|im| self.mk_complex(self.complex_zero(elt), im)))
+    }
+
+    /// Convert a cast to, from or between complex types.
+    pub fn convert_complex_cast(
+        &self,
+        ctx: ExprContext,
+        kind: CastKind,
+        ty: CQualTypeId,
+        val: WithStmts<Box<Expr>>,
+    ) -> TranslationResult<WithStmts<Box<Expr>>> {
+        match kind {
+            // C99 6.3.1.7: the imaginary part of the result is zero
+            CastKind::FloatingRealToComplex | CastKind::IntegralRealToComplex => {
+                let elt = self.complex_element(ty.ctype)?;
+                Ok(val.map(|re| self.mk_complex(re, self.complex_zero(elt))))
+            }
+
+            // C99 6.3.1.7: the imaginary part is discarded
+            CastKind::FloatingComplexToReal | CastKind::IntegralComplexToReal => {
+                Ok(val.map(|z| mk().field_expr(z, "re")))
+            }
+
+            CastKind::FloatingComplexCast
+            | CastKind::FloatingComplexToIntegralComplex
+            | CastKind::IntegralComplexCast
+            | CastKind::IntegralComplexToFloatingComplex => {
+                let elt = self.complex_element(ty.ctype)?;
+                let elt_ty = self.convert_type(elt)?;
+                let part =
+                    |z: Box<Expr>, field| mk().cast_expr(mk().field_expr(z, field), elt_ty.clone());
+
+                if ctx.is_static {
+                    // Statics can't bind a local, but their initializers are
+                    // free of side effects so the operand can be duplicated.
+                    return Ok(val.map(|z| self.mk_complex(part(z.clone(), "re"), part(z, "im"))));
+                }
+
+                val.and_then(|z| {
+                    let name = self.renamer.borrow_mut().fresh();
+                    let local =
+                        mk().local_stmt(Box::new(mk().local(mk().ident_pat(&name), None, Some(z))));
+                    let re = part(mk().ident_expr(&name), "re");
+                    let im = part(mk().ident_expr(&name), "im");
+                    Ok(WithStmts::new(vec![local], self.mk_complex(re, im)))
+                })
+            }
+
+            _ => Err(format_err!("Unexpected complex cast kind {:?}", kind).into()),
+        }
+    }
+
+    /// Convert `__real__` or `__imag__`. On a complex operand these project
+    /// out the corresponding field, which remains usable as a place. On a real
+    /// operand `__real__` is the identity and `__imag__` is zero.
+    pub fn convert_real_imag(
+        &self,
+        ctx: ExprContext,
+        op: c_ast::UnOp,
+        arg: CExprId,
+    ) -> TranslationResult<WithStmts<Box<Expr>>> {
+        let field = match op {
+            c_ast::UnOp::Real => "re",
+            _ => "im",
+        };
+        let arg_ty = self.ast_context[arg]
+            .kind
+            .get_type()
+            .ok_or_else(|| format_err!("bad {:?} operand type", op))?;
+
+        if self.is_complex_type(arg_ty) {
+            return Ok(self
+                .convert_expr(ctx, arg)?
+                .map(|z| mk().field_expr(z, field)));
+        }
+
+        match op {
+            c_ast::UnOp::Real => self.convert_expr(ctx, arg),
+            _ => {
+                let zero = self.complex_zero(arg_ty);
+                Ok(self.convert_expr(ctx.unused(), arg)?.map(|_| zero))
+            }
+        }
+    }
+
+    /// Convert a direct call to `fexp` if it names one of the `<complex.h>`
+    /// functions. Functions defined in the translation unit are left alone.
+    pub fn convert_complex_call(
+        &self,
+        ctx: ExprContext,
+        fexp: CExprId,
+        args: &[CExprId],
+    ) -> TranslationResult<Option<WithStmts<Box<Expr>>>> {
+        let decl_id = match self.ast_context[fexp].kind {
+            CExprKind::DeclRef(_, decl_id, _) => decl_id,
+            _ => return Ok(None),
+        };
+        match self.ast_context[decl_id].kind {
+            CDeclKind::Function {
+                ref name,
+                body: None,
+                ..
+            } => self.convert_complex_libcall(ctx, name, args),
+            _ => Ok(None),
+        }
+    }
+
+    /// Convert a call to one of the `<complex.h>` functions into the
+    /// equivalent `num_complex::Complex` operation. Returns `None` for
+    /// functions that should remain calls into libm, such as the
+    /// `long double` variants of the transcendental functions.
+    pub fn convert_complex_libcall(
+        &self,
+        ctx: ExprContext,
+        name: &str,
+        args: &[CExprId],
+    ) -> TranslationResult<Option<WithStmts<Box<Expr>>>> {
+        let name = name.strip_prefix("__builtin_").unwrap_or(name);
+
+        // Accessors work on any element type, including `f128`
+        let accessor = ["creal", "cimag", "conj"]
+            .into_iter()
+            .find(|base| name == *base || name.strip_suffix(&['f', 'l'][..]) == Some(*base));
+        let method = accessor.or_else(|| {
+            COMPLEX_METHODS
+                .iter()
+                .find(|(base, _)| name == *base || name.strip_suffix('f') == Some(*base))
+                .map(|&(_, method)| method)
+        });
+        let method = match method {
+            Some(method) => method,
+            None => return Ok(None),
+        };
+
+        let arity = if method == "powc" { 2 } else { 1 };
+        let is_complex_arg = |arg: &CExprId| {
+            self.ast_context[*arg]
+                .kind
+                .get_type()
+                .map_or(false, |ty| self.is_complex_type(ty))
+        };
+        if args.len() != arity || !args.iter().all(is_complex_arg) {
+            return Ok(None);
+        }
+
+        let args = self.convert_exprs(ctx.used(), args)?;
+        let call = args.map(|args| {
+            let mut args = args.into_iter();
+            let z = args.next().unwrap();
+            match method {
+                "creal" => mk().field_expr(z, "re"),
+                "cimag" => mk().field_expr(z, "im"),
+                _ => mk().method_call_expr(z, method, args.collect()),
+            }
+        });
+        self.use_crate(ExternCrate::NumComplex);
+
+        self.convert_side_effects_expr(
+            ctx,
+            call,
+            "Function call expression is not supposed to be used",
+        )
+        .map(Some)
+    }
+}
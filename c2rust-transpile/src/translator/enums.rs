@@ -0,0 +1,677 @@
+//! Translation of C enums into Rust enums (`--rust-enums`).
+//!
+//! C code may store any value of its underlying integer type in an enum, so by
+//! default an enum becomes a type alias for that integer type and each of its
+//! constants a `const` of the alias. When every value of an enum comes from its
+//! own constants, it can instead become a `#[repr(<int>)]` Rust enum with a
+//! `TryFrom<<int>>` impl for checked conversions. Uses of its constants as
+//! enum values, comparisons between its values, and the cases of a `switch` on
+//! it then refer to its variants.
+//!
+//! Enums whose values are used in arithmetic or bitwise operations, tested for
+//! truth, stored in bitfields, or built from anything other than one of their
+//! constants or a literal with the value of one, keep the default translation,
+//! as do enums with duplicate values or without a zero-valued constant, which
+//! zero-initialization needs.
+//!
+//! Values that C code writes without going through any of these uses must not
+//! reach a Rust enum either, so enums in the types of extern or externally
+//! visible functions and variables, of union fields, or behind pointers that
+//! are cast to or from other pointer types (as with the arguments of `memcpy`)
+//! keep the default translation as well, including enums in the fields of
+//! structs of these types. Since the translation units of a
+//! crate share their enums, an enum that any of them rejects keeps the default
+//! translation in all of them.
+
+use super::*;
+use crate::c_ast::iterators::immediate_children;
+use std::collections::HashSet;
+
+impl<'c> Translation<'c> {
+    /// Find the enums that can be translated into Rust enums, given the
+    /// [`Translation::rust_enum_key`]s of the enums that a translation unit of
+    /// the crate rejects.
+    pub(super) fn find_rust_enums(&self, rejected: &BTreeSet<String>) -> IndexSet<CEnumId> {
+        let mut rust_enums = self.unit_rust_enums();
+        rust_enums.retain(|&enum_id| !rejected.contains(&self.rust_enum_key(enum_id)));
+        rust_enums
+    }
+
+    /// The [`Translation::rust_enum_key`]s of the enums that this translation
+    /// unit can't translate into Rust enums.
+    pub(super) fn rejected_rust_enums(&self) -> Vec<String> {
+        let rust_enums = self.unit_rust_enums();
+        self.ast_context
+            .iter_decls()
+            .filter(|&(decl_id, decl)| {
+                matches!(decl.kind, CDeclKind::Enum { .. }) && !rust_enums.contains(decl_id)
+            })
+            .map(|(&decl_id, _)| self.rust_enum_key(decl_id))
+            .collect()
+    }
+
+    /// Identifies an enum across the translation units of a crate: `enum tag`,
+    /// or `enum { A, B }` for an anonymous enum.
+    fn rust_enum_key(&self, enum_id: CEnumId) -> String {
+        match self.ast_context[enum_id].kind {
+            CDeclKind::Enum {
+                name: Some(ref name),
+                ..
+            } => format!("enum {}", name),
+            CDeclKind::Enum { ref variants, .. } => {
+                let names = variants
+                    .iter()
+                    .filter_map(|&variant_id| match self.ast_context[variant_id].kind {
+                        CDeclKind::EnumConstant { ref name, .. } => Some(name.as_str()),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+                format!("enum {{ {} }}", names.join(", "))
+            }
+            _ => panic!("{:?} is not an enum", enum_id),
+        }
+    }
+
+    /// Find the enums that this translation unit alone allows to be translated
+    /// into Rust enums.
+    fn unit_rust_enums(&self) -> IndexSet<CEnumId> {
+        let mut rust_enums: IndexSet<CEnumId> = self
+            .ast_context
+            .iter_decls()
+            .map(|(&decl_id, _)| decl_id)
+            .filter(|&decl_id| self.rust_enum_repr(decl_id).is_some())
+            .collect();
+
+        let reject_in_type = |rust_enums: &mut IndexSet<CEnumId>, type_id: CTypeId| {
+            let mut enums = vec![];
+            self.enums_in_type(type_id, &mut enums);
+            for enum_id in enums {
+                rust_enums.remove(&enum_id);
+            }
+        };
+
+        for (_, decl) in self.ast_context.iter_decls() {
+            let root: SomeId = match decl.kind {
+                CDeclKind::Function {
+                    is_global,
+                    body,
+                    typ,
+                    ..
+                } => {
+                    // C code may call these or define them with any values
+                    if is_global || body.is_none() {
+                        reject_in_type(&mut rust_enums, typ);
+                    }
+                    match body {
+                        Some(body) => body.into(),
+                        None => continue,
+                    }
+                }
+                CDeclKind::Variable {
+                    is_externally_visible,
+                    initializer,
+                    typ,
+                    ..
+                } => {
+                    if is_externally_visible {
+                        reject_in_type(&mut rust_enums, typ.ctype);
+                    }
+                    match initializer {
+                        Some(initializer) => initializer.into(),
+                        None => continue,
+                    }
+                }
+                CDeclKind::Union {
+                    fields: Some(ref fields),
+                    ..
+                } => {
+                    for &field_id in fields {
+                        if let CDeclKind::Field { typ, .. } = self.ast_context[field_id].kind {
+                            reject_in_type(&mut rust_enums, typ.ctype);
+                        }
+                    }
+                    continue;
+                }
+                CDeclKind::Field {
+                    typ,
+                    bitfield_width: Some(_),
+                    ..
+                } => {
+                    if let Some(enum_id) = self.enum_of_type(typ.ctype) {
+                        rust_enums.remove(&enum_id);
+                    }
+                    continue;
+                }
+                _ => continue,
+            };
+            for node in DFExpr::new(&self.ast_context, root) {
+                match node {
+                    SomeId::Expr(expr_id) => self.check_enum_expr_use(expr_id, &mut rust_enums),
+                    SomeId::Stmt(stmt_id) => self.check_enum_stmt_use(stmt_id, &mut rust_enums),
+                    _ => {}
+                }
+            }
+        }
+
+        rust_enums
+    }
+
+    /// The `repr` of the Rust enum for `enum_id` if it's a complete enum whose
+    /// constants have distinct values, one of them zero.
+    fn rust_enum_repr(&self, enum_id: CEnumId) -> Option<&'static str> {
+        let (variants, integral_type) = match self.ast_context[enum_id].kind {
+            CDeclKind::Enum {
+                ref variants,
+                integral_type: Some(integral_type),
+                ..
+            } => (variants, integral_type),
+            _ => return None,
+        };
+
+        let mut values = HashSet::new();
+        for &variant_id in variants {
+            match self.ast_context[variant_id].kind {
+                CDeclKind::EnumConstant { value, .. } => {
+                    let value = match value {
+                        ConstIntExpr::I(value) => value as i128,
+                        ConstIntExpr::U(value) => value as i128,
+                    };
+                    if !values.insert(value) {
+                        return None;
+                    }
+                }
+                _ => return None,
+            }
+        }
+        if !values.contains(&0) {
+            return None;
+        }
+
+        use CTypeKind::*;
+        Some(
+            match self.ast_context.resolve_type(integral_type.ctype).kind {
+                Char | SChar => "i8",
+                UChar => "u8",
+                Short => "i16",
+                UShort => "u16",
+                Int => "i32",
+                UInt => "u32",
+                Long | LongLong => "i64",
+                ULong | ULongLong => "u64",
+                _ => return None,
+            },
+        )
+    }
+
+    /// Reject the enums that `expr_id` uses as plain integers or accesses
+    /// through a pointer of another type.
+    fn check_enum_expr_use(&self, expr_id: CExprId, rust_enums: &mut IndexSet<CEnumId>) {
+        match self.ast_context[expr_id].kind {
+            CExprKind::ImplicitCast(ty, sub, kind, _, _)
+            | CExprKind::ExplicitCast(ty, sub, kind, _, _) => {
+                let sub_ty = self.ast_context[sub].kind.get_type();
+                let is_ptr = |ty| {
+                    matches!(
+                        self.ast_context.resolve_type(ty).kind,
+                        CTypeKind::Pointer(..)
+                    )
+                };
+                let is_ptr_cast = kind != CastKind::NullToPointer
+                    && (is_ptr(ty.ctype) || sub_ty.map_or(false, is_ptr));
+                if is_ptr_cast {
+                    let mut target_enums = vec![];
+                    self.enums_in_type(ty.ctype, &mut target_enums);
+                    let mut source_enums = vec![];
+                    if let Some(sub_ty) = sub_ty {
+                        self.enums_in_type(sub_ty, &mut source_enums);
+                    }
+                    if target_enums != source_enums {
+                        for enum_id in target_enums.iter().chain(&source_enums) {
+                            rust_enums.remove(enum_id);
+                        }
+                    }
+                }
+
+                let target = self.enum_of_type(ty.ctype);
+                let source = sub_ty.and_then(|ty| self.enum_of_type(ty));
+                if target == source {
+                    return;
+                }
+                // Only the casts `Translation::enum_cast` can turn into a variant
+                if let Some(enum_id) = target {
+                    if kind != CastKind::IntegralCast || !self.is_enum_value_expr(enum_id, sub) {
+                        rust_enums.remove(&enum_id);
+                    }
+                }
+                if let Some(enum_id) = source {
+                    if kind != CastKind::IntegralCast || target.is_some() {
+                        rust_enums.remove(&enum_id);
+                    }
+                }
+            }
+
+            CExprKind::Binary(_, op, lhs, rhs, _, _) => {
+                use c_ast::BinOp::*;
+                let operands = match op {
+                    Less | Greater | LessEqual | GreaterEqual | EqualEqual | NotEqual | Assign
+                    | Comma => vec![],
+                    // Operands of these are tested for truth
+                    And | Or => [lhs, rhs]
+                        .iter()
+                        .filter_map(|&e| self.enum_typed(e))
+                        .collect(),
+                    _ => [lhs, rhs]
+                        .iter()
+                        .filter_map(|&e| self.enum_operand(e).map(|(enum_id, _)| enum_id))
+                        .collect(),
+                };
+                for enum_id in operands {
+                    rust_enums.remove(&enum_id);
+                }
+            }
+
+            CExprKind::Unary(_, op, e, _) => match op {
+                c_ast::UnOp::AddressOf | c_ast::UnOp::Deref => {}
+                _ => {
+                    if let Some((enum_id, _)) = self.enum_operand(e) {
+                        rust_enums.remove(&enum_id);
+                    }
+                }
+            },
+
+            CExprKind::Conditional(_, cond, _, _) | CExprKind::BinaryConditional(_, cond, _) => {
+                self.check_enum_condition(cond, rust_enums)
+            }
+
+            _ => {}
+        }
+    }
+
+    /// Reject the enums that `stmt_id` tests for truth or switches on with
+    /// case labels that aren't their constants.
+    fn check_enum_stmt_use(&self, stmt_id: CStmtId, rust_enums: &mut IndexSet<CEnumId>) {
+        match self.ast_context[stmt_id].kind {
+            CStmtKind::If {
+                scrutinee: cond, ..
+            }
+            | CStmtKind::While {
+                condition: cond, ..
+            }
+            | CStmtKind::DoWhile {
+                condition: cond, ..
+            }
+            | CStmtKind::ForLoop {
+                condition: Some(cond),
+                ..
+            } => self.check_enum_condition(cond, rust_enums),
+
+            CStmtKind::Switch { scrutinee, body } => {
+                let switch_enum = self.rust_enum_scrutinee(scrutinee);
+                let mut cases = vec![];
+                self.switch_case_labels(body, &mut cases);
+                for case_expr in cases {
+                    let variant_enum = self
+                        .case_label_variant(case_expr)
+                        .map(|variant_id| self.ast_context.parents[&variant_id]);
+                    if variant_enum != switch_enum {
+                        for enum_id in switch_enum.iter().chain(variant_enum.iter()) {
+                            rust_enums.remove(enum_id);
+                        }
+                    }
+                }
+            }
+
+            _ => {}
+        }
+    }
+
+    fn check_enum_condition(&self, cond: CExprId, rust_enums: &mut IndexSet<CEnumId>) {
+        if let Some(enum_id) = self.enum_typed(cond) {
+            rust_enums.remove(&enum_id);
+        }
+    }
+
+    /// Collect the case labels of the switch with body `stmt_id`, excluding
+    /// those of nested switches.
+    fn switch_case_labels(&self, stmt_id: CStmtId, cases: &mut Vec<CExprId>) {
+        match self.ast_context[stmt_id].kind {
+            CStmtKind::Switch { .. } => return,
+            CStmtKind::Case(case_expr, ..) => cases.push(case_expr),
+            _ => {}
+        }
+        for child in immediate_children(&self.ast_context, stmt_id.into()) {
+            if let SomeId::Stmt(child) = child {
+                self.switch_case_labels(child, cases);
+            }
+        }
+    }
+
+    /// The enum constant `case_expr` refers to, if any.
+    fn case_label_variant(&self, case_expr: CExprId) -> Option<CEnumConstantId> {
+        match self.ast_context[case_expr].kind {
+            CExprKind::ConstantExpr(_, child, _)
+            | CExprKind::Paren(_, child)
+            | CExprKind::ImplicitCast(_, child, _, _, _) => self.case_label_variant(child),
+            CExprKind::DeclRef(_, decl_id, _) => match self.ast_context[decl_id].kind {
+                CDeclKind::EnumConstant { .. } => Some(decl_id),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Whether `Translation::enum_cast` translates `expr_id` cast to the
+    /// enum `enum_id` as one of its variants.
+    fn is_enum_value_expr(&self, enum_id: CEnumId, expr_id: CExprId) -> bool {
+        let value = match self.ast_context[expr_id].kind {
+            CExprKind::DeclRef(_, decl_id, _) => {
+                return self.ast_context.parents.get(&decl_id) == Some(&enum_id)
+            }
+            CExprKind::Literal(_, CLiteral::Integer(i, _)) => i as i64,
+            CExprKind::Unary(_, c_ast::UnOp::Negate, e, _) => match self.ast_context[e].kind {
+                CExprKind::Literal(_, CLiteral::Integer(i, _)) => -(i as i64),
+                _ => return false,
+            },
+            _ => return false,
+        };
+        match self.ast_context[enum_id].kind {
+            CDeclKind::Enum { ref variants, .. } => variants.iter().any(|&variant_id| {
+                matches!(
+                    self.ast_context[variant_id].kind,
+                    CDeclKind::EnumConstant { value: v, .. }
+                        if v == ConstIntExpr::I(value) || v == ConstIntExpr::U(value as u64)
+                )
+            }),
+            _ => false,
+        }
+    }
+
+    /// Collect the enums that values of `type_id` are or point to, including
+    /// through the parameters and results of function pointers and the fields
+    /// of structs and unions.
+    fn enums_in_type(&self, type_id: CTypeId, enums: &mut Vec<CEnumId>) {
+        self.enums_in_type_rec(type_id, enums, &mut HashSet::new())
+    }
+
+    fn enums_in_type_rec(
+        &self,
+        type_id: CTypeId,
+        enums: &mut Vec<CEnumId>,
+        records: &mut HashSet<CRecordId>,
+    ) {
+        use CTypeKind::*;
+        match self.ast_context.resolve_type(type_id).kind {
+            Enum(enum_id) => enums.push(enum_id),
+            Pointer(qty) | BlockPointer(qty) | Vector(qty, _) => {
+                self.enums_in_type_rec(qty.ctype, enums, records)
+            }
+            ConstantArray(ty, _) | IncompleteArray(ty) | VariableArray(ty, _) => {
+                self.enums_in_type_rec(ty, enums, records)
+            }
+            Function(ret, ref params, ..) => {
+                self.enums_in_type_rec(ret.ctype, enums, records);
+                for param in params {
+                    self.enums_in_type_rec(param.ctype, enums, records);
+                }
+            }
+            // Records can point to themselves
+            Struct(record_id) | Union(record_id) if records.insert(record_id) => {
+                let fields = match self.ast_context[record_id].kind {
+                    CDeclKind::Struct {
+                        fields: Some(ref fields),
+                        ..
+                    }
+                    | CDeclKind::Union {
+                        fields: Some(ref fields),
+                        ..
+                    } => fields,
+                    _ => return,
+                };
+                for &field_id in fields {
+                    if let CDeclKind::Field { typ, .. } = self.ast_context[field_id].kind {
+                        self.enums_in_type_rec(typ.ctype, enums, records);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn enum_of_type(&self, type_id: CTypeId) -> Option<CEnumId> {
+        match self.ast_context.resolve_type(type_id).kind {
+            CTypeKind::Enum(enum_id) => Some(enum_id),
+            _ => None,
+        }
+    }
+
+    /// The enum `expr_id` has the type of, ignoring parentheses.
+    fn enum_typed(&self, expr_id: CExprId) -> Option<CEnumId> {
+        match self.ast_context[expr_id].kind {
+            CExprKind::Paren(_, e) => self.enum_typed(e),
+            ref kind => kind.get_type().and_then(|ty| self.enum_of_type(ty)),
+        }
+    }
+
+    /// The enum whose value `expr_id` is, ignoring parentheses and implicit
+    /// conversions, along with the expression of that enum's type or the
+    /// reference to its constant.
+    fn enum_operand(&self, expr_id: CExprId) -> Option<(CEnumId, CExprId)> {
+        match self.ast_context[expr_id].kind {
+            CExprKind::Paren(_, e) | CExprKind::ImplicitCast(_, e, _, _, _) => {
+                if let Some(enum_id) = self.enum_typed(expr_id) {
+                    return Some((enum_id, expr_id));
+                }
+                self.enum_operand(e)
+            }
+            CExprKind::DeclRef(_, decl_id, _)
+                if matches!(
+                    self.ast_context[decl_id].kind,
+                    CDeclKind::EnumConstant { .. }
+                ) =>
+            {
+                Some((self.ast_context.parents[&decl_id], expr_id))
+            }
+            ref kind => kind
+                .get_type()
+                .and_then(|ty| self.enum_of_type(ty))
+                .map(|enum_id| (enum_id, expr_id)),
+        }
+    }
+
+    /// The enum a switch on `scrutinee` switches on, if any.
+    fn rust_enum_scrutinee(&self, scrutinee: CExprId) -> Option<CEnumId> {
+        match self.ast_context[scrutinee].kind {
+            CExprKind::ImplicitCast(_, e, _, _, _) => self.rust_enum_scrutinee(e),
+            _ => self.enum_typed(scrutinee),
+        }
+    }
+
+    /// The path to the variant for `variant_id` if it's a constant of an enum
+    /// translated to a Rust enum.
+    pub(super) fn rust_enum_variant(&self, variant_id: CDeclId) -> Option<Box<Expr>> {
+        let enum_id = match self.ast_context[variant_id].kind {
+            CDeclKind::EnumConstant { .. } => self.ast_context.parents[&variant_id],
+            _ => return None,
+        };
+        if !self.rust_enums.contains(&enum_id) {
+            return None;
+        }
+
+        let enum_name = self
+            .type_converter
+            .borrow()
+            .resolve_decl_name(enum_id)
+            .expect("Enums should already be renamed");
+        let name = self
+            .renamer
+            .borrow()
+            .get(&variant_id)
+            .expect("Enum constant not named");
+        self.use_fn_macro_item(enum_id, &enum_name);
+        if let Some(cur_file) = *self.cur_file.borrow() {
+            self.add_import(cur_file, enum_id, &enum_name);
+        }
+        Some(mk().path_expr(vec![enum_name, name]))
+    }
+
+    /// Translate `enum_id` as a Rust enum and an impl of `TryFrom` for it.
+    pub(super) fn convert_rust_enum(
+        &self,
+        span: Span,
+        enum_id: CEnumId,
+    ) -> TranslationResult<ConvertedDecl> {
+        let variants = match self.ast_context[enum_id].kind {
+            CDeclKind::Enum { ref variants, .. } => variants,
+            _ => panic!("{:?} does not point to an `enum` declaration", enum_id),
+        };
+        let repr = self
+            .rust_enum_repr(enum_id)
+            .expect("Rust enums should have a repr");
+        let enum_name = self
+            .type_converter
+            .borrow()
+            .resolve_decl_name(enum_id)
+            .expect("Enums should already be renamed");
+
+        let mut enum_variants = vec![];
+        let mut arms = vec![];
+        for &variant_id in variants {
+            let value = match self.ast_context[variant_id].kind {
+                CDeclKind::EnumConstant { value, .. } => value,
+                _ => panic!("{:?} does not point to an enum variant", variant_id),
+            };
+            let value = match value {
+                ConstIntExpr::I(value) => signed_int_expr(value),
+                ConstIntExpr::U(value) => mk().lit_expr(mk().int_unsuffixed_lit(value as u128)),
+            };
+            let name = self
+                .renamer
+                .borrow()
+                .get(&variant_id)
+                .expect("Enum constant not named");
+            let variant = mk().path_expr(vec![enum_name.as_str(), name.as_str()]);
+
+            enum_variants.push(mk().unit_variant(name, Some(value.clone())));
+            arms.push(mk().arm(
+                mk().lit_pat(value),
+                None,
+                mk().call_expr(mk().ident_expr("Ok"), vec![variant]),
+            ));
+        }
+        arms.push(mk().arm(
+            mk().wild_pat(),
+            None,
+            mk().call_expr(mk().ident_expr("Err"), vec![mk().ident_expr("value")]),
+        ));
+
+        let enum_item = mk()
+            .span(span)
+            .pub_()
+            .call_attr("derive", vec!["Copy", "Clone", "PartialEq", "Eq"])
+            .call_attr("repr", vec![repr])
+            .enum_item(&enum_name, enum_variants);
+
+        let repr_ty = mk().ident_ty(repr);
+        let result_ty = mk().path_ty(vec![mk().path_segment_with_args(
+            "Result",
+            mk().angle_bracketed_args(vec![mk().ident_ty("Self"), repr_ty.clone()]),
+        )]);
+        let try_from = mk().fn_impl_item(
+            mk().fn_decl(
+                "try_from",
+                vec![mk().arg(repr_ty.clone(), mk().ident_pat("value"))],
+                None,
+                ReturnType::Type(Default::default(), result_ty),
+            ),
+            mk().block(vec![
+                mk().expr_stmt(mk().match_expr(mk().ident_expr("value"), arms))
+            ]),
+        );
+        let trait_path = mk().abs_path(vec![
+            mk().path_segment("core"),
+            mk().path_segment("convert"),
+            mk().path_segment_with_args(
+                "TryFrom",
+                mk().angle_bracketed_args(vec![repr_ty.clone()]),
+            ),
+        ]);
+        let impl_item = mk().trait_impl_item(
+            trait_path,
+            mk().ident_ty(&enum_name),
+            vec![mk().type_impl_item("Error", repr_ty), try_from],
+        );
+
+        Ok(ConvertedDecl::Items(vec![enum_item, impl_item]))
+    }
+
+    /// Translate an `==` or `!=` comparison of two values of the same Rust
+    /// enum, if that's what `lhs` and `rhs` are, as a comparison of those
+    /// values instead of their underlying integers.
+    pub(super) fn convert_rust_enum_comparison(
+        &self,
+        ctx: ExprContext,
+        op: c_ast::BinOp,
+        lhs: CExprId,
+        rhs: CExprId,
+    ) -> TranslationResult<Option<WithStmts<Box<Expr>>>> {
+        let op = match op {
+            c_ast::BinOp::EqualEqual => BinOp::Eq(Default::default()),
+            c_ast::BinOp::NotEqual => BinOp::Ne(Default::default()),
+            _ => return Ok(None),
+        };
+        let (lhs_enum, lhs) = match self.enum_operand(lhs) {
+            Some(operand) => operand,
+            None => return Ok(None),
+        };
+        let (rhs_enum, rhs) = match self.enum_operand(rhs) {
+            Some(operand) => operand,
+            None => return Ok(None),
+        };
+        if lhs_enum != rhs_enum || !self.rust_enums.contains(&lhs_enum) {
+            return Ok(None);
+        }
+
+        let lhs = self.convert_rust_enum_operand(ctx, lhs)?;
+        let rhs = self.convert_rust_enum_operand(ctx, rhs)?;
+        let val = lhs.and_then(|lhs| -> TranslationResult<_> {
+            Ok(rhs.map(|rhs| bool_to_int(mk().binary_expr(op, lhs, rhs))))
+        })?;
+        Ok(Some(val))
+    }
+
+    fn convert_rust_enum_operand(
+        &self,
+        ctx: ExprContext,
+        expr_id: CExprId,
+    ) -> TranslationResult<WithStmts<Box<Expr>>> {
+        if let CExprKind::DeclRef(_, decl_id, _) = self.ast_context[expr_id].kind {
+            if let Some(variant) = self.rust_enum_variant(decl_id) {
+                return Ok(WithStmts::new_val(variant));
+            }
+        }
+        self.convert_expr(ctx, expr_id)
+    }
+
+    /// Translate the scrutinee of a switch, as a Rust enum value if the
+    /// switch is on one.
+    pub fn convert_switch_scrutinee(
+        &self,
+        ctx: ExprContext,
+        scrutinee: CExprId,
+    ) -> TranslationResult<WithStmts<Box<Expr>>> {
+        if let Some(enum_id) = self.rust_enum_scrutinee(scrutinee) {
+            if self.rust_enums.contains(&enum_id) {
+                if let Some((_, expr_id)) = self.enum_operand(scrutinee) {
+                    return self.convert_expr(ctx, expr_id);
+                }
+            }
+        }
+        self.convert_expr(ctx, scrutinee)
+    }
+
+    /// The pattern for a case label that's a constant of a Rust enum.
+    pub fn rust_enum_case_pattern(&self, case_expr: CExprId) -> Option<Box<Expr>> {
+        self.case_label_variant(case_expr)
+            .and_then(|variant_id| self.rust_enum_variant(variant_id))
+    }
+}
@@ -0,0 +1,793 @@
+//! Translation of function-like macros (`--translate-fn-macros`).
+//!
+//! The AST exporter records which expressions were spelled as complete
+//! arguments of a function-like macro invocation. Using these, each expansion
+//! of a macro can be translated with its arguments replaced by the macro
+//! parameters, and each invocation can pass its translated arguments instead
+//! of having its expansion inlined:
+//!
+//! * If all invocations have the same argument and result types and use
+//!   neither their arguments nor their result as lvalues, the macro becomes an
+//!   `#[inline]` fn taking the arguments by value. Invocations with impure
+//!   arguments, which C evaluates as many times as the macro body mentions
+//!   them, and invocations in constant initializers are still inlined.
+//! * Otherwise, the macro becomes a `macro_rules!` macro with an `expr`
+//!   parameter for each C parameter, which is pasted at each invocation just
+//!   like the C macro. If the expansions translate differently at different
+//!   types, e.g. because of implicit casts, each translation gets its own arm,
+//!   selected by a tag like `@1` that the invocations pass first. Globals,
+//!   functions and constants are referred to by `$crate::` paths, so that the
+//!   macro can be invoked from other modules of the crate too.
+//!
+//! Macros whose arguments can't all be found in their expansions, that refer
+//! to local variables other than through their arguments, or that are
+//! variadic are not translated, and their invocations are inlined as before.
+
+use super::*;
+use crate::c_ast::iterators::immediate_children;
+use proc_macro2::{Delimiter, Group, Literal};
+use std::collections::HashSet;
+use syn::__private::ToTokens;
+
+/// How a function-like macro was translated
+#[derive(Clone, Debug)]
+pub(super) enum FnMacro {
+    /// An `#[inline]` fn with this signature
+    Fn(MacroSignature),
+    /// A `macro_rules!` macro, with the arm for each kind of expansion. The
+    /// arms are only tagged if there is more than one.
+    Rules(Vec<(ExpansionKind, usize)>),
+}
+
+/// The resolved types of an expansion and its arguments, and whether each is
+/// an lvalue. Expansions of the same kind translate the same way.
+type ExpansionKind = Vec<(Option<CTypeId>, bool)>;
+
+/// A translated macro body, with the uses of its parameters and the items it
+/// refers to
+struct MacroBody {
+    expr: WithStmts<Box<Expr>>,
+    /// Number of uses of the macro parameters
+    param_uses: usize,
+    /// Items referred to by name, with how often each name was used
+    items: IndexMap<String, (CDeclId, usize)>,
+}
+
+/// Argument and result types of a macro invocation
+#[derive(Clone, Debug)]
+pub(super) struct MacroSignature {
+    params: Vec<CQualTypeId>,
+    ret: CQualTypeId,
+}
+
+impl<'c> Translation<'c> {
+    /// Translate the definition of a function-like macro, if its invocations
+    /// allow it.
+    pub(super) fn convert_fn_macro(&self, decl_id: CDeclId) -> TranslationResult<ConvertedDecl> {
+        match self.translate_fn_macro(decl_id) {
+            Ok((fn_macro, items)) => {
+                self.fn_macros.borrow_mut().insert(decl_id, Some(fn_macro));
+                Ok(ConvertedDecl::Items(items))
+            }
+            Err(e) => {
+                self.fn_macros.borrow_mut().insert(decl_id, None);
+                let name = self.renamer.borrow().get(&decl_id);
+                info!(
+                    "Could not translate macro {}: {}",
+                    name.unwrap_or_default(),
+                    e
+                );
+                Ok(ConvertedDecl::NoItem)
+            }
+        }
+    }
+
+    /// Returns how a function-like macro is translated, translating it first
+    /// if we haven't reached its definition yet.
+    fn fn_macro(&self, decl_id: CDeclId) -> Option<FnMacro> {
+        let cached = self.fn_macros.borrow().get(&decl_id).cloned();
+        if let Some(fn_macro) = cached {
+            return fn_macro;
+        }
+        let fn_macro = self.translate_fn_macro(decl_id).ok().map(|(m, _)| m);
+        self.fn_macros
+            .borrow_mut()
+            .insert(decl_id, fn_macro.clone());
+        fn_macro
+    }
+
+    /// Imports the types used in the signature of a function-like macro
+    /// translated to a fn into the submodule of its header.
+    pub(super) fn import_fn_macro_types(&self, decl_id: CDeclId, decl_file_id: FileId) {
+        if let Some(Some(FnMacro::Fn(sig))) = self.fn_macros.borrow().get(&decl_id) {
+            for ty in sig.params.iter().chain(Some(&sig.ret)) {
+                self.import_type(ty.ctype, decl_file_id);
+            }
+        }
+    }
+
+    #[allow(clippy::vec_box)]
+    fn translate_fn_macro(&self, decl_id: CDeclId) -> TranslationResult<(FnMacro, Vec<Box<Item>>)> {
+        let (params, is_variadic) = match self.ast_context[decl_id].kind {
+            CDeclKind::MacroFunction {
+                ref parameters,
+                is_variadic,
+                ..
+            } => (parameters, is_variadic),
+            ref kind => panic!("Expected a function-like macro, found {:?}", kind),
+        };
+        if is_variadic {
+            return Err(TranslationError::generic(
+                "Variadic macros are not supported",
+            ));
+        }
+        let name = self
+            .renamer
+            .borrow()
+            .get(&decl_id)
+            .ok_or_else(|| format_err!("Macro name not declared"))?;
+        let span = self
+            .get_span(SomeId::Decl(decl_id))
+            .unwrap_or_else(Span::call_site);
+
+        let expansions = self
+            .ast_context
+            .macro_expansions
+            .get(&decl_id)
+            .map_or(&[][..], Vec::as_slice)
+            .iter()
+            .filter_map(|&root| Some((root, self.macro_call_args(decl_id, root)?)))
+            .collect::<Vec<_>>();
+        if expansions.is_empty() {
+            return Err(TranslationError::generic(
+                "No invocation with recognizable arguments",
+            ));
+        }
+        if expansions
+            .iter()
+            .any(|(root, args)| self.refers_to_locals(*root, args))
+        {
+            return Err(TranslationError::generic(
+                "Macro refers to local variables other than its arguments",
+            ));
+        }
+
+        let ctx = ExprContext {
+            used: true,
+            is_static: false,
+            is_const: false,
+            decay_ref: DecayRef::Default,
+            is_bitfield_write: false,
+            needs_address: false,
+            expecting_valistimpl: false,
+            ternary_needs_parens: false,
+            expanding_macro: Some(decl_id),
+        };
+
+        let mut sigs = expansions
+            .iter()
+            .map(|(root, args)| self.macro_signature(*root, args));
+        let sig = match sigs.next().unwrap() {
+            Some(sig) if sigs.all(|s| s.map_or(false, |s| self.same_signature(&s, &sig))) => {
+                Some(sig)
+            }
+            _ => None,
+        };
+
+        self.with_scope(|| {
+            let param_names = params
+                .iter()
+                .map(|param| self.renamer.borrow_mut().pick_name(param))
+                .collect::<Vec<_>>();
+
+            match sig {
+                Some(sig) => {
+                    let item =
+                        self.fn_macro_item(ctx, span, &name, &sig, &expansions[0], &param_names)?;
+                    Ok((FnMacro::Fn(sig), vec![item]))
+                }
+                None => {
+                    let (arms, items) =
+                        self.rules_macro_items(ctx, span, &name, &expansions, &param_names)?;
+                    Ok((FnMacro::Rules(arms), items))
+                }
+            }
+        })
+    }
+
+    fn fn_macro_item(
+        &self,
+        ctx: ExprContext,
+        span: Span,
+        name: &str,
+        sig: &MacroSignature,
+        (root, args): &(CExprId, Vec<Vec<CExprId>>),
+        param_names: &[String],
+    ) -> TranslationResult<Box<Item>> {
+        let fn_args = sig
+            .params
+            .iter()
+            .zip(param_names)
+            .map(|(ty, name)| Ok(mk().arg(self.convert_type(ty.ctype)?, mk().ident_pat(name))))
+            .collect::<TranslationResult<Vec<_>>>()?;
+
+        let is_void_ret = self.ast_context.resolve_type(sig.ret.ctype).kind == CTypeKind::Void;
+        let (ret, block) = if is_void_ret {
+            let body = self.convert_macro_body(ctx.unused(), *root, args, param_names)?;
+            (ReturnType::Default, mk().block(body.expr.into_stmts()))
+        } else {
+            let body = self.convert_macro_body(ctx, *root, args, param_names)?;
+            let ret = self.convert_type(sig.ret.ctype)?;
+            (
+                ReturnType::Type(Default::default(), ret),
+                body.expr.to_block(),
+            )
+        };
+
+        let decl = mk().fn_decl(name, fn_args, None, ret);
+        Ok(mk()
+            .span(span)
+            .pub_()
+            .unsafe_()
+            .single_attr("inline")
+            .fn_item(decl, block))
+    }
+
+    /// Build a `macro_rules!` macro with an arm for each distinct translation
+    /// of the expansions, and re-export it by path so that it can be used
+    /// before its definition and from other modules. Returns the arm for each
+    /// kind of expansion along with the items.
+    #[allow(clippy::vec_box, clippy::type_complexity)]
+    fn rules_macro_items(
+        &self,
+        ctx: ExprContext,
+        span: Span,
+        name: &str,
+        expansions: &[(CExprId, Vec<Vec<CExprId>>)],
+        param_names: &[String],
+    ) -> TranslationResult<(Vec<(ExpansionKind, usize)>, Vec<Box<Item>>)> {
+        let mut arms = vec![];
+        let mut bodies: Vec<(String, TokenStream)> = vec![];
+        for (root, args) in expansions {
+            let kind = self.expansion_kind(*root, args);
+            if arms.iter().any(|(k, _)| *k == kind) {
+                continue;
+            }
+
+            let body = self.convert_macro_body(ctx, *root, args, param_names)?;
+            let tokens = body.expr.to_expr().into_token_stream();
+            let mut replaced = 0;
+            let tokens = to_metavars(tokens, param_names, &mut replaced);
+            // A parameter name that is also used for something else, e.g. a
+            // field, can't be told apart from the parameter.
+            if replaced != body.param_uses {
+                return Err(TranslationError::generic(
+                    "Macro parameter name is ambiguous in the translated macro",
+                ));
+            }
+            let paths = body
+                .items
+                .iter()
+                .map(|(name, &(decl_id, _))| (name.clone(), self.item_module_path(decl_id)))
+                .collect::<HashMap<_, _>>();
+            let mut replaced = 0;
+            let tokens = to_crate_paths(tokens, &paths, span, &mut replaced);
+            if replaced != body.items.values().map(|&(_, uses)| uses).sum::<usize>() {
+                return Err(TranslationError::generic(
+                    "Item name is ambiguous in the translated macro",
+                ));
+            }
+
+            let text = tokens.to_string();
+            let arm = match bodies.iter().position(|(t, _)| *t == text) {
+                Some(arm) => arm,
+                None => {
+                    bodies.push((text, tokens));
+                    bodies.len() - 1
+                }
+            };
+            arms.push((kind, arm));
+        }
+
+        let tagged = bodies.len() > 1;
+        let mut rules = vec![];
+        for (arm, (_, body)) in bodies.into_iter().enumerate() {
+            let mut matcher = vec![];
+            if tagged {
+                matcher.push(TokenTree::Punct(Punct::new('@', Alone)));
+                matcher.push(TokenTree::Literal(Literal::usize_unsuffixed(arm)));
+            }
+            for (i, param) in param_names.iter().enumerate() {
+                if i > 0 {
+                    matcher.push(TokenTree::Punct(Punct::new(',', Alone)));
+                }
+                matcher.push(TokenTree::Punct(Punct::new('$', Alone)));
+                matcher.push(TokenTree::Ident(Ident::new(param, span)));
+                matcher.push(TokenTree::Punct(Punct::new(':', Alone)));
+                matcher.push(TokenTree::Ident(Ident::new("expr", span)));
+            }
+            rules.extend([
+                TokenTree::Group(Group::new(
+                    Delimiter::Parenthesis,
+                    matcher.into_iter().collect(),
+                )),
+                TokenTree::Punct(Punct::new('=', Joint)),
+                TokenTree::Punct(Punct::new('>', Alone)),
+                TokenTree::Group(Group::new(Delimiter::Brace, body)),
+                TokenTree::Punct(Punct::new(';', Alone)),
+            ]);
+        }
+
+        let items = vec![
+            mk().span(span).macro_rules_item(name, rules),
+            mk().span(span)
+                .vis("pub(crate)")
+                .use_simple_item(vec![name], None::<Ident>),
+        ];
+        Ok((arms, items))
+    }
+
+    /// Returns the kind of the expansion at `root` with arguments `args`.
+    fn expansion_kind(&self, root: CExprId, args: &[Vec<CExprId>]) -> ExpansionKind {
+        Some(root)
+            .into_iter()
+            .chain(args.iter().map(|nodes| nodes[0]))
+            .map(|expr_id| {
+                let kind = &self.ast_context[expr_id].kind;
+                let ty = kind
+                    .get_type()
+                    .map(|ty| self.ast_context.resolve_type_id(ty));
+                (ty, kind.lrvalue().is_lvalue())
+            })
+            .collect()
+    }
+
+    /// Returns the path of the module that the item `decl_id` is emitted in,
+    /// relative to the crate root.
+    fn item_module_path(&self, decl_id: CDeclId) -> Vec<String> {
+        let mut path = self.module_path.clone();
+        if self.tcfg.reorganize_definitions {
+            let file_id = self.ast_context.file_id(&self.ast_context[decl_id]);
+            if let Some(file_id) = file_id.filter(|&file_id| file_id != self.main_file) {
+                path.push(clean_path(
+                    &self.mod_names,
+                    self.ast_context.get_file_path(file_id),
+                ));
+            }
+        }
+        path
+    }
+
+    /// Translate the expansion at `root` with each of the macro arguments in
+    /// `args` replaced by the name of its parameter, and count how often the
+    /// names and the items it refers to were used. Fails if an argument isn't
+    /// translated on its own, e.g. because an enclosing expression inspects it.
+    fn convert_macro_body(
+        &self,
+        ctx: ExprContext,
+        root: CExprId,
+        args: &[Vec<CExprId>],
+        param_names: &[String],
+    ) -> TranslationResult<MacroBody> {
+        let params = args
+            .iter()
+            .zip(param_names)
+            .flat_map(|(nodes, name)| nodes.iter().map(move |&node| (node, (name.clone(), 0))))
+            .collect();
+        let outer_params = self.fn_macro_params.replace(params);
+        let outer_items = self.fn_macro_items.replace(Some(IndexMap::new()));
+        let expr = self.convert_expr(ctx, root);
+        let params = self.fn_macro_params.replace(outer_params);
+        let items = self.fn_macro_items.replace(outer_items).unwrap_or_default();
+        let expr = expr?;
+
+        if params.values().any(|&(_, uses)| uses == 0) {
+            return Err(TranslationError::generic(
+                "Macro argument is not translated on its own",
+            ));
+        }
+        Ok(MacroBody {
+            expr,
+            param_uses: params.values().map(|&(_, uses)| uses).sum(),
+            items,
+        })
+    }
+
+    /// Record a use of the name `name` of the item `decl_id` while translating
+    /// the body of a function-like macro.
+    pub(super) fn use_fn_macro_item(&self, decl_id: CDeclId, name: &str) {
+        if let Some(items) = self.fn_macro_items.borrow_mut().as_mut() {
+            // Locals declared by the macro body itself stay as they are
+            let is_item = match self.ast_context[decl_id].kind {
+                CDeclKind::Variable { .. } => self.ast_context.c_decls_top.contains(&decl_id),
+                _ => true,
+            };
+            if is_item {
+                items.entry(name.to_owned()).or_insert((decl_id, 0)).1 += 1;
+            }
+        }
+    }
+
+    /// Translate a macro argument to its parameter name while translating the
+    /// macro body.
+    pub(super) fn convert_fn_macro_param(
+        &self,
+        ctx: ExprContext,
+        expr_id: CExprId,
+    ) -> TranslationResult<Option<WithStmts<Box<Expr>>>> {
+        let name = match self.fn_macro_params.borrow_mut().get_mut(&expr_id) {
+            Some((name, uses)) => {
+                *uses += 1;
+                name.clone()
+            }
+            None => return Ok(None),
+        };
+        let val = WithStmts::new_val(mk().ident_expr(name));
+        self.convert_side_effects_expr(ctx, val, "Macro argument is not supposed to be used")
+            .map(Some)
+    }
+
+    /// Translate an invocation of a function-like macro into a call of the
+    /// macro's translation, if `expr_id` is the expansion of one.
+    pub(super) fn convert_fn_macro_invocation(
+        &self,
+        ctx: ExprContext,
+        expr_id: CExprId,
+    ) -> TranslationResult<Option<WithStmts<Box<Expr>>>> {
+        let macs = match self.ast_context.macro_invocations.get(&expr_id) {
+            Some(macs) => macs,
+            None => return Ok(None),
+        };
+        // Skip the macro we're currently translating and any it was invoked in
+        let macs = macs
+            .splitn(2, |mac_id| ctx.expanding_macro(mac_id))
+            .last()
+            .unwrap();
+
+        for &mac_id in macs {
+            if !matches!(
+                self.ast_context[mac_id].kind,
+                CDeclKind::MacroFunction { .. }
+            ) {
+                continue;
+            }
+            let fn_macro = match self.fn_macro(mac_id) {
+                Some(fn_macro) => fn_macro,
+                None => continue,
+            };
+            let args = match self.macro_call_args(mac_id, expr_id) {
+                Some(args) => args,
+                None => continue,
+            };
+            trace!("  found function-like macro invocation: {:?}", mac_id);
+
+            let converted = match fn_macro {
+                FnMacro::Fn(ref sig) => {
+                    self.convert_fn_macro_call(ctx, mac_id, sig, expr_id, &args)?
+                }
+                FnMacro::Rules(ref arms) => {
+                    self.convert_rules_macro_call(ctx, mac_id, arms, expr_id, &args)?
+                }
+            };
+            if converted.is_some() {
+                return Ok(converted);
+            }
+        }
+
+        Ok(None)
+    }
+
+    fn convert_fn_macro_call(
+        &self,
+        ctx: ExprContext,
+        mac_id: CDeclId,
+        sig: &MacroSignature,
+        expr_id: CExprId,
+        args: &[Vec<CExprId>],
+    ) -> TranslationResult<Option<WithStmts<Box<Expr>>>> {
+        if ctx.is_const || ctx.is_static {
+            return Ok(None);
+        }
+        match self.macro_signature(expr_id, args) {
+            Some(ref call_sig) if self.same_signature(call_sig, sig) => {}
+            _ => return Ok(None),
+        }
+        // The macro evaluates an argument as often as its body mentions it,
+        // which may be never or more than once.
+        let args = args.iter().map(|nodes| nodes[0]).collect::<Vec<_>>();
+        if !args.iter().all(|&arg| self.ast_context.is_expr_pure(arg)) {
+            return Ok(None);
+        }
+
+        let name = self.fn_macro_name(mac_id)?;
+        let mut call = self
+            .convert_exprs(ctx.used(), &args)?
+            .map(|args| mk().call_expr(mk().path_expr(vec![name]), args));
+        call.set_unsafe();
+        self.convert_side_effects_expr(
+            ctx,
+            call,
+            "Function-like macro call is not supposed to be used",
+        )
+        .map(Some)
+    }
+
+    fn convert_rules_macro_call(
+        &self,
+        ctx: ExprContext,
+        mac_id: CDeclId,
+        arms: &[(ExpansionKind, usize)],
+        expr_id: CExprId,
+        args: &[Vec<CExprId>],
+    ) -> TranslationResult<Option<WithStmts<Box<Expr>>>> {
+        let kind = self.expansion_kind(expr_id, args);
+        let arm = match arms.iter().find(|(k, _)| *k == kind) {
+            Some(&(_, arm)) => arm,
+            None => return Ok(None),
+        };
+
+        let mut tokens = vec![];
+        if arms.iter().any(|&(_, arm)| arm > 0) {
+            tokens.push(TokenTree::Punct(Punct::new('@', Alone)));
+            tokens.push(TokenTree::Literal(Literal::usize_unsuffixed(arm)));
+        }
+        let mut is_unsafe = false;
+        for (i, nodes) in args.iter().enumerate() {
+            let arg = self.convert_expr(ctx.used(), nodes[0])?;
+            is_unsafe |= arg.is_unsafe();
+            // The argument is pasted wherever the macro mentions it, so it
+            // can't come with statements.
+            let arg = match arg.to_pure_expr() {
+                Some(arg) => arg,
+                None => return Ok(None),
+            };
+            if i > 0 {
+                tokens.push(TokenTree::Punct(Punct::new(',', Alone)));
+            }
+            tokens.extend(arg.into_token_stream());
+        }
+
+        let name = self.fn_macro_name(mac_id)?;
+        let mut call = WithStmts::new_val(mk().mac_expr(mk().mac(
+            mk().path(name.as_str()),
+            tokens,
+            MacroDelimiter::Paren(Default::default()),
+        )));
+        call.merge_unsafe(is_unsafe);
+        self.convert_side_effects_expr(ctx, call, "Macro invocation is not supposed to be used")
+            .map(Some)
+    }
+
+    fn fn_macro_name(&self, mac_id: CDeclId) -> TranslationResult<String> {
+        let name = self
+            .renamer
+            .borrow()
+            .get(&mac_id)
+            .ok_or_else(|| format_err!("Macro name not declared"))?;
+        if let Some(cur_file) = self.cur_file.borrow().as_ref() {
+            self.add_import(*cur_file, mac_id, &name);
+        }
+        Ok(name)
+    }
+
+    /// Returns the arguments of the invocation of `mac_id` that expanded to
+    /// `root`, with all the expressions the expansion contains for each
+    /// argument. Fails unless every argument occurs in the expansion, always
+    /// with the same type, and is spelled like the argument at the invocation.
+    fn macro_call_args(&self, mac_id: CDeclId, root: CExprId) -> Option<Vec<Vec<CExprId>>> {
+        let (name, num_params) = match self.ast_context[mac_id].kind {
+            CDeclKind::MacroFunction {
+                ref name,
+                ref parameters,
+                ..
+            } => (name, parameters.len()),
+            _ => return None,
+        };
+        let text = self.ast_context.macro_expansion_text.get(&root)?;
+        let call_args = split_macro_call(name, text)?;
+        if call_args.len() != num_params {
+            return None;
+        }
+
+        let mut args = vec![vec![]; num_params];
+        let mut stack = vec![SomeId::Expr(root)];
+        while let Some(node) = stack.pop() {
+            if let SomeId::Expr(expr_id) = node {
+                match self.ast_context.macro_arguments.get(&expr_id) {
+                    Some(arg) if arg.macro_id == mac_id && expr_id != root => {
+                        let call_arg = call_args.get(arg.param)?;
+                        if !arg.text.split_whitespace().eq(call_arg.split_whitespace()) {
+                            return None;
+                        }
+                        args[arg.param].push(expr_id);
+                        continue;
+                    }
+                    _ => {}
+                }
+            }
+            stack.extend(immediate_children(&self.ast_context, node));
+        }
+
+        let arg_kind = |expr_id: CExprId| {
+            let kind = &self.ast_context[expr_id].kind;
+            let ty = kind
+                .get_type()
+                .map(|ty| self.ast_context.resolve_type_id(ty));
+            (ty, kind.lrvalue())
+        };
+        for nodes in &args {
+            let first = arg_kind(*nodes.first()?);
+            if nodes.iter().any(|&node| arg_kind(node) != first) {
+                return None;
+            }
+        }
+        Some(args)
+    }
+
+    /// Does the expansion at `root` refer to a local variable it doesn't
+    /// declare itself, other than through the macro arguments `args`?
+    fn refers_to_locals(&self, root: CExprId, args: &[Vec<CExprId>]) -> bool {
+        let args = args.iter().flatten().copied().collect::<HashSet<_>>();
+        let mut declared = HashSet::new();
+        let mut referenced = vec![];
+        let mut stack = vec![SomeId::Expr(root)];
+        while let Some(node) = stack.pop() {
+            match node {
+                SomeId::Expr(expr_id) if args.contains(&expr_id) => continue,
+                SomeId::Expr(expr_id) => {
+                    if let CExprKind::DeclRef(_, decl_id, _) = self.ast_context[expr_id].kind {
+                        referenced.push(decl_id);
+                    }
+                }
+                SomeId::Decl(decl_id) => {
+                    declared.insert(decl_id);
+                }
+                _ => {}
+            }
+            stack.extend(immediate_children(&self.ast_context, node));
+        }
+
+        referenced.into_iter().any(|decl_id| {
+            matches!(self.ast_context[decl_id].kind, CDeclKind::Variable { .. })
+                && !self.ast_context.c_decls_top.contains(&decl_id)
+                && !declared.contains(&decl_id)
+        })
+    }
+
+    /// Returns the argument and result types of the expansion at `root`, if
+    /// neither the expansion nor any of its arguments `args` are lvalues.
+    fn macro_signature(&self, root: CExprId, args: &[Vec<CExprId>]) -> Option<MacroSignature> {
+        let rvalue_type = |expr_id: CExprId| {
+            let kind = &self.ast_context[expr_id].kind;
+            match kind.lrvalue() {
+                LRValue::RValue => kind.get_qual_type(),
+                LRValue::LValue => None,
+            }
+        };
+        let params = args
+            .iter()
+            .map(|nodes| rvalue_type(nodes[0]))
+            .collect::<Option<Vec<_>>>()?;
+        let ret = rvalue_type(root)?;
+        Some(MacroSignature { params, ret })
+    }
+
+    fn same_signature(&self, a: &MacroSignature, b: &MacroSignature) -> bool {
+        let same_type = |a: &CQualTypeId, b: &CQualTypeId| {
+            self.ast_context.resolve_type_id(a.ctype) == self.ast_context.resolve_type_id(b.ctype)
+        };
+        a.params.len() == b.params.len()
+            && a.params.iter().zip(&b.params).all(|(a, b)| same_type(a, b))
+            && same_type(&a.ret, &b.ret)
+    }
+}
+
+/// Splits the source text of an invocation of the macro `name` into the text
+/// of its arguments.
+fn split_macro_call<'a>(name: &str, text: &'a str) -> Option<Vec<&'a str>> {
+    let args = text
+        .trim()
+        .strip_prefix(name)?
+        .trim_start()
+        .strip_prefix('(')?
+        .strip_suffix(')')?;
+
+    let mut split = vec![];
+    let mut depth = 0usize;
+    let mut quote = None;
+    let mut escaped = false;
+    let mut start = 0;
+    for (i, c) in args.char_indices() {
+        if let Some(q) = quote {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == q {
+                quote = None;
+            }
+            continue;
+        }
+        match c {
+            '"' | '\'' => quote = Some(c),
+            '(' => depth += 1,
+            ')' => depth = depth.checked_sub(1)?,
+            ',' if depth == 0 => {
+                split.push(&args[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if quote.is_some() || depth != 0 {
+        return None;
+    }
+
+    let last = &args[start..];
+    if !split.is_empty() || !last.trim().is_empty() {
+        split.push(last);
+    }
+    Some(split)
+}
+
+/// Replaces each identifier in `names` with the `macro_rules!` metavariable of
+/// the same name, counting the replacements.
+fn to_metavars(tokens: TokenStream, names: &[String], replaced: &mut usize) -> TokenStream {
+    let mut out = vec![];
+    for tt in tokens {
+        match tt {
+            TokenTree::Ident(ref ident) if names.iter().any(|name| ident == name) => {
+                *replaced += 1;
+                out.push(TokenTree::Punct(Punct::new('$', Alone)));
+                out.push(tt);
+            }
+            TokenTree::Group(group) => {
+                let stream = to_metavars(group.stream(), names, replaced);
+                let mut new_group = Group::new(group.delimiter(), stream);
+                new_group.set_span(group.span());
+                out.push(TokenTree::Group(new_group));
+            }
+            tt => out.push(tt),
+        }
+    }
+    out.into_iter().collect()
+}
+
+/// Replaces each identifier in `paths` with a `$crate::` path to it through the
+/// module it maps to, counting the replacements.
+fn to_crate_paths(
+    tokens: TokenStream,
+    paths: &HashMap<String, Vec<String>>,
+    span: Span,
+    replaced: &mut usize,
+) -> TokenStream {
+    let mut out = vec![];
+    for tt in tokens {
+        match tt {
+            TokenTree::Ident(ref ident) if paths.contains_key(&ident.to_string()) => {
+                *replaced += 1;
+                out.push(TokenTree::Punct(Punct::new('$', Alone)));
+                out.push(TokenTree::Ident(Ident::new("crate", span)));
+                for module in &paths[&ident.to_string()] {
+                    out.push(TokenTree::Punct(Punct::new(':', Joint)));
+                    out.push(TokenTree::Punct(Punct::new(':', Alone)));
+                    out.push(TokenTree::Ident(match module.strip_prefix("r#") {
+                        Some(raw) => Ident::new_raw(raw, span),
+                        None => Ident::new(module, span),
+                    }));
+                }
+                out.push(TokenTree::Punct(Punct::new(':', Joint)));
+                out.push(TokenTree::Punct(Punct::new(':', Alone)));
+                out.push(tt);
+            }
+            TokenTree::Group(group) => {
+                let stream = to_crate_paths(group.stream(), paths, span, replaced);
+                let mut new_group = Group::new(group.delimiter(), stream);
+                new_group.set_span(group.span());
+                out.push(TokenTree::Group(new_group));
+            }
+            tt => out.push(tt),
+        }
+    }
+    out.into_iter().collect()
+}
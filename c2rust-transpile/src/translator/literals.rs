@@ -46,6 +46,10 @@ impl<'c> Translation<'c> {
             match self.ast_context[variant_id].kind {
                 CDeclKind::EnumConstant { value: v, .. } => {
                     if v == ConstIntExpr::I(value) || v == ConstIntExpr::U(value as u64) {
+                        if let Some(variant) = self.rust_enum_variant(variant_id) {
+                            return variant;
+                        }
+
                         let name = self.renamer.borrow().get(&variant_id).unwrap();
 
                         // Import the enum variant if needed
@@ -1,6 +1,6 @@
 use std::cell::RefCell;
 use std::char;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::mem;
 use std::ops::Index;
 use std::path::{self, PathBuf};
@@ -33,6 +33,7 @@ use crate::c_ast::*;
 use crate::cfg;
 use crate::convert_type::TypeConverter;
 use crate::renamer::Renamer;
+use crate::report::{DeclReport, DeclStatus};
 use crate::with_stmts::WithStmts;
 use crate::{c_ast, format_translation_err};
 use crate::{ExternCrate, ExternCrateDetails, TranspilerConfig};
@@ -42,10 +43,14 @@ pub mod assembly;
 pub mod atomics;
 mod builtins;
 mod comments;
+mod complex;
+mod enums;
+mod fn_macros;
 mod literals;
 mod main_function;
 mod named_references;
 mod operators;
+mod setjmp;
 mod simd;
 mod structs;
 mod variadic;
@@ -57,6 +62,10 @@ use crate::PragmaVec;
 pub const INNER_SUFFIX: &str = "_Inner";
 pub const PADDING_SUFFIX: &str = "_PADDING";
 
+/// Features that the translation can use but which have been stabilized since
+/// the toolchain in `rust-toolchain.toml`, so `--stable` output doesn't need them.
+const STABILIZED_FEATURES: &[&str] = &["asm", "c_unwind", "label_break_value"];
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum DecayRef {
     Yes,
@@ -258,6 +267,32 @@ pub struct Translation<'c> {
     function_context: RefCell<FuncContext>,
     potential_flexible_array_members: RefCell<IndexSet<CDeclId>>,
     macro_expansions: RefCell<IndexMap<CDeclId, Option<MacroExpansion>>>,
+    fn_macros: RefCell<IndexMap<CDeclId, Option<fn_macros::FnMacro>>>,
+    /// Parameter names to translate macro arguments to while translating the
+    /// body of a function-like macro, and how often each argument was used
+    fn_macro_params: RefCell<HashMap<CExprId, (String, usize)>>,
+    /// Items referred to by name while translating the body of a function-like
+    /// macro, with their declarations and how often each name was used
+    fn_macro_items: RefCell<Option<IndexMap<String, (CDeclId, usize)>>>,
+    /// Path of the module of the main file, relative to the crate root, which
+    /// `macro_rules!` macros use to refer to its items
+    module_path: Vec<String>,
+    /// Enums translated to Rust enums rather than integer type aliases
+    rust_enums: IndexSet<CEnumId>,
+    /// Jump buffers whose `setjmp`s and `longjmp`s are lowered to unwinding
+    lowered_jmp_bufs: IndexSet<CDeclId>,
+    /// Functions that a lowered `longjmp` can unwind out of
+    unwinding_fns: IndexSet<CDeclId>,
+    /// Structs and unions whose definitions are needed since they are used by
+    /// value, through their fields or in `sizeof`, so they can't be opaque.
+    /// Only computed if the declaration filter excludes types.
+    complete_records: IndexSet<CRecordId>,
+
+    // Translation report support
+    replaced_decls: RefCell<IndexMap<CDeclId, TranslationError>>,
+    decl_reports: RefCell<Vec<DeclReport>>,
+    /// Features with no stable equivalent used by the current declaration in `--stable` mode
+    unstable_features: RefCell<IndexSet<&'static str>>,
 
     // Comment support
     pub comment_context: CommentContext,      // Incoming comments
@@ -489,12 +524,31 @@ pub fn translate_failure(tcfg: &TranspilerConfig, msg: &str) {
     }
 }
 
+/// The enums of a translation unit that can't be translated into Rust enums
+/// with `--rust-enums`, to pass to [`translate`] for every unit of the crate.
+pub fn rejected_rust_enums(
+    ast_context: TypedAstContext,
+    tcfg: &TranspilerConfig,
+    main_file: PathBuf,
+) -> Vec<String> {
+    let mut t = Translation::new(ast_context, tcfg, main_file.as_path());
+    t.ast_context
+        .prune_unwanted_decls(tcfg.preserve_unused_functions);
+    t.rejected_rust_enums()
+}
+
+/// Translate a translation unit into the module at `module_path` in its crate.
+/// With `--rust-enums`, `rejected_enums` are the [`rejected_rust_enums`] of all
+/// units of the crate.
 pub fn translate(
     ast_context: TypedAstContext,
     tcfg: &TranspilerConfig,
     main_file: PathBuf,
-) -> (String, PragmaVec, CrateSet) {
+    module_path: Vec<String>,
+    rejected_enums: &BTreeSet<String>,
+) -> (String, PragmaVec, CrateSet, Vec<DeclReport>) {
     let mut t = Translation::new(ast_context, tcfg, main_file.as_path());
+    t.module_path = module_path;
     let ctx = ExprContext {
         used: true,
         is_static: false,
@@ -521,6 +575,14 @@ pub fn translate(
         t.ast_context
             .prune_unwanted_decls(tcfg.preserve_unused_functions);
 
+        if tcfg.rust_enums {
+            t.rust_enums = t.find_rust_enums(rejected_enums);
+        }
+        (t.lowered_jmp_bufs, t.unwinding_fns) = t.find_setjmp_lowering();
+        if !tcfg.decl_filter.exclude_types.is_empty() {
+            t.complete_records = t.find_complete_records();
+        }
+
         enum Name<'a> {
             Var(&'a str),
             Type(&'a str),
@@ -618,6 +680,7 @@ pub fn translate(
                     Name::Var(ident)
                 }
                 MacroObject { ref name, .. } => Name::Var(name),
+                MacroFunction { ref name, .. } if tcfg.translate_fn_macros => Name::Var(name),
                 _ => Name::None,
             };
             match decl_name {
@@ -644,7 +707,9 @@ pub fn translate(
                 if t.tcfg.reorganize_definitions {
                     *t.cur_file.borrow_mut() = decl_file_id;
                 }
-                match t.convert_decl(ctx, decl_id) {
+                let converted = t.convert_decl(ctx, decl_id);
+                t.report_decl(decl_id, &converted);
+                match converted {
                     Err(e) => {
                         let k = &t.ast_context.get_decl(&decl_id).map(|x| &x.kind);
                         let msg = format!("Skipping declaration {:?} due to error: {}", k, e);
@@ -718,7 +783,9 @@ pub fn translate(
                 {
                     *t.cur_file.borrow_mut() = decl_file_id;
                 }
-                match t.convert_decl(ctx, *top_id) {
+                let converted = t.convert_decl(ctx, *top_id);
+                t.report_decl(*top_id, &converted);
+                match converted {
                     Err(e) => {
                         let decl = &t.ast_context.get_decl(top_id);
                         let msg = match decl {
@@ -883,7 +950,8 @@ pub fn translate(
                 items: all_items.into_iter().map(|x| *x).collect(),
             }
         });
-        (translation, pragmas, crates)
+        let decl_reports = t.decl_reports.take();
+        (translation, pragmas, crates, decl_reports)
     }
 }
 
@@ -905,7 +973,7 @@ fn item_ident(i: &Item) -> Option<&Ident> {
         TraitAlias(ita) => &ita.ident,
         Type(it) => &it.ident,
         Union(iu) => &iu.ident,
-        Use(ItemUse { tree: _, .. }) => unimplemented!(),
+        Use(_iu) => return None,
         Verbatim(_tokenstream) => {
             warn!("cannot determine name of tokenstream item");
             return None;
@@ -927,6 +995,8 @@ fn item_vis(i: &Item) -> Option<Visibility> {
             Fn(ifn) => &ifn.vis,
             ForeignMod(_ifm) => return None,
             Impl(_ii) => return None,
+            // `macro_rules!` macros are re-exported with a `pub(crate) use`
+            Macro(im) if im.ident.is_some() => return Some(Visibility::Inherited),
             Macro(_im) => return None,
             Macro2(im2) => &im2.vis,
             Mod(im) => &im.vis,
@@ -1195,6 +1265,8 @@ struct ConvertFunctionArgs<'a> {
     is_main: bool,
     is_variadic: bool,
     is_extern: bool,
+    /// Whether a lowered `longjmp` can unwind out of the function
+    can_unwind: bool,
     new_name: &'a str,
     name: &'a str,
     arguments: &'a [(CDeclId, String, CQualTypeId)],
@@ -1251,6 +1323,17 @@ impl<'c> Translation<'c> {
             function_context: RefCell::new(FuncContext::new()),
             potential_flexible_array_members: RefCell::new(IndexSet::new()),
             macro_expansions: RefCell::new(IndexMap::new()),
+            fn_macros: RefCell::new(IndexMap::new()),
+            fn_macro_params: RefCell::new(HashMap::new()),
+            fn_macro_items: RefCell::new(None),
+            module_path: Vec::new(),
+            rust_enums: IndexSet::new(),
+            lowered_jmp_bufs: IndexSet::new(),
+            unwinding_fns: IndexSet::new(),
+            complete_records: IndexSet::new(),
+            replaced_decls: RefCell::new(IndexMap::new()),
+            decl_reports: RefCell::new(Vec::new()),
+            unstable_features: RefCell::new(IndexSet::new()),
             comment_context,
             comment_store: RefCell::new(CommentStore::new()),
             spans: HashMap::new(),
@@ -1286,8 +1369,200 @@ impl<'c> Translation<'c> {
         f(item_store)
     }
 
+    /// Is `decl_id` a definition that the declaration filter excludes? Only
+    /// definitions that can be linked from the C object code, or types that
+    /// can be made opaque, are excluded.
+    fn is_excluded_decl(&self, decl_id: CDeclId) -> bool {
+        let filter = &self.tcfg.decl_filter;
+        let decl = match self.ast_context.get_decl(&decl_id) {
+            Some(decl) => decl,
+            None => return false,
+        };
+        use CDeclKind::*;
+        match decl.kind {
+            Function {
+                is_global: true,
+                is_inline: false,
+                body: Some(_),
+                ref name,
+                ..
+            } => filter.excludes_function(name),
+            Variable {
+                is_externally_visible: true,
+                is_defn: true,
+                ref ident,
+                ..
+            } => filter.excludes_global(ident),
+            Struct { .. } | Union { .. } => {
+                self.excluded_record_name(decl_id).is_some()
+                    && !self.complete_records.contains(&decl_id)
+            }
+            _ => false,
+        }
+    }
+
+    /// The name of the struct or union definition `decl_id` if the
+    /// declaration filter excludes it, whether or not it can be opaque.
+    fn excluded_record_name(&self, decl_id: CDeclId) -> Option<String> {
+        let name = match self.ast_context[decl_id].kind {
+            CDeclKind::Struct {
+                fields: Some(_),
+                ref name,
+                ..
+            }
+            | CDeclKind::Union {
+                fields: Some(_),
+                ref name,
+                ..
+            } => name.clone(),
+            _ => return None,
+        };
+        // Unnamed types are named after their typedef
+        let name = name.or_else(|| self.type_converter.borrow().resolve_decl_name(decl_id))?;
+        if self.tcfg.decl_filter.excludes_type(&name) {
+            Some(name)
+        } else {
+            None
+        }
+    }
+
+    /// Find the structs and unions that are used in ways that need their
+    /// definition: as the type of a value, which includes variables,
+    /// parameters, fields and array elements, to access their fields, or in
+    /// `sizeof` and `alignof`.
+    fn find_complete_records(&self) -> IndexSet<CRecordId> {
+        let mut records = IndexSet::new();
+        let mut by_value = |typ: CTypeId| records.extend(self.record_by_value(typ));
+
+        for (&decl_id, decl) in self.ast_context.iter_decls() {
+            // We don't translate the bodies and initializers of excluded
+            // definitions, but still declare them with their types
+            let is_excluded = self.is_excluded_decl(decl_id);
+            let root: SomeId = match decl.kind {
+                CDeclKind::Variable {
+                    typ, initializer, ..
+                } => {
+                    by_value(typ.ctype);
+                    match initializer {
+                        Some(initializer) if !is_excluded => initializer.into(),
+                        _ => continue,
+                    }
+                }
+                CDeclKind::Field { typ, .. } => {
+                    by_value(typ.ctype);
+                    continue;
+                }
+                CDeclKind::Function { typ, body, .. } => {
+                    if let CTypeKind::Function(ret, ref params, ..) =
+                        self.ast_context.resolve_type(typ).kind
+                    {
+                        by_value(ret.ctype);
+                        for param in params {
+                            by_value(param.ctype);
+                        }
+                    }
+                    match body {
+                        Some(body) if !is_excluded => body.into(),
+                        _ => continue,
+                    }
+                }
+                _ => continue,
+            };
+
+            for node in DFExpr::new(&self.ast_context, root) {
+                let expr_id = match node {
+                    SomeId::Expr(expr_id) => expr_id,
+                    _ => continue,
+                };
+                let expr = &self.ast_context[expr_id].kind;
+                if let Some(typ) = expr.get_type() {
+                    by_value(typ);
+                }
+                match *expr {
+                    CExprKind::Member(_, base, _, MemberKind::Arrow, _) => {
+                        let base_ty = self.ast_context[base].kind.get_type();
+                        if let Some(pointee) =
+                            base_ty.and_then(|ty| self.ast_context.get_pointee_qual_type(ty))
+                        {
+                            by_value(pointee.ctype);
+                        }
+                    }
+                    CExprKind::UnaryType(_, _, _, arg_ty) => by_value(arg_ty.ctype),
+                    _ => {}
+                }
+            }
+        }
+
+        records
+    }
+
+    /// The struct or union that values of type `typ` contain directly rather
+    /// than through a pointer, if any.
+    fn record_by_value(&self, typ: CTypeId) -> Option<CRecordId> {
+        match self.ast_context.resolve_type(typ).kind {
+            CTypeKind::Struct(record_id) | CTypeKind::Union(record_id) => Some(record_id),
+            CTypeKind::ConstantArray(elt, _)
+            | CTypeKind::IncompleteArray(elt)
+            | CTypeKind::VariableArray(elt, _) => self.record_by_value(elt),
+            _ => None,
+        }
+    }
+
+    /// Record the outcome of converting `decl_id` for the translation report,
+    /// and warn about any nightly features it needs in `--stable` mode.
+    fn report_decl(&self, decl_id: CDeclId, result: &TranslationResult<ConvertedDecl>) {
+        let unstable_features = mem::take(&mut *self.unstable_features.borrow_mut());
+        let decl = match self.ast_context.get_decl(&decl_id) {
+            Some(decl) => decl,
+            None => return,
+        };
+        if !unstable_features.is_empty() {
+            let name = decl.kind.get_name().map_or("<anonymous>", String::as_str);
+            let loc = self
+                .ast_context
+                .display_loc(&decl.loc)
+                .map_or_else(String::new, |loc| format!(" at {}", loc));
+            warn!(
+                "{}{} needs nightly features with no stable equivalent: {}",
+                name,
+                loc,
+                unstable_features
+                    .iter()
+                    .copied()
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+        if self.tcfg.report.is_none() {
+            return;
+        }
+        let mut report = DeclReport::new(&decl.kind, self.ast_context.display_loc(&decl.loc));
+        report.unstable_features = unstable_features.iter().map(|&f| f.to_owned()).collect();
+        let report = match result {
+            Err(e) => report.with_error(DeclStatus::Failed, e),
+            Ok(_) if self.is_excluded_decl(decl_id) => DeclReport {
+                status: DeclStatus::Excluded,
+                ..report
+            },
+            Ok(_) => match self.replaced_decls.borrow().get(&decl_id) {
+                Some(e) => report.with_error(DeclStatus::Replaced, e),
+                None => report,
+            },
+        };
+        self.decl_reports.borrow_mut().push(report);
+    }
+
     /// Called when translation makes use of a language feature that will require a feature-gate.
+    ///
+    /// In `--stable` mode, callers lower to stable code where they can instead, so any remaining
+    /// uses are reported for the current declaration.
     pub fn use_feature(&self, feature: &'static str) {
+        if self.tcfg.stable {
+            if STABILIZED_FEATURES.contains(&feature) {
+                return;
+            }
+            self.unstable_features.borrow_mut().insert(feature);
+        }
         self.features.borrow_mut().insert(feature);
     }
 
@@ -1308,8 +1583,11 @@ impl<'c> Translation<'c> {
             ],
         )];
 
-        features.push("register_tool");
-        pragmas.push(("register_tool", vec!["c2rust"]));
+        // The `c2rust` tool attributes are only needed to reorganize definitions
+        if !self.tcfg.stable || self.tcfg.reorganize_definitions {
+            features.push("register_tool");
+            pragmas.push(("register_tool", vec!["c2rust"]));
+        }
 
         if !features.is_empty() {
             pragmas.push(("feature", features));
@@ -1581,6 +1859,90 @@ impl<'c> Translation<'c> {
         (fn_item, static_item)
     }
 
+    /// Whether the thread-local variable `decl_id` is translated with `std::thread_local!`, as in
+    /// `--stable` mode, where `#[thread_local]` isn't available.  Statics declared with
+    /// `thread_local!` can't be exported, so this only applies to variables private to the
+    /// translation unit, and only to those whose initializer is compiled in place rather than run
+    /// by `run_static_initializers`.
+    fn uses_thread_local_macro(&self, decl_id: CDeclId) -> bool {
+        if !self.tcfg.stable || self.tcfg.emit_no_std {
+            return false;
+        }
+        match self.ast_context[decl_id].kind {
+            CDeclKind::Variable {
+                has_thread_duration: true,
+                is_externally_visible: false,
+                is_defn: true,
+                initializer,
+                typ,
+                ref attrs,
+                ..
+            } => {
+                !self.static_initializer_is_uncompilable(initializer, typ)
+                    && !attrs.iter().any(|attr| {
+                        matches!(attr, c_ast::Attribute::Used | c_ast::Attribute::Section(_))
+                    })
+            }
+            _ => false,
+        }
+    }
+
+    /// Declare a thread-local variable as
+    /// `thread_local! { static NAME: UnsafeCell<T> = UnsafeCell::new(init); }`.
+    /// References to it become `*NAME.with(UnsafeCell::get)`, which is a place like the
+    /// `static mut` we'd otherwise declare.
+    fn thread_local_macro_item(
+        &self,
+        span: Span,
+        name: &str,
+        ty: Box<Type>,
+        init: Box<Expr>,
+    ) -> Box<Item> {
+        use syn::__private::ToTokens;
+
+        let cell_ty = mk().path_ty(mk().abs_path(vec![
+            mk().path_segment("core"),
+            mk().path_segment("cell"),
+            mk().path_segment_with_args("UnsafeCell", mk().angle_bracketed_args(vec![ty])),
+        ]));
+        let cell_init = mk().call_expr(
+            mk().abs_path_expr(vec!["core", "cell", "UnsafeCell", "new"]),
+            vec![init],
+        );
+        let static_def = if self.cur_file.borrow().is_some() {
+            mk().pub_()
+        } else {
+            mk()
+        };
+        let static_item = static_def.static_item(name, cell_ty, cell_init);
+        mk().span(span).mac_item(mk().mac(
+            mk().abs_path(vec!["std", "thread_local"]),
+            static_item.to_token_stream(),
+            MacroDelimiter::Brace(Default::default()),
+        ))
+    }
+
+    /// Translate an incomplete or excluded type to an extern type, or in `--stable` mode,
+    /// where extern types aren't available, to an opaque zero-sized struct.
+    fn convert_opaque_type(&self, span: Span, name: String) -> ConvertedDecl {
+        if self.tcfg.stable {
+            let private_ty = mk().array_ty(
+                mk().path_ty(vec!["u8"]),
+                mk().lit_expr(mk().int_unsuffixed_lit(0)),
+            );
+            let private_field = mk().struct_field("_private", private_ty);
+            let struct_item = mk()
+                .span(span)
+                .pub_()
+                .call_attr("repr", vec!["C"])
+                .struct_item(name, vec![private_field], false);
+            ConvertedDecl::Item(struct_item)
+        } else {
+            self.use_feature("extern_types");
+            ConvertedDecl::ForeignItem(mk().span(span).pub_().ty_foreign_item(name))
+        }
+    }
+
     fn convert_decl(&self, ctx: ExprContext, decl_id: CDeclId) -> TranslationResult<ConvertedDecl> {
         let decl = self
             .ast_context
@@ -1591,6 +1953,14 @@ impl<'c> Translation<'c> {
             .get_span(SomeId::Decl(decl_id))
             .unwrap_or_else(Span::call_site);
 
+        let is_excluded = self.is_excluded_decl(decl_id);
+        if let (false, Some(name)) = (is_excluded, self.excluded_record_name(decl_id)) {
+            warn!(
+                "Translating excluded type {} since it is used by value, through its fields or in sizeof",
+                name
+            );
+        }
+
         use CDeclKind::*;
         match decl.kind {
             Struct { fields: None, .. }
@@ -1599,15 +1969,24 @@ impl<'c> Translation<'c> {
                 integral_type: None,
                 ..
             } => {
-                self.use_feature("extern_types");
                 let name = self
                     .type_converter
                     .borrow()
                     .resolve_decl_name(decl_id)
                     .unwrap();
 
-                let extern_item = mk().span(span).pub_().ty_foreign_item(name);
-                Ok(ConvertedDecl::ForeignItem(extern_item))
+                Ok(self.convert_opaque_type(span, name))
+            }
+
+            // Excluded structs and unions are opaque, like incomplete ones
+            Struct { .. } | Union { .. } if is_excluded => {
+                let name = self
+                    .type_converter
+                    .borrow()
+                    .resolve_decl_name(decl_id)
+                    .unwrap();
+
+                Ok(self.convert_opaque_type(span, name))
             }
 
             Struct {
@@ -1822,6 +2201,11 @@ impl<'c> Translation<'c> {
                 "Field declarations should be handled inside structs/unions",
             )),
 
+            Enum {
+                integral_type: Some(_),
+                ..
+            } if self.rust_enums.contains(&decl_id) => self.convert_rust_enum(span, decl_id),
+
             Enum {
                 integral_type: Some(integral_type),
                 ..
@@ -1837,6 +2221,14 @@ impl<'c> Translation<'c> {
                 ))
             }
 
+            EnumConstant { .. }
+                if self
+                    .rust_enums
+                    .contains(&self.ast_context.parents[&decl_id]) =>
+            {
+                Ok(ConvertedDecl::NoItem)
+            }
+
             EnumConstant { value, .. } => {
                 let name = self
                     .renamer
@@ -1917,6 +2309,22 @@ impl<'c> Translation<'c> {
 
                 let is_main = self.ast_context.c_main == Some(decl_id);
 
+                if body.is_some()
+                    && !is_global
+                    && self.tcfg.decl_filter.exclude_functions.contains(name)
+                {
+                    warn!(
+                        "Translating excluded function {} since it has internal linkage",
+                        name
+                    );
+                }
+                // Excluded functions are declared and linked from the C object
+                let (is_inline, body) = if is_excluded {
+                    (false, None)
+                } else {
+                    (is_inline, body)
+                };
+
                 let converted_function = self.convert_function(
                     ctx,
                     ConvertFunctionArgs {
@@ -1926,6 +2334,7 @@ impl<'c> Translation<'c> {
                         is_main,
                         is_variadic,
                         is_extern,
+                        can_unwind: self.unwinding_fns.contains(&decl_id),
                         new_name,
                         name,
                         arguments: &args,
@@ -1936,23 +2345,30 @@ impl<'c> Translation<'c> {
                 );
 
                 converted_function.or_else(|e| match self.tcfg.replace_unsupported_decls {
-                    ReplaceMode::Extern if body.is_none() => self.convert_function(
-                        ctx,
-                        ConvertFunctionArgs {
-                            span,
-                            is_global,
-                            is_inline: false,
-                            is_main,
-                            is_variadic,
-                            is_extern,
-                            new_name,
-                            name,
-                            arguments: &args,
-                            return_type: ret,
-                            body: None,
-                            attrs,
-                        },
-                    ),
+                    ReplaceMode::Extern if body.is_none() => {
+                        let replaced = self.convert_function(
+                            ctx,
+                            ConvertFunctionArgs {
+                                span,
+                                is_global,
+                                is_inline: false,
+                                is_main,
+                                is_variadic,
+                                is_extern,
+                                can_unwind: false,
+                                new_name,
+                                name,
+                                arguments: &args,
+                                return_type: ret,
+                                body: None,
+                                attrs,
+                            },
+                        );
+                        if replaced.is_ok() {
+                            self.replaced_decls.borrow_mut().insert(decl_id, e);
+                        }
+                        replaced
+                    }
                     _ => Err(e),
                 })
             }
@@ -1984,24 +2400,25 @@ impl<'c> Translation<'c> {
                 ))
             }
 
-            // Externally-visible variable without initializer (definition elsewhere)
+            // Externally-visible variable without initializer (definition elsewhere),
+            // or an excluded definition that we link from the C object
             Variable {
                 is_externally_visible: true,
                 has_static_duration,
                 has_thread_duration,
-                is_defn: false,
+                is_defn,
                 ref ident,
                 initializer,
                 typ,
                 ref attrs,
                 ..
-            } => {
+            } if !is_defn || is_excluded => {
                 assert!(
                     has_static_duration || has_thread_duration,
                     "An extern variable must be static or thread-local"
                 );
                 assert!(
-                    initializer.is_none(),
+                    is_defn || initializer.is_none(),
                     "An extern variable that isn't a definition can't have an initializer"
                 );
 
@@ -2055,7 +2472,8 @@ impl<'c> Translation<'c> {
                 ref attrs,
                 ..
             } if has_static_duration || has_thread_duration => {
-                if has_thread_duration {
+                let uses_thread_local_macro = self.uses_thread_local_macro(decl_id);
+                if has_thread_duration && !uses_thread_local_macro {
                     self.use_feature("thread_local");
                 }
 
@@ -2111,6 +2529,12 @@ impl<'c> Translation<'c> {
                     (ty, init)
                 };
 
+                if uses_thread_local_macro {
+                    return Ok(ConvertedDecl::Item(
+                        self.thread_local_macro_item(span, new_name, ty, init),
+                    ));
+                }
+
                 let static_def = if is_externally_visible {
                     mk_linkage(false, new_name, ident).pub_().extern_("C")
                 } else if self.cur_file.borrow().is_some() {
@@ -2188,9 +2612,7 @@ impl<'c> Translation<'c> {
                 }
             }
 
-            // We aren't doing anything with the definitions of function-like
-            // macros yet.
-            MacroFunction { .. } => Ok(ConvertedDecl::NoItem),
+            MacroFunction { .. } => self.convert_fn_macro(decl_id),
 
             // Do not translate non-canonical decls. They will be translated at
             // their canonical declaration.
@@ -2263,6 +2685,7 @@ impl<'c> Translation<'c> {
             is_main,
             is_variadic,
             is_extern,
+            can_unwind,
             new_name,
             name,
             arguments,
@@ -2386,15 +2809,23 @@ impl<'c> Translation<'c> {
                 let is_extern_inline =
                     is_inline && is_extern && !attrs.contains(&c_ast::Attribute::GnuInline);
 
+                // Unwinding out of an `extern "C"` function aborts
+                let abi = if can_unwind {
+                    self.use_feature("c_unwind");
+                    "C-unwind"
+                } else {
+                    "C"
+                };
+
                 // Only add linkage attributes if the function is `extern`
                 let mut mk_ = if is_main {
                     mk()
                 } else if (is_global && !is_inline) || is_extern_inline {
-                    mk_linkage(false, new_name, name).extern_("C").pub_()
+                    mk_linkage(false, new_name, name).extern_(abi).pub_()
                 } else if self.cur_file.borrow().is_some() {
-                    mk().extern_("C").pub_()
+                    mk().extern_(abi).pub_()
                 } else {
-                    mk().extern_("C")
+                    mk().extern_(abi)
                 };
 
                 for attr in attrs {
@@ -2408,8 +2839,6 @@ impl<'c> Translation<'c> {
 
                 // If this function is just a regular inline
                 if is_inline && !attrs.contains(&c_ast::Attribute::AlwaysInline) {
-                    mk_ = mk_.single_attr("inline");
-
                     // * In C99, a function defined inline will never, and a function defined extern
                     //   inline will always, emit an externally visible function.
                     // * If a non-static function is declared inline, then it must be defined in the
@@ -2423,7 +2852,16 @@ impl<'c> Translation<'c> {
                     //   even if the `inline` keyword isn't present
                     // * gnu_inline instead applies gnu89 rules. extern inline will not emit an
                     //   externally visible function.
-                    if is_global && is_extern && !attrs.contains(&c_ast::Attribute::GnuInline) {
+                    let is_exported =
+                        is_global && is_extern && !attrs.contains(&c_ast::Attribute::GnuInline);
+
+                    // `#[linkage]` is unstable, so in `--stable` mode we drop the `inline` hint
+                    // of exported functions instead, as functions that aren't `#[inline]` are
+                    // always emitted.
+                    if !(is_exported && self.tcfg.stable) {
+                        mk_ = mk_.single_attr("inline");
+                    }
+                    if is_exported && !self.tcfg.stable {
                         self.use_feature("linkage");
                         // ensures that public inlined rust function can be used in other modules
                         mk_ = mk_.str_attr("linkage", "external");
@@ -2964,6 +3402,8 @@ impl<'c> Translation<'c> {
         if let Some(cur_file) = *self.cur_file.borrow() {
             self.import_type(type_id, cur_file);
         }
+        self.import_simd_vector_type(type_id)?;
+        self.import_complex_type(type_id);
         self.type_converter
             .borrow_mut()
             .convert(&self.ast_context, type_id)
@@ -3195,6 +3635,8 @@ impl<'c> Translation<'c> {
         let ty = self.convert_type(type_id)?;
         let tys = vec![ty];
         let mut path = vec![mk().path_segment("core")];
+        // `align_of` is the ABI alignment, which can be smaller than the preferred alignment
+        // (like for `double` on 32-bit x86), so `--stable` reports this instead of lowering it
         if preferred {
             self.use_feature("core_intrinsics");
             path.push(mk().path_segment("intrinsics"));
@@ -3245,6 +3687,12 @@ impl<'c> Translation<'c> {
             self.ast_context[expr_id]
         );
 
+        if self.tcfg.translate_fn_macros {
+            if let Some(converted) = self.convert_fn_macro_param(ctx, expr_id)? {
+                return Ok(converted);
+            }
+        }
+
         if self.tcfg.translate_const_macros {
             if let Some(converted) = self.convert_macro_expansion(ctx, expr_id)? {
                 return Ok(converted);
@@ -3252,9 +3700,7 @@ impl<'c> Translation<'c> {
         }
 
         if self.tcfg.translate_fn_macros {
-            let text = self.ast_context.macro_expansion_text.get(&expr_id);
-            if let Some(converted) = text.and_then(|text| self.convert_macro_invocation(ctx, text))
-            {
+            if let Some(converted) = self.convert_fn_macro_invocation(ctx, expr_id)? {
                 return Ok(converted);
             }
         }
@@ -3275,7 +3721,9 @@ impl<'c> Translation<'c> {
                         e.context(TranslationErrorKind::OldLLVMSimd),
                     )
                 }),
-            ConvertVector(..) => Err(TranslationError::generic("convert vector not supported")),
+            ConvertVector(ty, ref child_expr_ids) => {
+                self.convert_vector_conversion(ctx, ty, child_expr_ids[0])
+            }
 
             UnaryType(_ty, kind, opt_expr, arg_ty) => {
                 let result = match kind {
@@ -3336,8 +3784,15 @@ impl<'c> Translation<'c> {
                     .get(&decl_id)
                     .ok_or_else(|| format_err!("name not declared: '{}'", varname))?;
 
+                // Constants of enums translated to Rust enums become variant paths
+                let variant = self.rust_enum_variant(decl_id);
+
+                if variant.is_none() {
+                    self.use_fn_macro_item(decl_id, &rustname);
+                }
+
                 // Import the referenced global decl into our submodule
-                if self.tcfg.reorganize_definitions {
+                if self.tcfg.reorganize_definitions && variant.is_none() {
                     if let Some(cur_file) = self.cur_file.borrow().as_ref() {
                         self.add_import(*cur_file, decl_id, &rustname);
                         // match decl {
@@ -3347,7 +3802,19 @@ impl<'c> Translation<'c> {
                     }
                 }
 
-                let mut val = mk().path_expr(vec![rustname]);
+                let mut val = variant.unwrap_or_else(|| mk().path_expr(vec![rustname]));
+                let mut set_unsafe = false;
+
+                if self.uses_thread_local_macro(decl_id) {
+                    let cell_ptr = mk().method_call_expr(
+                        val,
+                        "with",
+                        vec![mk().abs_path_expr(vec!["core", "cell", "UnsafeCell", "get"])],
+                    );
+                    val =
+                        mk().paren_expr(mk().unary_expr(UnOp::Deref(Default::default()), cell_ptr));
+                    set_unsafe = true;
+                }
 
                 // If the variable is volatile and used as something that isn't an LValue, this
                 // constitutes a volatile read.
@@ -3371,7 +3838,6 @@ impl<'c> Translation<'c> {
 
                 // If we are referring to a function and need its address, we
                 // need to cast it to fn() to ensure that it has a real address.
-                let mut set_unsafe = false;
                 if ctx.needs_address() {
                     if let &CDeclKind::Function { ref parameters, .. } = decl {
                         let ty = self.convert_type(qual_ty.ctype)?;
@@ -3476,6 +3942,8 @@ impl<'c> Translation<'c> {
 
             Literal(ty, ref kind) => self.convert_literal(ctx, ty, kind),
 
+            ImaginaryLiteral(ty, val) => self.convert_imaginary_literal(ctx, ty, val),
+
             ImplicitCast(ty, expr, kind, opt_field_id, _)
             | ExplicitCast(ty, expr, kind, opt_field_id, _) => {
                 let is_explicit = matches!(expr_kind, CExprKind::ExplicitCast(..));
@@ -3769,6 +4237,12 @@ impl<'c> Translation<'c> {
                     // callee is a declref
                     if matches!(self.ast_context[fexp].kind, CExprKind::DeclRef(..)) =>
                         {
+                            if let Some(call) = self.convert_jmp_call(ctx, expr_id, args)? {
+                                return Ok(call);
+                            }
+                            if let Some(call) = self.convert_complex_call(ctx, fexp, args)? {
+                                return Ok(call);
+                            }
                             self.convert_expr(ctx.used(), fexp)?
                         }
 
@@ -4057,23 +4531,6 @@ impl<'c> Translation<'c> {
         Ok(None)
     }
 
-    fn convert_macro_invocation(
-        &self,
-        _ctx: ExprContext,
-        text: &str,
-    ) -> Option<WithStmts<Box<Expr>>> {
-        let mut split = text.splitn(2, '(');
-        let ident = split.next()?;
-        let args = split.next()?.trim_end_matches(')');
-
-        let ts: TokenStream = syn::parse_str(args).ok()?;
-        Some(WithStmts::new_val(mk().mac_expr(mk().mac(
-            mk().path(ident),
-            ts,
-            MacroDelimiter::Paren(Default::default()),
-        ))))
-    }
-
     /// If `ctx` is unused, convert `expr` to a semi statement, otherwise return
     /// `expr`.
     fn convert_side_effects_expr(
@@ -4439,7 +4896,9 @@ impl<'c> Translation<'c> {
 
             CastKind::IntegralToBoolean
             | CastKind::FloatingToBoolean
-            | CastKind::PointerToBoolean => {
+            | CastKind::PointerToBoolean
+            | CastKind::FloatingComplexToBoolean
+            | CastKind::IntegralComplexToBoolean => {
                 if let Some(expr) = expr {
                     self.convert_condition(ctx, true, expr)
                 } else {
@@ -4459,14 +4918,11 @@ impl<'c> Translation<'c> {
             | CastKind::IntegralComplexToReal
             | CastKind::IntegralRealToComplex
             | CastKind::IntegralComplexCast
-            | CastKind::IntegralComplexToFloatingComplex
-            | CastKind::IntegralComplexToBoolean => Err(TranslationError::generic(
-                "TODO casts with complex numbers not supported",
-            )),
+            | CastKind::IntegralComplexToFloatingComplex => {
+                self.convert_complex_cast(ctx, kind, ty, val)
+            }
 
-            CastKind::VectorSplat => Err(TranslationError::generic(
-                "TODO vector splat casts not supported",
-            )),
+            CastKind::VectorSplat => self.convert_vector_splat(ty, val),
         }
     }
 
@@ -4786,6 +5242,14 @@ impl<'c> Translation<'c> {
             } else {
                 mk().unary_expr(UnOp::Not(Default::default()), val)
             }
+        } else if let CTypeKind::Complex(elt) = *ty {
+            // A complex number is true if either part is nonzero
+            let zero = self.complex_zero_value(elt);
+            if target {
+                mk().binary_expr(BinOp::Ne(Default::default()), val, zero)
+            } else {
+                mk().binary_expr(BinOp::Eq(Default::default()), val, zero)
+            }
         } else {
             // One simplification we can make at the cost of inspecting `val` more closely: if `val`
             // is already in the form `(x <op> y) as <ty>` where `<op>` is a Rust operator
@@ -5019,7 +5483,9 @@ impl<'c> Translation<'c> {
                 }
             }
 
-            CDeclKind::Function { .. } | CDeclKind::MacroFunction { .. } => {
+            CDeclKind::MacroFunction { .. } => self.import_fn_macro_types(decl_id, decl_file_id),
+
+            CDeclKind::Function { .. } => {
                 // TODO: We may need to explicitly skip SIMD functions here when getting types for
                 // a fn definition in a header since SIMD headers define functions but we're using imports
                 // rather than translating the original definition
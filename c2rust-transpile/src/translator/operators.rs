@@ -160,6 +160,12 @@ impl<'c> Translation<'c> {
                 // once the above issue is resolved.
                 if op == c_ast::BinOp::EqualEqual || op == c_ast::BinOp::NotEqual {
                     ctx = ctx.decay_ref();
+
+                    if !ctx.is_unused() {
+                        if let Some(val) = self.convert_rust_enum_comparison(ctx, op, lhs, rhs)? {
+                            return Ok(val);
+                        }
+                    }
                 }
 
                 let ty = self.convert_type(type_id.ctype)?;
@@ -584,6 +590,24 @@ impl<'c> Translation<'c> {
             lhs_rhs_ids,
         } = args;
 
+        if let CTypeKind::Vector(..) = self.ast_context.resolve_type(ctype).kind {
+            return self.convert_vector_binary_operator(op, ctype, lhs, rhs);
+        }
+
+        // `num_complex` implements these operators for mixed real and complex
+        // operands as well, matching the C99 Annex G semantics where clang
+        // leaves the real operand unconverted
+        if self.is_complex_type(ctype) {
+            let op = match op {
+                c_ast::BinOp::Add => BinOp::Add(Default::default()),
+                c_ast::BinOp::Subtract => BinOp::Sub(Default::default()),
+                c_ast::BinOp::Multiply => BinOp::Mul(Default::default()),
+                c_ast::BinOp::Divide => BinOp::Div(Default::default()),
+                op => return Err(format_err!("Unsupported complex operator {:?}", op).into()),
+            };
+            return Ok(mk().binary_expr(op, lhs, rhs));
+        }
+
         let is_unsigned_integral_type = self
             .ast_context
             .index(ctype)
@@ -1012,6 +1036,10 @@ impl<'c> Translation<'c> {
                     Ok(val.map(neg_expr))
                 }
             }
+            // `~` on a complex number is the GNU spelling of conjugation
+            c_ast::UnOp::Complement if self.is_complex_type(ctype) => Ok(self
+                .convert_expr(ctx.used(), arg)?
+                .map(|a| mk().method_call_expr(a, "conj", vec![]))),
             c_ast::UnOp::Complement => Ok(self
                 .convert_expr(ctx.used(), arg)?
                 .map(|a| mk().unary_expr(UnOp::Not(Default::default()), a))),
@@ -1024,9 +1052,8 @@ impl<'c> Translation<'c> {
                 let arg = self.convert_expr(ctx, arg)?;
                 Ok(arg)
             }
-            c_ast::UnOp::Real | c_ast::UnOp::Imag | c_ast::UnOp::Coawait => {
-                panic!("Unsupported extension operator")
-            }
+            c_ast::UnOp::Real | c_ast::UnOp::Imag => self.convert_real_imag(ctx, name, arg),
+            c_ast::UnOp::Coawait => panic!("Unsupported extension operator"),
         }
     }
 }
@@ -0,0 +1,601 @@
+//! Lowering of `setjmp`/`longjmp`-based error recovery.
+//!
+//! C code commonly uses `setjmp` like an exception handler:
+//!
+//! ```c
+//! if (setjmp(env)) {
+//!     /* handler */
+//! } else {
+//!     /* body, which may longjmp(env, 1) from any depth */
+//! }
+//! ```
+//!
+//! Calling `setjmp` from Rust is undefined behavior, so we lower this pattern to
+//! unwinding instead. The body runs in a closure under `catch_unwind`, `longjmp`
+//! becomes `resume_unwind` with the address of the jump buffer as its payload,
+//! and the handler runs when a payload for this buffer is caught. Payloads for
+//! other buffers, as well as genuine panics, keep unwinding.
+//!
+//! The value passed to `longjmp` is not available to the handler, so `setjmp`
+//! results that are used for anything other than this test are not lowered.
+//! These, and branches that can't be moved into a closure because control
+//! leaves them through `return`, `goto`, `break`, or `continue`, are translated
+//! as ordinary calls and flagged with a `-Wsetjmp` diagnostic.
+//!
+//! Unwinding out of an `extern "C"` function aborts, so the functions that a
+//! lowered `longjmp` can unwind out of are `extern "C-unwind"` instead. Callers
+//! in other translation units and calls through function pointers would still
+//! expect `extern "C"`, so the `setjmp`s and `longjmp`s on a jump buffer are
+//! only lowered if the buffer is a variable that isn't externally visible and
+//! is only used as their argument, every `setjmp` on it can be lowered, and
+//! none of the functions a `longjmp` to it can unwind out of is externally
+//! visible or has its address taken. The other calls are translated as
+//! ordinary calls and flagged as well.
+//!
+//! With `--emit-no-std` there is no unwinding to lower to, so nothing is
+//! lowered and the declarations that call `setjmp` or `longjmp` fail to
+//! translate instead.
+
+use super::*;
+use crate::c_ast::iterators::immediate_children;
+use crate::diagnostics::{diag, Diagnostic};
+use c2rust_ast_builder::CaptureBy;
+use std::collections::HashSet;
+
+/// Functions that save a jump context. glibc's `<setjmp.h>` defines `setjmp`
+/// and `sigsetjmp` as macros that expand to the underscored variants.
+const SETJMP_FNS: &[&str] = &["setjmp", "_setjmp", "sigsetjmp", "__sigsetjmp"];
+
+/// Functions that restore a jump context saved by one of [`SETJMP_FNS`].
+const LONGJMP_FNS: &[&str] = &["longjmp", "_longjmp", "siglongjmp", "__longjmp_chk"];
+
+/// A call that a `longjmp` can unwind out of a function through.
+enum Jump {
+    /// A call to a function defined in this translation unit
+    Call(CDeclId),
+    /// A `longjmp` to a jump buffer variable
+    Longjmp(CDeclId),
+}
+
+/// The uses of jump buffers and functions that decide which `setjmp`s and
+/// `longjmp`s can be lowered, collected by [`Translation::collect_jmp_uses`].
+#[derive(Default)]
+struct JmpUses {
+    /// Jump buffers with a `setjmp` of the form [`Translation::convert_setjmp_if`] lowers
+    caught: IndexSet<CDeclId>,
+    /// Variables used other than as the jump buffer of a lowerable `setjmp` or a `longjmp`
+    unlowerable: HashSet<CDeclId>,
+    /// Functions whose address is taken
+    address_taken: HashSet<CDeclId>,
+    /// The jumps in each function, along with the jump buffers whose lowered
+    /// `setjmp` body they are in, and so can't unwind out of the function to
+    jumps: IndexMap<CDeclId, Vec<(Jump, Vec<CDeclId>)>>,
+}
+
+impl JmpUses {
+    fn add_jump(&mut self, func: Option<CDeclId>, jump: Jump, catching: &[CDeclId]) {
+        if let Some(func) = func {
+            self.jumps
+                .entry(func)
+                .or_default()
+                .push((jump, catching.to_vec()));
+        }
+    }
+}
+
+impl<'c> Translation<'c> {
+    /// Find the jump buffers whose `setjmp`s and `longjmp`s can be lowered, and
+    /// the functions those `longjmp`s can unwind out of.
+    pub(super) fn find_setjmp_lowering(&self) -> (IndexSet<CDeclId>, IndexSet<CDeclId>) {
+        // `--emit-no-std` output has no unwinding to lower to
+        if self.tcfg.emit_no_std {
+            return Default::default();
+        }
+
+        let mut uses = JmpUses::default();
+        for (&decl_id, decl) in self.ast_context.iter_decls() {
+            let (root, func): (SomeId, _) = match decl.kind {
+                CDeclKind::Function {
+                    body: Some(body), ..
+                } => (body.into(), Some(decl_id)),
+                CDeclKind::Variable {
+                    initializer: Some(initializer),
+                    ..
+                } => (initializer.into(), None),
+                _ => continue,
+            };
+            self.collect_jmp_uses(root, func, &mut vec![], &mut uses);
+        }
+
+        let mut lowered_bufs = IndexSet::new();
+        let mut unwinding_fns = IndexSet::new();
+        for &buf in &uses.caught {
+            let is_externally_visible = matches!(
+                self.ast_context[buf].kind,
+                CDeclKind::Variable {
+                    is_externally_visible: true,
+                    ..
+                }
+            );
+            if is_externally_visible || uses.unlowerable.contains(&buf) {
+                continue;
+            }
+
+            // The functions that a `longjmp` to `buf` can unwind out of
+            let mut unwinding = IndexSet::new();
+            loop {
+                let len = unwinding.len();
+                for (&func, jumps) in &uses.jumps {
+                    let unwinds = jumps.iter().any(|(jump, catching)| {
+                        !catching.contains(&buf)
+                            && match *jump {
+                                Jump::Call(callee) => unwinding.contains(&callee),
+                                Jump::Longjmp(jmp_buf) => jmp_buf == buf,
+                            }
+                    });
+                    if unwinds {
+                        unwinding.insert(func);
+                    }
+                }
+                if unwinding.len() == len {
+                    break;
+                }
+            }
+
+            let is_contained = unwinding.iter().all(|func| {
+                !uses.address_taken.contains(func)
+                    && !matches!(
+                        self.ast_context[*func].kind,
+                        CDeclKind::Function {
+                            is_global: true,
+                            ..
+                        }
+                    )
+            });
+            if is_contained {
+                lowered_bufs.insert(buf);
+                unwinding_fns.extend(unwinding);
+            }
+        }
+
+        (lowered_bufs, unwinding_fns)
+    }
+
+    /// Collect the [`JmpUses`] in `node`, which is in the body of `func` if it's
+    /// `Some`, inside the lowered `setjmp` bodies of the buffers in `catching`.
+    fn collect_jmp_uses(
+        &self,
+        node: SomeId,
+        func: Option<CDeclId>,
+        catching: &mut Vec<CDeclId>,
+        uses: &mut JmpUses,
+    ) {
+        match node {
+            SomeId::Stmt(stmt_id) => {
+                if let CStmtKind::If {
+                    scrutinee,
+                    true_variant,
+                    false_variant,
+                } = self.ast_context[stmt_id].kind
+                {
+                    let lowerable = self
+                        .match_setjmp_if_shape(scrutinee, true_variant, false_variant)
+                        .and_then(|(env, body, handler)| {
+                            Some((self.jmp_buf_decl(env)?, body, handler))
+                        });
+                    if let Some((buf, body, handler)) = lowerable {
+                        uses.caught.insert(buf);
+                        catching.push(buf);
+                        self.collect_jmp_uses(body.into(), func, catching, uses);
+                        catching.pop();
+                        self.collect_jmp_uses(handler.into(), func, catching, uses);
+                        return;
+                    }
+                }
+            }
+            SomeId::Expr(expr_id) => match self.ast_context[expr_id].kind {
+                CExprKind::Call(_, callee, ref args) => {
+                    let is_jmp_fn =
+                        |name: &str| SETJMP_FNS.contains(&name) || LONGJMP_FNS.contains(&name);
+                    let jmp = match self.extern_call(expr_id) {
+                        Some((name, &[env, ..])) if is_jmp_fn(name) => {
+                            self.jmp_buf_decl(env).map(|buf| (name, buf))
+                        }
+                        _ => None,
+                    };
+                    let args = match jmp {
+                        Some((name, buf)) => {
+                            if SETJMP_FNS.contains(&name) {
+                                // Only the `setjmp`s matched above are lowered
+                                uses.unlowerable.insert(buf);
+                            } else {
+                                uses.add_jump(func, Jump::Longjmp(buf), catching);
+                            }
+                            &args[1..]
+                        }
+                        None => {
+                            match *self.ast_context.resolve_expr(callee).1 {
+                                CExprKind::DeclRef(_, decl_id, _)
+                                    if matches!(
+                                        self.ast_context[decl_id].kind,
+                                        CDeclKind::Function { .. }
+                                    ) =>
+                                {
+                                    uses.add_jump(func, Jump::Call(decl_id), catching)
+                                }
+                                _ => self.collect_jmp_uses(callee.into(), func, catching, uses),
+                            }
+                            &args[..]
+                        }
+                    };
+                    for &arg in args {
+                        self.collect_jmp_uses(arg.into(), func, catching, uses);
+                    }
+                    return;
+                }
+                CExprKind::DeclRef(_, decl_id, _) => {
+                    match self.ast_context[decl_id].kind {
+                        CDeclKind::Function { .. } => uses.address_taken.insert(decl_id),
+                        _ => uses.unlowerable.insert(decl_id),
+                    };
+                    return;
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+        for child in immediate_children(&self.ast_context, node) {
+            self.collect_jmp_uses(child, func, catching, uses);
+        }
+    }
+
+    /// The variable that the jump buffer argument of a `setjmp` or `longjmp`
+    /// names, if it names one directly.
+    fn jmp_buf_decl(&self, env: CExprId) -> Option<CDeclId> {
+        match *self.ast_context.resolve_expr(env).1 {
+            CExprKind::DeclRef(_, decl_id, _) => match self.ast_context[decl_id].kind {
+                CDeclKind::Variable { .. } => Some(decl_id),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Returns the name and arguments of a direct call to a function without a
+    /// body in this translation unit, looking through parentheses and casts.
+    fn extern_call(&self, expr: CExprId) -> Option<(&str, &[CExprId])> {
+        let (func, args) = match self.ast_context.resolve_expr(expr).1 {
+            CExprKind::Call(_, func, args) => (*func, args),
+            _ => return None,
+        };
+        match self.ast_context.resolve_expr(func).1 {
+            CExprKind::DeclRef(_, decl_id, _) => match self.ast_context[*decl_id].kind {
+                CDeclKind::Function {
+                    ref name,
+                    body: None,
+                    ..
+                } => Some((name.as_str(), args.as_slice())),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Match a condition that tests the result of `setjmp`. Returns the jump
+    /// buffer argument and whether the condition holds after a `longjmp`.
+    fn match_setjmp_condition(&self, cond: CExprId) -> Option<(CExprId, bool)> {
+        let is_zero = |expr: CExprId| {
+            matches!(
+                self.ast_context.resolve_expr(expr).1,
+                CExprKind::Literal(_, CLiteral::Integer(0, _))
+            )
+        };
+        match *self.ast_context.resolve_expr(cond).1 {
+            CExprKind::Unary(_, c_ast::UnOp::Not, expr, _) => self
+                .match_setjmp_condition(expr)
+                .map(|(env, after_jmp)| (env, !after_jmp)),
+            CExprKind::Binary(
+                _,
+                op @ (c_ast::BinOp::EqualEqual | c_ast::BinOp::NotEqual),
+                lhs,
+                rhs,
+                _,
+                _,
+            ) => {
+                let expr = if is_zero(rhs) {
+                    lhs
+                } else if is_zero(lhs) {
+                    rhs
+                } else {
+                    return None;
+                };
+                let is_ne = op == c_ast::BinOp::NotEqual;
+                self.match_setjmp_condition(expr)
+                    .map(|(env, after_jmp)| (env, after_jmp == is_ne))
+            }
+            _ => match self.extern_call(cond)? {
+                (name, &[env, ..]) if SETJMP_FNS.contains(&name) => Some((env, true)),
+                _ => None,
+            },
+        }
+    }
+
+    /// Returns true if control can only leave `stmt_id` by reaching its end or
+    /// by `longjmp`, so that it can be moved into a closure.
+    fn is_self_contained(&self, stmt_id: CStmtId) -> bool {
+        let leaves = DFExpr::new(&self.ast_context, stmt_id.into())
+            .flat_map(SomeId::stmt)
+            .any(|stmt_id| {
+                matches!(
+                    self.ast_context[stmt_id].kind,
+                    CStmtKind::Return(..) | CStmtKind::Goto(..) | CStmtKind::Label(..)
+                )
+            });
+        !leaves && self.has_local_jumps(stmt_id, false, false)
+    }
+
+    /// Returns false if a `break`, `continue`, or `case` label in `stmt_id`
+    /// belongs to a loop or `switch` around it.
+    fn has_local_jumps(&self, stmt_id: CStmtId, in_loop: bool, in_switch: bool) -> bool {
+        let local = |stmt_id: CStmtId| self.has_local_jumps(stmt_id, in_loop, in_switch);
+        match self.ast_context[stmt_id].kind {
+            CStmtKind::Break => in_loop || in_switch,
+            CStmtKind::Continue => in_loop,
+            CStmtKind::Case(_, stmt_id, _) | CStmtKind::Default(stmt_id) => {
+                in_switch && local(stmt_id)
+            }
+            CStmtKind::Label(stmt_id)
+            | CStmtKind::Attributed {
+                substatement: stmt_id,
+                ..
+            } => local(stmt_id),
+            CStmtKind::Compound(ref stmt_ids) => stmt_ids.iter().all(|&stmt_id| local(stmt_id)),
+            CStmtKind::If {
+                true_variant,
+                false_variant,
+                ..
+            } => local(true_variant) && false_variant.map_or(true, local),
+            CStmtKind::Switch { body, .. } => self.has_local_jumps(body, in_loop, true),
+            CStmtKind::While { body, .. }
+            | CStmtKind::DoWhile { body, .. }
+            | CStmtKind::ForLoop { body, .. } => self.has_local_jumps(body, true, in_switch),
+            _ => true,
+        }
+    }
+
+    /// Match `if (setjmp(env)) { handler } else { body }` on a jump buffer
+    /// whose `setjmp`s and `longjmp`s are lowered. Returns the jump buffer
+    /// argument, the body, and the handler.
+    fn match_setjmp_if(
+        &self,
+        scrutinee: CExprId,
+        true_variant: CStmtId,
+        false_variant: Option<CStmtId>,
+    ) -> Option<(CExprId, CStmtId, CStmtId)> {
+        self.match_setjmp_if_shape(scrutinee, true_variant, false_variant)
+            .filter(|&(env, _, _)| {
+                self.jmp_buf_decl(env)
+                    .map_or(false, |buf| self.lowered_jmp_bufs.contains(&buf))
+            })
+    }
+
+    /// Match `if (setjmp(env)) { handler } else { body }`, or a negation of it,
+    /// where both branches are self-contained. Returns the jump buffer argument,
+    /// the body, and the handler.
+    fn match_setjmp_if_shape(
+        &self,
+        scrutinee: CExprId,
+        true_variant: CStmtId,
+        false_variant: Option<CStmtId>,
+    ) -> Option<(CExprId, CStmtId, CStmtId)> {
+        let (env, after_jmp) = self.match_setjmp_condition(scrutinee)?;
+        let (body, handler) = if after_jmp {
+            (false_variant?, true_variant)
+        } else {
+            (true_variant, false_variant?)
+        };
+        if self.is_self_contained(body) && self.is_self_contained(handler) {
+            Some((env, body, handler))
+        } else {
+            None
+        }
+    }
+
+    /// Returns true if the `if` statement with these parts can be translated
+    /// with [`Translation::convert_setjmp_if`].
+    pub fn is_setjmp_if(
+        &self,
+        scrutinee: CExprId,
+        true_variant: CStmtId,
+        false_variant: Option<CStmtId>,
+    ) -> bool {
+        self.match_setjmp_if(scrutinee, true_variant, false_variant)
+            .is_some()
+    }
+
+    /// Lower `if (setjmp(env)) { handler } else { body }` to an unwinding-based
+    /// equivalent. Any other use of `setjmp` is translated (and flagged) as an
+    /// ordinary call.
+    pub fn convert_setjmp_if(
+        &self,
+        ctx: ExprContext,
+        scrutinee: CExprId,
+        true_variant: CStmtId,
+        false_variant: Option<CStmtId>,
+    ) -> TranslationResult<WithStmts<Box<Expr>>> {
+        let (env, body, handler) = self
+            .match_setjmp_if(scrutinee, true_variant, false_variant)
+            .ok_or_else(|| format_err!("Not a setjmp if statement"))?;
+
+        let jmp_env = self.renamer.borrow_mut().pick_name("jmp_env");
+        let env = self.convert_expr(ctx.used(), env)?;
+        let body_stmts = self.convert_function_body(
+            ctx,
+            &format!("<setjmp-body_{:?}>", body),
+            &[body],
+            cfg::ImplicitReturnType::Void,
+        )?;
+        let handler_stmts = self.convert_function_body(
+            ctx,
+            &format!("<setjmp-handler_{:?}>", handler),
+            &[handler],
+            cfg::ImplicitReturnType::Void,
+        )?;
+
+        // let jmp_env = env as usize;
+        // match ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| { body })) {
+        //     Ok(()) => {}
+        //     Err(jmp) if jmp.downcast_ref::<usize>() == Some(&jmp_env) => { handler }
+        //     Err(jmp) => ::std::panic::resume_unwind(jmp),
+        // }
+        env.and_then(|env| {
+            let usize_ty = mk().path_ty(vec!["usize"]);
+            let env_local = mk().local(
+                mk().ident_pat(&jmp_env),
+                None,
+                Some(mk().cast_expr(env, usize_ty.clone())),
+            );
+
+            let closure_decl = mk().fn_decl("body", vec![], None, ReturnType::Default);
+            let closure = mk().closure_expr(
+                CaptureBy::Ref,
+                Movability::Movable,
+                *closure_decl,
+                mk().block_expr(mk().block(body_stmts)),
+            );
+            let caught = mk().call_expr(
+                mk().abs_path_expr(vec!["std", "panic", "catch_unwind"]),
+                vec![mk().call_expr(
+                    mk().abs_path_expr(vec!["std", "panic", "AssertUnwindSafe"]),
+                    vec![closure],
+                )],
+            );
+
+            let downcast = mk().method_call_expr(
+                mk().ident_expr("jmp"),
+                mk().path_segment_with_args(
+                    "downcast_ref",
+                    mk().angle_bracketed_args(vec![usize_ty]),
+                ),
+                vec![],
+            );
+            let is_this_env = mk().binary_expr(
+                BinOp::Eq(Default::default()),
+                downcast,
+                mk().call_expr(
+                    mk().path_expr(vec!["Some"]),
+                    vec![mk().addr_of_expr(mk().ident_expr(&jmp_env))],
+                ),
+            );
+            let resume = mk().call_expr(
+                mk().abs_path_expr(vec!["std", "panic", "resume_unwind"]),
+                vec![mk().ident_expr("jmp")],
+            );
+            let arms = vec![
+                mk().arm(
+                    mk().tuple_struct_pat(vec!["Ok"], vec![mk().tuple_pat(vec![])]),
+                    None,
+                    mk().block_expr(mk().block(vec![])),
+                ),
+                mk().arm(
+                    mk().tuple_struct_pat(vec!["Err"], vec![mk().ident_pat("jmp")]),
+                    Some(is_this_env),
+                    mk().block_expr(mk().block(handler_stmts)),
+                ),
+                mk().arm(
+                    mk().tuple_struct_pat(vec!["Err"], vec![mk().ident_pat("jmp")]),
+                    None,
+                    resume,
+                ),
+            ];
+
+            Ok(WithStmts::new(
+                vec![mk().local_stmt(Box::new(env_local))],
+                mk().match_expr(caught, arms),
+            ))
+        })
+    }
+
+    /// Translate a call to `longjmp` into `resume_unwind` with a payload that
+    /// [`Translation::convert_setjmp_if`] catches, and flag calls to `setjmp`
+    /// that weren't lowered with it. Returns `None` for any other call, and an
+    /// error for calls to either with `--emit-no-std`.
+    pub fn convert_jmp_call(
+        &self,
+        ctx: ExprContext,
+        call_id: CExprId,
+        args: &[CExprId],
+    ) -> TranslationResult<Option<WithStmts<Box<Expr>>>> {
+        let name = match self.extern_call(call_id) {
+            Some((name, _)) => name,
+            None => return Ok(None),
+        };
+        let loc = || {
+            self.ast_context
+                .display_loc(&self.ast_context[call_id].loc)
+                .map_or_else(String::new, |loc| format!(" at {}", loc))
+        };
+
+        if self.tcfg.emit_no_std && (SETJMP_FNS.contains(&name) || LONGJMP_FNS.contains(&name)) {
+            return Err(format_err!(
+                "call to `{}`{} can't be translated with `--emit-no-std`, \
+                 which has no unwinding to lower it to",
+                name,
+                loc(),
+            )
+            .into());
+        }
+        if SETJMP_FNS.contains(&name) {
+            diag!(
+                Diagnostic::Setjmp,
+                "call to `{}`{} is not of the form `if ({}(env)) {{ ... }} else {{ ... }}` \
+                 with self-contained branches on a jump buffer that can be lowered, \
+                 so it was translated as a plain call, which is undefined behavior in Rust",
+                name,
+                loc(),
+                name,
+            );
+            return Ok(None);
+        }
+        if !LONGJMP_FNS.contains(&name) {
+            return Ok(None);
+        }
+
+        let (env, val) = match *args {
+            [env, val, ..] => (env, val),
+            _ => return Ok(None),
+        };
+        let is_lowered = self
+            .jmp_buf_decl(env)
+            .map_or(false, |buf| self.lowered_jmp_bufs.contains(&buf));
+        if !is_lowered {
+            diag!(
+                Diagnostic::Setjmp,
+                "call to `{}`{} is not to a jump buffer whose `setjmp`s were lowered, \
+                 so it was translated as a plain call",
+                name,
+                loc(),
+            );
+            return Ok(None);
+        }
+        let mut env = self.convert_expr(ctx.used(), env)?;
+        if !self.ast_context.is_expr_pure(val) {
+            let val = self.convert_expr(ctx.unused(), val)?;
+            env.prepend_stmts(val.into_stmts());
+        }
+
+        // ::std::panic::resume_unwind(::std::boxed::Box::new(env as usize))
+        let call = env.map(|env| {
+            let payload = mk().call_expr(
+                mk().abs_path_expr(vec!["std", "boxed", "Box", "new"]),
+                vec![mk().cast_expr(env, mk().path_ty(vec!["usize"]))],
+            );
+            mk().call_expr(
+                mk().abs_path_expr(vec!["std", "panic", "resume_unwind"]),
+                vec![payload],
+            )
+        });
+        self.convert_side_effects_expr(ctx, call, "longjmp is not supposed to be used")
+            .map(Some)
+    }
+}
@@ -8,6 +8,7 @@ use crate::c_ast::CExprKind::{Binary, Call, Conditional, ExplicitCast, ImplicitC
 use crate::c_ast::CLiteral::Integer;
 use crate::c_ast::CTypeKind::{Char, Double, Float, Int, LongLong, Short};
 use crate::c_ast::CastKind::{BitCast, IntegralCast};
+use crate::convert_type::{simd_vector_type_name, vector_lane_size};
 
 /// As of rustc 1.29, rust is known to be missing some SIMD functions.
 /// See <https://github.com/rust-lang-nursery/stdsimd/issues/579>
@@ -161,8 +162,11 @@ impl<'c> Translation<'c> {
             }
 
             // The majority of x86/64 SIMD is stable, however there are still some
-            // bits that are behind a feature gate.
-            self.use_feature("stdsimd");
+            // bits that are behind a feature gate. `--stable` output only gets to
+            // use the stable ones.
+            if !self.tcfg.stable {
+                self.use_feature("stdsimd");
+            }
 
             self.with_cur_file_item_store(|item_store| {
                 // REVIEW: Also a linear lookup
@@ -179,6 +183,241 @@ impl<'c> Translation<'c> {
         }
     }
 
+    /// Import the `core::arch` type used to represent `ctype` if it is a
+    /// vector type with an x86 counterpart.
+    pub fn import_simd_vector_type(&self, ctype: CTypeId) -> TranslationResult<()> {
+        if let CTypeKind::Vector(elt, len) = self.ast_context[ctype].kind {
+            if let Some(name) = simd_vector_type_name(&self.ast_context, elt.ctype, len) {
+                self.import_simd_typedef(name)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Get the lane type and the number of lanes of a vector type, which must
+    /// have an x86 counterpart.
+    fn vector_lanes(&self, ctype: CTypeId) -> TranslationResult<(CTypeId, usize)> {
+        match self.ast_context.resolve_type(ctype).kind {
+            CTypeKind::Vector(CQualTypeId { ctype, .. }, len) => {
+                if simd_vector_type_name(&self.ast_context, ctype, len).is_none() {
+                    return Err(format_err!(
+                        "Unsupported vector type of {} x {:?}",
+                        len,
+                        self.ast_context.resolve_type(ctype).kind
+                    )
+                    .into());
+                }
+                Ok((ctype, len))
+            }
+            ref t => Err(format_err!("Expected a vector type, found {:?}", t).into()),
+        }
+    }
+
+    /// Convert a vector splat cast, which copies a scalar into every lane of a
+    /// vector. Clang inserts these for mixed vector/scalar operands such as
+    /// `vec * 2.0f`.
+    pub fn convert_vector_splat(
+        &self,
+        ty: CQualTypeId,
+        val: WithStmts<Box<Expr>>,
+    ) -> TranslationResult<WithStmts<Box<Expr>>> {
+        let (elt, len) = self.vector_lanes(ty.ctype)?;
+        let kind = &self.ast_context.resolve_type(elt).kind;
+        let simd_ty = simd_vector_type_name(&self.ast_context, elt, len);
+        let (fn_name, param_ty) = match (simd_ty, vector_lane_size(&self.ast_context, kind)) {
+            (Some("__m128"), _) => ("_mm_set1_ps", None),
+            (Some("__m256"), _) => ("_mm256_set1_ps", None),
+            (Some("__m128d"), _) => ("_mm_set1_pd", None),
+            (Some("__m256d"), _) => ("_mm256_set1_pd", None),
+            (Some("__m128i"), Some(1)) => ("_mm_set1_epi8", Some("i8")),
+            (Some("__m256i"), Some(1)) => ("_mm256_set1_epi8", Some("i8")),
+            (Some("__m128i"), Some(2)) => ("_mm_set1_epi16", Some("i16")),
+            (Some("__m256i"), Some(2)) => ("_mm256_set1_epi16", Some("i16")),
+            (Some("__m128i"), Some(4)) => ("_mm_set1_epi32", Some("i32")),
+            (Some("__m256i"), Some(4)) => ("_mm256_set1_epi32", Some("i32")),
+            (Some("__m128i"), Some(8)) => ("_mm_set1_epi64x", Some("i64")),
+            (Some("__m256i"), Some(8)) => ("_mm256_set1_epi64x", Some("i64")),
+            _ => {
+                return Err(format_err!("Unsupported vector splat of {} x {:?}", len, kind).into())
+            }
+        };
+
+        self.import_simd_function(fn_name)?;
+        Ok(val.map(|val| {
+            // The integer intrinsics take signed lanes
+            let val = match param_ty {
+                Some(param_ty) => mk().cast_expr(val, mk().path_ty(vec![param_ty])),
+                None => val,
+            };
+            mk().call_expr(mk().ident_expr(fn_name), vec![val])
+        }))
+    }
+
+    /// Convert a `__builtin_convertvector` call, which converts each lane of
+    /// a vector as if by a C cast. Uses an x86 conversion intrinsic when one
+    /// exists and converts lane by lane otherwise.
+    pub fn convert_vector_conversion(
+        &self,
+        ctx: ExprContext,
+        ty: CQualTypeId,
+        src_id: CExprId,
+    ) -> TranslationResult<WithStmts<Box<Expr>>> {
+        let src_ty = self.ast_context[src_id]
+            .kind
+            .get_type()
+            .ok_or_else(|| format_err!("bad convertvector source type"))?;
+        let (src_elt, len) = self.vector_lanes(src_ty)?;
+        let (dst_elt, dst_len) = self.vector_lanes(ty.ctype)?;
+        if len != dst_len {
+            return Err(format_err!(
+                "Unsupported vector conversion from {} to {} lanes",
+                len,
+                dst_len
+            )
+            .into());
+        }
+
+        let src_kind = &self.ast_context.resolve_type(src_elt).kind;
+        let dst_kind = &self.ast_context.resolve_type(dst_elt).kind;
+        let src_simd_ty = simd_vector_type_name(&self.ast_context, src_elt, len);
+        let dst_simd_ty = simd_vector_type_name(&self.ast_context, dst_elt, len);
+
+        let val = self.convert_expr(ctx.used(), src_id)?;
+
+        let intrinsic = vector_conversion_intrinsic(src_kind, dst_kind, len)
+            .filter(|&(_, feature)| self.ast_context.target_features.contains(feature));
+        let converted = if src_simd_ty == dst_simd_ty
+            && (src_kind == dst_kind || src_kind.is_integral_type() && dst_kind.is_integral_type())
+        {
+            // Lanes of the same width and representation (e.g. a change of
+            // signedness), so there is nothing to convert
+            val
+        } else if let Some((fn_name, _)) = intrinsic {
+            self.import_simd_function(fn_name)?;
+            val.map(|val| mk().call_expr(mk().ident_expr(fn_name), vec![val]))
+        } else {
+            let src_lane_ty = self.convert_type(src_elt)?;
+            let dst_lane_ty = self.convert_type(dst_elt)?;
+            let len_expr = || mk().lit_expr(mk().int_unsuffixed_lit(len as u128));
+
+            val.and_then(|val| -> TranslationResult<_> {
+                let src =
+                    transmute_expr(mk().infer_ty(), mk().array_ty(src_lane_ty, len_expr()), val);
+                let src_name = self.renamer.borrow_mut().fresh();
+                let src_let = mk().local_stmt(Box::new(mk().local(
+                    mk().ident_pat(&src_name),
+                    None,
+                    Some(src),
+                )));
+
+                let lanes = (0..len)
+                    .map(|i| {
+                        let lane = mk().index_expr(
+                            mk().ident_expr(&src_name),
+                            mk().lit_expr(mk().int_unsuffixed_lit(i as u128)),
+                        );
+                        mk().cast_expr(lane, dst_lane_ty.clone())
+                    })
+                    .collect();
+                let lanes = mk().array_expr(lanes);
+
+                let lanes_ty = mk().array_ty(dst_lane_ty.clone(), len_expr());
+                let mut converted = WithStmts::new(
+                    vec![src_let],
+                    transmute_expr(lanes_ty, mk().infer_ty(), lanes),
+                );
+                converted.set_unsafe();
+                Ok(converted)
+            })?
+        };
+
+        if ctx.is_used() {
+            Ok(converted)
+        } else {
+            converted.and_then(|converted| {
+                Ok(WithStmts::new(
+                    vec![mk().semi_stmt(converted)],
+                    self.panic_or_err("No value for unused vector conversion"),
+                ))
+            })
+        }
+    }
+
+    /// Convert a lane-wise binary operation on two vectors. Uses an x86
+    /// intrinsic when one exists and operates lane by lane otherwise.
+    pub fn convert_vector_binary_operator(
+        &self,
+        op: c_ast::BinOp,
+        ctype: CTypeId,
+        lhs: Box<Expr>,
+        rhs: Box<Expr>,
+    ) -> TranslationResult<Box<Expr>> {
+        let (elt, len) = self.vector_lanes(ctype)?;
+        let elt_kind = &self.ast_context.resolve_type(elt).kind;
+
+        if let Some(fn_name) = vector_binary_intrinsic(&self.ast_context, op, elt_kind, len) {
+            self.import_simd_function(fn_name)?;
+            return Ok(mk().call_expr(mk().ident_expr(fn_name), vec![lhs, rhs]));
+        }
+
+        use c_ast::BinOp::*;
+        let is_integral = elt_kind.is_integral_type();
+        let lane_op = |lhs: Box<Expr>, rhs: Box<Expr>| -> TranslationResult<Box<Expr>> {
+            let method = |name: &str| mk().method_call_expr(lhs.clone(), name, vec![rhs.clone()]);
+            let binary = |op: BinOp| mk().binary_expr(op, lhs.clone(), rhs.clone());
+            Ok(match op {
+                Add if is_integral => method("wrapping_add"),
+                Subtract if is_integral => method("wrapping_sub"),
+                Multiply if is_integral => method("wrapping_mul"),
+                Add => binary(BinOp::Add(Default::default())),
+                Subtract => binary(BinOp::Sub(Default::default())),
+                Multiply => binary(BinOp::Mul(Default::default())),
+                Divide => binary(BinOp::Div(Default::default())),
+                Modulus => binary(BinOp::Rem(Default::default())),
+                BitAnd => binary(BinOp::BitAnd(Default::default())),
+                BitOr => binary(BinOp::BitOr(Default::default())),
+                BitXor => binary(BinOp::BitXor(Default::default())),
+                ShiftLeft => binary(BinOp::Shl(Default::default())),
+                ShiftRight => binary(BinOp::Shr(Default::default())),
+                op => return Err(format_err!("Unsupported vector binary operator {:?}", op).into()),
+            })
+        };
+
+        let lanes_ty = mk().array_ty(
+            self.convert_type(elt)?,
+            mk().lit_expr(mk().int_unsuffixed_lit(len as u128)),
+        );
+        let to_lanes = |val: Box<Expr>| transmute_expr(mk().infer_ty(), lanes_ty.clone(), val);
+
+        let lhs_name = self.renamer.borrow_mut().fresh();
+        let rhs_name = self.renamer.borrow_mut().fresh();
+        let lane = |name: &str, i: usize| {
+            mk().index_expr(
+                mk().ident_expr(name),
+                mk().lit_expr(mk().int_unsuffixed_lit(i as u128)),
+            )
+        };
+        let lanes = (0..len)
+            .map(|i| lane_op(lane(&lhs_name, i), lane(&rhs_name, i)))
+            .collect::<TranslationResult<Vec<_>>>()?;
+        let lanes = mk().array_expr(lanes);
+        let val = transmute_expr(lanes_ty.clone(), mk().infer_ty(), lanes);
+
+        let stmts = vec![
+            mk().local_stmt(Box::new(mk().local(
+                mk().ident_pat(&lhs_name),
+                None,
+                Some(to_lanes(lhs)),
+            ))),
+            mk().local_stmt(Box::new(mk().local(
+                mk().ident_pat(&rhs_name),
+                None,
+                Some(to_lanes(rhs)),
+            ))),
+        ];
+        Ok(WithStmts::new(stmts, val).to_expr())
+    }
+
     /// This function will strip either an implicitly casted int or explicitly casted
     /// vector as both casts are unnecessary (and problematic) for our purposes
     fn clean_int_or_vector_param(&self, expr_id: CExprId) -> CExprId {
@@ -567,3 +806,80 @@ impl<'c> Translation<'c> {
         }
     }
 }
+
+/// The x86 intrinsic converting the lanes of a `len` lane vector of `src`
+/// to `dst`, if there is one, and the target feature it needs. Float to
+/// integer conversions truncate, like C casts.
+fn vector_conversion_intrinsic(
+    src: &CTypeKind,
+    dst: &CTypeKind,
+    len: usize,
+) -> Option<(&'static str, &'static str)> {
+    use CTypeKind::*;
+    Some(match (src, dst, len) {
+        (Int, Float, 4) => ("_mm_cvtepi32_ps", "sse2"),
+        (Int, Float, 8) => ("_mm256_cvtepi32_ps", "avx"),
+        (Float, Int, 4) => ("_mm_cvttps_epi32", "sse2"),
+        (Float, Int, 8) => ("_mm256_cvttps_epi32", "avx"),
+        (Int, Double, 4) => ("_mm256_cvtepi32_pd", "avx"),
+        (Double, Int, 4) => ("_mm256_cvttpd_epi32", "avx"),
+        (Float, Double, 4) => ("_mm256_cvtps_pd", "avx"),
+        (Double, Float, 4) => ("_mm256_cvtpd_ps", "avx"),
+        // Widening integer conversions only depend on the source signedness
+        (SChar, Short | UShort, 16) => ("_mm256_cvtepi8_epi16", "avx2"),
+        (UChar, Short | UShort, 16) => ("_mm256_cvtepu8_epi16", "avx2"),
+        (Short, Int | UInt, 8) => ("_mm256_cvtepi16_epi32", "avx2"),
+        (UShort, Int | UInt, 8) => ("_mm256_cvtepu16_epi32", "avx2"),
+        (Int, LongLong | ULongLong, 4) => ("_mm256_cvtepi32_epi64", "avx2"),
+        (UInt, LongLong | ULongLong, 4) => ("_mm256_cvtepu32_epi64", "avx2"),
+        _ => return None,
+    })
+}
+
+/// The x86 intrinsic performing `op` on each lane of a `len` lane vector of
+/// `elt`, if there is one.
+fn vector_binary_intrinsic(
+    ctxt: &TypedAstContext,
+    op: c_ast::BinOp,
+    elt: &CTypeKind,
+    len: usize,
+) -> Option<&'static str> {
+    use c_ast::BinOp::*;
+    use CTypeKind::*;
+    Some(match (op, elt, len) {
+        (Add, Float, 4) => "_mm_add_ps",
+        (Subtract, Float, 4) => "_mm_sub_ps",
+        (Multiply, Float, 4) => "_mm_mul_ps",
+        (Divide, Float, 4) => "_mm_div_ps",
+        (Add, Float, 8) => "_mm256_add_ps",
+        (Subtract, Float, 8) => "_mm256_sub_ps",
+        (Multiply, Float, 8) => "_mm256_mul_ps",
+        (Divide, Float, 8) => "_mm256_div_ps",
+        (Add, Double, 2) => "_mm_add_pd",
+        (Subtract, Double, 2) => "_mm_sub_pd",
+        (Multiply, Double, 2) => "_mm_mul_pd",
+        (Divide, Double, 2) => "_mm_div_pd",
+        (Add, Double, 4) => "_mm256_add_pd",
+        (Subtract, Double, 4) => "_mm256_sub_pd",
+        (Multiply, Double, 4) => "_mm256_mul_pd",
+        (Divide, Double, 4) => "_mm256_div_pd",
+        (Add, Char | SChar | UChar, 16) => "_mm_add_epi8",
+        (Subtract, Char | SChar | UChar, 16) => "_mm_sub_epi8",
+        (Add, Short | UShort, 8) => "_mm_add_epi16",
+        (Subtract, Short | UShort, 8) => "_mm_sub_epi16",
+        (Multiply, Short | UShort, 8) => "_mm_mullo_epi16",
+        (Add, Int | UInt, 4) => "_mm_add_epi32",
+        (Subtract, Int | UInt, 4) => "_mm_sub_epi32",
+        (Add, LongLong | ULongLong, 2) => "_mm_add_epi64",
+        (Subtract, LongLong | ULongLong, 2) => "_mm_sub_epi64",
+        (BitAnd | BitOr | BitXor, _, _) if elt.is_integral_type() => {
+            match (op, vector_lane_size(ctxt, elt)? * len) {
+                (BitAnd, 16) => "_mm_and_si128",
+                (BitOr, 16) => "_mm_or_si128",
+                (BitXor, 16) => "_mm_xor_si128",
+                _ => return None,
+            }
+        }
+        _ => return None,
+    })
+}
@@ -4,9 +4,12 @@ use std::collections::HashSet;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
-use c2rust_transpile::{Diagnostic, ReplaceMode, TranspilerConfig};
+use c2rust_transpile::{DeclFilter, Diagnostic, ReplaceMode, ReportFormat, TranspilerConfig};
 
 fn main() {
+    // `--jobs` runs the AST exporter in subprocesses running this executable
+    c2rust_transpile::run_as_exporter_subprocess();
+
     let yaml = load_yaml!("../transpile.yaml");
     let matches = App::from_yaml(yaml).get_matches();
 
@@ -48,6 +51,8 @@ fn main() {
         dump_cfg_liveness: matches.is_present("dump-cfgs-liveness"),
         dump_structures: matches.is_present("dump-structures"),
         debug_ast_exporter: matches.is_present("debug-ast-exporter"),
+        save_ast: matches.value_of("save-ast").map(PathBuf::from),
+        from_ast: matches.value_of("from-ast").map(PathBuf::from),
         verbose: matches.is_present("verbose"),
 
         incremental_relooper: !matches.is_present("no-incremental-relooper"),
@@ -61,6 +66,19 @@ fn main() {
                 None
             }
         },
+        decl_filter: {
+            let mut decl_filter = match matches.value_of("decl-filter") {
+                Some(path) => DeclFilter::from_file(Path::new(path)).unwrap(),
+                None => DeclFilter::default(),
+            };
+            if let Some(names) = matches.values_of("include-decls") {
+                decl_filter.include(names);
+            }
+            if let Some(names) = matches.values_of("exclude-decls") {
+                decl_filter.exclude(names);
+            }
+            decl_filter
+        },
         debug_relooper_labels: matches.is_present("debug-labels"),
         prefix_function_names: matches.value_of("prefix-function-names").map(String::from),
 
@@ -76,6 +94,7 @@ fn main() {
 
         translate_const_macros: matches.is_present("translate-const-macros"),
         translate_fn_macros: matches.is_present("translate-fn-macros"),
+        rust_enums: matches.is_present("rust-enums"),
         disable_refactoring: matches.is_present("disable-refactoring"),
         preserve_unused_functions: matches.is_present("preserve-unused-functions"),
 
@@ -85,6 +104,12 @@ fn main() {
         overwrite_existing: matches.is_present("overwrite-existing"),
         reduce_type_annotations: matches.is_present("reduce-type-annotations"),
         reorganize_definitions: matches.is_present("reorganize-definitions"),
+        jobs: matches
+            .value_of("jobs")
+            .map(|jobs| jobs.parse().expect("Invalid number of jobs"))
+            .unwrap(),
+        incremental: matches.is_present("incremental"),
+        cache_dir: matches.value_of("cache-dir").map(PathBuf::from),
         emit_modules: matches.is_present("emit-modules"),
         emit_build_files: matches.is_present("emit-build-files"),
         output_dir: matches.value_of("output-dir").map(PathBuf::from),
@@ -101,13 +126,27 @@ fn main() {
         },
         replace_unsupported_decls: ReplaceMode::Extern,
         emit_no_std: matches.is_present("emit-no-std"),
+        stable: matches.is_present("stable"),
         enabled_warnings,
         log_level,
+        report: matches.value_of("report").map(PathBuf::from),
+        report_format: matches
+            .value_of("report-format")
+            .map(|s| ReportFormat::from_str(s).unwrap())
+            .unwrap(),
     };
     // binaries imply emit-build-files
     if !tcfg.binaries.is_empty() {
         tcfg.emit_build_files = true
     };
+    // cache-dir implies incremental
+    if tcfg.cache_dir.is_some() {
+        tcfg.incremental = true
+    };
+    // incremental runs replace the outputs of previous runs
+    if tcfg.incremental {
+        tcfg.overwrite_existing = true
+    };
     // emit-build-files implies emit-modules
     if tcfg.emit_build_files {
         tcfg.emit_modules = true
@@ -157,6 +157,7 @@ impl<'tcx> Visitor<'tcx> for CollectInstrumentationPoints<'_, 'tcx> {
     fn visit_assign(&mut self, dest: &Place<'tcx>, value: &Rvalue<'tcx>, location: Location) {
         let copy_fn = self.hooks().find("ptr_copy");
         let addr_local_fn = self.hooks().find("addr_of_local");
+        let addr_static_fn = self.hooks().find("addr_of_static");
         let ptr_contrive_fn = self.hooks().find("ptr_contrive");
         let ptr_to_int_fn = self.hooks().find("ptr_to_int");
         let load_value_fn = self.hooks().find("load_value");
@@ -257,10 +258,18 @@ impl<'tcx> Visitor<'tcx> for CollectInstrumentationPoints<'_, 'tcx> {
                     .dest(&dest)
                     .add_to(self);
             }
+            Rvalue::Use(Operand::Constant(c)) if c.check_static_ptr(self.tcx()).is_some() => {
+                // In MIR, every use of a static starts by copying its address into a local.
+                let static_did = c.check_static_ptr(self.tcx()).unwrap();
+                self.loc(location, location.successor_within_block(), addr_static_fn)
+                    .arg_var(dest)
+                    .arg_def_path_hash(static_did)
+                    .dest(&dest)
+                    .add_to(self);
+            }
             Rvalue::Use(Operand::Constant(..)) => {
                 // Track (as copies) assignments that give local names to constants so that code
                 // taking references to said constants can refer to these assignments as sources.
-                // TODO: should be replaced by AddrOfStatic when support for that is added
                 self.loc(location, location.successor_within_block(), copy_fn)
                     .arg_var(dest)
                     .dest(&dest)
@@ -1,6 +1,6 @@
 use rustc_middle::{
     mir::{Constant, ConstantKind, Local, Operand, Place},
-    ty::{self, ParamEnv, TyCtxt},
+    ty::{self, ParamEnv, Ty, TyCtxt},
 };
 use rustc_span::DUMMY_SP;
 
@@ -17,7 +17,13 @@ impl<'tcx> IntoOperand<'tcx> for Place<'tcx> {
 
 impl<'tcx> IntoOperand<'tcx> for u32 {
     fn op(self, tcx: TyCtxt<'tcx>) -> Operand<'tcx> {
-        make_const(tcx, self)
+        make_const(tcx, self.into(), tcx.types.u32)
+    }
+}
+
+impl<'tcx> IntoOperand<'tcx> for u64 {
+    fn op(self, tcx: TyCtxt<'tcx>) -> Operand<'tcx> {
+        make_const(tcx, self.into(), tcx.types.u64)
     }
 }
 
@@ -33,14 +39,10 @@ impl<'tcx> IntoOperand<'tcx> for Operand<'tcx> {
     }
 }
 
-fn make_const(tcx: TyCtxt, idx: u32) -> Operand {
+fn make_const<'tcx>(tcx: TyCtxt<'tcx>, bits: u128, ty: Ty<'tcx>) -> Operand<'tcx> {
     Operand::Constant(Box::new(Constant {
         span: DUMMY_SP,
         user_ty: None,
-        literal: ConstantKind::Ty(ty::Const::from_bits(
-            tcx,
-            idx.into(),
-            ParamEnv::empty().and(tcx.types.u32),
-        )),
+        literal: ConstantKind::Ty(ty::Const::from_bits(tcx, bits, ParamEnv::empty().and(ty))),
     }))
 }
@@ -94,14 +94,15 @@ impl<'tcx, 'a> InstrumentationApplier<'tcx, 'a> {
                 args.iter_mut().for_each(|arg| *arg = arg.to_copy());
 
                 let place_ty = &place.ty(locals, tcx).ty;
-                // The return type of a hooked fn is always a raw ptr, reference, or unit
+                // The return type of a hooked fn is a raw ptr, an integer (as for `strlen` or
+                // `strcmp`), or unit.  Integers are passed through as is, like integer arguments,
+                // so the hook's last parameter must have the same type as the hooked fn's return.
                 if place_ty.is_unit() {
                     // It's somewhat wrong to call unit an AddressUsize, but it has the pass-through
                     // semantics we want
                     InstrumentationArg::Op(ArgKind::AddressUsize(Operand::Copy(*place)))
                 } else {
-                    assert!(place_ty.is_unsafe_ptr());
-                    InstrumentationArg::Op(ArgKind::RawPtr(Operand::Copy(*place)))
+                    InstrumentationArg::Op(ArgKind::from_type(Operand::Copy(*place), place_ty))
                 }
             } else {
                 panic!(
@@ -118,6 +118,13 @@ impl<'tcx> InstrumentationBuilder<'_, 'tcx> {
         self.arg_var(index)
     }
 
+    /// Add two `u64` arguments to this [`InstrumentationPoint`] that together are the
+    /// [`DefPathHash`](rustc_span::def_id::DefPathHash) of `did`.
+    pub fn arg_def_path_hash(self, did: DefId) -> Self {
+        let (hash_0, hash_1) = self.tcx.def_path_hash(did).0.as_value();
+        self.arg_var(hash_0).arg_var(hash_1)
+    }
+
     /// Add an argument to this [`InstrumentationPoint`] that is the address of the argument.
     pub fn arg_addr_of(mut self, arg: impl IntoOperand<'tcx>) -> Self {
         let op = arg.op(self.tcx);
@@ -0,0 +1,138 @@
+//! Inspect and truncate event logs written by the `c2rust-analysis-rt` runtime.
+
+use c2rust_analysis_rt::event_log::{self, EventLogWriter, RawChunk};
+use c2rust_analysis_rt::events::ThreadId;
+use c2rust_pdg::builder::read_metadata;
+use c2rust_pdg::init;
+use clap::Parser;
+use color_eyre::eyre;
+use fs_err::File;
+use std::collections::BTreeMap;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Inspect and truncate event logs.
+#[derive(Debug, Parser)]
+#[clap(author, version, about, long_about = None)]
+enum Args {
+    /// Print a summary of an event log.
+    Inspect {
+        /// Path to an event log from a run of an instrumented program.
+        #[clap(value_parser)]
+        event_log: PathBuf,
+
+        /// Also print every event.
+        #[clap(long)]
+        events: bool,
+
+        /// Path to the instrumented program's metadata, used to print the MIR location of each
+        /// event instead of its id.
+        #[clap(long, value_parser)]
+        metadata: Option<PathBuf>,
+    },
+
+    /// Write the first events of an event log to a new event log.
+    Truncate {
+        /// Path to an event log from a run of an instrumented program.
+        #[clap(value_parser)]
+        event_log: PathBuf,
+
+        /// Path to write the truncated event log to.
+        #[clap(long, short, value_parser)]
+        output: PathBuf,
+
+        /// The number of events to keep.
+        #[clap(long)]
+        events: u64,
+    },
+}
+
+fn open(path: &Path) -> eyre::Result<BufReader<File>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    event_log::read_header(&mut reader)?;
+    Ok(reader)
+}
+
+fn inspect(event_log: &Path, print_events: bool, metadata: Option<&Path>) -> eyre::Result<()> {
+    let metadata = metadata.map(read_metadata).transpose()?;
+    let mut reader = open(event_log)?;
+
+    let mut num_chunks = 0u64;
+    let mut compressed_size = 0u64;
+    let mut events_per_thread = BTreeMap::<ThreadId, u64>::new();
+    while let Some(chunk) = RawChunk::read(&mut reader)? {
+        num_chunks += 1;
+        compressed_size += chunk.compressed.len() as u64;
+        for event in chunk.events()? {
+            *events_per_thread.entry(event.thread).or_default() += 1;
+            if print_events {
+                let thread = event.thread;
+                let seq = event.seq;
+                let kind = &event.kind;
+                match metadata {
+                    Some(ref metadata) => {
+                        let mir_loc = metadata.get(event.mir_loc);
+                        println!("[thread {thread}, #{seq}] {mir_loc:?} -> {kind:?}");
+                    }
+                    None => {
+                        let mir_loc = event.mir_loc;
+                        println!("[thread {thread}, #{seq}] loc {mir_loc} -> {kind:?}");
+                    }
+                }
+            }
+        }
+    }
+
+    let num_events = events_per_thread.values().sum::<u64>();
+    println!("version = {}", event_log::VERSION);
+    println!("num_chunks = {num_chunks}");
+    println!("num_events = {num_events}");
+    println!("compressed_size = {compressed_size}");
+    for (thread, n) in events_per_thread {
+        println!("thread {thread}: num_events = {n}");
+    }
+    Ok(())
+}
+
+fn truncate(event_log: &Path, output: &Path, mut events_left: u64) -> eyre::Result<()> {
+    let mut reader = open(event_log)?;
+    let mut writer = BufWriter::new(File::create(output)?);
+    event_log::write_header(&mut writer)?;
+
+    while events_left > 0 {
+        let chunk = match RawChunk::read(&mut reader)? {
+            Some(chunk) => chunk,
+            None => break,
+        };
+        if u64::from(chunk.num_events) <= events_left {
+            // Copy whole chunks as is, without recompressing them.
+            chunk.write(&mut writer)?;
+            events_left -= u64::from(chunk.num_events);
+        } else {
+            let mut chunk_writer = EventLogWriter::new(&mut writer);
+            for event in chunk.events()?.iter().take(events_left as usize) {
+                chunk_writer.write(event)?;
+            }
+            chunk_writer.flush()?;
+            events_left = 0;
+        }
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+fn main() -> eyre::Result<()> {
+    init();
+    match Args::parse() {
+        Args::Inspect {
+            event_log,
+            events,
+            metadata,
+        } => inspect(&event_log, events, metadata.as_deref()),
+        Args::Truncate {
+            event_log,
+            output,
+            events,
+        } => truncate(&event_log, &output, events),
+    }
+}
@@ -1,20 +1,20 @@
 use crate::graph::{Graph, GraphId, Graphs, Node, NodeId, NodeKind};
-use c2rust_analysis_rt::events::{Event, EventKind, Pointer};
+use c2rust_analysis_rt::event_log::EventLogReader;
+use c2rust_analysis_rt::events::{Event, EventKind, Pointer, ThreadId};
 use c2rust_analysis_rt::metadata::Metadata;
 use c2rust_analysis_rt::mir_loc::{EventMetadata, Func, MirLoc, TransferKind};
 use color_eyre::eyre;
 use fs_err::File;
-use itertools::Itertools;
+use rustc_index::vec::IndexVec;
 use std::collections::HashMap;
 use std::io::{self, BufReader};
-use std::iter;
+use std::mem;
 use std::path::Path;
 
-pub fn read_event_log(path: &Path) -> io::Result<Vec<Event>> {
+/// Open an event log, whose [`Event`]s are then read one chunk at a time.
+pub fn read_event_log(path: &Path) -> io::Result<EventLogReader<BufReader<File>>> {
     let file = File::open(path)?;
-    let mut reader = BufReader::new(file);
-    let events = iter::from_fn(|| bincode::deserialize_from(&mut reader).ok()).collect::<Vec<_>>();
-    Ok(events)
+    EventLogReader::new(BufReader::new(file))
 }
 
 pub fn read_metadata(path: &Path) -> eyre::Result<Metadata> {
@@ -40,6 +40,8 @@ impl EventKindExt for EventKind {
             Ret(ptr) => ptr,
             LoadAddr(ptr) => ptr,
             StoreAddr(ptr) => ptr,
+            LoadBytes(ptr, _) => ptr,
+            StoreBytes(ptr, _) => ptr,
             LoadValue(ptr) => ptr,
             StoreValue(ptr) => ptr,
             CopyRef => return None, // FIXME
@@ -48,6 +50,7 @@ impl EventKindExt for EventKind {
             FromInt(lhs) => lhs,
             Alloc { ptr, .. } => ptr,
             AddrOfLocal(lhs, _) => lhs,
+            AddrOfStatic(ptr, _) => ptr,
             Offset(ptr, _, _) => ptr,
             Done => return None,
         })
@@ -73,11 +76,13 @@ impl EventKindExt for EventKind {
             Free { .. } => NodeKind::Free,
             CopyPtr(..) | CopyRef => NodeKind::Copy,
             Field(_, field) => NodeKind::Field(field.into()),
-            LoadAddr(..) => NodeKind::LoadAddr,
-            StoreAddr(..) => NodeKind::StoreAddr,
+            LoadAddr(..) | LoadBytes(..) => NodeKind::LoadAddr,
+            StoreAddr(..) | StoreBytes(..) => NodeKind::StoreAddr,
             LoadValue(..) => NodeKind::LoadValue,
             StoreValue(..) => NodeKind::StoreValue,
             AddrOfLocal(_, local) => NodeKind::AddrOfLocal(local.as_u32().into()),
+            // This becomes a `Copy` of the static's root node in `add_node`.
+            AddrOfStatic(_, static_) => NodeKind::AddrOfStatic(static_),
             ToInt(_) => NodeKind::PtrToInt,
             FromInt(_) => NodeKind::IntToPtr,
             Ret(_) => return None,
@@ -87,46 +92,99 @@ impl EventKindExt for EventKind {
     }
 }
 
+/// Maps addresses to the latest [`Node`] that produced them.
+///
+/// An object whose allocation wasn't observed, like a `Box`, has no known lifetime, so once it's
+/// freed, its address may be reused by an object of another thread.  The addresses of such
+/// objects are only looked up within the thread that produced them, while those of observed
+/// allocations, locals and statics are shared by all threads.
+#[derive(Default)]
+pub struct Provenances {
+    shared: HashMap<Pointer, Provenance>,
+    per_thread: HashMap<(ThreadId, Pointer), Provenance>,
+    /// Orders entries across the two maps, so that the latest one wins.
+    next_stamp: u64,
+}
+
+#[derive(Clone, Copy)]
+struct Provenance {
+    stamp: u64,
+    mapping: (GraphId, NodeId),
+}
+
+impl Provenances {
+    fn get(&self, thread: ThreadId, ptr: Pointer) -> Option<(GraphId, NodeId)> {
+        let shared = self.shared.get(&ptr);
+        let per_thread = self.per_thread.get(&(thread, ptr));
+        [shared, per_thread]
+            .into_iter()
+            .flatten()
+            .max_by_key(|p| p.stamp)
+            .map(|p| p.mapping)
+    }
+
+    fn insert(
+        &mut self,
+        graphs: &Graphs,
+        thread: ThreadId,
+        ptr: Pointer,
+        mapping: (GraphId, NodeId),
+    ) {
+        let provenance = Provenance {
+            stamp: self.next_stamp,
+            mapping,
+        };
+        self.next_stamp += 1;
+        let (gid, _) = mapping;
+        let observed = graphs.graphs[gid].nodes.iter().next().map_or(true, |root| {
+            matches!(
+                root.kind,
+                NodeKind::Alloc(_) | NodeKind::AddrOfLocal(_) | NodeKind::AddrOfStatic(_)
+            )
+        });
+        if observed {
+            self.shared.insert(ptr, provenance);
+        } else {
+            self.per_thread.insert((thread, ptr), provenance);
+        }
+    }
+}
+
 fn update_provenance(
-    provenances: &mut HashMap<Pointer, (GraphId, NodeId)>,
-    event_kind: &EventKind,
+    provenances: &mut Provenances,
+    graphs: &Graphs,
+    event: &Event,
     metadata: &EventMetadata,
     mapping: (GraphId, NodeId),
 ) {
     use EventKind::*;
-    match *event_kind {
-        Alloc { ptr, .. } => {
-            provenances.insert(ptr, mapping);
-        }
+    let thread = event.thread;
+    let mut insert = |ptr| provenances.insert(graphs, thread, ptr, mapping);
+    match event.kind {
+        Alloc { ptr, .. } => insert(ptr),
         CopyPtr(ptr) => {
             // only insert if not already there
-            if let Err(..) = provenances.try_insert(ptr, mapping) {
+            if provenances.get(thread, ptr).is_some() {
                 log::warn!("0x{:x} doesn't have a source", ptr);
+            } else {
+                provenances.insert(graphs, thread, ptr, mapping);
             }
         }
-        Realloc { new_ptr, .. } => {
-            provenances.insert(new_ptr, mapping);
-        }
-        Offset(_, _, new_ptr) => {
-            provenances.insert(new_ptr, mapping);
-        }
-        CopyRef => {
-            provenances.insert(metadata.destination.clone().unwrap().local.into(), mapping);
-        }
-        AddrOfLocal(ptr, _) => {
-            provenances.insert(ptr, mapping);
-        }
+        Realloc { new_ptr, .. } => insert(new_ptr),
+        Offset(_, _, new_ptr) => insert(new_ptr),
+        CopyRef => insert(metadata.destination.clone().unwrap().local.into()),
+        AddrOfLocal(ptr, _) | AddrOfStatic(ptr, _) => insert(ptr),
         _ => {}
     }
 }
 
 pub fn add_node(
     graphs: &mut Graphs,
-    provenances: &mut HashMap<Pointer, (GraphId, NodeId)>,
+    provenances: &mut Provenances,
     event: &Event,
     metadata: &Metadata,
 ) -> Option<NodeId> {
-    let node_kind = event.kind.to_node_kind()?;
+    let mut node_kind = event.kind.to_node_kind()?;
 
     let MirLoc {
         func,
@@ -151,12 +209,16 @@ pub fn add_node(
     let head = event
         .kind
         .ptr(event_metadata)
-        .and_then(|ptr| provenances.get(&ptr).cloned());
+        .and_then(|ptr| provenances.get(event.thread, ptr));
     let ptr = head.and_then(|(gid, _last_nid_ref)| {
         graphs.graphs[gid]
             .nodes
             .iter()
             .rposition(|n| {
+                // Another thread's locals of the same name are different places.
+                if n.thread != event.thread {
+                    return false;
+                }
                 if let (Some(d), Some(s)) = (&n.dest, &event_metadata.source) {
                     d == s
                 } else {
@@ -166,12 +228,29 @@ pub fn add_node(
             .map(|nid| (gid, NodeId::from(nid)))
     });
 
-    let source = ptr.or_else(|| {
+    // The accesses made by libc functions are reported by their hooks, which have a single source
+    // place (their first argument) even though they may access any of their arguments.  Since the
+    // accessed pointer is known, use the operation that produced it instead.
+    let ptr = match event.kind {
+        EventKind::LoadBytes(..) | EventKind::StoreBytes(..) => head,
+        _ => ptr,
+    };
+
+    let mut source = ptr.or_else(|| {
         event_metadata.source.as_ref().and_then(|src| {
-            let latest_assignment = graphs.latest_assignment.get(&(src_fn, src.local)).cloned();
+            let latest_assignment = graphs
+                .latest_assignment
+                .get(&(event.thread, src_fn, src.local))
+                .cloned();
             if !src.projection.is_empty() {
                 if let Some((gid, _)) = latest_assignment {
-                    if let Some((nid, n)) = graphs.graphs[gid].nodes.iter_enumerated().rev().next()
+                    // The object may be in use by other threads at the same time, so look for the
+                    // latest operation on it by this thread.
+                    if let Some((nid, n)) = graphs.graphs[gid]
+                        .nodes
+                        .iter_enumerated()
+                        .rev()
+                        .find(|(_, n)| n.thread == event.thread)
                     {
                         if let NodeKind::Field(..) = n.kind {
                             return Some((gid, nid));
@@ -195,7 +274,34 @@ pub fn add_node(
         name: metadata.functions[&dest_fn].clone(),
     };
 
+    if let EventKind::AddrOfStatic(_, static_) = event.kind {
+        // All uses of a static refer to the same object, so create its graph only once.
+        let gid = *graphs.statics.entry(static_).or_insert_with(|| {
+            let mut graph = Graph::new();
+            graph.nodes.push(Node {
+                thread: event.thread,
+                function: function.clone(),
+                block: basic_block_idx.into(),
+                statement_idx,
+                dest: None,
+                kind: NodeKind::AddrOfStatic(static_),
+                source: None,
+                debug_info: event_metadata.debug_info.clone(),
+            });
+            graphs.graphs.push(graph)
+        });
+        source = Some((gid, NodeId::from_u32(0)));
+        node_kind = NodeKind::Copy;
+    }
+
+    // The destination of a hooked libc call is its return value, not the memory it accessed.
+    let dest = match event.kind {
+        EventKind::LoadBytes(..) | EventKind::StoreBytes(..) => None,
+        _ => event_metadata.destination.clone(),
+    };
+
     let node = Node {
+        thread: event.thread,
         function,
         block: basic_block_idx.into(),
         statement_idx,
@@ -203,7 +309,7 @@ pub fn add_node(
         source: source
             .and_then(|p| event.kind.parent(p))
             .map(|(_, nid)| nid),
-        dest: event_metadata.destination.clone(),
+        dest: dest.clone(),
         debug_info: event_metadata.debug_info.clone(),
     };
 
@@ -217,13 +323,14 @@ pub fn add_node(
 
     update_provenance(
         provenances,
-        &event.kind,
+        graphs,
+        event,
         event_metadata,
         (graph_id, node_id),
     );
 
-    if let Some(dest) = &event_metadata.destination {
-        let unique_place = (dest_fn, dest.local);
+    if let Some(dest) = &dest {
+        let unique_place = (event.thread, dest_fn, dest.local);
         let last_setting = (graph_id, node_id);
 
         if let Some(last @ (last_gid, last_nid)) =
@@ -245,13 +352,72 @@ pub fn add_node(
     Some(node_id)
 }
 
-pub fn construct_pdg(events: &[Event], metadata: &Metadata) -> Graphs {
+/// Checks that the [`Event`]s of each thread appear in order and without gaps.
+///
+/// The runtime writes the [`Event`]s of all threads to a single log, interleaving them in the
+/// order in which they were sent.  That order is only meaningful within each thread, which
+/// [`construct_pdg`] relies on.
+#[derive(Default)]
+struct ThreadOrder {
+    next_seq: HashMap<ThreadId, u64>,
+}
+
+impl ThreadOrder {
+    fn check(&mut self, event: &Event) {
+        let expected = self.next_seq.entry(event.thread).or_default();
+        if event.seq != *expected {
+            log::warn!(
+                "thread {}: expected event #{}, but found #{}; events may have been lost or reordered",
+                event.thread,
+                expected,
+                event.seq,
+            );
+        }
+        *expected = event.seq + 1;
+    }
+}
+
+/// Build the PDG from a stream of [`Event`]s, such as an [`EventLogReader`].
+/// [`Event`]s are processed one at a time, so the whole log never needs to be in memory.
+///
+/// The [`Graphs`] themselves are not bounded, though: almost every pointer [`Event`] becomes a
+/// [`Node`], so the memory needed grows linearly with the number of
+/// [`Event`]s in the log.  For very long runs, build the PDG from a prefix of the log instead,
+/// as cut by `c2rust-event-log truncate`.
+pub fn construct_pdg(
+    events: impl IntoIterator<Item = io::Result<Event>>,
+    metadata: &Metadata,
+) -> io::Result<Graphs> {
+    let mut thread_order = ThreadOrder::default();
     let mut graphs = Graphs::new();
-    let mut provenances = HashMap::new();
+    let mut provenances = Provenances::default();
     for event in events {
-        add_node(&mut graphs, &mut provenances, event, metadata);
+        let event = event?;
+        thread_order.check(&event);
+        add_node(&mut graphs, &mut provenances, &event, metadata);
+    }
+    dedup_graphs(&mut graphs);
+    Ok(graphs)
+}
+
+/// Merge identical graphs, updating the [`GraphId`]s in [`Graphs::latest_assignment`] and
+/// [`Graphs::statics`] to refer to the merged graphs.
+fn dedup_graphs(graphs: &mut Graphs) {
+    let mut unique_gids = HashMap::new();
+    let mut unique_graphs = IndexVec::new();
+    let new_gids = mem::take(&mut graphs.graphs)
+        .into_iter()
+        .map(|graph| {
+            *unique_gids
+                .entry(graph)
+                .or_insert_with_key(|graph| unique_graphs.push(graph.clone()))
+        })
+        .collect::<IndexVec<GraphId, GraphId>>();
+    graphs.graphs = unique_graphs;
+    for (gid, _) in graphs.latest_assignment.values_mut() {
+        *gid = new_gids[*gid];
+    }
+    for gid in graphs.statics.values_mut() {
+        *gid = new_gids[*gid];
     }
-    // TODO(kkysen) check if I have to remove any `GraphId`s from `graphs.latest_assignment`
-    graphs.graphs = graphs.graphs.into_iter().unique().collect();
-    graphs
 }
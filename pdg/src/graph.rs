@@ -1,3 +1,4 @@
+use c2rust_analysis_rt::events::ThreadId;
 use c2rust_analysis_rt::mir_loc::MirPlace;
 use c2rust_analysis_rt::mir_loc::{self, DefPathHash, Func};
 use rustc_index::newtype_index;
@@ -32,9 +33,11 @@ pub enum NodeKind {
     /// shows up as a `LoadAddr` or `StoreAddr`.  This allows us to track uses of the local that
     /// interfere with an existing reference, even when those uses don't go through a pointer.
     AddrOfLocal(Local),
-    /// Get the address of a static.  These are treated the same as locals, with an
-    /// `AddressOfStatic` attributed to the first statement.
-    _AddrOfStatic(DefPathHash),
+    /// Get the address of a static.  These are treated the same as locals: each static has a
+    /// single graph, whose root node is an `AddrOfStatic` attributed to the first statement
+    /// observed taking its address.  That statement and every later one that takes the address
+    /// of the static appear as copies of the root pointer.
+    AddrOfStatic(DefPathHash),
     /// Heap allocation.  The `usize` is the number of array elements allocated; for allocations of
     /// a single object, this value is 1.
     Alloc(usize),
@@ -50,9 +53,11 @@ pub enum NodeKind {
     Free,
     /// Pointer to int conversion.  Details TBD.
     PtrToInt,
-    /// The pointer appears as the address of a load operation.
+    /// The pointer appears as the address of a load operation, including reads done by hooked libc
+    /// functions like `memcpy` and `strlen`.
     LoadAddr,
-    /// The pointer appears as the address of a store operation.
+    /// The pointer appears as the address of a store operation, including writes done by hooked
+    /// libc functions like `memcpy` and `strcpy`.
     StoreAddr,
     /// The pointer is stored through some other pointer.  Details TBD.
     StoreValue,
@@ -66,7 +71,9 @@ impl Display for NodeKind {
             Field(field) => write!(f, "field.{}", field.as_usize()),
             Offset(offset) => write!(f, "offset[{offset}]"),
             AddrOfLocal(local) => write!(f, "&{local:?}"),
-            _AddrOfStatic(static_) => write!(f, "&'static {static_:?}"),
+            // The `DefPathHash` depends on the crate's `-C metadata`, so leave it out to keep the
+            // output stable; the node's `debug_info` shows which static it is.
+            AddrOfStatic(_) => write!(f, "&'static"),
             Alloc(n) => {
                 // Right now we only create `Alloc(1)`, so special case it,
                 // as the increased readability helps.
@@ -96,6 +103,9 @@ impl Display for NodeKind {
 /// by comparing `NodeId`s.
 #[derive(Debug, Eq, PartialEq, Hash, Clone)]
 pub struct Node {
+    /// The thread that performed this operation.  Together with `function`, this identifies the
+    /// stack frame whose locals `dest` refers to.
+    pub thread: ThreadId,
     /// The function that contains this operation.
     ///
     /// For function calls, copies from the caller's values into the callee's argument locals are
@@ -141,6 +151,7 @@ impl Display for BlockStatement<'_> {
 impl Node {
     fn fmt_with_sep(&self, f: &mut Formatter, sep: char) -> fmt::Result {
         let Self {
+            thread: _,
             function,
             block,
             statement_idx,
@@ -257,7 +268,12 @@ pub struct Graphs {
     pub graphs: IndexVec<GraphId, Graph>,
 
     /// Lookup table for finding all nodes in all graphs that store to a particular MIR local.
-    pub latest_assignment: HashMap<(mir_loc::DefPathHash, mir_loc::Local), (GraphId, NodeId)>,
+    /// Each thread has its own copy of every local, so the thread is part of the key.
+    pub latest_assignment:
+        HashMap<(ThreadId, mir_loc::DefPathHash, mir_loc::Local), (GraphId, NodeId)>,
+
+    /// The graph of each static whose address has been taken.
+    pub statics: HashMap<DefPathHash, GraphId>,
 }
 
 impl Graphs {
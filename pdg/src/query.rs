@@ -1,19 +1,38 @@
-//! ## Future Plans for Queries
-//! TODO(kkysen, aneksteind)
+//! Queries over a [`Pdg`], for investigating why a pointer ended up with some permission
+//! without reading through whole [`Graph`] dumps.
 //!
-//! It would be nice to test properties like "an object is create in `foo`,
-//! and its object [`Graph`] contains a [`StoreAddr`] node".
+//! A [`Query`] is a command followed by its arguments, separated by whitespace:
 //!
-//! [`StoreAddr`]: NodeKind::StoreAddr.
+//! * `nodes <function>`: all [`Node`]s in the function named `<function>`.
+//! * `provenance <graph> <node>`: the chain of [`Node`]s that `<node>` is derived from,
+//!   starting from `<node>` itself and ending at the root of its [`Graph`].
+//! * `freed <function>`: the [`Free`] [`Node`]s in `<function>`,
+//!   which identify the objects freed there.
+//! * `writes <graph>`: the MIR locations of the [`StoreAddr`] [`Node`]s of the object `<graph>`.
 //!
-//! For example PDGs that we have already (manually) confirmed are correct,
-//! we want to set up snapshot testing to make sure we don't introduce any regressions,
-//! and be able to test if certain changes have any effect on the PDG output.
-//! We are thinking about using [`insta`](https://insta.rs/) for this.
+//! Graphs and nodes are given by index, either bare (`3`) or as printed (`g[3]`, `n[3]`).
+//! Results are [`QueryResult`]s, which are printed as JSON.
+//!
+//! [`Node`]: crate::graph::Node
+//! [`Free`]: NodeKind::Free
+//! [`StoreAddr`]: NodeKind::StoreAddr
+//!
+//! Queries can also be run from tests through [`Pdg::query`], to check properties like
+//! "an object is created in `foo`, and its object [`Graph`] contains a [`StoreAddr`] node"
+//! without depending on the rest of the PDG, whose full output is covered by snapshot tests.
+
+use std::io::{BufRead, Write};
+use std::str::FromStr;
+
+use c2rust_analysis_rt::events::ThreadId;
+use color_eyre::eyre::{self, bail, eyre, Context};
+use itertools::Itertools;
+use serde::Serialize;
 
 use linked_hash_set::LinkedHashSet;
 
-use crate::graph::{Graph, NodeId, NodeKind};
+use crate::graph::{Graph, GraphId, Node, NodeId, NodeKind};
+use crate::Pdg;
 
 impl Graph {
     /// Query an object [`Graph`] to determine which of its [`Node`]s (returned as [`NodeId`]s)
@@ -51,3 +70,220 @@ impl Graph {
         needs_write.into_iter()
     }
 }
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Query {
+    Nodes { function: String },
+    Provenance { graph: GraphId, node: NodeId },
+    Freed { function: String },
+    Writes { graph: GraphId },
+}
+
+/// Parse an index that may be written as printed, like `g[3]`, or bare, like `3`.
+fn parse_index(s: &str, prefix: &str) -> eyre::Result<usize> {
+    let bare = s
+        .strip_prefix(prefix)
+        .and_then(|s| s.strip_prefix('['))
+        .and_then(|s| s.strip_suffix(']'))
+        .unwrap_or(s);
+    bare.parse()
+        .wrap_err_with(|| format!("expected a {prefix}[..] index, but found {s:?}"))
+}
+
+impl FromStr for Query {
+    type Err = eyre::Report;
+
+    fn from_str(s: &str) -> eyre::Result<Self> {
+        let words = s.split_whitespace().collect::<Vec<_>>();
+        let query = match words[..] {
+            ["nodes", function] => Self::Nodes {
+                function: function.to_owned(),
+            },
+            ["provenance", graph, node] => Self::Provenance {
+                graph: GraphId::from_usize(parse_index(graph, "g")?),
+                node: NodeId::from_usize(parse_index(node, "n")?),
+            },
+            ["freed", function] => Self::Freed {
+                function: function.to_owned(),
+            },
+            ["writes", graph] => Self::Writes {
+                graph: GraphId::from_usize(parse_index(graph, "g")?),
+            },
+            _ => bail!("invalid query {s:?}; {QUERY_HELP}"),
+        };
+        Ok(query)
+    }
+}
+
+const QUERY_HELP: &str = "expected one of `nodes <function>`, `provenance <graph> <node>`, \
+                          `freed <function>` or `writes <graph>`";
+
+#[derive(Debug, Serialize)]
+pub struct NodeInfo {
+    pub graph: usize,
+    pub node: usize,
+    pub thread: ThreadId,
+    pub function: String,
+    pub block: usize,
+    pub statement: usize,
+    pub kind: String,
+    pub source: Option<usize>,
+    pub dest: Option<String>,
+    pub debug_info: String,
+}
+
+impl NodeInfo {
+    fn new(graph: GraphId, node_id: NodeId, node: &Node) -> Self {
+        Self {
+            graph: graph.as_usize(),
+            node: node_id.as_usize(),
+            thread: node.thread,
+            function: node.function.name.clone(),
+            block: node.block.as_usize(),
+            statement: node.statement_idx,
+            kind: node.kind.to_string(),
+            source: node.source.map(|source| source.as_usize()),
+            dest: node.dest.as_ref().map(|dest| dest.to_string()),
+            debug_info: node.debug_info.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct MirLocation {
+    pub function: String,
+    pub block: usize,
+    pub statement: usize,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QueryResult {
+    Nodes(Vec<NodeInfo>),
+    Locations(Vec<MirLocation>),
+}
+
+fn node_infos<'a>(nodes: impl Iterator<Item = (GraphId, NodeId, &'a Node)>) -> Vec<NodeInfo> {
+    nodes
+        .map(|(graph, node_id, node)| NodeInfo::new(graph, node_id, node))
+        .collect()
+}
+
+impl Pdg {
+    fn graph(&self, graph: GraphId) -> eyre::Result<&Graph> {
+        self.graphs
+            .graphs
+            .get(graph)
+            .ok_or_else(|| eyre!("{graph} doesn't exist"))
+    }
+
+    /// All [`Node`]s, with the [`GraphId`] of the object they belong to.
+    fn all_nodes(&self) -> impl Iterator<Item = (GraphId, NodeId, &Node)> {
+        self.graphs
+            .graphs
+            .iter_enumerated()
+            .flat_map(|(graph_id, graph)| {
+                graph
+                    .nodes
+                    .iter_enumerated()
+                    .map(move |(node_id, node)| (graph_id, node_id, node))
+            })
+    }
+
+    pub fn query(&self, query: &Query) -> eyre::Result<QueryResult> {
+        let result = match query {
+            Query::Nodes { function } => QueryResult::Nodes(node_infos(
+                self.all_nodes()
+                    .filter(|(_, _, node)| &node.function.name == function),
+            )),
+            Query::Provenance { graph, node } => {
+                let graph_id = *graph;
+                let graph = self.graph(graph_id)?;
+                if graph.nodes.get(*node).is_none() {
+                    bail!("{node} doesn't exist in {graph_id}");
+                }
+                let chain = std::iter::successors(Some(*node), |&id| graph.nodes[id].source)
+                    .map(|id| (graph_id, id, &graph.nodes[id]));
+                QueryResult::Nodes(node_infos(chain))
+            }
+            Query::Freed { function } => {
+                QueryResult::Nodes(node_infos(self.all_nodes().filter(|(_, _, node)| {
+                    node.kind == NodeKind::Free && &node.function.name == function
+                })))
+            }
+            Query::Writes { graph } => QueryResult::Locations(
+                self.graph(*graph)?
+                    .nodes
+                    .iter()
+                    .filter(|node| node.kind == NodeKind::StoreAddr)
+                    .map(|node| MirLocation {
+                        function: node.function.name.clone(),
+                        block: node.block.as_usize(),
+                        statement: node.statement_idx,
+                    })
+                    .unique()
+                    .collect(),
+            ),
+        };
+        Ok(result)
+    }
+
+    /// Run the queries read one per line from `input`, writing the results to `output` as JSON.
+    ///
+    /// Errors in a query are reported on `stderr` instead of ending the session.
+    pub fn repl(&self, input: impl BufRead, mut output: impl Write) -> eyre::Result<()> {
+        let prompt = |output: &mut dyn Write| -> eyre::Result<()> {
+            write!(output, "> ")?;
+            output.flush()?;
+            Ok(())
+        };
+        prompt(&mut output)?;
+        for line in input.lines() {
+            let line = line?;
+            match line.trim() {
+                "" => {}
+                "quit" | "exit" => break,
+                "help" => writeln!(output, "{QUERY_HELP}")?,
+                line => match line.parse().and_then(|query| self.query(&query)) {
+                    Ok(result) => {
+                        serde_json::to_writer_pretty(&mut output, &result)?;
+                        writeln!(output)?;
+                    }
+                    Err(e) => eprintln!("error: {e:#}"),
+                },
+            }
+            prompt(&mut output)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_queries() {
+        assert_eq!(
+            "provenance g[3] 5".parse::<Query>().unwrap(),
+            Query::Provenance {
+                graph: GraphId::from_usize(3),
+                node: NodeId::from_usize(5),
+            }
+        );
+        assert_eq!(
+            "  writes 2 ".parse::<Query>().unwrap(),
+            Query::Writes {
+                graph: GraphId::from_usize(2),
+            }
+        );
+        assert_eq!(
+            "freed main".parse::<Query>().unwrap(),
+            Query::Freed {
+                function: "main".to_owned(),
+            }
+        );
+        assert!("nodes".parse::<Query>().is_err());
+        assert!("writes n[2]".parse::<Query>().is_err());
+    }
+}
@@ -18,7 +18,7 @@ SCRIPT_DIR="${CWD}/$(dirname "${SCRIPT_PATH}")"
 # 3. Redirect `c2rust-dynamic-instrumentation` stdout to `instrument.out.log` in the test crate directory,
 #    as it prints a lot of debugging info currently.
 # 4. Run the instrumented binary directly.
-#    The `bincode`-encoded event log is written to `log.bc`.
+#    The compressed event log is written to `log.bc`.
 # 5. Using the `metadata.bc` metadata and the `log.bc` event log,
 #    run `c2rust-pdg` to generate the pdg.
 #    The output is saved to `pdg.log` (relative to the test crate directory).
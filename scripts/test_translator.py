@@ -1,6 +1,7 @@
 #!/usr/bin/env python3
 
 import errno
+import json
 import os
 from pathlib import Path
 import sys
@@ -72,8 +73,16 @@ class CFile:
         self.disable_incremental_relooper = "disable_incremental_relooper" in flags
         self.disallow_current_block = "disallow_current_block" in flags
         self.translate_const_macros = "translate_const_macros" in flags
+        self.translate_fn_macros = "translate_fn_macros" in flags
+        self.rust_enums = "rust_enums" in flags
         self.reorganize_definitions = "reorganize_definitions" in flags
         self.emit_build_files = "emit_build_files" in flags
+        self.stable = "stable" in flags
+        self.emit_no_std = "emit_no_std" in flags
+        self.expect_unstable = {flag[16:] for flag in flags if flag.startswith("expect_unstable_")}
+        self.expect_untranslated = {flag[20:] for flag in flags
+                                    if flag.startswith("expect_untranslated_")}
+        self.exclude_decls = sorted(flag[13:] for flag in flags if flag.startswith("exclude_decl_"))
 
     def translate(self, cc_db, ld_lib_path, extra_args: List[str] = []) -> RustFile:
         extensionless_file, _ = os.path.splitext(self.path)
@@ -94,10 +103,22 @@ class CFile:
             args.append("--fail-on-multiple")
         if self.translate_const_macros:
             args.append("--translate-const-macros")
+        if self.translate_fn_macros:
+            args.append("--translate-fn-macros")
+        if self.rust_enums:
+            args.append("--rust-enums")
         if self.reorganize_definitions:
             args.append("--reorganize-definitions")
         if self.emit_build_files:
             args.append("--emit-build-files")
+        if self.exclude_decls:
+            args.append("--exclude-decls=" + ",".join(self.exclude_decls))
+        if self.emit_no_std:
+            args.append("--emit-no-std")
+        if self.stable:
+            args.append("--stable")
+        if self.stable or self.expect_untranslated:
+            args.extend(["--report", self.report_path()])
 
         if self.log_level == 'DEBUG':
             args.append("--log-level=debug")
@@ -119,7 +140,68 @@ class CFile:
         if retcode != 0:
             raise NonZeroReturn(stderr)
 
-        return RustFile(extensionless_file + ".rs")
+        if self.stable or self.expect_untranslated:
+            self.check_report()
+
+        rust_file = extensionless_file + ".rs"
+        if self.disallow_current_block:
+            self.check_no_current_block(rust_file)
+
+        return RustFile(rust_file)
+
+    def report_path(self) -> str:
+        extensionless_file, _ = os.path.splitext(self.path)
+        return extensionless_file + ".report.json"
+
+    def check_report(self) -> None:
+        """
+        Fail unless the `--report` output has every declaration except those
+        in `expect_untranslated` translated, and, with `--stable`, exactly the
+        declarations in `expect_unstable` need a nightly feature.
+        """
+        with open(self.report_path(), 'r', encoding="utf-8") as file:
+            report = json.load(file)
+
+        problems = []
+        reported = set()
+        for unit in report["translation_units"]:
+            if unit["status"] != "translated":
+                problems.append("{}: {}".format(unit["input"], unit["status"]))
+            for decl in unit["declarations"]:
+                name = decl["name"] or decl["kind"]
+                if decl["name"] in self.expect_untranslated:
+                    reported.add(decl["name"])
+                    if decl["status"] == "translated":
+                        problems.append("{}: translated".format(name))
+                elif decl["status"] != "translated":
+                    problems.append("{}: {}".format(name, decl["status"]))
+                if decl["name"] in self.expect_unstable:
+                    reported.add(decl["name"])
+                    if not decl.get("unstable_features"):
+                        problems.append("{}: not reported as needing nightly".format(name))
+                elif decl.get("unstable_features"):
+                    problems.append("{}: needs {}".format(
+                        name, ", ".join(decl["unstable_features"])))
+        expected = self.expect_unstable | self.expect_untranslated
+        for name in sorted(expected - reported):
+            problems.append("{}: missing from the report".format(name))
+
+        if problems:
+            raise NonZeroReturn("unexpected --report entries:\n" +
+                                "\n".join(problems))
+
+    @staticmethod
+    def check_no_current_block(rust_file: str) -> None:
+        """
+        Fail if the translation in `rust_file` falls back to a `current_block`
+        variable for its control flow.
+        """
+        with open(rust_file, 'r', encoding="utf-8") as file:
+            lines = [i + 1 for i, line in enumerate(file)
+                     if "current_block" in line]
+        if lines:
+            raise NonZeroReturn("{} uses `current_block` on lines {}".format(
+                rust_file, ", ".join(map(str, lines))))
 
 def get_native_arch() -> str:
     rustc_cfg_args = ["--print", "cfg"]
@@ -376,16 +458,19 @@ class TestDirectory:
         self.generated_files["c_obj"].extend(static_library.obj_files)
 
         rust_file_builder = RustFileBuilder()
-        rust_file_builder.add_features([
-            "libc",
-            "extern_types",
-            "simd_ffi",
-            "stdsimd",
-            "nll",
-            "linkage",
-            "register_tool",
-        ])
-        rust_file_builder.add_pragma("register_tool", ["c2rust"])
+        # Directories whose C files are all translated with `--stable` are
+        # built with the stable toolchain in their own `rust-toolchain.toml`
+        if not all(c_file.stable for c_file in self.c_files):
+            rust_file_builder.add_features([
+                "libc",
+                "extern_types",
+                "simd_ffi",
+                "stdsimd",
+                "nll",
+                "linkage",
+                "register_tool",
+            ])
+            rust_file_builder.add_pragma("register_tool", ["c2rust"])
 
 
         # Ensure that path to rustc's lib dir is in`LD_LIBRARY_PATH`
@@ -403,6 +488,8 @@ class TestDirectory:
             self.print_status(Colors.WARNING, "RUNNING", description)
 
             self._generate_cc_db(c_file.path)
+            if c_file.stable or c_file.expect_untranslated:
+                self.generated_files["rust_src"].append(c_file.report_path())
 
             try:
                 logging.debug("translating %s", c_file_short)
@@ -419,6 +506,10 @@ class TestDirectory:
                 continue
 
             self.generated_files["rust_src"].append(translated_rust_file)
+            if c_file.expect_unstable or c_file.expect_untranslated:
+                # The translation is incomplete or needs nightly, so it's only
+                # checked, not built
+                continue
             if c_file.emit_build_files:
                 self.generated_files["rust_src"].append(self.full_path + "/src/Cargo.toml")
                 self.generated_files["rust_src"].append(self.full_path + "/src/build.rs")
@@ -66,3 +66,21 @@ void new_atomics(const unsigned buffer_size, int buffer[const])
     __atomic_store_n(&x, 0, __ATOMIC_RELAXED);
     buffer[i++] = x;
 }
+
+void dynamic_order_atomics(const unsigned buffer_size, int buffer[const])
+{
+    int i = 0, x = 34, y = 0;
+    for (int order = __ATOMIC_RELAXED; order <= __ATOMIC_SEQ_CST; order++) {
+        buffer[i++] = __atomic_fetch_add(&x, order, order);
+        buffer[i++] = __atomic_sub_fetch(&x, 3, order);
+        buffer[i++] = __atomic_load_n(&x, order);
+        __atomic_store_n(&y, x + order, order);
+        buffer[i++] = y;
+        buffer[i++] = __atomic_exchange_n(&y, order, order);
+        int expected = order;
+        buffer[i++] = __atomic_compare_exchange_n(&y, &expected, 99, 0, order, __ATOMIC_RELAXED);
+        buffer[i++] = __atomic_compare_exchange_n(&y, &expected, 7, 0, __ATOMIC_SEQ_CST, order);
+        buffer[i++] = expected;
+        buffer[i++] = y;
+    }
+}
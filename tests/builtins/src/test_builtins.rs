@@ -1,6 +1,6 @@
 //! feature_core_intrinsics
 
-use crate::atomics::{rust_atomics_entry, rust_new_atomics};
+use crate::atomics::{rust_atomics_entry, rust_dynamic_order_atomics, rust_new_atomics};
 use crate::math::{rust_ffs, rust_ffsl, rust_ffsll, rust_isfinite, rust_isinf_sign, rust_isnan};
 use crate::mem_x_fns::{rust_assume_aligned, rust_mem_x};
 use libc::{c_char, c_double, c_int, c_long, c_longlong, c_uint};
@@ -9,6 +9,7 @@ use libc::{c_char, c_double, c_int, c_long, c_longlong, c_uint};
 extern "C" {
     fn atomics_entry(_: c_uint, _: *mut c_int);
     fn new_atomics(_: c_uint, _: *mut c_int);
+    fn dynamic_order_atomics(_: c_uint, _: *mut c_int);
     fn mem_x(_: *const c_char, _: *mut c_char);
     fn ffs(_: c_int) -> c_int;
     fn ffsl(_: c_long) -> c_int;
@@ -50,6 +51,20 @@ pub fn test_new_atomics() {
     }
 }
 
+pub fn test_dynamic_order_atomics() {
+    let mut buffer = [0; BUFFER_SIZE];
+    let mut rust_buffer = [0; BUFFER_SIZE];
+
+    unsafe {
+        dynamic_order_atomics(BUFFER_SIZE as u32, buffer.as_mut_ptr());
+        rust_dynamic_order_atomics(BUFFER_SIZE as u32, rust_buffer.as_mut_ptr());
+    }
+
+    for index in 0..BUFFER_SIZE {
+        assert_eq!(buffer[index], rust_buffer[index]);
+    }
+}
+
 pub fn test_mem_fns() {
     let const_string = "I am ten!\0";
     let mut buffer = [0; BUFFER_SIZE2];
@@ -0,0 +1,74 @@
+//! rust_enums
+
+#include <string.h>
+
+// Only used through its constants, so it becomes a Rust enum
+enum shape { CIRCLE, SQUARE, TRIANGLE };
+
+// Used as a set of flags, so it stays an integer type
+enum perm { NONE = 0, READ = 1, WRITE = 2, EXEC = 4 };
+
+static int sides(enum shape s) {
+    switch (s) {
+    case CIRCLE:
+        return 0;
+    case SQUARE:
+        return 4;
+    default:
+        return 3;
+    }
+}
+
+void shapes(const unsigned buffer_size, int buffer[]) {
+    if (buffer_size < 6) { return; }
+
+    enum shape s = SQUARE;
+    enum shape t = TRIANGLE;
+    enum perm p = READ | WRITE;
+
+    buffer[0] = sides(s);
+    buffer[1] = sides(t);
+    buffer[2] = s == SQUARE;
+    buffer[3] = s != t;
+    buffer[4] = t;
+    buffer[5] = p;
+}
+
+// Part of the signature of an externally visible function, so it stays an
+// integer type that C callers can pass any value of
+enum color { RED, GREEN, BLUE };
+
+int color_value(enum color c) {
+    return c == GREEN ? 10 : c;
+}
+
+// A field of a struct passed to an externally visible function, so it stays an
+// integer type that C callers can store any value in
+enum level { LOW, MID, HIGH };
+
+struct setting {
+    enum level level;
+    int value;
+};
+
+int setting_value(const struct setting *s) {
+    return s->level == HIGH ? s->value : s->level;
+}
+
+// A field of a struct that is copied from another type of pointer, so it stays
+// an integer type that can hold any value copied into it
+enum phase { IDLE, RUNNING, DONE };
+
+struct job {
+    enum phase phase;
+};
+
+static void copy_job(struct job *dst, const void *src) {
+    memcpy(dst, src, sizeof *dst);
+}
+
+int job_phase(int raw) {
+    struct job j;
+    copy_job(&j, &raw);
+    return j.phase;
+}
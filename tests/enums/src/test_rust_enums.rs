@@ -0,0 +1,71 @@
+use crate::rust_enums::{
+    color, level, phase, rust_color_value, rust_job_phase, rust_setting_value, rust_shapes,
+    setting, shape, BLUE, HIGH,
+};
+
+use libc::{c_int, c_uint};
+use std::convert::TryFrom;
+
+#[link(name = "test")]
+extern "C" {
+    fn shapes(_: c_uint, _: *mut c_int);
+    fn color_value(_: c_uint) -> c_int;
+    fn setting_value(_: *const setting) -> c_int;
+    fn job_phase(_: c_int) -> c_int;
+}
+
+const BUFFER_SIZE: usize = 6;
+
+pub fn test_try_from() {
+    assert!(shape::try_from(2u32) == Ok(shape::TRIANGLE));
+    assert!(shape::try_from(3u32) == Err(3));
+}
+
+pub fn test_buffer() {
+    let mut buffer = [0; BUFFER_SIZE];
+    let mut rust_buffer = [0; BUFFER_SIZE];
+    let expected_buffer = [4, 3, 1, 1, 2, 3];
+
+    unsafe {
+        shapes(BUFFER_SIZE as c_uint, buffer.as_mut_ptr());
+        rust_shapes(BUFFER_SIZE as c_uint, rust_buffer.as_mut_ptr());
+    }
+
+    assert_eq!(buffer, rust_buffer);
+    assert_eq!(buffer, expected_buffer);
+}
+
+pub fn test_ffi_enum() {
+    // `color` stays an integer type, so values without a constant pass through
+    let out_of_range: color = 7;
+
+    unsafe {
+        assert_eq!(color_value(BLUE), rust_color_value(BLUE));
+        assert_eq!(color_value(1), rust_color_value(1));
+        assert_eq!(color_value(out_of_range), rust_color_value(out_of_range));
+    }
+}
+
+pub fn test_struct_field_enums() {
+    // `level` and `phase` stay integer types, so values without a constant pass
+    // through the struct fields
+    let out_of_range: level = 7;
+    let settings = [
+        setting {
+            level: out_of_range,
+            value: 1,
+        },
+        setting {
+            level: HIGH,
+            value: 2,
+        },
+    ];
+    let raw: phase = 5;
+
+    unsafe {
+        for s in &settings {
+            assert_eq!(setting_value(s), rust_setting_value(s));
+        }
+        assert_eq!(job_phase(raw as c_int), rust_job_phase(raw as c_int));
+    }
+}
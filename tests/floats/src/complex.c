@@ -0,0 +1,62 @@
+#include <complex.h>
+
+void complex_arithmetic(unsigned buffer_size, double buffer[]) {
+    double _Complex a = 1.0 + 2.0 * I;
+    double _Complex b = 1.0 - 1.0 * I;
+    double _Complex zero = 0;
+    double _Complex c;
+    float _Complex f = a;
+    int i = 0;
+
+    if (buffer_size < 30) return;
+
+    c = a + b;
+    buffer[i++] = creal(c);
+    buffer[i++] = cimag(c);
+    c = a - b;
+    buffer[i++] = creal(c);
+    buffer[i++] = cimag(c);
+    c = a * b;
+    buffer[i++] = creal(c);
+    buffer[i++] = cimag(c);
+    c = a / b;
+    buffer[i++] = creal(c);
+    buffer[i++] = cimag(c);
+    c = a * 2.0;
+    buffer[i++] = creal(c);
+    buffer[i++] = cimag(c);
+    c = -a;
+    buffer[i++] = creal(c);
+    buffer[i++] = cimag(c);
+
+    // Conjugation, both the library call and the GNU operator
+    c = conj(a);
+    buffer[i++] = creal(c);
+    buffer[i++] = cimag(c);
+    c = ~a;
+    buffer[i++] = creal(c);
+    buffer[i++] = cimag(c);
+
+    // GNU extensions
+    buffer[i++] = __real__ a;
+    buffer[i++] = __imag__ a;
+    c = 2.0i;
+    buffer[i++] = creal(c);
+    buffer[i++] = cimag(c);
+
+    // Casts
+    buffer[i++] = crealf(f);
+    buffer[i++] = cimagf(f);
+    buffer[i++] = (double)a;
+    buffer[i++] = a ? 1.0 : 0.0;
+    buffer[i++] = zero ? 1.0 : 0.0;
+
+    // Compound assignment
+    c = a;
+    c *= b;
+    c += 1.0;
+    buffer[i++] = creal(c);
+    buffer[i++] = cimag(c);
+
+    buffer[i++] = cabs(3.0 + 4.0 * I);
+}
@@ -0,0 +1,28 @@
+//! extern_crate_num_complex
+
+use crate::complex::rust_complex_arithmetic;
+use libc::{c_double, c_uint};
+
+#[link(name = "test")]
+extern "C" {
+    fn complex_arithmetic(_: c_uint, _: *mut c_double);
+}
+
+const BUFFER_SIZE: usize = 30;
+
+pub fn test_complex_arithmetic() {
+    let mut buffer = [0.; BUFFER_SIZE];
+    let mut rust_buffer = [0.; BUFFER_SIZE];
+    let expected_buffer = [
+        2., 1., 0., 3., 3., 1., -0.5, 1.5, 2., 4., -1., -2., 1., -2., 1., -2., 1., 2., 0., 2., 1.,
+        2., 1., 1., 0., 4., 1., 5., 0., 0.,
+    ];
+
+    unsafe {
+        complex_arithmetic(BUFFER_SIZE as u32, buffer.as_mut_ptr());
+        rust_complex_arithmetic(BUFFER_SIZE as u32, rust_buffer.as_mut_ptr());
+    }
+
+    assert_eq!(buffer, rust_buffer);
+    assert_eq!(buffer, expected_buffer);
+}
@@ -1,3 +1,5 @@
+//! disallow_current_block
+
 // This shoud translate to a straightforward `match`.
 int idiomatic_switch(int x) {
 
@@ -15,3 +17,27 @@ int idiomatic_switch(int x) {
 
     return x;
 }
+
+// The `default` group needn't come last, and groups may break out early.
+// This should also translate to a `match`.
+int idiomatic_switch_default_first(int x) {
+    int y = 0;
+
+    switch (x) {
+        default:
+            y = -1;
+            break;
+        case 1:
+        case 3:
+            if (x > 2) {
+                y = 30;
+                break;
+            }
+            y = 10;
+            break;
+        case 2:
+            return 20;
+    }
+
+    return y;
+}
@@ -1,5 +1,5 @@
 use crate::idiomatic_nested_loops::rust_break_multiple;
-use crate::idiomatic_switch::rust_idiomatic_switch;
+use crate::idiomatic_switch::{rust_idiomatic_switch, rust_idiomatic_switch_default_first};
 
 pub fn test_idiomatic_switch() {
     unsafe {
@@ -10,6 +10,15 @@ pub fn test_idiomatic_switch() {
     }
 }
 
+pub fn test_idiomatic_switch_default_first() {
+    unsafe {
+        assert_eq!(rust_idiomatic_switch_default_first(0), -1);
+        assert_eq!(rust_idiomatic_switch_default_first(1), 10);
+        assert_eq!(rust_idiomatic_switch_default_first(2), 20);
+        assert_eq!(rust_idiomatic_switch_default_first(3), 30);
+    }
+}
+
 pub fn test_break_multiple_loops() {
     unsafe {
         assert_eq!(rust_break_multiple(0), 4);
@@ -0,0 +1,30 @@
+//! translate_fn_macros
+
+int macro_calls = 0;
+
+int macro_step(void) { return 1; }
+
+// Used at a single type, so it becomes a function
+#define SQUARE(x) ((x) * (x))
+// Used at two types, so it becomes a macro_rules! macro
+#define MAX(a, b) ((a) > (b) ? (a) : (b))
+// Used at two types and refers to a global and a function, so it becomes a
+// macro_rules! macro that reaches them through their paths in the crate
+#define ADD_COUNTED(a, b) (macro_calls += macro_step(), (a) + (b))
+// Translated with a cast of the call at one of its two types, so it becomes a
+// macro_rules! macro with an arm for each
+#define ADD_STEP(x) ((x) + macro_step())
+
+void fn_macros(unsigned buffer_size, int buffer[]) {
+    int i = 3;
+    long l = 4;
+
+    buffer[0] = SQUARE(i);
+    buffer[1] = SQUARE(i + 1);
+    buffer[2] = MAX(i, 2);
+    buffer[3] = (int)MAX(l, 5L);
+    buffer[4] = ADD_COUNTED(i, 2);
+    buffer[5] = (int)ADD_COUNTED(l, 3L);
+    buffer[6] = ADD_STEP(i);
+    buffer[7] = (int)ADD_STEP(l) + macro_calls;
+}
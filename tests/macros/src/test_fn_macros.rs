@@ -0,0 +1,42 @@
+use crate::fn_macros::{rust_fn_macros, rust_macro_calls, ADD_COUNTED, MAX, SQUARE};
+use libc::{c_int, c_long, c_uint};
+
+#[link(name = "test")]
+extern "C" {
+    fn fn_macros(_: c_uint, _: *mut c_int);
+}
+
+const BUFFER_SIZE: usize = 8;
+
+pub fn test_fn_macros() {
+    let mut buffer = [0; BUFFER_SIZE];
+    let mut rust_buffer = [0; BUFFER_SIZE];
+    let expected_buffer = [9, 16, 3, 5, 5, 7, 4, 7];
+
+    unsafe {
+        fn_macros(BUFFER_SIZE as c_uint, buffer.as_mut_ptr());
+        rust_fn_macros(BUFFER_SIZE as c_uint, rust_buffer.as_mut_ptr());
+    }
+
+    assert_eq!(buffer, rust_buffer);
+    assert_eq!(buffer, expected_buffer);
+}
+
+pub fn test_fn_macro_items() {
+    // `SQUARE` is only used with `int`s, so it is a fn
+    let square: unsafe fn(c_int) -> c_int = SQUARE;
+    assert_eq!(unsafe { square(7) }, 49);
+
+    // `MAX` is used with `int`s and `long`s, so it is a macro
+    assert_eq!(MAX!(3 as c_int, 8), 8);
+    assert_eq!(MAX!(-2 as c_long, -5), -2);
+
+    // `ADD_COUNTED` refers to a global and a function of the `fn_macros`
+    // module by their paths, so it works here too
+    unsafe {
+        let calls = rust_macro_calls;
+        assert_eq!(ADD_COUNTED!(2 as c_int, 3), 5);
+        assert_eq!(ADD_COUNTED!(2 as c_long, 4), 6);
+        assert_eq!(rust_macro_calls, calls + 2);
+    }
+}
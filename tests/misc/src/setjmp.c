@@ -0,0 +1,56 @@
+#include <setjmp.h>
+
+static jmp_buf env;
+
+static int checked_div(int a, int b) {
+    if (b == 0) {
+        longjmp(env, 1);
+    }
+    return a / b;
+}
+
+static int div_twice(int a, int b) {
+    return checked_div(checked_div(a, b), b);
+}
+
+static int safe_div(int a, int b) {
+    int result = -1;
+    if (setjmp(env)) {
+        result = 0;
+    } else {
+        result = checked_div(a, b);
+    }
+    return result;
+}
+
+static int safe_div_negated(int a, int b) {
+    int result = -1;
+    if (setjmp(env) == 0) {
+        result = checked_div(a, b);
+    } else {
+        result = 0;
+    }
+    return result;
+}
+
+static int safe_div_twice(int a, int b) {
+    int result = -1;
+    if (!setjmp(env)) {
+        result = div_twice(a, b);
+    } else {
+        result = 0;
+    }
+    return result;
+}
+
+void jmp_recover(unsigned buffer_size, int buffer[]) {
+    int i = 0;
+    if (buffer_size < 6)
+        return;
+    buffer[i++] = safe_div(10, 2);
+    buffer[i++] = safe_div(10, 0);
+    buffer[i++] = safe_div_negated(9, 3);
+    buffer[i++] = safe_div_negated(9, 0);
+    buffer[i++] = safe_div_twice(100, 5);
+    buffer[i++] = safe_div_twice(100, 0);
+}
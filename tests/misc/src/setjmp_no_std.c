@@ -0,0 +1,25 @@
+//! emit_no_std, expect_untranslated_no_std_checked_div, expect_untranslated_no_std_safe_div
+
+#include <setjmp.h>
+
+// Without std, there is no unwinding to lower `setjmp` and `longjmp` to, so
+// the functions that call them are reported as untranslatable.
+
+static jmp_buf no_std_env;
+
+int no_std_checked_div(int a, int b) {
+    if (b == 0) {
+        longjmp(no_std_env, 1);
+    }
+    return a / b;
+}
+
+int no_std_safe_div(int a, int b) {
+    int result = -1;
+    if (setjmp(no_std_env)) {
+        result = 0;
+    } else {
+        result = no_std_checked_div(a, b);
+    }
+    return result;
+}
@@ -0,0 +1,25 @@
+//! feature_c_unwind
+
+use crate::setjmp::rust_jmp_recover;
+use libc::{c_int, c_uint};
+
+#[link(name = "test")]
+extern "C" {
+    fn jmp_recover(_: c_uint, _: *mut c_int);
+}
+
+const BUFFER_SIZE: usize = 6;
+
+pub fn test_jmp_recover() {
+    let mut buffer = [0; BUFFER_SIZE];
+    let mut rust_buffer = [0; BUFFER_SIZE];
+    let expected_buffer = [5, 0, 3, 0, 4, 0];
+
+    unsafe {
+        jmp_recover(BUFFER_SIZE as c_uint, buffer.as_mut_ptr());
+        rust_jmp_recover(BUFFER_SIZE as c_uint, rust_buffer.as_mut_ptr());
+    }
+
+    assert_eq!(buffer, rust_buffer);
+    assert_eq!(buffer, expected_buffer);
+}
@@ -3,10 +3,11 @@ use crate::x86::{
     rust_static_m128i, rust_static_m256, rust_static_m256d, rust_static_m256i,
     rust_static_uninit_m128, rust_static_uninit_m128d, rust_static_uninit_m128i,
     rust_static_uninit_m256, rust_static_uninit_m256d, rust_static_uninit_m256i,
-    rust_unpack_128_2x128, rust_vector_init_lists, rust_vector_init_lists_used, rust_zero_init_all,
-    ShuffleVectors, VectorInitLists,
+    rust_unpack_128_2x128, rust_vector_conversions, rust_vector_init_lists,
+    rust_vector_init_lists_used, rust_zero_init_all, ShuffleVectors, VectorInitLists,
 };
 
+use libc::c_long;
 #[cfg(target_arch = "x86")]
 use std::arch::x86::{
     __m128, __m128d, __m128i, __m256, __m256d, __m256i, _mm_set_epi32, _mm_setzero_si128,
@@ -32,6 +33,8 @@ extern "C" {
 
     fn vector_init_lists_used() -> VectorInitLists;
 
+    fn vector_conversions(out_f: *mut f32, out_i: *mut i32, out_u: *mut u32, out_l: *mut c_long);
+
     static static_m128: __m128;
 
     static static_m256: __m256;
@@ -227,3 +230,32 @@ pub fn test_static_init_lists() {
 
     assert_eq!(static_init_lists, rust_static_init_lists);
 }
+
+pub fn test_vector_conversions() {
+    assert!(is_x86_feature_detected!("sse"), "{}", UNSAFETY_ERROR);
+    assert!(is_x86_feature_detected!("sse2"), "{}", UNSAFETY_ERROR);
+
+    let (mut c_f, mut c_i, mut c_u, mut c_l) = ([0f32; 4], [0i32; 4], [0u32; 4], [0; 2]);
+    let (mut r_f, mut r_i, mut r_u, mut r_l) = ([0f32; 4], [0i32; 4], [0u32; 4], [0; 2]);
+
+    unsafe {
+        vector_conversions(
+            c_f.as_mut_ptr(),
+            c_i.as_mut_ptr(),
+            c_u.as_mut_ptr(),
+            c_l.as_mut_ptr(),
+        );
+        rust_vector_conversions(
+            r_f.as_mut_ptr(),
+            r_i.as_mut_ptr(),
+            r_u.as_mut_ptr(),
+            r_l.as_mut_ptr(),
+        );
+    }
+
+    assert_eq!(c_f, r_f);
+    assert_eq!(c_i, r_i);
+    assert_eq!(c_u, r_u);
+    assert_eq!(c_l, r_l);
+    assert_eq!(c_l, [7, -20]);
+}
@@ -293,3 +293,24 @@ void simd_fn_codegen(__m128i i, __m128d d, __m128 y, __m256i m256i) {
     i = _mm_insert_epi64(i, 2, 1);
 #endif // __clang_major__
 }
+
+typedef float v4f __attribute__((vector_size(16)));
+typedef int v4i __attribute__((vector_size(16)));
+typedef unsigned v4u __attribute__((vector_size(16)));
+typedef double v2d __attribute__((vector_size(16)));
+typedef long v2l __attribute__((vector_size(2 * sizeof(long))));
+
+void vector_conversions(float out_f[4], int out_i[4], unsigned out_u[4], long out_l[2])
+{
+    v4i ints = {-3, 1, 2, 7};
+    v4f floats = __builtin_convertvector(ints, v4f) * 2.5f;
+    v4i truncated = __builtin_convertvector(floats, v4i) - 1;
+    v4u unsigneds = __builtin_convertvector(truncated, v4u) * 3;
+    v2d doubles = {2.5, -7.25};
+    v2l longs = __builtin_convertvector(doubles, v2l) * 3 + 1;
+
+    __builtin_memcpy(out_f, &floats, sizeof(floats));
+    __builtin_memcpy(out_i, &truncated, sizeof(truncated));
+    __builtin_memcpy(out_u, &unsigneds, sizeof(unsigneds));
+    __builtin_memcpy(out_l, &longs, sizeof(longs));
+}
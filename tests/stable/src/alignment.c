@@ -0,0 +1,8 @@
+//! stable, expect_unstable_preferred_alignment
+
+// `__alignof__` is the preferred alignment, which can be larger than the
+// `align_of` that stable Rust has (like for `double` on 32-bit x86)
+unsigned preferred_alignment(void)
+{
+    return __alignof__(double);
+}
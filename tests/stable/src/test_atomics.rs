@@ -0,0 +1,69 @@
+use crate::atomics::{
+    rust_atomic_widths, rust_atomics_entry, rust_dynamic_order_atomics, rust_new_atomics,
+};
+use libc::{c_int, c_longlong, c_uint};
+
+#[link(name = "test")]
+extern "C" {
+    fn atomics_entry(_: c_uint, _: *mut c_int);
+    fn new_atomics(_: c_uint, _: *mut c_int);
+    fn dynamic_order_atomics(_: c_uint, _: *mut c_int);
+    fn atomic_widths(_: c_uint, _: *mut c_longlong);
+}
+
+const BUFFER_SIZE: usize = 1024;
+const BUFFER_SIZE2: usize = 16;
+
+pub fn test_atomics() {
+    let mut buffer = [0; BUFFER_SIZE];
+    let mut rust_buffer = [0; BUFFER_SIZE];
+
+    unsafe {
+        atomics_entry(BUFFER_SIZE as u32, buffer.as_mut_ptr());
+        rust_atomics_entry(BUFFER_SIZE as u32, rust_buffer.as_mut_ptr());
+    }
+
+    for index in 0..BUFFER_SIZE {
+        assert_eq!(buffer[index], rust_buffer[index]);
+    }
+}
+
+pub fn test_new_atomics() {
+    let mut buffer = [0; BUFFER_SIZE];
+    let mut rust_buffer = [0; BUFFER_SIZE];
+
+    unsafe {
+        new_atomics(BUFFER_SIZE as u32, buffer.as_mut_ptr());
+        rust_new_atomics(BUFFER_SIZE as u32, rust_buffer.as_mut_ptr());
+    }
+
+    for index in 0..BUFFER_SIZE {
+        assert_eq!(buffer[index], rust_buffer[index]);
+    }
+}
+
+pub fn test_dynamic_order_atomics() {
+    let mut buffer = [0; BUFFER_SIZE];
+    let mut rust_buffer = [0; BUFFER_SIZE];
+
+    unsafe {
+        dynamic_order_atomics(BUFFER_SIZE as u32, buffer.as_mut_ptr());
+        rust_dynamic_order_atomics(BUFFER_SIZE as u32, rust_buffer.as_mut_ptr());
+    }
+
+    for index in 0..BUFFER_SIZE {
+        assert_eq!(buffer[index], rust_buffer[index]);
+    }
+}
+
+pub fn test_atomic_widths() {
+    let mut buffer = [0; BUFFER_SIZE2];
+    let mut rust_buffer = [0; BUFFER_SIZE2];
+
+    unsafe {
+        atomic_widths(BUFFER_SIZE2 as u32, buffer.as_mut_ptr());
+        rust_atomic_widths(BUFFER_SIZE2 as u32, rust_buffer.as_mut_ptr());
+    }
+
+    assert_eq!(buffer, rust_buffer);
+}
@@ -0,0 +1,23 @@
+#include <stdlib.h>
+
+// Implementation of the counter that excluded_structs.c only uses through
+// pointers
+struct counter {
+    int count;
+};
+
+struct counter *counter_new(void) {
+    return calloc(1, sizeof(struct counter));
+}
+
+void counter_incr(struct counter *c) {
+    c->count++;
+}
+
+int counter_get(const struct counter *c) {
+    return c->count;
+}
+
+void counter_free(struct counter *c) {
+    free(c);
+}
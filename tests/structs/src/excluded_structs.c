@@ -0,0 +1,30 @@
+//! exclude_decl_counter, exclude_decl_point
+
+// Only used through pointers, so it becomes an opaque type
+struct counter {
+    int count;
+};
+
+// Used by value, so it's translated even though it's excluded
+struct point {
+    int x;
+    int y;
+};
+
+struct counter *counter_new(void);
+void counter_incr(struct counter *c);
+int counter_get(const struct counter *c);
+void counter_free(struct counter *c);
+
+static int point_sum(struct point p) {
+    return p.x + p.y;
+}
+
+int excluded_structs(void) {
+    struct counter *c = counter_new();
+    counter_incr(c);
+    counter_incr(c);
+    struct point p = { counter_get(c), 3 };
+    counter_free(c);
+    return point_sum(p);
+}
@@ -0,0 +1,21 @@
+use crate::excluded_structs::{point, rust_excluded_structs};
+use libc::c_int;
+use std::mem::size_of;
+
+#[link(name = "test")]
+extern "C" {
+    fn excluded_structs() -> c_int;
+}
+
+pub fn test_excluded_structs() {
+    let ret = unsafe { excluded_structs() };
+    let rust_ret = unsafe { rust_excluded_structs() };
+
+    assert_eq!(ret, 5);
+    assert_eq!(rust_ret, ret);
+}
+
+pub fn test_excluded_struct_used_by_value() {
+    // `point` is passed by value, so its definition is kept
+    assert_eq!(size_of::<point>(), 2 * size_of::<c_int>());
+}